@@ -0,0 +1,40 @@
+use sentinel_macros::sentinel_resource;
+use sentinel_rs::utils::sleep_for_ms;
+use sentinel_rs::{flow, Error};
+use std::sync::Arc;
+
+/// an example on guarding functions with the `#[sentinel_resource]` attribute macro
+fn main() {
+    // Init sentienl configurations
+    sentinel_rs::init_default().unwrap_or_else(|err| sentinel_rs::logging::error!("{:?}", err));
+
+    // Load sentinel rules
+    flow::load_rules(vec![Arc::new(flow::Rule {
+        resource: "task".into(),
+        threshold: 10.0,
+        calculate_strategy: flow::CalculateStrategy::Direct,
+        control_strategy: flow::ControlStrategy::Reject,
+        ..Default::default()
+    })]);
+
+    let mut handlers = Vec::new();
+    for i in 0..20 {
+        handlers.push(std::thread::spawn(move || loop {
+            println!("{}: {}", sentinel_rs::utils::curr_time_millis(), task(i));
+            sleep_for_ms(10);
+        }));
+    }
+    for h in handlers {
+        h.join().expect("Couldn't join on the associated thread");
+    }
+}
+
+fn task_fallback(i: u32, _err: Error) -> String {
+    format!("task {} blocked", i)
+}
+
+#[sentinel_resource(traffic_type = "Outbound", fallback = "task_fallback")]
+fn task(i: u32) -> String {
+    sleep_for_ms(10);
+    format!("task {} passed", i)
+}
@@ -1,4 +1,3 @@
-use sentinel_rs;
 use sentinel_rs::{base, flow, EntryBuilder};
 use std::sync::Arc;
 use tokio::time::{sleep, Duration};
@@ -25,12 +24,13 @@ async fn main() {
             loop {
                 let entry_builder = EntryBuilder::new(res_name.clone())
                     .with_traffic_type(base::TrafficType::Inbound);
-                if let Ok(entry) = entry_builder.build() {
+                if let Ok(entry) = entry_builder.build_async() {
                     // Passed, wrap the logic here.
-                    println!("{}: {}", sentinel_rs::utils::curr_time_millis(), "passed");
+                    println!("{}: passed", sentinel_rs::utils::curr_time_millis());
                     task().await;
                     // Be sure the entry is exited finally.
-                    entry.read().unwrap().exit()
+                    // `AsyncEntry` is `Send + Sync`, it can be exited in any task.
+                    entry.exit()
                 } else {
                     sentinel_rs::utils::sleep_for_ms(100);
                 }
@@ -45,6 +45,20 @@ pub(crate) fn build(attr: TokenStream, func: TokenStream) -> TokenStream {
             return TokenStream::from(e.write_errors());
         }
     };
+    // the rule is bound to the function itself, there is no associated resource or entrance
+    if let Some(relation_strategy) = &rule.relation_strategy {
+        if relation_strategy != "CurrentResource" {
+            return syn::Error::new(
+                proc_macro2::Span::call_site(),
+                format!(
+                    "unsupported relation_strategy \"{}\", only \"CurrentResource\" is supported",
+                    relation_strategy
+                ),
+            )
+            .to_compile_error()
+            .into();
+        }
+    }
 
     let func = parse_macro_input!(func as ItemFn);
     let func = process_func(func);
@@ -102,7 +116,7 @@ fn wrap_sentinel(rule: Rule, func: ItemFn) -> TokenStream {
                     // Passed, wrap the logic here.
                     let result = {#(#stmts)*};
                     // Be sure the entry is exited finally.
-                    entry.borrow().exit();
+                    entry.read().unwrap().exit();
                     Ok(result)
                 },
                 Err(err) => {
@@ -111,7 +125,6 @@ fn wrap_sentinel(rule: Rule, func: ItemFn) -> TokenStream {
             }
         }
     };
-    println!("{:?}", expanded.to_string());
     expanded.into()
 }
 
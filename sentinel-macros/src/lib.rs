@@ -9,6 +9,7 @@ use proc_macro::TokenStream;
 mod macros;
 
 mod flow;
+mod resource;
 
 /// Use this macro by attribute `#[flow()]`.
 /// By default, it simply neglect the blocked task.
@@ -16,3 +17,25 @@ mod flow;
 pub fn flow(attr: TokenStream, item: TokenStream) -> TokenStream {
     flow::build(attr, item)
 }
+
+/// Use this macro by attribute `#[sentinel_resource()]`.
+/// It wraps a sync or async function with the sentinel entry,
+/// the entry is exited and the round trip time is recorded once the function returns.
+/// The async functions await the waits required by the rules rather than blocking the thread,
+/// thus the `async` feature of `sentinel_rs` is required by them.
+///
+/// Arguments:
+/// - `name`: the resource name, by default, it is the name of the function.
+/// - `traffic_type`: `"Inbound"` (default) or `"Outbound"`.
+/// - `fallback`: the function called when the entry is blocked.
+///   It takes the arguments of the original function and the `sentinel_rs::Error`,
+///   and returns the original return type (it is awaited if the original function is async).
+///   Without fallback, the return type is wrapped with `sentinel_rs::Result<T>`.
+/// - `error_predicate`: the function `fn(&T) -> bool`, returning true if the returned value is a business error,
+///   the error is recorded in the entry context, which would be counted by the circuit breakers.
+///
+/// Rules are not loaded by this macro, load them by the APIs in `sentinel_rs`.
+#[proc_macro_attribute]
+pub fn sentinel_resource(attr: TokenStream, item: TokenStream) -> TokenStream {
+    resource::build(attr, item)
+}
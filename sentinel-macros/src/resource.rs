@@ -0,0 +1,214 @@
+use darling::util::SpannedValue;
+use darling::FromMeta;
+use proc_macro::TokenStream;
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, AttributeArgs, FnArg, ItemFn, Pat, Path, ReturnType};
+
+#[derive(Debug, FromMeta)]
+pub(crate) struct Resource {
+    /// the resource name, by default, it is the name of the function
+    #[darling(default)]
+    pub name: Option<String>,
+    /// `"Inbound"` (default) or `"Outbound"`
+    #[darling(default)]
+    pub traffic_type: Option<SpannedValue<String>>,
+    /// the function called when the entry is blocked,
+    /// it takes the arguments of the original function (the receiver goes first)
+    /// and the `sentinel_rs::Error`, and returns the original return type
+    #[darling(default)]
+    pub fallback: Option<Path>,
+    /// the function `fn(&T) -> bool` judging whether the returned value is a business error,
+    /// the error would be recorded in the entry context thus the circuit breakers can count it
+    #[darling(default)]
+    pub error_predicate: Option<Path>,
+}
+
+pub(crate) fn build(attr: TokenStream, func: TokenStream) -> TokenStream {
+    let attr = parse_macro_input!(attr as AttributeArgs);
+    let resource = match Resource::from_list(&attr) {
+        Ok(v) => v,
+        Err(e) => {
+            return TokenStream::from(e.write_errors());
+        }
+    };
+
+    let func = parse_macro_input!(func as ItemFn);
+    match wrap_sentinel(resource, func) {
+        Ok(expanded) => expanded.into(),
+        Err(err) => err.into(),
+    }
+}
+
+/// build the sentinel entry around the function body
+fn wrap_sentinel(resource: Resource, func: ItemFn) -> Result<TokenStream2, TokenStream2> {
+    let ItemFn {
+        attrs,
+        vis,
+        mut sig,
+        block,
+    } = func;
+    let is_async = sig.asyncness.is_some();
+    let resource_name = resource.name.unwrap_or_else(|| sig.ident.to_string());
+    let traffic_type = parse_traffic(&resource.traffic_type).map_err(|e| e.to_compile_error())?;
+    let origin_output = match &sig.output {
+        ReturnType::Default => quote! { () },
+        ReturnType::Type(_, ty) => quote! { #ty },
+    };
+    // `impl Trait` is not allowed in the annotations of the bindings and the closures,
+    // thus the result type is inferred for it, otherwise it is annotated to resolve the conversions of `?`
+    let is_opaque = has_impl_trait(origin_output.clone());
+
+    let blocked = match &resource.fallback {
+        Some(fallback) => {
+            let args = fallback_args(&sig)?;
+            if is_async {
+                quote! { #fallback(#(#args,)* __sentinel_err).await }
+            } else {
+                quote! { #fallback(#(#args,)* __sentinel_err) }
+            }
+        }
+        None => {
+            // Without fallback, the blocked error is returned to the caller,
+            // so the original return type is wrapped with `sentinel_rs::Result<T>`
+            sig.output = syn::parse2(quote! { -> sentinel_rs::Result<#origin_output> }).unwrap();
+            quote! { return Err(__sentinel_err) }
+        }
+    };
+    let passed = if resource.fallback.is_some() {
+        quote! { __sentinel_result }
+    } else {
+        quote! { Ok(__sentinel_result) }
+    };
+
+    // Wrap the original body in a closure (or an async block),
+    // thus `return` and `?` in it would not bypass the exit of entry.
+    let invoke = match (is_async, is_opaque) {
+        (true, _) => quote! { (async move #block).await },
+        (false, true) => quote! { (move || #block)() },
+        (false, false) => quote! { (move || -> #origin_output #block)() },
+    };
+    let result_binding = if is_opaque {
+        quote! { let __sentinel_result = #invoke; }
+    } else {
+        quote! { let __sentinel_result: #origin_output = #invoke; }
+    };
+    // The async functions await the waits of the rules (e.g., the queueing of the throttling flow rules)
+    // instead of blocking the thread of the executor, which requires the `async` feature of `sentinel_rs`.
+    let (build, wait) = if is_async {
+        (
+            quote! { build_async_deferred },
+            quote! { __sentinel_entry.wait().await; },
+        )
+    } else {
+        (quote! { build }, quote! {})
+    };
+    let entry_ptr = if is_async {
+        quote! { std::sync::Arc::clone(__sentinel_entry.entry()) }
+    } else {
+        quote! { __sentinel_entry }
+    };
+
+    let record_err = match &resource.error_predicate {
+        Some(predicate) => quote! {
+            if #predicate(&__sentinel_result) {
+                __sentinel_guard
+                    .0
+                    .read()
+                    .unwrap()
+                    .context()
+                    .write()
+                    .unwrap()
+                    .set_err(sentinel_rs::Error::msg(concat!("business error in resource ", #resource_name)));
+            }
+        },
+        None => quote! {},
+    };
+
+    Ok(quote! {
+        #(#attrs)* #vis #sig {
+            // The generated identifiers are prefixed to avoid shadowing the arguments.
+            let __sentinel_builder = sentinel_rs::EntryBuilder::new(String::from(#resource_name))
+                .with_traffic_type(#traffic_type);
+            match __sentinel_builder.#build() {
+                Ok(__sentinel_entry) => {
+                    // Be sure the entry is exited finally, even if the function panics
+                    // (or the future is cancelled), the round trip time is recorded when exiting.
+                    struct __SentinelEntryGuard(sentinel_rs::base::EntryStrongPtr);
+                    impl Drop for __SentinelEntryGuard {
+                        fn drop(&mut self) {
+                            if let Ok(entry) = self.0.read() {
+                                if std::thread::panicking() {
+                                    if let Ok(mut ctx) = entry.context().write() {
+                                        ctx.set_err(sentinel_rs::Error::msg(concat!("panicked in resource ", #resource_name)));
+                                    }
+                                }
+                                entry.exit();
+                            }
+                        }
+                    }
+                    let __sentinel_guard = __SentinelEntryGuard(#entry_ptr);
+                    #wait
+                    // Passed, wrap the logic here.
+                    #result_binding
+                    #record_err
+                    drop(__sentinel_guard);
+                    #passed
+                }
+                Err(__sentinel_err) => {
+                    #blocked
+                }
+            }
+        }
+    })
+}
+
+/// Whether the type contains `impl Trait`.
+fn has_impl_trait(ty: TokenStream2) -> bool {
+    ty.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => ident == "impl",
+        TokenTree::Group(group) => has_impl_trait(group.stream()),
+        _ => false,
+    })
+}
+
+/// Collect the arguments forwarded to the fallback function.
+fn fallback_args(sig: &syn::Signature) -> Result<Vec<TokenStream2>, TokenStream2> {
+    let mut args = Vec::new();
+    for input in &sig.inputs {
+        match input {
+            FnArg::Receiver(_) => args.push(quote! { self }),
+            FnArg::Typed(pat_type) => match &*pat_type.pat {
+                Pat::Ident(pat_ident) => {
+                    let ident = &pat_ident.ident;
+                    args.push(quote! { #ident });
+                }
+                pat => {
+                    return Err(quote_spanned! {pat.span()=>
+                        compile_error!("only identifier patterns are supported in arguments when `fallback` is specified");
+                    })
+                }
+            },
+        }
+    }
+    Ok(args)
+}
+
+fn parse_traffic(traffic_type: &Option<SpannedValue<String>>) -> syn::Result<TokenStream2> {
+    let traffic_type = match traffic_type {
+        Some(traffic_type) => traffic_type,
+        None => return Ok(quote! {sentinel_rs::base::TrafficType::Inbound}),
+    };
+    match traffic_type.as_str() {
+        "Inbound" => Ok(quote! {sentinel_rs::base::TrafficType::Inbound}),
+        "Outbound" => Ok(quote! {sentinel_rs::base::TrafficType::Outbound}),
+        other => Err(syn::Error::new(
+            traffic_type.span(),
+            format!(
+                "unknown traffic_type \"{}\", expected \"Inbound\" or \"Outbound\"",
+                other
+            ),
+        )),
+    }
+}
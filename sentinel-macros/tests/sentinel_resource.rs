@@ -0,0 +1,144 @@
+use sentinel_macros::sentinel_resource;
+use sentinel_rs::{flow, Error};
+use std::sync::Arc;
+
+fn block_all(resource: &str) {
+    flow::load_rules_of_resource(
+        &String::from(resource),
+        vec![Arc::new(flow::Rule {
+            resource: String::from(resource),
+            threshold: 0.0,
+            ..Default::default()
+        })],
+    )
+    .unwrap();
+}
+
+#[sentinel_resource]
+fn passed(a: u32, b: u32) -> u32 {
+    if a == 0 {
+        // early return should not bypass the exit of entry
+        return b;
+    }
+    a + b
+}
+
+#[sentinel_resource(name = "macro_blocked", traffic_type = "Outbound")]
+fn blocked() -> u32 {
+    1
+}
+
+fn fallback(a: u32, _err: Error) -> u32 {
+    a * 10
+}
+
+#[sentinel_resource(name = "macro_fallback", fallback = "fallback")]
+fn with_fallback(a: u32) -> u32 {
+    a
+}
+
+fn is_err(result: &Result<u32, String>) -> bool {
+    result.is_err()
+}
+
+#[sentinel_resource(name = "macro_predicate", error_predicate = "is_err")]
+fn with_predicate(ok: bool) -> Result<u32, String> {
+    if ok {
+        Ok(1)
+    } else {
+        Err(String::from("biz error"))
+    }
+}
+
+#[sentinel_resource(name = "macro_async")]
+async fn async_passed(a: u32) -> u32 {
+    tokio::task::yield_now().await;
+    a
+}
+
+async fn async_fallback(_a: u32, _err: Error) -> u32 {
+    0
+}
+
+#[sentinel_resource(name = "macro_async_fallback", fallback = "async_fallback")]
+async fn async_with_fallback(a: u32) -> u32 {
+    a
+}
+
+#[sentinel_resource(name = "macro_impl_trait")]
+fn impl_trait(n: u32) -> impl Iterator<Item = u32> {
+    0..n
+}
+
+#[sentinel_resource(name = "macro_async_impl_trait")]
+async fn async_impl_trait(n: u32) -> impl std::fmt::Display {
+    n
+}
+
+#[sentinel_resource(name = "macro_async_throttled")]
+async fn async_throttled() {}
+
+#[sentinel_resource(name = "macro_panicked")]
+fn panicked() -> u32 {
+    panic!("boom")
+}
+
+fn assert_send<T: Send>(_: &T) {}
+
+#[test]
+fn sync_resource() {
+    assert_eq!(passed(1, 2).unwrap(), 3);
+    assert_eq!(passed(0, 2).unwrap(), 2);
+    block_all("macro_blocked");
+    assert!(blocked().is_err());
+    block_all("macro_fallback");
+    assert_eq!(with_fallback(1), 10);
+    assert_eq!(with_predicate(true).unwrap(), Ok(1));
+    assert!(with_predicate(false).unwrap().is_err());
+    assert_eq!(impl_trait(3).unwrap().sum::<u32>(), 3);
+}
+
+#[test]
+fn panicked_resource() {
+    assert!(std::panic::catch_unwind(panicked).is_err());
+    // the entry is exited by the guard, with the panic recorded as the error
+    let stat = sentinel_rs::resource_stat(&String::from("macro_panicked")).unwrap();
+    assert_eq!(stat.concurrency, 0);
+    assert!(stat.error_qps > 0.0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn async_resource() {
+    let fut = async_passed(1);
+    assert_send(&fut);
+    assert_eq!(fut.await.unwrap(), 1);
+    block_all("macro_async_fallback");
+    assert_eq!(async_with_fallback(1).await, 0);
+    assert_eq!(async_impl_trait(1).await.unwrap().to_string(), "1");
+}
+
+#[tokio::test]
+async fn async_wait_without_blocking() {
+    // a request per 300ms, the second one is queued
+    flow::load_rules_of_resource(
+        &String::from("macro_async_throttled"),
+        vec![Arc::new(flow::Rule {
+            resource: String::from("macro_async_throttled"),
+            threshold: 1.0,
+            stat_interval_ms: 300,
+            control_strategy: flow::ControlStrategy::Throttling,
+            max_queueing_time_ms: 1000,
+            ..Default::default()
+        })],
+    )
+    .unwrap();
+    // the runtime of `tokio::test` is single-threaded, the ticker is stalled if the thread sleeps
+    let start = std::time::Instant::now();
+    let (first, second, ticked) = tokio::join!(async_throttled(), async_throttled(), async {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        start.elapsed()
+    });
+    assert!(first.is_ok() && second.is_ok());
+    assert!(start.elapsed() >= std::time::Duration::from_millis(250));
+    assert!(ticked < std::time::Duration::from_millis(200));
+}
@@ -0,0 +1,234 @@
+//! The adapter of [actix-web](https://github.com/actix/actix-web).
+//! `Sentinel` is the middleware registered by `App::wrap()` (or `Scope::wrap()`),
+//! and builds an inbound entry for each request:
+//!
+//!  - the resource is `{method}:{matched pattern}` by default, e.g., `GET:/users/{id}`,
+//!    or `{method}:<unmatched>` if there is no matched pattern (see `unmatched_resource()`), it can be replaced by `with_resource_extractor()`
+//!  - the origin is read from the header set by `with_origin_header()`
+//!  - the blocked request is answered by `block_response()`, unless another builder is set
+//!  - the server errors are recorded for the circuit breakers
+//!  - the quota of the flow rules is reported by the `RateLimit-*` headers if `with_rate_limit_headers(true)` is set
+//!
+//! The entry is exited once the response head is produced,
+//! or if the handler panics or the client disconnects before that.
+use super::{
+    block_body, block_error_of, block_status_code, insert_headers, rate_limit_headers,
+    retry_after_secs, unmatched_resource,
+};
+use crate::base::{BlockError, EntryGuard, ResourceType, TrafficType};
+use crate::{EntryBuilder, Error};
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{self, HeaderName};
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use std::fmt;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+
+pub type ResourceExtractor = Arc<dyn Fn(&ServiceRequest) -> String + Send + Sync>;
+pub type BlockResponseBuilder = Arc<dyn Fn(&BlockError) -> HttpResponse + Send + Sync>;
+pub type ErrorPredicate = Arc<dyn Fn(StatusCode) -> bool + Send + Sync>;
+
+/// `block_response` is the default response of the blocked requests,
+/// i.e., 429 (or 503 for circuit breaking) with the `Retry-After` header and the JSON details.
+pub fn block_response(block_error: &BlockError) -> HttpResponse {
+    let status = StatusCode::from_u16(block_status_code(block_error))
+        .unwrap_or(StatusCode::TOO_MANY_REQUESTS);
+    let mut builder = HttpResponse::build(status);
+    builder.content_type("application/json");
+    if let Some(secs) = retry_after_secs(block_error) {
+        builder.insert_header((header::RETRY_AFTER, secs));
+    }
+    builder.body(block_body(block_error))
+}
+
+fn default_resource(req: &ServiceRequest) -> String {
+    match req.match_pattern() {
+        Some(pattern) => format!("{}:{}", req.method(), pattern),
+        None => unmatched_resource(req.method()),
+    }
+}
+
+/// `Sentinel` is the `Transform` wrapping the services in `SentinelMiddleware`.
+#[derive(Clone)]
+pub struct Sentinel {
+    resource_extractor: ResourceExtractor,
+    origin_header: Option<HeaderName>,
+    block_response: BlockResponseBuilder,
+    is_error: ErrorPredicate,
+    rate_limit_headers: bool,
+}
+
+impl Default for Sentinel {
+    fn default() -> Self {
+        Sentinel {
+            resource_extractor: Arc::new(default_resource),
+            origin_header: None,
+            block_response: Arc::new(block_response),
+            is_error: Arc::new(|status| status.is_server_error()),
+            rate_limit_headers: false,
+        }
+    }
+}
+
+impl fmt::Debug for Sentinel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sentinel")
+            .field("origin_header", &self.origin_header)
+            .field("rate_limit_headers", &self.rate_limit_headers)
+            .finish()
+    }
+}
+
+impl Sentinel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `with_resource_extractor` replaces the resource derived from the matched pattern.
+    pub fn with_resource_extractor(
+        mut self,
+        f: impl Fn(&ServiceRequest) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.resource_extractor = Arc::new(f);
+        self
+    }
+
+    /// `with_origin_header` sets the header carrying the origin (caller) of the request, e.g., `x-sentinel-origin`.
+    pub fn with_origin_header(mut self, name: HeaderName) -> Self {
+        self.origin_header = Some(name);
+        self
+    }
+
+    /// `with_block_response` replaces the default `block_response()`.
+    pub fn with_block_response(
+        mut self,
+        f: impl Fn(&BlockError) -> HttpResponse + Send + Sync + 'static,
+    ) -> Self {
+        self.block_response = Arc::new(f);
+        self
+    }
+
+    /// `with_error_predicate` sets the status codes recorded as errors, the server errors by default.
+    pub fn with_error_predicate(
+        mut self,
+        f: impl Fn(StatusCode) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.is_error = Arc::new(f);
+        self
+    }
+
+    /// `with_rate_limit_headers` sets whether the responses carry the headers of `rate_limit_headers()`,
+    /// including the block responses, `false` by default.
+    pub fn with_rate_limit_headers(mut self, rate_limit_headers: bool) -> Self {
+        self.rate_limit_headers = rate_limit_headers;
+        self
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Sentinel
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Transform = SentinelMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SentinelMiddleware {
+            service: Rc::new(service),
+            config: self.clone(),
+        }))
+    }
+}
+
+pub struct SentinelMiddleware<S> {
+    service: Rc<S>,
+    config: Sentinel,
+}
+
+impl<S, B> Service<ServiceRequest> for SentinelMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let resource = (self.config.resource_extractor)(&req);
+        let origin = self
+            .config
+            .origin_header
+            .as_ref()
+            .and_then(|name| req.headers().get(name))
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+        let mut builder = EntryBuilder::new(resource.clone())
+            .with_resource_type(ResourceType::Web)
+            .with_traffic_type(TrafficType::Inbound);
+        if let Some(origin) = origin {
+            builder = builder.with_origin(origin);
+        }
+        let entry = match builder.build_async_deferred() {
+            Ok(entry) => entry,
+            Err(err) => {
+                let block_error = match block_error_of(err) {
+                    Ok(block_error) => block_error,
+                    Err(_) => {
+                        let response = HttpResponse::InternalServerError().finish();
+                        return Box::pin(ready(Ok(req
+                            .into_response(response)
+                            .map_into_right_body())));
+                    }
+                };
+                let mut response = (self.config.block_response)(&block_error);
+                if self.config.rate_limit_headers {
+                    insert_headers(
+                        rate_limit_headers(&resource, Some(&block_error)),
+                        |name: HeaderName, value| {
+                            response.headers_mut().insert(name, value);
+                        },
+                    );
+                }
+                return Box::pin(ready(Ok(req.into_response(response).map_into_right_body())));
+            }
+        };
+        // the quota is taken when the request passes
+        let headers = if self.config.rate_limit_headers {
+            rate_limit_headers(&resource, None)
+        } else {
+            Vec::new()
+        };
+        let service = Rc::clone(&self.service);
+        let is_error = Arc::clone(&self.config.is_error);
+        Box::pin(async move {
+            let guard = EntryGuard::new(entry);
+            guard.entry().wait().await;
+            let mut result = guard.catch_panic(service.call(req)).await;
+            if let Ok(response) = &mut result {
+                insert_headers(headers, |name: HeaderName, value| {
+                    response.headers_mut().insert(name, value);
+                });
+            }
+            let status = match &result {
+                Ok(response) => response.status(),
+                Err(err) => err.as_response_error().status_code(),
+            };
+            if is_error(status) {
+                guard.set_err(Error::msg(format!("the response status is {}", status)));
+            }
+            guard.exit();
+            result.map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
@@ -0,0 +1,215 @@
+//! The adapter of [async-graphql](https://github.com/async-graphql/async-graphql).
+//! `Sentinel` is an `async_graphql::extensions::ExtensionFactory`, it is registered by `SchemaBuilder::extension()`,
+//! and builds the inbound entries finer than a single limit of the HTTP endpoint:
+//!
+//!  - an entry per operation, the resource is `graphql:{operation name}` by default (`graphql:anonymous` if unnamed),
+//!    the variables of the request are the attachments of the entry
+//!  - an entry per resolving of the expensive fields registered by `with_field()`, e.g., `Query.search`,
+//!    the resource is `graphql:{type}.{field}`, and the arguments of the field (the variables resolved) are the attachments
+//!
+//! Thus the hotspot rules limit the operations and the fields by the argument values with `param_key`,
+//! e.g., `param_key: "keyword"` for `search(keyword: $keyword)`, the string values are the raw strings, and the others are
+//! the GraphQL literals. The blocked operation (or field) fails with the error of `block_server_error()`,
+//! and the operations (or the resolvers) responded with errors are recorded for the circuit breakers.
+use super::{block_error_of, retry_after_secs};
+use crate::base::{BlockError, BlockType, EntryGuard, ParamsMap, ResourceType, TrafficType};
+use crate::{EntryBuilder, Error};
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextExecute, NextParseQuery, NextResolve,
+    ResolveInfo,
+};
+use async_graphql::parser::types::ExecutableDocument;
+use async_graphql::{
+    ErrorExtensionValues, Name, Response, ServerError, ServerResult, Value, Variables,
+};
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+pub type OperationResourceExtractor = Arc<dyn Fn(Option<&str>) -> String + Send + Sync>;
+
+/// `block_server_error` is the GraphQL error of the blocked operations and fields,
+/// its `code` extension is `TOO_MANY_REQUESTS` (or `SERVICE_UNAVAILABLE` for circuit breaking),
+/// with the `retryAfter` extension in seconds if the hint is provided by the rules.
+pub fn block_server_error(block_error: &BlockError) -> ServerError {
+    let mut extensions = ErrorExtensionValues::default();
+    let code = match block_error.block_type() {
+        BlockType::CircuitBreaking => "SERVICE_UNAVAILABLE",
+        _ => "TOO_MANY_REQUESTS",
+    };
+    extensions.set("code", code);
+    if let Some(secs) = retry_after_secs(block_error) {
+        extensions.set("retryAfter", secs);
+    }
+    let mut err = ServerError::new(block_error.to_string(), None);
+    err.extensions = Some(extensions);
+    err
+}
+
+fn param_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// `Sentinel` creates the extension guarding the operations and the registered fields of each request.
+#[derive(Clone)]
+pub struct Sentinel {
+    operation_resource: OperationResourceExtractor,
+    fields: Arc<HashSet<String>>,
+}
+
+impl Default for Sentinel {
+    fn default() -> Self {
+        Sentinel {
+            operation_resource: Arc::new(|name| format!("graphql:{}", name.unwrap_or("anonymous"))),
+            fields: Arc::new(HashSet::new()),
+        }
+    }
+}
+
+impl fmt::Debug for Sentinel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sentinel")
+            .field("fields", &self.fields)
+            .finish()
+    }
+}
+
+impl Sentinel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `with_operation_resource` names the resource of the operation by its name.
+    pub fn with_operation_resource(
+        mut self,
+        f: impl Fn(Option<&str>) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.operation_resource = Arc::new(f);
+        self
+    }
+
+    /// `with_field` guards the resolver of the field by the coordinate `{type}.{field}`, e.g., `Query.search`.
+    pub fn with_field(mut self, coordinate: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.fields).insert(coordinate.into());
+        self
+    }
+}
+
+impl ExtensionFactory for Sentinel {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(SentinelExtension {
+            config: self.clone(),
+            variables: Mutex::new(Variables::default()),
+        })
+    }
+}
+
+// SentinelExtension is created per request, the variables are kept to resolve the arguments of the fields
+struct SentinelExtension {
+    config: Sentinel,
+    variables: Mutex<Variables>,
+}
+
+impl SentinelExtension {
+    async fn enter(&self, resource: String, attachments: ParamsMap) -> ServerResult<EntryGuard> {
+        let guard = EntryBuilder::new(resource)
+            .with_resource_type(ResourceType::Web)
+            .with_traffic_type(TrafficType::Inbound)
+            .with_attachment(attachments)
+            .build_async_deferred()
+            .map(EntryGuard::new)
+            .map_err(|err| match block_error_of(err) {
+                Ok(block_error) => block_server_error(&block_error),
+                Err(err) => {
+                    let mut extensions = ErrorExtensionValues::default();
+                    extensions.set("code", "INTERNAL_SERVER_ERROR");
+                    let mut err = ServerError::new(err.to_string(), None);
+                    err.extensions = Some(extensions);
+                    err
+                }
+            })?;
+        guard.entry().wait().await;
+        Ok(guard)
+    }
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for SentinelExtension {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        *self.variables.lock().unwrap() = variables.clone();
+        next.run(ctx, query, variables).await
+    }
+
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        let attachments = self
+            .variables
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, value)| (name.to_string(), param_value(value)))
+            .collect();
+        let resource = (self.config.operation_resource)(operation_name);
+        let guard = match self.enter(resource, attachments).await {
+            Ok(guard) => guard,
+            Err(err) => return Response::from_errors(vec![err]),
+        };
+        let response = guard.catch_panic(next.run(ctx, operation_name)).await;
+        if let Some(err) = response.errors.first() {
+            guard.set_err(Error::msg(err.message.clone()));
+        }
+        guard.exit();
+        response
+    }
+
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        let coordinate = format!("{}.{}", info.parent_type, info.name);
+        if !self.config.fields.contains(&coordinate) {
+            return next.run(ctx, info).await;
+        }
+        let attachments = {
+            let variables = self.variables.lock().unwrap();
+            info.field
+                .arguments
+                .iter()
+                .filter_map(|(name, value)| {
+                    let value = value
+                        .node
+                        .clone()
+                        .into_const_with(|variable: Name| {
+                            variables.get(&variable).cloned().ok_or(())
+                        })
+                        .ok()?;
+                    Some((name.node.to_string(), param_value(&value)))
+                })
+                .collect()
+        };
+        let guard = self
+            .enter(format!("graphql:{}", coordinate), attachments)
+            .await?;
+        let result = guard.catch_panic(next.run(ctx, info)).await;
+        if let Err(err) = &result {
+            guard.set_err(Error::msg(err.message.clone()));
+        }
+        guard.exit();
+        result
+    }
+}
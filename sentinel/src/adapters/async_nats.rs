@@ -0,0 +1,158 @@
+//! The subscriber side adapter of [async-nats](https://github.com/nats-io/nats.rs).
+//! `SentinelSubscriber` guards the handlers of the messages by the resource of the subject
+//! (see `with_resource_extractor()`), thus the flow rules limit the handling rate per subject,
+//! and the circuit breakers of a subject are fed with the errors of its handlers:
+//!
+//! ```ignore
+//! let subscriber = SentinelSubscriber::new();
+//! while let Some(message) = messages.next().await {
+//!     let message = message?;
+//!     match subscriber.process(&message, || handle(&message)).await {
+//!         Ok(_) => message.ack().await?,
+//!         Err(MessageError::Blocked(_)) => {} // negatively acknowledged with the delay
+//!         Err(MessageError::Handler(err)) => log(err),
+//!     }
+//! }
+//! ```
+//!
+//! The blocked message is not handled, it is passed to `NatsMessage::requeue()` with the retry-after hint of the block error
+//! (or the backoff set by `with_backoff()`): the JetStream messages are negatively acknowledged with the delay,
+//! thus they are redelivered by the server, while the core NATS messages have no redelivery and are dropped,
+//! reply to them to let the publishers retry if needed.
+use super::{block_error_of, MessageError};
+use crate::base::{EntryGuard, ResourceType, TrafficType};
+use crate::{logging, EntryBuilder, Error};
+use async_nats::jetstream::{self, AckKind};
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+pub type ResourceExtractor = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+/// `NatsMessage` is the message guarded by `SentinelSubscriber`,
+/// implemented by the core NATS messages and the JetStream messages.
+pub trait NatsMessage {
+    fn subject(&self) -> &str;
+
+    /// `requeue` asks the server to redeliver the blocked message after the delay.
+    fn requeue(&self, delay: Duration) -> impl Future<Output = Result<(), BoxError>> + Send;
+}
+
+impl NatsMessage for async_nats::Message {
+    fn subject(&self) -> &str {
+        self.subject.as_str()
+    }
+
+    /// The core NATS messages are delivered at most once, thus they are not requeued.
+    fn requeue(&self, _delay: Duration) -> impl Future<Output = Result<(), BoxError>> + Send {
+        async { Ok(()) }
+    }
+}
+
+impl NatsMessage for jetstream::Message {
+    fn subject(&self) -> &str {
+        self.subject.as_str()
+    }
+
+    fn requeue(&self, delay: Duration) -> impl Future<Output = Result<(), BoxError>> + Send {
+        self.ack_with(AckKind::Nak(Some(delay)))
+    }
+}
+
+/// `SentinelSubscriber` guards the handlers of the subscribed messages.
+#[derive(Clone)]
+pub struct SentinelSubscriber {
+    resource_extractor: ResourceExtractor,
+    backoff: Duration,
+}
+
+impl Default for SentinelSubscriber {
+    fn default() -> Self {
+        SentinelSubscriber {
+            resource_extractor: Arc::new(|subject| subject.into()),
+            backoff: Duration::from_millis(100),
+        }
+    }
+}
+
+impl fmt::Debug for SentinelSubscriber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SentinelSubscriber")
+            .field("backoff", &self.backoff)
+            .finish()
+    }
+}
+
+impl SentinelSubscriber {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `with_resource_extractor` names the resource by the subject of the message,
+    /// e.g., `|subject| subject.split('.').take(2).collect::<Vec<_>>().join(".")` for the limits of the subject hierarchies,
+    /// the resource is the subject by default.
+    pub fn with_resource_extractor(
+        mut self,
+        f: impl Fn(&str) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.resource_extractor = Arc::new(f);
+        self
+    }
+
+    /// `with_backoff` sets the redelivery delay of the blocked messages if the block error carries no retry-after hint,
+    /// 100ms by default.
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// `process` runs the handler of the message if the entry of its subject passes,
+    /// the error of the handler is recorded and returned as `MessageError::Handler`,
+    /// otherwise the message is requeued and `MessageError::Blocked` is returned.
+    pub async fn process<M, F, Fut, T, E>(
+        &self,
+        message: &M,
+        handler: F,
+    ) -> Result<T, MessageError<E>>
+    where
+        M: NatsMessage,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: fmt::Display,
+    {
+        let resource = (self.resource_extractor)(message.subject());
+        let entry = match EntryBuilder::new(resource)
+            .with_resource_type(ResourceType::MQ)
+            .with_traffic_type(TrafficType::Inbound)
+            .build_async_deferred()
+        {
+            Ok(entry) => entry,
+            Err(err) => {
+                let block_error = match block_error_of(err) {
+                    Ok(block_error) => block_error,
+                    // the message is handled without being guarded, rather than dropped
+                    Err(_) => return handler().await.map_err(MessageError::Handler),
+                };
+                let delay = block_error.retry_after().unwrap_or(self.backoff);
+                if let Err(err) = message.requeue(delay).await {
+                    logging::warn!(
+                        "[NATS] Failed to requeue the blocked message of subject {}, error: {:?}",
+                        message.subject(),
+                        err
+                    );
+                }
+                return Err(MessageError::Blocked(block_error));
+            }
+        };
+        let guard = EntryGuard::new(entry);
+        guard.entry().wait().await;
+        let result = guard.catch_panic(handler()).await;
+        if let Err(err) = &result {
+            guard.set_err(Error::msg(err.to_string()));
+        }
+        guard.exit();
+        result.map_err(MessageError::Handler)
+    }
+}
@@ -0,0 +1,319 @@
+//! The adapter of [axum](https://github.com/tokio-rs/axum).
+//! `SentinelLayer` is a `tower::Layer`, it is added by `Router::layer()` (or `ServiceBuilder`),
+//! and builds an inbound entry for each request:
+//!
+//!  - the resource is `{method}:{matched route}` by default, e.g., `GET:/users/{id}`,
+//!    or `{method}:<unmatched>` if there is no matched route (see `unmatched_resource()`)
+//!  - the origin is read from the header set by `with_origin_header()`
+//!  - the blocked request is answered by `block_response()`, unless another builder is set
+//!  - the server errors are recorded for the circuit breakers
+//!  - the quota of the flow rules is reported by the `RateLimit-*` headers if `with_rate_limit_headers(true)` is set
+//!
+//! A handler can also be guarded by its own resource with the extractor `SentinelResource<R>`, e.g.,
+//! `async fn checkout(_: SentinelResource<Checkout>) {}` where `Checkout` implements `ResourceName`.
+//! The extractor follows the configurations of the `SentinelLayer` in front of the handler (e.g., the origin header
+//! and the block response builder), the defaults are used if there is no such layer.
+use super::{
+    block_body, block_error_of, block_status_code, insert_headers, rate_limit_headers,
+    retry_after_secs, unmatched_resource,
+};
+use crate::base::{BlockError, EntryGuard, ResourceType, TrafficType};
+use crate::{EntryBuilder, Error};
+use axum::body::Body;
+use axum::extract::{FromRequestParts, MatchedPath, Request};
+use axum::http::{header, request::Parts, HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::response::Response;
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+
+pub type ResourceExtractor = Arc<dyn Fn(&Request) -> String + Send + Sync>;
+pub type BlockResponseBuilder = Arc<dyn Fn(&BlockError) -> Response + Send + Sync>;
+pub type ErrorPredicate = Arc<dyn Fn(StatusCode) -> bool + Send + Sync>;
+
+/// `Origin` is inserted into the extensions of the request by `SentinelLayer` if the origin header is present,
+/// it is used by the extractor `SentinelResource<R>` as well.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Origin(pub String);
+
+/// `block_response` is the default response of the blocked requests,
+/// i.e., 429 (or 503 for circuit breaking) with the `Retry-After` header and the JSON details.
+pub fn block_response(block_error: &BlockError) -> Response {
+    let mut response = Response::new(Body::from(block_body(block_error)));
+    *response.status_mut() = StatusCode::from_u16(block_status_code(block_error))
+        .unwrap_or(StatusCode::TOO_MANY_REQUESTS);
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    if let Some(secs) = retry_after_secs(block_error) {
+        headers.insert(header::RETRY_AFTER, HeaderValue::from(secs));
+    }
+    response
+}
+
+fn default_resource(req: &Request) -> String {
+    match req.extensions().get::<MatchedPath>() {
+        Some(path) => format!("{}:{}", req.method(), path.as_str()),
+        None => unmatched_resource(req.method()),
+    }
+}
+
+/// `SentinelLayer` wraps the services of the routes in `SentinelService`.
+#[derive(Clone)]
+pub struct SentinelLayer {
+    resource_extractor: ResourceExtractor,
+    origin_header: Option<HeaderName>,
+    block_response: BlockResponseBuilder,
+    is_error: ErrorPredicate,
+    rate_limit_headers: bool,
+}
+
+impl Default for SentinelLayer {
+    fn default() -> Self {
+        SentinelLayer {
+            resource_extractor: Arc::new(default_resource),
+            origin_header: None,
+            block_response: Arc::new(block_response),
+            is_error: Arc::new(|status| status.is_server_error()),
+            rate_limit_headers: false,
+        }
+    }
+}
+
+impl fmt::Debug for SentinelLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SentinelLayer")
+            .field("origin_header", &self.origin_header)
+            .field("rate_limit_headers", &self.rate_limit_headers)
+            .finish()
+    }
+}
+
+impl SentinelLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `with_resource_extractor` replaces the resource derived from the matched route.
+    pub fn with_resource_extractor(
+        mut self,
+        f: impl Fn(&Request) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.resource_extractor = Arc::new(f);
+        self
+    }
+
+    /// `with_origin_header` sets the header carrying the origin (caller) of the request, e.g., `x-sentinel-origin`.
+    pub fn with_origin_header(mut self, name: HeaderName) -> Self {
+        self.origin_header = Some(name);
+        self
+    }
+
+    /// `with_block_response` replaces the default `block_response()`.
+    pub fn with_block_response(
+        mut self,
+        f: impl Fn(&BlockError) -> Response + Send + Sync + 'static,
+    ) -> Self {
+        self.block_response = Arc::new(f);
+        self
+    }
+
+    /// `with_error_predicate` sets the status codes recorded as errors, the server errors by default.
+    pub fn with_error_predicate(
+        mut self,
+        f: impl Fn(StatusCode) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.is_error = Arc::new(f);
+        self
+    }
+
+    /// `with_rate_limit_headers` sets whether the responses carry the headers of `rate_limit_headers()`,
+    /// including the block responses, `false` by default.
+    pub fn with_rate_limit_headers(mut self, rate_limit_headers: bool) -> Self {
+        self.rate_limit_headers = rate_limit_headers;
+        self
+    }
+
+    fn origin(&self, headers: &HeaderMap) -> Option<String> {
+        self.origin_header
+            .as_ref()
+            .and_then(|name| headers.get(name))
+            .and_then(|value| value.to_str().ok())
+            .map(String::from)
+    }
+
+    fn entry_builder(&self, resource: String, origin: Option<String>) -> EntryBuilder {
+        let builder = EntryBuilder::new(resource)
+            .with_resource_type(ResourceType::Web)
+            .with_traffic_type(TrafficType::Inbound);
+        match origin {
+            Some(origin) => builder.with_origin(origin),
+            None => builder,
+        }
+    }
+
+    /// `reject` answers the request failing to build the entry of `resource`,
+    /// by the block response builder, or `500 Internal Server Error` if the error is not a `BlockError`.
+    fn reject(&self, resource: &str, err: Error) -> Response {
+        let block_error = match block_error_of(err) {
+            Ok(block_error) => block_error,
+            Err(_) => {
+                let mut response = Response::new(Body::empty());
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                return response;
+            }
+        };
+        let mut response = (self.block_response)(&block_error);
+        if self.rate_limit_headers {
+            insert_headers(
+                rate_limit_headers(resource, Some(&block_error)),
+                |name: HeaderName, value| {
+                    response.headers_mut().insert(name, value);
+                },
+            );
+        }
+        response
+    }
+}
+
+impl<S> Layer<S> for SentinelLayer {
+    type Service = SentinelService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SentinelService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SentinelService<S> {
+    inner: S,
+    layer: SentinelLayer,
+}
+
+impl<S> Service<Request> for SentinelService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: fmt::Display,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request) -> Self::Future {
+        let resource = (self.layer.resource_extractor)(&req);
+        let origin = self.layer.origin(req.headers());
+        if let Some(origin) = &origin {
+            req.extensions_mut().insert(Origin(origin.clone()));
+        }
+        // the configurations are shared with the extractor `SentinelResource<R>`
+        req.extensions_mut().insert(self.layer.clone());
+        let builder = self.layer.entry_builder(resource.clone(), origin);
+        let entry = match builder.build_async_deferred() {
+            Ok(entry) => entry,
+            Err(err) => {
+                let response = self.layer.reject(&resource, err);
+                return Box::pin(async move { Ok(response) });
+            }
+        };
+        // the quota is taken when the request passes
+        let headers = if self.layer.rate_limit_headers {
+            rate_limit_headers(&resource, None)
+        } else {
+            Vec::new()
+        };
+        // the service driven to readiness is taken, see the docs of `tower::Service`
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let is_error = self.layer.is_error.clone();
+        Box::pin(async move {
+            let guard = EntryGuard::new(entry);
+            guard.entry().wait().await;
+            let mut result = guard.catch_panic(inner.call(req)).await;
+            if let Ok(response) = &mut result {
+                insert_headers(headers, |name: HeaderName, value| {
+                    response.headers_mut().insert(name, value);
+                });
+            }
+            match &result {
+                Ok(response) if is_error(response.status()) => guard.set_err(Error::msg(format!(
+                    "the response status is {}",
+                    response.status()
+                ))),
+                Err(err) => guard.set_err(Error::msg(err.to_string())),
+                _ => {}
+            }
+            guard.exit();
+            result
+        })
+    }
+}
+
+/// `ResourceName` names the resource of the extractor `SentinelResource<R>`.
+pub trait ResourceName: Send + Sync + 'static {
+    const NAME: &'static str;
+}
+
+/// `SentinelResource<R>` is the extractor guarding the handler by the resource `R::NAME`,
+/// instead of (or besides) the resource derived by `SentinelLayer`,
+/// e.g., several routes sharing a resource, or a handler with finer rules.
+/// The request is rejected by the block response builder of the `SentinelLayer` if it is blocked,
+/// and the entry is exited when the extractor is dropped, usually at the end of the handler.
+pub struct SentinelResource<R> {
+    guard: EntryGuard,
+    _resource: PhantomData<R>,
+}
+
+impl<R> SentinelResource<R> {
+    pub fn guard(&self) -> &EntryGuard {
+        &self.guard
+    }
+}
+
+impl<R, S> FromRequestParts<S> for SentinelResource<R>
+where
+    R: ResourceName,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let layer = parts
+            .extensions
+            .get::<SentinelLayer>()
+            .cloned()
+            .unwrap_or_default();
+        let origin = match parts.extensions.get::<Origin>() {
+            Some(Origin(origin)) => Some(origin.clone()),
+            None => layer.origin(&parts.headers),
+        };
+        match layer
+            .entry_builder(R::NAME.into(), origin)
+            .build_async_deferred()
+        {
+            Ok(entry) => {
+                let guard = EntryGuard::new(entry);
+                guard.entry().wait().await;
+                Ok(SentinelResource {
+                    guard,
+                    _resource: PhantomData,
+                })
+            }
+            Err(err) => Err(layer.reject(R::NAME, err)),
+        }
+    }
+}
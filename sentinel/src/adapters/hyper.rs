@@ -0,0 +1,285 @@
+//! The adapter of the [hyper](https://github.com/hyperium/hyper) clients, for the users not on reqwest.
+//! The wrappers guard the outbound traffic per authority, i.e., the host with the explicit port
+//! (the same resource as the default of the reqwest adapter), thus the flow rules and the circuit breakers
+//! of a dependency apply to both the clients:
+//!
+//!  - `SentinelClientLayer` wraps the client service (e.g., `hyper_util::client::legacy::Client`) in `SentinelClientService`,
+//!    the server errors (by default), the timeouts set by `with_timeout()` and the failures of the client are recorded as errors,
+//!    and the round trip is measured until the response headers arrive
+//!  - `SentinelConnector` wraps the connector (e.g., `HttpConnector`) to guard the establishment of the connections
+//!    by the resource `connect:{authority}`, see `connect_resource()`, the failed connections are recorded as errors,
+//!    thus the connections to a broken dependency are not dialed once the circuit breakers of its connections are open
+//!
+//! The connections are named apart from the requests, thus they are not counted by the flow rules of the requests,
+//! share the resource of the requests by `SentinelConnector::with_resource_extractor(authority)` if needed.
+//! The blocked requests (and connections) fail with `BlockError`, boxed as `BoxError`.
+use super::block_error_of;
+use crate::base::{EntryGuard, ResourceType, TrafficType};
+use crate::{EntryBuilder, Error};
+use http::{Method, Request, Response, StatusCode, Uri};
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower_layer::Layer;
+use tower_service::Service;
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+pub type ResourceExtractor = Arc<dyn Fn(&Method, &Uri) -> String + Send + Sync>;
+pub type ConnectResourceExtractor = Arc<dyn Fn(&Uri) -> String + Send + Sync>;
+pub type ErrorPredicate = Arc<dyn Fn(StatusCode) -> bool + Send + Sync>;
+
+/// `authority` returns the host with the explicit port of the URI, the default resource of `SentinelClientLayer`.
+pub fn authority(uri: &Uri) -> String {
+    match (uri.host(), uri.port_u16()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_owned(),
+        (None, _) => String::new(),
+    }
+}
+
+/// `connect_resource` returns `connect:{authority}`, the default resource of `SentinelConnector`.
+pub fn connect_resource(uri: &Uri) -> String {
+    format!("connect:{}", authority(uri))
+}
+
+/// `TimeoutError` is the error of the request not responded within the timeout of `SentinelClientLayer`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeoutError(pub Duration);
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the request timed out after {:?}", self.0)
+    }
+}
+
+impl std::error::Error for TimeoutError {}
+
+/// `SentinelClientLayer` wraps the client service in `SentinelClientService`.
+#[derive(Clone)]
+pub struct SentinelClientLayer {
+    resource_extractor: ResourceExtractor,
+    is_error: ErrorPredicate,
+    timeout: Option<Duration>,
+}
+
+impl Default for SentinelClientLayer {
+    fn default() -> Self {
+        SentinelClientLayer {
+            resource_extractor: Arc::new(|_, uri| authority(uri)),
+            is_error: Arc::new(|status| status.is_server_error()),
+            timeout: None,
+        }
+    }
+}
+
+impl fmt::Debug for SentinelClientLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SentinelClientLayer")
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl SentinelClientLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `with_resource_extractor` names the resource by the method and the URI of the request,
+    /// e.g., `|method, uri| format!("{}:{}{}", method, authority(uri), uri.path())`.
+    pub fn with_resource_extractor(
+        mut self,
+        f: impl Fn(&Method, &Uri) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.resource_extractor = Arc::new(f);
+        self
+    }
+
+    /// `with_error_predicate` sets the status codes recorded as errors, the server errors by default.
+    pub fn with_error_predicate(
+        mut self,
+        f: impl Fn(StatusCode) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.is_error = Arc::new(f);
+        self
+    }
+
+    /// `with_timeout` fails the requests not responded within the timeout with `TimeoutError`,
+    /// which is recorded as an error.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl<S> Layer<S> for SentinelClientLayer {
+    type Service = SentinelClientService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SentinelClientService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SentinelClientService<S> {
+    inner: S,
+    layer: SentinelClientLayer,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for SentinelClientService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Error: Into<BoxError>,
+    S::Future: Send + 'static,
+    ResBody: Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let resource = (self.layer.resource_extractor)(req.method(), req.uri());
+        let entry = match EntryBuilder::new(resource)
+            .with_resource_type(ResourceType::Web)
+            .with_traffic_type(TrafficType::Outbound)
+            .build_async_deferred()
+        {
+            Ok(entry) => entry,
+            Err(err) => {
+                let err = match block_error_of(err) {
+                    Ok(block_error) => BoxError::from(block_error),
+                    Err(err) => BoxError::from(err),
+                };
+                return Box::pin(async move { Err(err) });
+            }
+        };
+        let guard = EntryGuard::new(entry);
+        let future = self.inner.call(req);
+        let is_error = self.layer.is_error.clone();
+        let timeout = self.layer.timeout;
+        Box::pin(async move {
+            guard.entry().wait().await;
+            let result = match timeout {
+                Some(timeout) => guard.catch_panic(with_timeout(future, timeout)).await,
+                None => guard.catch_panic(future).await.map_err(Into::into),
+            };
+            match &result {
+                Ok(response) if is_error(response.status()) => guard.set_err(Error::msg(format!(
+                    "the response status is {}",
+                    response.status()
+                ))),
+                Err(err) => guard.set_err(Error::msg(err.to_string())),
+                _ => {}
+            }
+            guard.exit();
+            result
+        })
+    }
+}
+
+async fn with_timeout<F, T, E>(future: F, timeout: Duration) -> Result<T, BoxError>
+where
+    F: Future<Output = Result<T, E>>,
+    E: Into<BoxError>,
+{
+    let mut future = Box::pin(future);
+    let mut delay = futures_timer::Delay::new(timeout);
+    poll_fn(|cx| {
+        if let Poll::Ready(result) = future.as_mut().poll(cx) {
+            return Poll::Ready(result.map_err(Into::into));
+        }
+        Pin::new(&mut delay)
+            .poll(cx)
+            .map(|_| Err(BoxError::from(TimeoutError(timeout))))
+    })
+    .await
+}
+
+/// `SentinelConnector` guards the connections dialed by the inner connector.
+#[derive(Clone)]
+pub struct SentinelConnector<C> {
+    inner: C,
+    resource_extractor: ConnectResourceExtractor,
+}
+
+impl<C: fmt::Debug> fmt::Debug for SentinelConnector<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SentinelConnector")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl<C> SentinelConnector<C> {
+    pub fn new(inner: C) -> Self {
+        SentinelConnector {
+            inner,
+            resource_extractor: Arc::new(connect_resource),
+        }
+    }
+
+    /// `with_resource_extractor` names the resource by the URI to connect, `connect_resource()` by default,
+    /// e.g., `authority` to share the resource of the requests.
+    pub fn with_resource_extractor(
+        mut self,
+        f: impl Fn(&Uri) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.resource_extractor = Arc::new(f);
+        self
+    }
+}
+
+impl<C> Service<Uri> for SentinelConnector<C>
+where
+    C: Service<Uri>,
+    C::Error: Into<BoxError>,
+    C::Future: Send + 'static,
+    C::Response: Send + 'static,
+{
+    type Response = C::Response;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<C::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let resource = (self.resource_extractor)(&uri);
+        let entry = match EntryBuilder::new(resource)
+            .with_resource_type(ResourceType::Web)
+            .with_traffic_type(TrafficType::Outbound)
+            .build_async_deferred()
+        {
+            Ok(entry) => entry,
+            Err(err) => {
+                let err = match block_error_of(err) {
+                    Ok(block_error) => BoxError::from(block_error),
+                    Err(err) => BoxError::from(err),
+                };
+                return Box::pin(async move { Err(err) });
+            }
+        };
+        let guard = EntryGuard::new(entry);
+        let future = self.inner.call(uri);
+        Box::pin(async move {
+            guard.entry().wait().await;
+            let result = guard.catch_panic(future).await.map_err(Into::into);
+            if let Err(err) = &result {
+                guard.set_err(Error::msg(err.to_string()));
+            }
+            guard.exit();
+            result
+        })
+    }
+}
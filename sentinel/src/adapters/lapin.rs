@@ -0,0 +1,131 @@
+//! The consumer side adapter of [lapin](https://github.com/amqp-rs/lapin), the AMQP client.
+//! `SentinelConsumer` guards the handlers of the deliveries by the resource of the queue
+//! (or the queue and the routing key, see `with_resource_extractor()`), thus the flow rules limit the handling rate
+//! and the circuit breakers of a queue are fed with the errors of its handlers:
+//!
+//! ```ignore
+//! let consumer = SentinelConsumer::new("orders");
+//! while let Some(delivery) = deliveries.next().await {
+//!     let delivery = delivery?;
+//!     match consumer.process(&delivery, || handle(&delivery)).await {
+//!         Ok(_) => delivery.ack(BasicAckOptions::default()).await?,
+//!         Err(MessageError::Blocked(_)) => {} // rejected
+//!         Err(MessageError::Handler(err)) => log(err),
+//!     }
+//! }
+//! ```
+//!
+//! The blocked delivery is not handled: it is negatively acknowledged at once, without holding the consumer.
+//! It is requeued by default, i.e., redelivered by the broker right away, or dead-lettered by `with_requeue(false)`,
+//! thus the redelivery can be delayed by the broker, e.g., by a dead letter exchange routing to a queue
+//! whose message TTL routes the deliveries back. The successful deliveries are left to be acknowledged by the caller.
+use super::{block_error_of, MessageError};
+use crate::base::{EntryGuard, ResourceType, TrafficType};
+use crate::{logging, EntryBuilder, Error};
+use lapin::message::Delivery;
+use lapin::options::BasicNackOptions;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+
+pub type ResourceExtractor = Arc<dyn Fn(&str, &str) -> String + Send + Sync>;
+
+/// `SentinelConsumer` guards the handlers of the deliveries consumed from a queue.
+#[derive(Clone)]
+pub struct SentinelConsumer {
+    queue: String,
+    resource_extractor: ResourceExtractor,
+    requeue: bool,
+}
+
+impl fmt::Debug for SentinelConsumer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SentinelConsumer")
+            .field("queue", &self.queue)
+            .field("requeue", &self.requeue)
+            .finish()
+    }
+}
+
+impl SentinelConsumer {
+    pub fn new(queue: impl Into<String>) -> Self {
+        SentinelConsumer {
+            queue: queue.into(),
+            resource_extractor: Arc::new(|queue, _| queue.into()),
+            requeue: true,
+        }
+    }
+
+    /// `with_resource_extractor` names the resource by the queue and the routing key of the delivery,
+    /// e.g., `|queue, routing_key| format!("{}:{}", queue, routing_key)` for the limits per routing key,
+    /// the resource is the queue by default.
+    pub fn with_resource_extractor(
+        mut self,
+        f: impl Fn(&str, &str) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.resource_extractor = Arc::new(f);
+        self
+    }
+
+    /// `with_requeue` sets whether the blocked deliveries are requeued (by default),
+    /// or dead-lettered to delay the redelivery by the dead letter exchange of the queue.
+    pub fn with_requeue(mut self, requeue: bool) -> Self {
+        self.requeue = requeue;
+        self
+    }
+
+    /// `process` runs the handler of the delivery if the entry of its resource passes,
+    /// the error of the handler is recorded and returned as `MessageError::Handler`,
+    /// otherwise the delivery is rejected (see `with_requeue()`) and `MessageError::Blocked` is returned.
+    pub async fn process<F, Fut, T, E>(
+        &self,
+        delivery: &Delivery,
+        handler: F,
+    ) -> Result<T, MessageError<E>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: fmt::Display,
+    {
+        let resource = (self.resource_extractor)(&self.queue, delivery.routing_key.as_str());
+        let entry = match EntryBuilder::new(resource)
+            .with_resource_type(ResourceType::MQ)
+            .with_traffic_type(TrafficType::Inbound)
+            .build_async_deferred()
+        {
+            Ok(entry) => entry,
+            Err(err) => {
+                let block_error = match block_error_of(err) {
+                    Ok(block_error) => block_error,
+                    // the message is handled without being guarded, rather than dropped
+                    Err(_) => return handler().await.map_err(MessageError::Handler),
+                };
+                self.reject(delivery).await;
+                return Err(MessageError::Blocked(block_error));
+            }
+        };
+        let guard = EntryGuard::new(entry);
+        guard.entry().wait().await;
+        let result = guard.catch_panic(handler()).await;
+        if let Err(err) = &result {
+            guard.set_err(Error::msg(err.to_string()));
+        }
+        guard.exit();
+        result.map_err(MessageError::Handler)
+    }
+
+    async fn reject(&self, delivery: &Delivery) {
+        let options = BasicNackOptions {
+            multiple: false,
+            requeue: self.requeue,
+        };
+        if let Err(err) = delivery.nack(options).await {
+            logging::warn!(
+                "[AMQP] Failed to reject the blocked delivery {} of queue {}, error: {:?}",
+                delivery.delivery_tag,
+                self.queue,
+                err
+            );
+        }
+    }
+}
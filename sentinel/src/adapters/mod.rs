@@ -0,0 +1,311 @@
+//! mod `adapters` integrates Sentinel with the web frameworks, the RPC frameworks and the clients.
+//! Each adapter is gated by the feature named after the integrated framework (e.g., `axum`),
+//! which enables the `async` feature as well. The adapters share the conventions:
+//!
+//!  - the inbound resources are named by the routes, the requests matching no route share `unmatched_resource()`
+//!  - a blocked inbound request is answered with `429 Too Many Requests`,
+//!    or `503 Service Unavailable` if it is rejected by an open circuit breaker,
+//!    with the `Retry-After` header if the hint is provided by the rules, and the details in a JSON body
+//!  - the errors of building the entries other than the `BlockError` are logged and answered as the internal errors
+//!    (e.g., `500 Internal Server Error`), or the invocation is not guarded if there is no such response (e.g., the subscribers)
+//!  - the server errors (5xx) are recorded as the business errors, which are consumed by the circuit breakers
+//!  - the entry is exited when the response is produced, when the handler panics
+//!    (the panic is recorded as an error and resumed) and when the request is cancelled (e.g., the client disconnects),
+//!    see `base::EntryGuard`
+//!  - the HTTP adapters (and the gRPC server by the metadata) report the quota of the flow rules
+//!    by the `RateLimit-Limit` and `RateLimit-Remaining` headers once `with_rate_limit_headers(true)` is set,
+//!    see `rate_limit_headers()`
+//!  - the waits required by the rules (e.g., the queueing of the throttling flow rules and the latency faults)
+//!    are awaited before the invocation, rather than blocking the thread, see `EntryBuilder::build_async_deferred()`
+use crate::base::{BlockError, BlockType, MetricEvent};
+use crate::{flow, logging, Error};
+use serde_json::json;
+use std::convert::TryFrom;
+
+#[cfg(feature = "actix-web")]
+#[cfg_attr(docsrs, doc(cfg(feature = "actix-web")))]
+pub mod actix_web;
+#[cfg(feature = "async-graphql")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async-graphql")))]
+pub mod async_graphql;
+#[cfg(feature = "async-nats")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async-nats")))]
+pub mod async_nats;
+#[cfg(feature = "axum")]
+#[cfg_attr(docsrs, doc(cfg(feature = "axum")))]
+pub mod axum;
+#[cfg(feature = "hyper")]
+#[cfg_attr(docsrs, doc(cfg(feature = "hyper")))]
+pub mod hyper;
+#[cfg(feature = "lapin")]
+#[cfg_attr(docsrs, doc(cfg(feature = "lapin")))]
+pub mod lapin;
+#[cfg(feature = "poem")]
+#[cfg_attr(docsrs, doc(cfg(feature = "poem")))]
+pub mod poem;
+#[cfg(feature = "proxy-wasm")]
+#[cfg_attr(docsrs, doc(cfg(feature = "proxy-wasm")))]
+pub mod proxy_wasm;
+#[cfg(feature = "rdkafka")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rdkafka")))]
+pub mod rdkafka;
+#[cfg(feature = "reqwest-middleware")]
+#[cfg_attr(docsrs, doc(cfg(feature = "reqwest-middleware")))]
+pub mod reqwest_middleware;
+#[cfg(feature = "rocket")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rocket")))]
+pub mod rocket;
+#[cfg(feature = "salvo")]
+#[cfg_attr(docsrs, doc(cfg(feature = "salvo")))]
+pub mod salvo;
+#[cfg(feature = "sqlx")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sqlx")))]
+pub mod sqlx;
+#[cfg(feature = "tonic")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tonic")))]
+pub mod tonic;
+#[cfg(feature = "tower")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tower")))]
+pub mod tower;
+#[cfg(feature = "warp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "warp")))]
+pub mod warp;
+
+/// `unmatched_resource` is the resource of the inbound requests matching no route, i.e., `{method}:<unmatched>`,
+/// rather than the raw paths (e.g., of the probes answered with 404), thus the resources are bounded by the routes.
+pub fn unmatched_resource(method: impl std::fmt::Display) -> String {
+    format!("{}:<unmatched>", method)
+}
+
+/// `block_status_code` returns the HTTP status code of the blocked request.
+pub fn block_status_code(block_error: &BlockError) -> u16 {
+    match block_error.block_type() {
+        BlockType::CircuitBreaking => 503,
+        _ => 429,
+    }
+}
+
+/// `retry_after_secs` rounds up the retry-after hint of the block error to the seconds of the `Retry-After` header.
+pub fn retry_after_secs(block_error: &BlockError) -> Option<u64> {
+    block_error.retry_after().map(|retry_after| {
+        let secs = retry_after.as_secs();
+        if retry_after.subsec_nanos() > 0 {
+            secs + 1
+        } else {
+            secs
+        }
+    })
+}
+
+/// `block_body` returns the JSON details of the block error, the body of the default block responses.
+pub fn block_body(block_error: &BlockError) -> String {
+    json!({
+        "resource": block_error.resource(),
+        "block_type": block_error.block_type().to_string(),
+        "message": block_error.block_msg(),
+        "rule_id": block_error.triggered_rule().and_then(|r| r.rule_id()),
+        "retry_after_ms": block_error.retry_after().map(|d| d.as_millis() as u64),
+    })
+    .to_string()
+}
+
+/// `RateLimit` is the quota of a flow rule in the current statistic window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// the threshold of the rule, i.e., the number of the requests allowed in the statistic window
+    pub limit: u64,
+    /// the number of the requests still allowed in the current statistic window
+    pub remaining: u64,
+}
+
+/// `rate_limit` returns the quota of the most restrictive flow rule of the resource rejecting the exceeded requests,
+/// the throttling rules pace the requests instead and are skipped, `None` if there is no such rule.
+pub fn rate_limit(resource: &str) -> Option<RateLimit> {
+    flow::get_traffic_controller_list_for(&resource.to_owned())
+        .iter()
+        .filter(|controller| controller.rule().control_strategy == flow::ControlStrategy::Reject)
+        .map(|controller| {
+            let threshold = controller
+                .get_calculator()
+                .lock()
+                .unwrap()
+                .calculate_allowed_threshold(1, 0);
+            let limit = threshold.max(0.0) as u64;
+            let passed = controller.stat().read_only_metric().sum(MetricEvent::Pass);
+            RateLimit {
+                limit,
+                remaining: limit.saturating_sub(passed),
+            }
+        })
+        .min_by_key(|quota| quota.remaining)
+}
+
+/// `rate_limit_headers` returns the headers reporting the quota of the resource to the clients:
+///
+///  - `RateLimit-Limit` and `RateLimit-Remaining` of `rate_limit()`, the remaining is 0 if the request is blocked by the flow rules
+///  - `Retry-After` of the block error in seconds, if the hint is provided by the rules
+pub fn rate_limit_headers(
+    resource: &str,
+    block_error: Option<&BlockError>,
+) -> Vec<(&'static str, String)> {
+    let mut headers = Vec::with_capacity(3);
+    if let Some(mut quota) = rate_limit(resource) {
+        if block_error.map_or(false, |err| err.block_type() == BlockType::Flow) {
+            quota.remaining = 0;
+        }
+        headers.push(("RateLimit-Limit", quota.limit.to_string()));
+        headers.push(("RateLimit-Remaining", quota.remaining.to_string()));
+    }
+    if let Some(secs) = block_error.and_then(retry_after_secs) {
+        headers.push(("Retry-After", secs.to_string()));
+    }
+    headers
+}
+
+/// `block_error_of` downcasts the error of building the entry to the `BlockError`.
+/// The other errors are not caused by the rules, they are logged and returned,
+/// thus the adapters fail the invocation as an internal error, rather than reporting it as blocked.
+pub(crate) fn block_error_of(err: Error) -> Result<BlockError, Error> {
+    err.downcast::<BlockError>().map_err(|err| {
+        logging::error!("[Adapters] Failed to build the entry, error: {:?}", err);
+        err
+    })
+}
+
+/// `insert_headers` inserts the headers (e.g., of `rate_limit_headers()`) by `insert`, the invalid ones are skipped.
+/// The header types are left to `insert`, since the frameworks depend on different versions of `http`.
+pub(crate) fn insert_headers<N, V>(
+    headers: Vec<(&'static str, String)>,
+    mut insert: impl FnMut(N, V),
+) where
+    N: TryFrom<&'static str>,
+    V: TryFrom<String>,
+{
+    for (name, value) in headers {
+        if let (Ok(name), Ok(value)) = (N::try_from(name), V::try_from(value)) {
+            insert(name, value);
+        }
+    }
+}
+
+/// `MessageError` is the error of the message handlers guarded by the subscriber side adapters.
+#[cfg(any(feature = "async-nats", feature = "lapin"))]
+#[derive(Debug)]
+pub enum MessageError<E> {
+    /// the message is blocked without being handled, it is requeued if the broker supports the redelivery
+    Blocked(BlockError),
+    /// the error returned by the handler, which is recorded for the circuit breakers
+    Handler(E),
+}
+
+#[cfg(any(feature = "async-nats", feature = "lapin"))]
+impl<E> MessageError<E> {
+    /// `block_error` returns the `BlockError` if the message was blocked by Sentinel.
+    pub fn block_error(&self) -> Option<&BlockError> {
+        match self {
+            MessageError::Blocked(block_error) => Some(block_error),
+            MessageError::Handler(_) => None,
+        }
+    }
+}
+
+#[cfg(any(feature = "async-nats", feature = "lapin"))]
+impl<E: std::fmt::Display> std::fmt::Display for MessageError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MessageError::Blocked(block_error) => {
+                write!(f, "the message is blocked: {}", block_error)
+            }
+            MessageError::Handler(err) => err.fmt(f),
+        }
+    }
+}
+
+#[cfg(any(feature = "async-nats", feature = "lapin"))]
+impl<E: std::error::Error + 'static> std::error::Error for MessageError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MessageError::Blocked(block_error) => Some(block_error),
+            MessageError::Handler(err) => Some(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn block_response() {
+        let mut block_error = BlockError::new_with_msg(BlockType::Flow, "flow exceeded".into());
+        block_error.set_resource("GET /users/:id".into());
+        assert_eq!(block_status_code(&block_error), 429);
+        assert_eq!(retry_after_secs(&block_error), None);
+        block_error.set_retry_after(Duration::from_millis(1200));
+        assert_eq!(retry_after_secs(&block_error), Some(2));
+        let body: serde_json::Value = serde_json::from_str(&block_body(&block_error)).unwrap();
+        assert_eq!(body["resource"], "GET /users/:id");
+        assert_eq!(body["message"], "flow exceeded");
+        assert_eq!(body["retry_after_ms"], 1200);
+
+        let block_error = BlockError::new(BlockType::CircuitBreaking);
+        assert_eq!(block_status_code(&block_error), 503);
+    }
+
+    #[test]
+    #[ignore]
+    fn rate_limit_headers() {
+        let resource = String::from("GET:/adapters/rate_limit");
+        assert_eq!(rate_limit(&resource), None);
+        flow::load_rules_of_resource(
+            &resource,
+            vec![
+                Arc::new(flow::Rule {
+                    resource: resource.clone(),
+                    threshold: 100.0,
+                    ..Default::default()
+                }),
+                Arc::new(flow::Rule {
+                    resource: resource.clone(),
+                    threshold: 10.0,
+                    ..Default::default()
+                }),
+                Arc::new(flow::Rule {
+                    resource: resource.clone(),
+                    threshold: 1.0,
+                    control_strategy: flow::ControlStrategy::Throttling,
+                    ..Default::default()
+                }),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            rate_limit(&resource),
+            Some(RateLimit {
+                limit: 10,
+                remaining: 10
+            })
+        );
+        assert_eq!(
+            super::rate_limit_headers(&resource, None),
+            vec![
+                ("RateLimit-Limit", "10".to_owned()),
+                ("RateLimit-Remaining", "10".to_owned())
+            ]
+        );
+
+        let mut block_error = BlockError::new(BlockType::Flow);
+        block_error.set_retry_after(Duration::from_millis(300));
+        assert_eq!(
+            super::rate_limit_headers(&resource, Some(&block_error)),
+            vec![
+                ("RateLimit-Limit", "10".to_owned()),
+                ("RateLimit-Remaining", "0".to_owned()),
+                ("Retry-After", "1".to_owned())
+            ]
+        );
+        flow::clear_rules_of_resource(&resource);
+    }
+}
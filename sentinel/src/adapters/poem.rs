@@ -0,0 +1,214 @@
+//! The adapter of [poem](https://github.com/poem-web/poem).
+//! `Sentinel` is a `poem::Middleware`, it is applied by `EndpointExt::with()`,
+//! and builds an inbound entry for each request:
+//!
+//!  - the resource is `{method}:{path pattern}` by default, e.g., `GET:/users/:id`,
+//!    or `{method}:<unmatched>` if there is no path pattern (see `unmatched_resource()`)
+//!  - the origin is read from the header set by `with_origin_header()`
+//!  - the blocked request is answered by `block_response()`, unless another builder is set
+//!  - the server errors (the responses and the returned `poem::Error`s) are recorded for the circuit breakers
+//!  - the quota of the flow rules is reported by the `RateLimit-*` headers if `with_rate_limit_headers(true)` is set
+//!
+//! The path pattern is only known after routing, thus the middleware should be applied to the endpoints of the routes,
+//! e.g., `Route::new().at("/users/:id", get(user).with(Sentinel::new()))`,
+//! while the middleware applied to the whole `Route` sees no path pattern.
+use super::{
+    block_body, block_error_of, block_status_code, insert_headers, rate_limit_headers,
+    retry_after_secs, unmatched_resource,
+};
+use crate::base::{BlockError, EntryGuard, ResourceType, TrafficType};
+use crate::{EntryBuilder, Error};
+use poem::http::{header, HeaderName, HeaderValue, StatusCode};
+use poem::{Endpoint, IntoResponse, Middleware, PathPattern, Request, Response, Result};
+use std::fmt;
+use std::sync::Arc;
+
+pub type ResourceExtractor = Arc<dyn Fn(&Request) -> String + Send + Sync>;
+pub type BlockResponseBuilder = Arc<dyn Fn(&BlockError) -> Response + Send + Sync>;
+pub type ErrorPredicate = Arc<dyn Fn(StatusCode) -> bool + Send + Sync>;
+
+/// `block_response` is the default response of the blocked requests,
+/// i.e., 429 (or 503 for circuit breaking) with the `Retry-After` header and the JSON details.
+pub fn block_response(block_error: &BlockError) -> Response {
+    let mut response = Response::builder()
+        .status(
+            StatusCode::from_u16(block_status_code(block_error))
+                .unwrap_or(StatusCode::TOO_MANY_REQUESTS),
+        )
+        .header(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        )
+        .body(block_body(block_error));
+    if let Some(secs) = retry_after_secs(block_error) {
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+    }
+    response
+}
+
+fn default_resource(req: &Request) -> String {
+    match req.data::<PathPattern>() {
+        Some(pattern) => format!("{}:{}", req.method(), pattern.0),
+        None => unmatched_resource(req.method()),
+    }
+}
+
+/// `Sentinel` wraps the endpoints in `SentinelEndpoint`.
+#[derive(Clone)]
+pub struct Sentinel {
+    resource_extractor: ResourceExtractor,
+    origin_header: Option<HeaderName>,
+    block_response: BlockResponseBuilder,
+    is_error: ErrorPredicate,
+    rate_limit_headers: bool,
+}
+
+impl Default for Sentinel {
+    fn default() -> Self {
+        Sentinel {
+            resource_extractor: Arc::new(default_resource),
+            origin_header: None,
+            block_response: Arc::new(block_response),
+            is_error: Arc::new(|status| status.is_server_error()),
+            rate_limit_headers: false,
+        }
+    }
+}
+
+impl fmt::Debug for Sentinel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sentinel")
+            .field("origin_header", &self.origin_header)
+            .field("rate_limit_headers", &self.rate_limit_headers)
+            .finish()
+    }
+}
+
+impl Sentinel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `with_resource_extractor` replaces the resource derived from the path pattern.
+    pub fn with_resource_extractor(
+        mut self,
+        f: impl Fn(&Request) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.resource_extractor = Arc::new(f);
+        self
+    }
+
+    /// `with_origin_header` sets the header carrying the origin (caller) of the request, e.g., `x-sentinel-origin`.
+    pub fn with_origin_header(mut self, name: HeaderName) -> Self {
+        self.origin_header = Some(name);
+        self
+    }
+
+    /// `with_block_response` replaces the default `block_response()`.
+    pub fn with_block_response(
+        mut self,
+        f: impl Fn(&BlockError) -> Response + Send + Sync + 'static,
+    ) -> Self {
+        self.block_response = Arc::new(f);
+        self
+    }
+
+    /// `with_error_predicate` sets the status codes recorded as errors, the server errors by default.
+    pub fn with_error_predicate(
+        mut self,
+        f: impl Fn(StatusCode) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.is_error = Arc::new(f);
+        self
+    }
+
+    /// `with_rate_limit_headers` sets whether the responses carry the headers of `rate_limit_headers()`,
+    /// including the block responses, `false` by default.
+    pub fn with_rate_limit_headers(mut self, rate_limit_headers: bool) -> Self {
+        self.rate_limit_headers = rate_limit_headers;
+        self
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for Sentinel {
+    type Output = SentinelEndpoint<E>;
+
+    fn transform(&self, inner: E) -> Self::Output {
+        SentinelEndpoint {
+            inner,
+            config: self.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SentinelEndpoint<E> {
+    inner: E,
+    config: Sentinel,
+}
+
+impl<E: Endpoint> Endpoint for SentinelEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let resource = (self.config.resource_extractor)(&req);
+        let mut builder = EntryBuilder::new(resource.clone())
+            .with_resource_type(ResourceType::Web)
+            .with_traffic_type(TrafficType::Inbound);
+        if let Some(origin) = self
+            .config
+            .origin_header
+            .as_ref()
+            .and_then(|name| req.headers().get(name))
+            .and_then(|value| value.to_str().ok())
+        {
+            builder = builder.with_origin(origin.into());
+        }
+        let entry = match builder.build_async_deferred() {
+            Ok(entry) => entry,
+            Err(err) => {
+                let block_error = block_error_of(err).map_err(|err| {
+                    poem::Error::from_string(err.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+                })?;
+                let mut response = (self.config.block_response)(&block_error);
+                if self.config.rate_limit_headers {
+                    insert_headers(
+                        rate_limit_headers(&resource, Some(&block_error)),
+                        |name: HeaderName, value| {
+                            response.headers_mut().insert(name, value);
+                        },
+                    );
+                }
+                return Ok(response);
+            }
+        };
+        // the quota is taken when the request passes
+        let headers = if self.config.rate_limit_headers {
+            rate_limit_headers(&resource, None)
+        } else {
+            Vec::new()
+        };
+        let guard = EntryGuard::new(entry);
+        guard.entry().wait().await;
+        let mut result = guard
+            .catch_panic(self.inner.call(req))
+            .await
+            .map(IntoResponse::into_response);
+        if let Ok(response) = &mut result {
+            insert_headers(headers, |name: HeaderName, value| {
+                response.headers_mut().insert(name, value);
+            });
+        }
+        let status = match &result {
+            Ok(response) => response.status(),
+            Err(err) => err.status(),
+        };
+        if (self.config.is_error)(status) {
+            guard.set_err(Error::msg(format!("the response status is {}", status)));
+        }
+        guard.exit();
+        result
+    }
+}
@@ -0,0 +1,217 @@
+//! The shim of [proxy-wasm](https://github.com/proxy-wasm/proxy-wasm-rust-sdk), running the rules of Sentinel
+//! as an HTTP filter of Envoy (or Istio). The filter crate is built as a `cdylib` for `wasm32-wasip1`,
+//! and registers the root context:
+//!
+//! ```ignore
+//! proxy_wasm::main! {{
+//!     proxy_wasm::set_log_level(LogLevel::Info);
+//!     proxy_wasm::set_root_context(|_| Box::new(SentinelRootContext::new()));
+//! }}
+//! ```
+//!
+//! The plugin configuration is the JSON of `FilterConfig`, e.g.,
+//! `{"resource": "{method}:{path}", "origin_header": "x-sentinel-origin", "rules": {"flow": [...]}}`,
+//! the rules are loaded by `load_rule_set()` whenever the configuration is pushed by the proxy.
+//! Each request is guarded like the HTTP adapters: the blocked request is answered with 429 (or 503 for circuit breaking)
+//! by the proxy without reaching the upstream, and the server errors of the upstream are recorded for the circuit breakers.
+//!
+//! The clock of the host is the time source of Sentinel once the VM starts, see `utils::set_time_source()`,
+//! and the background tasks (e.g., the metric logs and the system metric collectors) are not started on wasm32,
+//! since there are no threads in the sandbox.
+use super::{block_body, block_error_of, block_status_code, retry_after_secs};
+use crate::base::{BlockError, EntryGuard, ResourceType, TrafficType};
+use crate::{load_rule_set, logging, utils, EntryBuilder, Error, Result, RuleSet};
+use proxy_wasm::hostcalls;
+use proxy_wasm::traits::{Context, HttpContext, RootContext};
+use proxy_wasm::types::{Action, ContextType};
+use serde::Deserialize;
+use std::fmt;
+use std::rc::Rc;
+use std::time::UNIX_EPOCH;
+
+/// `FilterConfig` is the plugin configuration of the filter.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FilterConfig {
+    /// the template of the resource, the placeholders are `{method}`, `{authority}` and `{path}` (without the query),
+    /// `{method}:{path}` by default
+    pub resource: String,
+    /// the header carrying the origin (caller) of the request
+    pub origin_header: Option<String>,
+    pub rules: RuleSet,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        FilterConfig {
+            resource: "{method}:{path}".into(),
+            origin_header: None,
+            rules: RuleSet::default(),
+        }
+    }
+}
+
+impl FilterConfig {
+    /// `render_resource` names the resource of the request by the template.
+    pub fn render_resource(&self, method: &str, authority: &str, path: &str) -> String {
+        let path = path.split('?').next().unwrap_or_default();
+        self.resource
+            .replace("{method}", method)
+            .replace("{authority}", authority)
+            .replace("{path}", path)
+    }
+}
+
+// the unix timestamp of the host clock in nanoseconds
+fn host_time_nanos() -> i128 {
+    hostcalls::get_current_time()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |elapsed| elapsed.as_nanos() as i128)
+}
+
+/// `SentinelRootContext` is the root context of the filter, it loads the plugin configuration
+/// and creates a `SentinelHttpContext` for each request.
+#[derive(Debug, Default)]
+pub struct SentinelRootContext {
+    config: Rc<FilterConfig>,
+}
+
+impl SentinelRootContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn config(&self) -> &FilterConfig {
+        &self.config
+    }
+
+    /// `configure` parses the plugin configuration and loads its rules,
+    /// nothing changes if the configuration (or any rule) is invalid.
+    pub fn configure(&mut self, configuration: &[u8]) -> Result<()> {
+        let config: FilterConfig = serde_json::from_slice(configuration)?;
+        load_rule_set(config.rules.clone())?;
+        self.config = Rc::new(config);
+        Ok(())
+    }
+}
+
+impl Context for SentinelRootContext {}
+
+impl RootContext for SentinelRootContext {
+    fn on_vm_start(&mut self, _vm_configuration_size: usize) -> bool {
+        utils::set_time_source(host_time_nanos);
+        true
+    }
+
+    fn on_configure(&mut self, _plugin_configuration_size: usize) -> bool {
+        let configuration = self.get_plugin_configuration().unwrap_or_default();
+        match self.configure(&configuration) {
+            Ok(()) => true,
+            Err(err) => {
+                logging::warn!(
+                    "[ProxyWasm] Failed to load the plugin configuration, error: {:?}",
+                    err
+                );
+                false
+            }
+        }
+    }
+
+    fn create_http_context(&self, _context_id: u32) -> Option<Box<dyn HttpContext>> {
+        Some(Box::new(SentinelHttpContext {
+            config: Rc::clone(&self.config),
+            guard: None,
+        }))
+    }
+
+    fn get_type(&self) -> Option<ContextType> {
+        Some(ContextType::HttpContext)
+    }
+}
+
+/// `SentinelHttpContext` guards a request, the entry is exited when the stream is logged (or the context is dropped).
+pub struct SentinelHttpContext {
+    config: Rc<FilterConfig>,
+    guard: Option<EntryGuard>,
+}
+
+impl fmt::Debug for SentinelHttpContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SentinelHttpContext")
+            .field("config", &self.config)
+            .field("guarded", &self.guard.is_some())
+            .finish()
+    }
+}
+
+impl SentinelHttpContext {
+    fn send_block_response(&self, block_error: &BlockError) {
+        let retry_after = retry_after_secs(block_error).map(|secs| secs.to_string());
+        let mut headers = vec![("content-type", "application/json")];
+        if let Some(retry_after) = &retry_after {
+            headers.push(("retry-after", retry_after.as_str()));
+        }
+        let body = block_body(block_error);
+        self.send_http_response(
+            block_status_code(block_error) as u32,
+            headers,
+            Some(body.as_bytes()),
+        );
+    }
+}
+
+impl Context for SentinelHttpContext {}
+
+impl HttpContext for SentinelHttpContext {
+    fn on_http_request_headers(&mut self, _num_headers: usize, _end_of_stream: bool) -> Action {
+        let header = |name: &str| self.get_http_request_header(name).unwrap_or_default();
+        let resource = self.config.render_resource(
+            &header(":method"),
+            &header(":authority"),
+            &header(":path"),
+        );
+        let mut builder = EntryBuilder::new(resource)
+            .with_resource_type(ResourceType::Web)
+            .with_traffic_type(TrafficType::Inbound);
+        if let Some(origin) = self
+            .config
+            .origin_header
+            .as_ref()
+            .and_then(|name| self.get_http_request_header(name))
+        {
+            builder = builder.with_origin(origin);
+        }
+        match builder.build_async() {
+            Ok(entry) => {
+                self.guard = Some(EntryGuard::new(entry));
+                Action::Continue
+            }
+            Err(err) => {
+                match block_error_of(err) {
+                    Ok(block_error) => self.send_block_response(&block_error),
+                    Err(_) => self.send_http_response(500, Vec::new(), None),
+                }
+                Action::Pause
+            }
+        }
+    }
+
+    fn on_http_response_headers(&mut self, _num_headers: usize, _end_of_stream: bool) -> Action {
+        let status = self
+            .get_http_response_header(":status")
+            .and_then(|status| status.parse::<u16>().ok());
+        if let (Some(guard), Some(status)) = (&self.guard, status) {
+            if status >= 500 {
+                guard.set_err(Error::msg(format!("the response status is {}", status)));
+            }
+        }
+        Action::Continue
+    }
+
+    fn on_log(&mut self) {
+        if let Some(guard) = self.guard.take() {
+            guard.exit();
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! The consumer side adapter of [rdkafka](https://github.com/fede1024/rust-rdkafka).
+//! `SentinelConsumer` guards the processing of the consumed messages by the resource of the topic
+//! (or the topic and the partition, see `with_resource_extractor()`), thus the flow rules limit the processing rate
+//! and the circuit breakers are fed with the errors of the handlers:
+//!
+//! ```ignore
+//! let consumer = SentinelConsumer::new(stream_consumer);
+//! loop {
+//!     let message = consumer.consumer().recv().await?;
+//!     consumer.process(&message, || handle(&message)).await?;
+//! }
+//! ```
+//!
+//! The messages are never dropped: if the entry is blocked, the partitions of the messages are paused,
+//! and the entry is retried after the retry-after hint of the block error (or the backoff set by `with_backoff()`)
+//! until it passes, then the partitions are resumed, thus the broker keeps the unprocessed messages instead of the local queues.
+//! The batch of messages is acquired at once by `process_batch()`, with the batch count of the size of the batch.
+use super::block_error_of;
+use crate::base::{EntryGuard, ResourceType, TrafficType};
+use crate::{logging, EntryBuilder, Error};
+use rdkafka::consumer::{BaseConsumer, ConsumerContext, StreamConsumer};
+use rdkafka::error::KafkaResult;
+use rdkafka::{Message, TopicPartitionList};
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub type ResourceExtractor = Arc<dyn Fn(&str, i32) -> String + Send + Sync>;
+
+/// `PartitionControl` pauses and resumes the fetching of the partitions, implemented by the rdkafka consumers.
+pub trait PartitionControl {
+    fn pause(&self, topic: &str, partition: i32) -> KafkaResult<()>;
+    fn resume(&self, topic: &str, partition: i32) -> KafkaResult<()>;
+}
+
+fn partition_list(topic: &str, partition: i32) -> TopicPartitionList {
+    let mut partitions = TopicPartitionList::new();
+    partitions.add_partition(topic, partition);
+    partitions
+}
+
+impl<C: ConsumerContext> PartitionControl for BaseConsumer<C> {
+    fn pause(&self, topic: &str, partition: i32) -> KafkaResult<()> {
+        rdkafka::consumer::Consumer::pause(self, &partition_list(topic, partition))
+    }
+
+    fn resume(&self, topic: &str, partition: i32) -> KafkaResult<()> {
+        rdkafka::consumer::Consumer::resume(self, &partition_list(topic, partition))
+    }
+}
+
+impl<C: ConsumerContext, R> PartitionControl for StreamConsumer<C, R> {
+    fn pause(&self, topic: &str, partition: i32) -> KafkaResult<()> {
+        rdkafka::consumer::Consumer::pause(self, &partition_list(topic, partition))
+    }
+
+    fn resume(&self, topic: &str, partition: i32) -> KafkaResult<()> {
+        rdkafka::consumer::Consumer::resume(self, &partition_list(topic, partition))
+    }
+}
+
+/// `SentinelConsumer` wraps the consumer to guard the processing of its messages.
+pub struct SentinelConsumer<C> {
+    consumer: C,
+    resource_extractor: ResourceExtractor,
+    backoff: Duration,
+}
+
+impl<C> fmt::Debug for SentinelConsumer<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SentinelConsumer")
+            .field("backoff", &self.backoff)
+            .finish()
+    }
+}
+
+impl<C: PartitionControl> SentinelConsumer<C> {
+    pub fn new(consumer: C) -> Self {
+        SentinelConsumer {
+            consumer,
+            resource_extractor: Arc::new(|topic, _| topic.into()),
+            backoff: Duration::from_millis(100),
+        }
+    }
+
+    pub fn consumer(&self) -> &C {
+        &self.consumer
+    }
+
+    /// `with_resource_extractor` names the resource by the topic and the partition,
+    /// e.g., `|topic, partition| format!("{}-{}", topic, partition)` for the limits per partition,
+    /// the resource is the topic by default.
+    pub fn with_resource_extractor(
+        mut self,
+        f: impl Fn(&str, i32) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.resource_extractor = Arc::new(f);
+        self
+    }
+
+    /// `with_backoff` sets the pause of the blocked messages if the block error carries no retry-after hint, 100ms by default.
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// `process` runs the handler of the message once the entry of its resource passes,
+    /// the error of the handler is recorded and returned.
+    pub async fn process<M, F, Fut, T, E>(&self, message: &M, handler: F) -> Result<T, E>
+    where
+        M: Message,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: fmt::Display,
+    {
+        self.process_batch(std::slice::from_ref(message), handler)
+            .await
+    }
+
+    /// `process_batch` runs the handler of the batch once the entry of the resource of the first message
+    /// passes with the batch count of the size of the batch, all the partitions of the batch are paused if it is blocked.
+    /// The handler of an empty batch is run without the entry.
+    pub async fn process_batch<M, F, Fut, T, E>(&self, messages: &[M], handler: F) -> Result<T, E>
+    where
+        M: Message,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: fmt::Display,
+    {
+        let guard = match messages.first() {
+            Some(first) => self.acquire(first, messages).await,
+            None => None,
+        };
+        let result = match &guard {
+            Some(guard) => guard.catch_panic(handler()).await,
+            None => handler().await,
+        };
+        if let Some(guard) = guard {
+            if let Err(err) = &result {
+                guard.set_err(Error::msg(err.to_string()));
+            }
+            guard.exit();
+        }
+        result
+    }
+
+    // the errors of building the entry other than the `BlockError` are logged,
+    // and the messages are processed without the entry rather than paused forever
+    async fn acquire<M: Message>(&self, first: &M, messages: &[M]) -> Option<EntryGuard> {
+        let resource = (self.resource_extractor)(first.topic(), first.partition());
+        let mut paused: Option<Paused<'_, C>> = None;
+        loop {
+            let result = EntryBuilder::new(resource.clone())
+                .with_resource_type(ResourceType::MQ)
+                .with_traffic_type(TrafficType::Inbound)
+                .with_batch_count(messages.len() as u32)
+                .build_async_deferred();
+            let block_error = match result {
+                // the partitions are resumed when `paused` is dropped
+                Ok(entry) => {
+                    let guard = EntryGuard::new(entry);
+                    guard.entry().wait().await;
+                    return Some(guard);
+                }
+                Err(err) => match block_error_of(err) {
+                    Ok(block_error) => block_error,
+                    Err(_) => return None,
+                },
+            };
+            if paused.is_none() {
+                paused = Some(Paused::new(&self.consumer, messages));
+            }
+            futures_timer::Delay::new(block_error.retry_after().unwrap_or(self.backoff)).await;
+        }
+    }
+}
+
+// Paused pauses the partitions of the messages until it is dropped,
+// thus the partitions are resumed even if the processing is cancelled
+struct Paused<'a, C: PartitionControl> {
+    consumer: &'a C,
+    partitions: Vec<(String, i32)>,
+}
+
+impl<'a, C: PartitionControl> Paused<'a, C> {
+    fn new<M: Message>(consumer: &'a C, messages: &[M]) -> Self {
+        let mut partitions: Vec<(String, i32)> = messages
+            .iter()
+            .map(|message| (message.topic().to_owned(), message.partition()))
+            .collect();
+        partitions.sort_unstable();
+        partitions.dedup();
+        let paused = Paused {
+            consumer,
+            partitions,
+        };
+        paused.for_each(C::pause);
+        paused
+    }
+
+    fn for_each(&self, f: fn(&C, &str, i32) -> KafkaResult<()>) {
+        for (topic, partition) in &self.partitions {
+            if let Err(err) = f(self.consumer, topic, *partition) {
+                logging::warn!(
+                    "[Kafka] Failed to pause or resume the partition {} of topic {}, error: {:?}",
+                    partition,
+                    topic,
+                    err
+                );
+            }
+        }
+    }
+}
+
+impl<C: PartitionControl> Drop for Paused<'_, C> {
+    fn drop(&mut self) {
+        self.for_each(C::resume);
+    }
+}
@@ -0,0 +1,148 @@
+//! The adapter of [reqwest-middleware](https://github.com/TrueLayer/reqwest-middleware),
+//! guarding the outbound HTTP calls, e.g.,
+//! `ClientBuilder::new(reqwest::Client::new()).with(SentinelMiddleware::new()).build()`.
+//! The resource is the host (with the explicit port) of the URL by default, thus the flow rules
+//! and the circuit breakers are kept per dependency, or it is rendered from the template set by
+//! `with_resource_template()`, whose placeholders are:
+//!
+//!  - `{method}`, e.g., `GET`
+//!  - `{scheme}`, e.g., `https`
+//!  - `{host}`, the host with the explicit port, e.g., `api.example.com:8443`
+//!  - `{path}`, e.g., `/users/1`
+//!
+//! The statuses matching the error predicate (the server errors by default), the timeouts and the other
+//! failures of sending are recorded as errors, and the round trip is measured until the response headers arrive.
+//! The blocked call fails with `reqwest_middleware::Error::Middleware` wrapping the `BlockError`
+//! without being sent, which is extracted by `block_error()`.
+use super::block_error_of;
+use crate::base::{BlockError, EntryGuard, ResourceType, TrafficType};
+use crate::{EntryBuilder, Error};
+use async_trait::async_trait;
+use http::Extensions;
+use reqwest_middleware::reqwest::{Request, Response, StatusCode};
+use reqwest_middleware::{Middleware, Next, Result};
+use std::fmt;
+use std::sync::Arc;
+
+pub type ResourceExtractor = Arc<dyn Fn(&Request) -> String + Send + Sync>;
+pub type ErrorPredicate = Arc<dyn Fn(StatusCode) -> bool + Send + Sync>;
+
+/// `block_error` returns the `BlockError` if the call was blocked by Sentinel.
+pub fn block_error(err: &reqwest_middleware::Error) -> Option<&BlockError> {
+    match err {
+        reqwest_middleware::Error::Middleware(err) => err.downcast_ref::<BlockError>(),
+        _ => None,
+    }
+}
+
+fn render_resource(template: &str, req: &Request) -> String {
+    let url = req.url();
+    let host = match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_owned(),
+        (None, _) => String::new(),
+    };
+    template
+        .replace("{method}", req.method().as_str())
+        .replace("{scheme}", url.scheme())
+        .replace("{host}", &host)
+        .replace("{path}", url.path())
+}
+
+/// `SentinelMiddleware` builds an outbound entry for each call of the client.
+#[derive(Clone)]
+pub struct SentinelMiddleware {
+    resource_extractor: ResourceExtractor,
+    is_error: ErrorPredicate,
+    timeout_as_error: bool,
+}
+
+impl Default for SentinelMiddleware {
+    fn default() -> Self {
+        SentinelMiddleware {
+            resource_extractor: Arc::new(|req| render_resource("{host}", req)),
+            is_error: Arc::new(|status| status.is_server_error()),
+            timeout_as_error: true,
+        }
+    }
+}
+
+impl fmt::Debug for SentinelMiddleware {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SentinelMiddleware")
+            .field("timeout_as_error", &self.timeout_as_error)
+            .finish()
+    }
+}
+
+impl SentinelMiddleware {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `with_resource_template` names the resource by the template, e.g., `{method}:{host}{path}`.
+    pub fn with_resource_template(self, template: impl Into<String>) -> Self {
+        let template = template.into();
+        self.with_resource_extractor(move |req| render_resource(&template, req))
+    }
+
+    /// `with_resource_extractor` names the resource by the request, it replaces the template.
+    pub fn with_resource_extractor(
+        mut self,
+        f: impl Fn(&Request) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.resource_extractor = Arc::new(f);
+        self
+    }
+
+    /// `with_error_predicate` sets the status codes recorded as errors, the server errors by default,
+    /// e.g., `|status| status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS`.
+    pub fn with_error_predicate(
+        mut self,
+        f: impl Fn(StatusCode) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.is_error = Arc::new(f);
+        self
+    }
+
+    /// `with_timeout_as_error` sets whether the timeouts are recorded as errors, `true` by default.
+    pub fn with_timeout_as_error(mut self, timeout_as_error: bool) -> Self {
+        self.timeout_as_error = timeout_as_error;
+        self
+    }
+}
+
+#[async_trait]
+impl Middleware for SentinelMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        let resource = (self.resource_extractor)(&req);
+        let entry = EntryBuilder::new(resource)
+            .with_resource_type(ResourceType::Web)
+            .with_traffic_type(TrafficType::Outbound)
+            .build_async_deferred()
+            .map_err(|err| {
+                reqwest_middleware::Error::Middleware(
+                    block_error_of(err).map_or_else(|err| err, Error::new),
+                )
+            })?;
+        let guard = EntryGuard::new(entry);
+        guard.entry().wait().await;
+        let result = guard.catch_panic(next.run(req, extensions)).await;
+        match &result {
+            Ok(response) if (self.is_error)(response.status()) => guard.set_err(Error::msg(
+                format!("the response status is {}", response.status()),
+            )),
+            Err(reqwest_middleware::Error::Reqwest(err))
+                if err.is_timeout() && !self.timeout_as_error => {}
+            Err(err) => guard.set_err(Error::msg(err.to_string())),
+            _ => {}
+        }
+        guard.exit();
+        result
+    }
+}
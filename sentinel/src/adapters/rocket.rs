@@ -0,0 +1,234 @@
+//! The adapter of [Rocket](https://github.com/rwf2/Rocket).
+//! Since the route of a request is only known after routing, the entries are opened by the request guard
+//! `SentinelGuard`, which is declared as an argument of the handlers, e.g., `fn index(_guard: SentinelGuard)`,
+//! and the fairing `Sentinel` (attached by `rocket::build().attach(Sentinel::new())`) completes them:
+//!
+//!  - the resource is `{method}:{route}`, e.g., `GET:/users/<id>`, see `unmatched_resource()` for the requests without the route
+//!  - the origin is read from the header set by `with_origin_header()`
+//!  - the blocked request is short-circuited before the handler, and answered by the block responder,
+//!    `block_response()` by default
+//!  - the server errors (including the panicked handlers) are recorded for the circuit breakers
+//!  - the quota of the flow rules is reported by the `RateLimit-*` headers if `with_rate_limit_headers(true)` is set
+//!
+//! The entry is exited when the response is produced, or when the request is dropped.
+use super::{
+    block_body, block_error_of, block_status_code, rate_limit_headers, retry_after_secs,
+    unmatched_resource,
+};
+use crate::base::{BlockError, EntryGuard, ResourceType, TrafficType};
+use crate::{EntryBuilder, Error};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::Response;
+use std::fmt;
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+
+pub type BlockResponder = Arc<dyn Fn(&BlockError) -> Response<'static> + Send + Sync>;
+pub type ErrorPredicate = Arc<dyn Fn(Status) -> bool + Send + Sync>;
+
+/// `block_response` is the default response of the blocked requests,
+/// i.e., 429 (or 503 for circuit breaking) with the `Retry-After` header and the JSON details.
+pub fn block_response(block_error: &BlockError) -> Response<'static> {
+    let body = block_body(block_error);
+    let mut response = Response::build()
+        .status(
+            Status::from_code(block_status_code(block_error)).unwrap_or(Status::TooManyRequests),
+        )
+        .header(ContentType::JSON)
+        .sized_body(body.len(), Cursor::new(body))
+        .finalize();
+    if let Some(secs) = retry_after_secs(block_error) {
+        response.set_header(Header::new("Retry-After", secs.to_string()));
+    }
+    response
+}
+
+/// `Sentinel` is the fairing completing the entries opened by `SentinelGuard`.
+#[derive(Clone)]
+pub struct Sentinel {
+    origin_header: Option<String>,
+    block_responder: BlockResponder,
+    is_error: ErrorPredicate,
+    rate_limit_headers: bool,
+}
+
+impl Default for Sentinel {
+    fn default() -> Self {
+        Sentinel {
+            origin_header: None,
+            block_responder: Arc::new(block_response),
+            is_error: Arc::new(|status| status.class().is_server_error()),
+            rate_limit_headers: false,
+        }
+    }
+}
+
+impl fmt::Debug for Sentinel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sentinel")
+            .field("origin_header", &self.origin_header)
+            .field("rate_limit_headers", &self.rate_limit_headers)
+            .finish()
+    }
+}
+
+impl Sentinel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `with_origin_header` sets the header carrying the origin (caller) of the request, e.g., `X-Sentinel-Origin`.
+    pub fn with_origin_header(mut self, name: impl Into<String>) -> Self {
+        self.origin_header = Some(name.into());
+        self
+    }
+
+    /// `with_block_responder` replaces the default `block_response()`.
+    pub fn with_block_responder(
+        mut self,
+        f: impl Fn(&BlockError) -> Response<'static> + Send + Sync + 'static,
+    ) -> Self {
+        self.block_responder = Arc::new(f);
+        self
+    }
+
+    /// `with_error_predicate` sets the statuses recorded as errors, the server errors by default.
+    pub fn with_error_predicate(
+        mut self,
+        f: impl Fn(Status) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.is_error = Arc::new(f);
+        self
+    }
+
+    /// `with_rate_limit_headers` sets whether the responses carry the headers of `rate_limit_headers()`,
+    /// including the block responses, `false` by default.
+    pub fn with_rate_limit_headers(mut self, rate_limit_headers: bool) -> Self {
+        self.rate_limit_headers = rate_limit_headers;
+        self
+    }
+}
+
+fn set_headers(res: &mut Response<'_>, pairs: Vec<(&'static str, String)>) {
+    for (name, value) in pairs {
+        res.set_header(Header::new(name, value));
+    }
+}
+
+// the request-local state shared by the fairing and the guard
+#[derive(Default)]
+struct RequestState {
+    config: Option<Sentinel>,
+    guard: Mutex<Option<EntryGuard>>,
+    blocked: Mutex<Option<BlockError>>,
+    // the quota taken when the request passes
+    headers: Mutex<Vec<(&'static str, String)>>,
+}
+
+#[rocket::async_trait]
+impl Fairing for Sentinel {
+    fn info(&self) -> Info {
+        Info {
+            name: "Sentinel",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut rocket::Data<'_>) {
+        req.local_cache(|| RequestState {
+            config: Some(self.clone()),
+            ..Default::default()
+        });
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let state = req.local_cache(RequestState::default);
+        if let Some(block_error) = state.blocked.lock().unwrap().take() {
+            *res = (self.block_responder)(&block_error);
+            if self.rate_limit_headers {
+                set_headers(
+                    res,
+                    rate_limit_headers(&block_error.resource(), Some(&block_error)),
+                );
+            }
+            return;
+        }
+        set_headers(res, std::mem::take(&mut *state.headers.lock().unwrap()));
+        if let Some(guard) = state.guard.lock().unwrap().take() {
+            if (self.is_error)(res.status()) {
+                guard.set_err(Error::msg(format!(
+                    "the response status is {}",
+                    res.status()
+                )));
+            }
+            guard.exit();
+        }
+    }
+}
+
+/// `SentinelGuard` is the request guard opening the entry of the route,
+/// the blocked request fails with 429 (or 503 for circuit breaking) and the `BlockError`,
+/// which is replaced by the block responder if the fairing `Sentinel` is attached.
+/// The other errors of building the entry fail with 500.
+#[derive(Debug)]
+pub struct SentinelGuard {
+    resource: String,
+}
+
+impl SentinelGuard {
+    pub fn resource(&self) -> &str {
+        &self.resource
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SentinelGuard {
+    type Error = Error;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let resource = match req.route() {
+            Some(route) => format!("{}:{}", req.method(), route.uri),
+            None => unmatched_resource(req.method()),
+        };
+        let state = req.local_cache(RequestState::default);
+        let mut builder = EntryBuilder::new(resource.clone())
+            .with_resource_type(ResourceType::Web)
+            .with_traffic_type(TrafficType::Inbound);
+        let origin = state
+            .config
+            .as_ref()
+            .and_then(|config| config.origin_header.as_ref())
+            .and_then(|name| req.headers().get_one(name));
+        if let Some(origin) = origin {
+            builder = builder.with_origin(origin.into());
+        }
+        match builder.build_async_deferred() {
+            Ok(entry) => {
+                let guard = EntryGuard::new(entry);
+                guard.entry().wait().await;
+                // the entry of the previous guard (e.g., a forwarded request) is exited
+                *state.guard.lock().unwrap() = Some(guard);
+                if state
+                    .config
+                    .as_ref()
+                    .map_or(false, |config| config.rate_limit_headers)
+                {
+                    *state.headers.lock().unwrap() = rate_limit_headers(&resource, None);
+                }
+                Outcome::Success(SentinelGuard { resource })
+            }
+            Err(err) => {
+                let block_error = match block_error_of(err) {
+                    Ok(block_error) => block_error,
+                    Err(err) => return Outcome::Error((Status::InternalServerError, err)),
+                };
+                let status = Status::from_code(block_status_code(&block_error))
+                    .unwrap_or(Status::TooManyRequests);
+                *state.blocked.lock().unwrap() = Some(block_error.clone());
+                Outcome::Error((status, Error::new(block_error)))
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! The adapter of [salvo](https://github.com/salvo-rs/salvo).
+//! `Sentinel` is a `salvo_core::Handler` used as a hoop, i.e., `Router::hoop(Sentinel::new())`,
+//! and builds an inbound entry for each request:
+//!
+//!  - the resource is `{method}:/{matched path}` by default, e.g., `GET:/users/{id}`,
+//!    or `{method}:<unmatched>` if there is no matched path (see `unmatched_resource()`)
+//!  - the origin is read from the header set by `with_origin_header()`
+//!  - the blocked request is answered by `block_response()`, unless another responder is set,
+//!    and the rest handlers are skipped
+//!  - the server errors are recorded for the circuit breakers
+//!  - the quota of the flow rules is reported by the `RateLimit-*` headers if `with_rate_limit_headers(true)` is set
+use super::{
+    block_body, block_error_of, block_status_code, insert_headers, rate_limit_headers,
+    retry_after_secs, unmatched_resource,
+};
+use crate::base::{BlockError, EntryGuard, ResourceType, TrafficType};
+use crate::{EntryBuilder, Error};
+use salvo_core::http::{header, HeaderName, HeaderValue, StatusCode};
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler, Request, Response};
+use std::fmt;
+use std::sync::Arc;
+
+pub type ResourceExtractor = Arc<dyn Fn(&Request) -> String + Send + Sync>;
+pub type BlockResponder = Arc<dyn Fn(&BlockError, &mut Response) + Send + Sync>;
+pub type ErrorPredicate = Arc<dyn Fn(StatusCode) -> bool + Send + Sync>;
+
+/// `block_response` writes the default response of the blocked requests,
+/// i.e., 429 (or 503 for circuit breaking) with the `Retry-After` header and the JSON details.
+pub fn block_response(block_error: &BlockError, res: &mut Response) {
+    res.status_code(
+        StatusCode::from_u16(block_status_code(block_error))
+            .unwrap_or(StatusCode::TOO_MANY_REQUESTS),
+    );
+    let headers = res.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    if let Some(secs) = retry_after_secs(block_error) {
+        headers.insert(header::RETRY_AFTER, HeaderValue::from(secs));
+    }
+    res.body(block_body(block_error));
+}
+
+fn default_resource(req: &Request) -> String {
+    match req.matched_path() {
+        "" => unmatched_resource(req.method()),
+        path => format!("{}:/{}", req.method(), path),
+    }
+}
+
+/// `Sentinel` is the hoop guarding the handlers of the router.
+#[derive(Clone)]
+pub struct Sentinel {
+    resource_extractor: ResourceExtractor,
+    origin_header: Option<HeaderName>,
+    block_responder: BlockResponder,
+    is_error: ErrorPredicate,
+    rate_limit_headers: bool,
+}
+
+impl Default for Sentinel {
+    fn default() -> Self {
+        Sentinel {
+            resource_extractor: Arc::new(default_resource),
+            origin_header: None,
+            block_responder: Arc::new(block_response),
+            is_error: Arc::new(|status| status.is_server_error()),
+            rate_limit_headers: false,
+        }
+    }
+}
+
+impl fmt::Debug for Sentinel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sentinel")
+            .field("origin_header", &self.origin_header)
+            .field("rate_limit_headers", &self.rate_limit_headers)
+            .finish()
+    }
+}
+
+impl Sentinel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `with_resource_extractor` replaces the resource derived from the matched path.
+    pub fn with_resource_extractor(
+        mut self,
+        f: impl Fn(&Request) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.resource_extractor = Arc::new(f);
+        self
+    }
+
+    /// `with_origin_header` sets the header carrying the origin (caller) of the request, e.g., `x-sentinel-origin`.
+    pub fn with_origin_header(mut self, name: HeaderName) -> Self {
+        self.origin_header = Some(name);
+        self
+    }
+
+    /// `with_block_responder` replaces the default `block_response()`.
+    pub fn with_block_responder(
+        mut self,
+        f: impl Fn(&BlockError, &mut Response) + Send + Sync + 'static,
+    ) -> Self {
+        self.block_responder = Arc::new(f);
+        self
+    }
+
+    /// `with_error_predicate` sets the status codes recorded as errors, the server errors by default.
+    pub fn with_error_predicate(
+        mut self,
+        f: impl Fn(StatusCode) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.is_error = Arc::new(f);
+        self
+    }
+
+    /// `with_rate_limit_headers` sets whether the responses carry the headers of `rate_limit_headers()`,
+    /// including the block responses, `false` by default.
+    pub fn with_rate_limit_headers(mut self, rate_limit_headers: bool) -> Self {
+        self.rate_limit_headers = rate_limit_headers;
+        self
+    }
+}
+
+#[async_trait]
+impl Handler for Sentinel {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        let resource = (self.resource_extractor)(req);
+        let mut builder = EntryBuilder::new(resource.clone())
+            .with_resource_type(ResourceType::Web)
+            .with_traffic_type(TrafficType::Inbound);
+        if let Some(origin) = self
+            .origin_header
+            .as_ref()
+            .and_then(|name| req.headers().get(name))
+            .and_then(|value| value.to_str().ok())
+        {
+            builder = builder.with_origin(origin.into());
+        }
+        let entry = match builder.build_async_deferred() {
+            Ok(entry) => entry,
+            Err(err) => {
+                let block_error = match block_error_of(err) {
+                    Ok(block_error) => block_error,
+                    Err(_) => {
+                        res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+                        ctrl.skip_rest();
+                        return;
+                    }
+                };
+                (self.block_responder)(&block_error, res);
+                if self.rate_limit_headers {
+                    insert_headers(
+                        rate_limit_headers(&resource, Some(&block_error)),
+                        |name: HeaderName, value| {
+                            res.headers_mut().insert(name, value);
+                        },
+                    );
+                }
+                ctrl.skip_rest();
+                return;
+            }
+        };
+        // the quota is taken when the request passes
+        let headers = if self.rate_limit_headers {
+            rate_limit_headers(&resource, None)
+        } else {
+            Vec::new()
+        };
+        let guard = EntryGuard::new(entry);
+        guard.entry().wait().await;
+        guard.catch_panic(ctrl.call_next(req, depot, res)).await;
+        insert_headers(headers, |name: HeaderName, value| {
+            res.headers_mut().insert(name, value);
+        });
+        let status = res.status_code.unwrap_or(StatusCode::OK);
+        if (self.is_error)(status) {
+            guard.set_err(Error::msg(format!("the response status is {}", status)));
+        }
+        guard.exit();
+    }
+}
@@ -0,0 +1,367 @@
+//! The adapter of [sqlx](https://github.com/launchbadge/sqlx), guarding the queries by the statement labels,
+//! thus the concurrency of the database access can be isolated per statement by the isolation rules,
+//! and the slow queries can trip the circuit breakers (with the round trips of the queries):
+//!
+//!  - `SentinelExecutor::new(label, executor)` wraps any executor, e.g., `&mut PgConnection` or a transaction,
+//!    e.g., `sqlx::query("SELECT ...").fetch_all(SentinelExecutor::new("users.by_id", &mut *conn))`
+//!  - `SentinelPool::guard(label)` returns the executor acquiring a connection of the pool for each query,
+//!    the time waiting for the connection is counted toward the round trip unless `with_acquire_time(false)` is set
+//!
+//! The entry is built when the query is polled and exited when the result (or the last row) is returned,
+//! the failed queries are recorded as errors unless the error predicate says no.
+//! The blocked query fails with `sqlx::Error::Io` wrapping the `BlockError` without being sent,
+//! which is extracted by `block_error()`.
+use super::block_error_of;
+use crate::base::{BlockError, EntryGuard, ResourceType, TrafficType};
+use crate::{utils, EntryBuilder, Error};
+use async_stream::stream;
+use futures_util::future::BoxFuture;
+use futures_util::stream::{BoxStream, StreamExt};
+use sqlx::{Database, Describe, Either, Execute, Executor, Pool};
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+pub type ErrorPredicate = Arc<dyn Fn(&sqlx::Error) -> bool + Send + Sync>;
+
+/// `block_error` returns the `BlockError` if the query was blocked by Sentinel.
+pub fn block_error(err: &sqlx::Error) -> Option<&BlockError> {
+    match err {
+        sqlx::Error::Io(err) => err
+            .get_ref()
+            .and_then(|err| err.downcast_ref::<BlockError>()),
+        _ => None,
+    }
+}
+
+// QueryGuard holds the entry of a query
+struct QueryGuard {
+    guard: EntryGuard,
+    start: u64,
+    // the time waiting for the connection, excluded from the round trip
+    excluded_ms: u64,
+    is_error: ErrorPredicate,
+}
+
+impl QueryGuard {
+    async fn enter(label: &str, is_error: ErrorPredicate) -> Result<Self, sqlx::Error> {
+        let entry = EntryBuilder::new(label.into())
+            .with_resource_type(ResourceType::DBSQL)
+            .with_traffic_type(TrafficType::Outbound)
+            .build_async_deferred()
+            .map_err(|err| {
+                let err = match block_error_of(err) {
+                    Ok(block_error) => io::Error::new(io::ErrorKind::Other, block_error),
+                    Err(err) => io::Error::new(io::ErrorKind::Other, err),
+                };
+                sqlx::Error::Io(err)
+            })?;
+        let guard = EntryGuard::new(entry);
+        guard.entry().wait().await;
+        Ok(QueryGuard {
+            guard,
+            start: utils::curr_time_millis(),
+            excluded_ms: 0,
+            is_error,
+        })
+    }
+
+    fn record<T>(&self, result: &Result<T, sqlx::Error>) {
+        if let Err(err) = result {
+            if (self.is_error)(err) {
+                self.guard.set_err(Error::msg(err.to_string()));
+            }
+        }
+    }
+
+    fn exit(&self) {
+        let round_trip = utils::curr_time_millis()
+            .saturating_sub(self.start)
+            .saturating_sub(self.excluded_ms);
+        self.guard.entry().exit_with_round_trip(round_trip);
+    }
+}
+
+/// `SentinelExecutor` guards the queries of the inner executor by the label.
+pub struct SentinelExecutor<E> {
+    inner: E,
+    label: String,
+    is_error: ErrorPredicate,
+}
+
+impl<E: fmt::Debug> fmt::Debug for SentinelExecutor<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SentinelExecutor")
+            .field("inner", &self.inner)
+            .field("label", &self.label)
+            .finish()
+    }
+}
+
+impl<E> SentinelExecutor<E> {
+    pub fn new(label: impl Into<String>, inner: E) -> Self {
+        SentinelExecutor {
+            inner,
+            label: label.into(),
+            is_error: Arc::new(|_| true),
+        }
+    }
+
+    /// `with_error_predicate` sets the errors recorded for the circuit breakers, all the errors by default.
+    pub fn with_error_predicate(
+        mut self,
+        f: impl Fn(&sqlx::Error) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.is_error = Arc::new(f);
+        self
+    }
+}
+
+type Step<DB> = Either<<DB as Database>::QueryResult, <DB as Database>::Row>;
+
+impl<'c, E: Executor<'c> + 'c> Executor<'c> for SentinelExecutor<E> {
+    type Database = E::Database;
+
+    fn fetch_many<'e, 'q: 'e, Q>(
+        self,
+        query: Q,
+    ) -> BoxStream<'e, Result<Step<Self::Database>, sqlx::Error>>
+    where
+        'c: 'e,
+        Q: 'q + Execute<'q, Self::Database>,
+    {
+        let SentinelExecutor {
+            inner,
+            label,
+            is_error,
+        } = self;
+        Box::pin(stream! {
+            let guard = match QueryGuard::enter(&label, is_error).await {
+                Ok(guard) => guard,
+                Err(err) => {
+                    yield Err(err);
+                    return;
+                }
+            };
+            let mut steps = inner.fetch_many(query);
+            while let Some(step) = steps.next().await {
+                guard.record(&step);
+                yield step;
+            }
+            guard.exit();
+        })
+    }
+
+    fn fetch_optional<'e, 'q: 'e, Q>(
+        self,
+        query: Q,
+    ) -> BoxFuture<'e, Result<Option<<Self::Database as Database>::Row>, sqlx::Error>>
+    where
+        'c: 'e,
+        Q: 'q + Execute<'q, Self::Database>,
+    {
+        Box::pin(async move {
+            let guard = QueryGuard::enter(&self.label, self.is_error).await?;
+            let result = self.inner.fetch_optional(query).await;
+            guard.record(&result);
+            guard.exit();
+            result
+        })
+    }
+
+    fn prepare_with<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+        parameters: &'e [<Self::Database as Database>::TypeInfo],
+    ) -> BoxFuture<'e, Result<<Self::Database as Database>::Statement<'q>, sqlx::Error>>
+    where
+        'c: 'e,
+    {
+        self.inner.prepare_with(sql, parameters)
+    }
+
+    fn describe<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+    ) -> BoxFuture<'e, Result<Describe<Self::Database>, sqlx::Error>>
+    where
+        'c: 'e,
+    {
+        self.inner.describe(sql)
+    }
+}
+
+/// `SentinelPool` is the pool whose queries are guarded by the labels.
+pub struct SentinelPool<DB: Database> {
+    pool: Pool<DB>,
+    count_acquire_time: bool,
+    is_error: ErrorPredicate,
+}
+
+impl<DB: Database> Clone for SentinelPool<DB> {
+    fn clone(&self) -> Self {
+        SentinelPool {
+            pool: self.pool.clone(),
+            count_acquire_time: self.count_acquire_time,
+            is_error: Arc::clone(&self.is_error),
+        }
+    }
+}
+
+impl<DB: Database> fmt::Debug for SentinelPool<DB> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SentinelPool")
+            .field("pool", &self.pool)
+            .field("count_acquire_time", &self.count_acquire_time)
+            .finish()
+    }
+}
+
+impl<DB: Database> SentinelPool<DB> {
+    pub fn new(pool: Pool<DB>) -> Self {
+        SentinelPool {
+            pool,
+            count_acquire_time: true,
+            is_error: Arc::new(|_| true),
+        }
+    }
+
+    pub fn pool(&self) -> &Pool<DB> {
+        &self.pool
+    }
+
+    /// `with_acquire_time` sets whether the time waiting for the connection is counted toward the round trip,
+    /// `true` by default, thus the exhausted pool trips the slow request breakers as well.
+    pub fn with_acquire_time(mut self, count_acquire_time: bool) -> Self {
+        self.count_acquire_time = count_acquire_time;
+        self
+    }
+
+    /// `with_error_predicate` sets the errors recorded for the circuit breakers, all the errors by default,
+    /// including the timeouts of acquiring the connections.
+    pub fn with_error_predicate(
+        mut self,
+        f: impl Fn(&sqlx::Error) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.is_error = Arc::new(f);
+        self
+    }
+
+    /// `guard` returns the executor of the queries labeled by `label`.
+    pub fn guard(&self, label: impl Into<String>) -> SentinelPoolExecutor<DB> {
+        SentinelPoolExecutor {
+            pool: self.clone(),
+            label: label.into(),
+        }
+    }
+}
+
+/// `SentinelPoolExecutor` acquires a connection of the pool for each query guarded by the label.
+pub struct SentinelPoolExecutor<DB: Database> {
+    pool: SentinelPool<DB>,
+    label: String,
+}
+
+impl<DB: Database> fmt::Debug for SentinelPoolExecutor<DB> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SentinelPoolExecutor")
+            .field("pool", &self.pool)
+            .field("label", &self.label)
+            .finish()
+    }
+}
+
+impl<DB: Database> SentinelPoolExecutor<DB> {
+    async fn enter(&self) -> Result<QueryGuard, sqlx::Error> {
+        QueryGuard::enter(&self.label, Arc::clone(&self.pool.is_error)).await
+    }
+
+    async fn acquire(
+        &self,
+        guard: &mut QueryGuard,
+    ) -> Result<sqlx::pool::PoolConnection<DB>, sqlx::Error> {
+        let start = utils::curr_time_millis();
+        let result = self.pool.pool.acquire().await;
+        if !self.pool.count_acquire_time {
+            guard.excluded_ms = utils::curr_time_millis().saturating_sub(start);
+        }
+        result
+    }
+}
+
+impl<'p, DB: Database> Executor<'p> for SentinelPoolExecutor<DB>
+where
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+{
+    type Database = DB;
+
+    fn fetch_many<'e, 'q: 'e, Q>(self, query: Q) -> BoxStream<'e, Result<Step<DB>, sqlx::Error>>
+    where
+        'p: 'e,
+        Q: 'q + Execute<'q, DB>,
+    {
+        Box::pin(stream! {
+            let mut guard = match self.enter().await {
+                Ok(guard) => guard,
+                Err(err) => {
+                    yield Err(err);
+                    return;
+                }
+            };
+            let mut conn = match self.acquire(&mut guard).await {
+                Ok(conn) => conn,
+                Err(err) => {
+                    let result = Err(err);
+                    guard.record(&result);
+                    guard.exit();
+                    yield result;
+                    return;
+                }
+            };
+            let mut steps = (&mut *conn).fetch_many(query);
+            while let Some(step) = steps.next().await {
+                guard.record(&step);
+                yield step;
+            }
+            guard.exit();
+        })
+    }
+
+    fn fetch_optional<'e, 'q: 'e, Q>(
+        self,
+        query: Q,
+    ) -> BoxFuture<'e, Result<Option<DB::Row>, sqlx::Error>>
+    where
+        'p: 'e,
+        Q: 'q + Execute<'q, DB>,
+    {
+        Box::pin(async move {
+            let mut guard = self.enter().await?;
+            let result = match self.acquire(&mut guard).await {
+                Ok(mut conn) => (&mut *conn).fetch_optional(query).await,
+                Err(err) => Err(err),
+            };
+            guard.record(&result);
+            guard.exit();
+            result
+        })
+    }
+
+    fn prepare_with<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+        parameters: &'e [DB::TypeInfo],
+    ) -> BoxFuture<'e, Result<DB::Statement<'q>, sqlx::Error>>
+    where
+        'p: 'e,
+    {
+        Box::pin(async move { self.pool.pool.prepare_with(sql, parameters).await })
+    }
+
+    fn describe<'e, 'q: 'e>(self, sql: &'q str) -> BoxFuture<'e, Result<Describe<DB>, sqlx::Error>>
+    where
+        'p: 'e,
+    {
+        Box::pin(async move { self.pool.pool.describe(sql).await })
+    }
+}
@@ -0,0 +1,159 @@
+//! The client side adapter guarding the outbound RPCs to a target, e.g.,
+//! `GreeterClient::new(ServiceBuilder::new().layer(SentinelClientLayer::new("greeter")).service(channel))`.
+//! The resource is the target by default, thus the flow rules and the circuit breakers are kept per target,
+//! the status and the latency (until the end of the response body) of each RPC are fed to them.
+//! The blocked RPC fails with the status of `block_status()` without being sent.
+//!
+//! Since a channel may balance over several endpoints, `is_ejected()` reports whether the circuit breakers
+//! of a target are open, the discovery of the balancer can use it to skip the ejected endpoints,
+//! with a client layer built for each endpoint.
+use super::{block_status, grpc_code, ErrorPredicate, SentinelBody};
+use crate::adapters::block_error_of;
+use crate::base::{EntryGuard, ResourceType, TrafficType};
+use crate::circuitbreaker::{self, State};
+use crate::{EntryBuilder, Error};
+use http::{Request, Response};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::{Code, Status};
+use tower_layer::Layer;
+use tower_service::Service;
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+pub type ResourceExtractor = Arc<dyn Fn(&str, &str) -> String + Send + Sync>;
+
+/// `is_ejected` indicates whether any circuit breaker of the resource (the target by default) is open.
+pub fn is_ejected(resource: &str) -> bool {
+    circuitbreaker::get_breakers_of_resource(&resource.to_owned())
+        .iter()
+        .any(|breaker| breaker.breaker().current_state() == State::Open)
+}
+
+/// `SentinelClientLayer` wraps the channel to a target in `SentinelClientService`.
+#[derive(Clone)]
+pub struct SentinelClientLayer {
+    target: Arc<str>,
+    resource_extractor: Option<ResourceExtractor>,
+    is_error: ErrorPredicate,
+}
+
+impl fmt::Debug for SentinelClientLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SentinelClientLayer")
+            .field("target", &self.target)
+            .finish()
+    }
+}
+
+impl SentinelClientLayer {
+    pub fn new(target: impl Into<String>) -> Self {
+        SentinelClientLayer {
+            target: target.into().into(),
+            resource_extractor: None,
+            is_error: Arc::new(|code| code != Code::Ok),
+        }
+    }
+
+    /// `with_resource_extractor` names the resource by the target and the method path,
+    /// e.g., `|target, path| format!("{}{}", target, path)` for the breakers per method.
+    pub fn with_resource_extractor(
+        mut self,
+        f: impl Fn(&str, &str) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.resource_extractor = Some(Arc::new(f));
+        self
+    }
+
+    /// `with_error_predicate` sets the status codes recorded as errors, all the non-OK ones by default.
+    pub fn with_error_predicate(
+        mut self,
+        f: impl Fn(Code) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.is_error = Arc::new(f);
+        self
+    }
+
+    fn resource(&self, path: &str) -> String {
+        match &self.resource_extractor {
+            Some(f) => f(&self.target, path),
+            None => self.target.to_string(),
+        }
+    }
+}
+
+impl<S> Layer<S> for SentinelClientLayer {
+    type Service = SentinelClientService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SentinelClientService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SentinelClientService<S> {
+    inner: S,
+    layer: SentinelClientLayer,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for SentinelClientService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: Into<BoxError>,
+    ResBody: Send + 'static,
+{
+    type Response = Response<SentinelBody<ResBody>>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let builder = EntryBuilder::new(self.layer.resource(req.uri().path()))
+            .with_resource_type(ResourceType::RPC)
+            .with_traffic_type(TrafficType::Outbound);
+        let guard = match builder.build_async_deferred() {
+            Ok(entry) => EntryGuard::new(entry),
+            Err(err) => {
+                let status = match block_error_of(err) {
+                    Ok(block_error) => block_status(&block_error),
+                    Err(err) => Status::internal(err.to_string()),
+                };
+                return Box::pin(async move { Err(BoxError::from(status)) });
+            }
+        };
+        let is_error = Arc::clone(&self.layer.is_error);
+        let future = self.inner.call(req);
+        Box::pin(async move {
+            guard.entry().wait().await;
+            match guard.catch_panic(future).await {
+                Ok(response) => {
+                    // the trailers-only response, e.g., the server returns an error
+                    if let Some(code) = grpc_code(response.headers()) {
+                        if is_error(code) {
+                            guard.set_err(Error::msg(format!(
+                                "the status of the RPC is {:?}",
+                                code
+                            )));
+                        }
+                    }
+                    Ok(response.map(|body| SentinelBody::new(body, guard, is_error)))
+                }
+                Err(err) => {
+                    // e.g., the connection is refused or the deadline is exceeded
+                    let err = err.into();
+                    guard.set_err(Error::msg(err.to_string()));
+                    Err(err)
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,177 @@
+//! The adapters of [tonic](https://github.com/hyperium/tonic).
+//! `server::SentinelLayer` guards the inbound RPCs, it is added by `Server::builder().layer()`,
+//! the resource of an RPC is its method path, e.g., `/helloworld.Greeter/SayHello`.
+//! `client::SentinelClientLayer` guards the outbound RPCs to a target, see the docs of `client`.
+//!
+//! The blocked RPC fails with `RESOURCE_EXHAUSTED`, or `UNAVAILABLE` if it is rejected by an open circuit breaker,
+//! the details are attached in the metadata `x-sentinel-block` (JSON) and `retry-after` (seconds).
+//! Since the status of a gRPC response is usually sent in the trailers,
+//! the entry is exited once the response body ends, and the non-OK statuses are recorded as errors.
+use super::{block_body, retry_after_secs};
+use crate::base::{BlockError, BlockType, EntryGuard};
+use crate::Error;
+use http::HeaderMap;
+use http_body::{Body, Frame, SizeHint};
+use std::fmt;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::{Code, Status};
+
+pub mod client;
+pub mod server;
+
+pub type ErrorPredicate = Arc<dyn Fn(Code) -> bool + Send + Sync>;
+
+/// `BLOCK_METADATA_KEY` is the metadata key of the JSON details of the block error.
+pub const BLOCK_METADATA_KEY: &str = "x-sentinel-block";
+const GRPC_STATUS: &str = "grpc-status";
+
+/// `block_status` is the status of the blocked RPCs.
+pub fn block_status(block_error: &BlockError) -> Status {
+    let code = match block_error.block_type() {
+        BlockType::CircuitBreaking => Code::Unavailable,
+        _ => Code::ResourceExhausted,
+    };
+    let mut status = Status::new(code, block_error.to_string());
+    let metadata = status.metadata_mut();
+    if let Ok(details) = block_body(block_error).parse::<MetadataValue<Ascii>>() {
+        metadata.insert(BLOCK_METADATA_KEY, details);
+    }
+    if let Some(secs) = retry_after_secs(block_error) {
+        metadata.insert("retry-after", MetadataValue::from(secs));
+    }
+    status
+}
+
+/// `grpc_code` returns the status code in the headers or the trailers, if there is.
+pub fn grpc_code(headers: &HeaderMap) -> Option<Code> {
+    headers
+        .get(GRPC_STATUS)
+        .map(|code| Code::from_bytes(code.as_bytes()))
+}
+
+/// `SentinelBody` is the response body holding the entry of the RPC,
+/// the entry is exited when the body ends or is dropped.
+pub struct SentinelBody<B> {
+    inner: Option<Pin<Box<B>>>,
+    guard: Option<EntryGuard>,
+    is_error: Option<ErrorPredicate>,
+}
+
+impl<B> Default for SentinelBody<B> {
+    /// The empty body, e.g., of the blocked RPCs.
+    fn default() -> Self {
+        SentinelBody {
+            inner: None,
+            guard: None,
+            is_error: None,
+        }
+    }
+}
+
+impl<B> fmt::Debug for SentinelBody<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SentinelBody")
+            .field("exited", &self.guard.is_none())
+            .finish()
+    }
+}
+
+impl<B> SentinelBody<B> {
+    pub(crate) fn new(inner: B, guard: EntryGuard, is_error: ErrorPredicate) -> Self {
+        SentinelBody {
+            inner: Some(Box::pin(inner)),
+            guard: Some(guard),
+            is_error: Some(is_error),
+        }
+    }
+
+    fn record(&self, code: Code) {
+        if let (Some(guard), Some(is_error)) = (&self.guard, &self.is_error) {
+            if is_error(code) {
+                guard.set_err(Error::msg(format!("the status of the RPC is {:?}", code)));
+            }
+        }
+    }
+
+    fn complete(&mut self) {
+        if let Some(guard) = self.guard.take() {
+            guard.exit();
+        }
+    }
+}
+
+impl<B: Body> Body for SentinelBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        let inner = match this.inner.as_mut() {
+            Some(inner) => inner,
+            None => return Poll::Ready(None),
+        };
+        let frame = match inner.as_mut().poll_frame(cx) {
+            Poll::Ready(frame) => frame,
+            Poll::Pending => return Poll::Pending,
+        };
+        match &frame {
+            Some(Ok(frame)) => {
+                if let Some(code) = frame.trailers_ref().and_then(grpc_code) {
+                    this.record(code);
+                    this.complete();
+                }
+            }
+            Some(Err(_)) => {
+                this.record(Code::Internal);
+                this.complete();
+            }
+            None => this.complete(),
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner
+            .as_ref()
+            .map_or(true, |inner| inner.is_end_stream())
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner
+            .as_ref()
+            .map_or_else(|| SizeHint::with_exact(0), |inner| inner.size_hint())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn status() {
+        let mut block_error = BlockError::new(BlockType::Flow);
+        block_error.set_resource("/helloworld.Greeter/SayHello".into());
+        block_error.set_retry_after(Duration::from_millis(500));
+        let status = block_status(&block_error);
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        assert_eq!(status.metadata().get("retry-after").unwrap(), "1");
+        let details = status.metadata().get(BLOCK_METADATA_KEY).unwrap();
+        let details: serde_json::Value = serde_json::from_str(details.to_str().unwrap()).unwrap();
+        assert_eq!(details["resource"], "/helloworld.Greeter/SayHello");
+
+        let status = block_status(&BlockError::new(BlockType::CircuitBreaking));
+        assert_eq!(status.code(), Code::Unavailable);
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(grpc_code(&headers), None);
+        headers.insert(GRPC_STATUS, "14".parse().unwrap());
+        assert_eq!(grpc_code(&headers), Some(Code::Unavailable));
+    }
+}
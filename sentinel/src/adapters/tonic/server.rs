@@ -0,0 +1,173 @@
+//! The server side adapter, e.g.,
+//! `Server::builder().layer(SentinelLayer::new()).add_service(GreeterServer::new(greeter))`.
+use super::{block_status, grpc_code, ErrorPredicate, SentinelBody};
+use crate::adapters::{block_error_of, insert_headers, rate_limit_headers};
+use crate::base::{EntryGuard, ResourceType, TrafficType};
+use crate::{EntryBuilder, Error};
+use http::{HeaderName, Request, Response};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::{Code, Status};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// `SentinelLayer` wraps the gRPC services in `SentinelService`.
+#[derive(Clone)]
+pub struct SentinelLayer {
+    origin_header: Option<HeaderName>,
+    is_error: ErrorPredicate,
+    rate_limit_headers: bool,
+}
+
+impl Default for SentinelLayer {
+    fn default() -> Self {
+        SentinelLayer {
+            origin_header: None,
+            is_error: Arc::new(|code| code != Code::Ok),
+            rate_limit_headers: false,
+        }
+    }
+}
+
+impl fmt::Debug for SentinelLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SentinelLayer")
+            .field("origin_header", &self.origin_header)
+            .field("rate_limit_headers", &self.rate_limit_headers)
+            .finish()
+    }
+}
+
+impl SentinelLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `with_origin_header` sets the metadata carrying the origin (caller) of the RPC, e.g., `x-sentinel-origin`.
+    pub fn with_origin_header(mut self, name: HeaderName) -> Self {
+        self.origin_header = Some(name);
+        self
+    }
+
+    /// `with_error_predicate` sets the status codes recorded as errors, all the non-OK ones by default.
+    pub fn with_error_predicate(
+        mut self,
+        f: impl Fn(Code) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.is_error = Arc::new(f);
+        self
+    }
+
+    /// `with_rate_limit_headers` sets whether the responses carry the headers of `rate_limit_headers()` as the metadata,
+    /// including the block responses, `false` by default.
+    pub fn with_rate_limit_headers(mut self, rate_limit_headers: bool) -> Self {
+        self.rate_limit_headers = rate_limit_headers;
+        self
+    }
+}
+
+impl<S> Layer<S> for SentinelLayer {
+    type Service = SentinelService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SentinelService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SentinelService<S> {
+    inner: S,
+    layer: SentinelLayer,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for SentinelService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    ResBody: Send + 'static,
+{
+    type Response = Response<SentinelBody<ResBody>>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let resource = req.uri().path().to_owned();
+        let mut builder = EntryBuilder::new(resource.clone())
+            .with_resource_type(ResourceType::RPC)
+            .with_traffic_type(TrafficType::Inbound);
+        let origin = self
+            .layer
+            .origin_header
+            .as_ref()
+            .and_then(|name| req.headers().get(name))
+            .and_then(|value| value.to_str().ok());
+        if let Some(origin) = origin {
+            builder = builder.with_origin(origin.into());
+        }
+        let guard = match builder.build_async_deferred() {
+            Ok(entry) => EntryGuard::new(entry),
+            Err(err) => {
+                let block_error = match block_error_of(err) {
+                    Ok(block_error) => block_error,
+                    Err(err) => {
+                        let response = Status::internal(err.to_string()).into_http();
+                        return Box::pin(async move { Ok(response) });
+                    }
+                };
+                let mut response = block_status(&block_error).into_http();
+                if self.layer.rate_limit_headers {
+                    insert_headers(
+                        rate_limit_headers(&resource, Some(&block_error)),
+                        |name: HeaderName, value| {
+                            response.headers_mut().insert(name, value);
+                        },
+                    );
+                }
+                return Box::pin(async move { Ok(response) });
+            }
+        };
+        // the quota is taken when the RPC passes
+        let headers = if self.layer.rate_limit_headers {
+            rate_limit_headers(&resource, None)
+        } else {
+            Vec::new()
+        };
+        let is_error = Arc::clone(&self.layer.is_error);
+        let future = self.inner.call(req);
+        Box::pin(async move {
+            guard.entry().wait().await;
+            let result = guard.catch_panic(future).await;
+            match result {
+                Ok(mut response) => {
+                    insert_headers(headers, |name: HeaderName, value| {
+                        response.headers_mut().insert(name, value);
+                    });
+                    // the trailers-only response, e.g., the handler returns an error
+                    if let Some(code) = grpc_code(response.headers()) {
+                        if is_error(code) {
+                            guard.set_err(Error::msg(format!(
+                                "the status of the RPC is {:?}",
+                                code
+                            )));
+                        }
+                    }
+                    Ok(response.map(|body| SentinelBody::new(body, guard, is_error)))
+                }
+                Err(err) => {
+                    guard.set_err(Error::msg("the gRPC service failed"));
+                    Err(err)
+                }
+            }
+        })
+    }
+}
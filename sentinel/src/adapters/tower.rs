@@ -0,0 +1,175 @@
+//! The generic adapter of [tower](https://github.com/tower-rs/tower).
+//! `SentinelLayer<Req>` wraps any `tower::Service<Req>` in `SentinelService`,
+//! thus it can be stacked in both the servers and the clients (e.g., hyper, tonic or the proxies).
+//! The resource of an entry is extracted from the request by the given function,
+//! the traffic type is inbound by default, call `with_traffic_type(TrafficType::Outbound)` for the clients.
+//!
+//! The round trip time starts when the service is polled for readiness,
+//! thus the time waiting for the capacity of the inner service (e.g., a connection pool) is counted.
+//! The blocked request fails with `BlockError` (boxed as `BoxError`), and the errors of the inner service are recorded.
+use super::block_error_of;
+use crate::base::{EntryGuard, ResourceType, TrafficType};
+use crate::{utils, EntryBuilder, Error};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// `BoxError` is the error type of `SentinelService`, the blocked requests fail with `BlockError`.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+pub type ResourceExtractor<Req> = Arc<dyn Fn(&Req) -> String + Send + Sync>;
+pub type OriginExtractor<Req> = Arc<dyn Fn(&Req) -> Option<String> + Send + Sync>;
+
+pub struct SentinelLayer<Req> {
+    resource_extractor: ResourceExtractor<Req>,
+    origin_extractor: Option<OriginExtractor<Req>>,
+    resource_type: ResourceType,
+    traffic_type: TrafficType,
+}
+
+impl<Req> Clone for SentinelLayer<Req> {
+    fn clone(&self) -> Self {
+        SentinelLayer {
+            resource_extractor: Arc::clone(&self.resource_extractor),
+            origin_extractor: self.origin_extractor.clone(),
+            resource_type: self.resource_type,
+            traffic_type: self.traffic_type,
+        }
+    }
+}
+
+impl<Req> fmt::Debug for SentinelLayer<Req> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SentinelLayer")
+            .field("resource_type", &self.resource_type)
+            .field("traffic_type", &self.traffic_type)
+            .finish()
+    }
+}
+
+impl<Req> SentinelLayer<Req> {
+    pub fn new(resource_extractor: impl Fn(&Req) -> String + Send + Sync + 'static) -> Self {
+        SentinelLayer {
+            resource_extractor: Arc::new(resource_extractor),
+            origin_extractor: None,
+            resource_type: ResourceType::Common,
+            traffic_type: TrafficType::Inbound,
+        }
+    }
+
+    /// `with_origin_extractor` sets the origin (caller) of the request, e.g., from a header.
+    pub fn with_origin_extractor(
+        mut self,
+        f: impl Fn(&Req) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.origin_extractor = Some(Arc::new(f));
+        self
+    }
+
+    pub fn with_resource_type(mut self, resource_type: ResourceType) -> Self {
+        self.resource_type = resource_type;
+        self
+    }
+
+    pub fn with_traffic_type(mut self, traffic_type: TrafficType) -> Self {
+        self.traffic_type = traffic_type;
+        self
+    }
+
+    fn entry_builder(&self, req: &Req) -> EntryBuilder {
+        let mut builder = EntryBuilder::new((self.resource_extractor)(req))
+            .with_resource_type(self.resource_type)
+            .with_traffic_type(self.traffic_type);
+        if let Some(origin) = self.origin_extractor.as_ref().and_then(|f| f(req)) {
+            builder = builder.with_origin(origin);
+        }
+        builder
+    }
+}
+
+impl<S, Req> Layer<S> for SentinelLayer<Req> {
+    type Service = SentinelService<S, Req>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SentinelService {
+            inner,
+            layer: self.clone(),
+            ready_since: None,
+        }
+    }
+}
+
+pub struct SentinelService<S, Req> {
+    inner: S,
+    layer: SentinelLayer<Req>,
+    // the time of polling the readiness for the next request
+    ready_since: Option<u64>,
+}
+
+impl<S: Clone, Req> Clone for SentinelService<S, Req> {
+    fn clone(&self) -> Self {
+        SentinelService {
+            inner: self.inner.clone(),
+            layer: self.layer.clone(),
+            ready_since: None,
+        }
+    }
+}
+
+impl<S: fmt::Debug, Req> fmt::Debug for SentinelService<S, Req> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SentinelService")
+            .field("inner", &self.inner)
+            .field("layer", &self.layer)
+            .finish()
+    }
+}
+
+impl<S, Req> Service<Req> for SentinelService<S, Req>
+where
+    S: Service<Req>,
+    S::Future: Send + 'static,
+    S::Error: Into<BoxError>,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.ready_since.get_or_insert_with(utils::curr_time_millis);
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let start = self
+            .ready_since
+            .take()
+            .unwrap_or_else(utils::curr_time_millis);
+        let entry = match self.layer.entry_builder(&req).build_async_deferred() {
+            Ok(entry) => entry,
+            Err(err) => {
+                let err = match block_error_of(err) {
+                    Ok(block_error) => BoxError::from(block_error),
+                    Err(err) => BoxError::from(err),
+                };
+                return Box::pin(async move { Err(err) });
+            }
+        };
+        let guard = EntryGuard::new(entry);
+        let future = self.inner.call(req);
+        Box::pin(async move {
+            guard.entry().wait().await;
+            let result = guard.catch_panic(future).await.map_err(Into::into);
+            if let Err(err) = &result {
+                guard.set_err(Error::msg(err.to_string()));
+            }
+            guard
+                .entry()
+                .exit_with_round_trip(utils::curr_time_millis().saturating_sub(start));
+            result
+        })
+    }
+}
@@ -0,0 +1,157 @@
+//! The adapter of [warp](https://github.com/seanmonstar/warp).
+//! `protect(resource_fn)` is the filter opening the entry of the resource named by `resource_fn`
+//! from the method and the path of the request. It extracts a `SentinelGuard` for the handler,
+//! or rejects the blocked request with `BlockRejection`, which is converted to 429 (or 503) by `recover_blocked`:
+//!
+//! ```ignore
+//! let route = warp::path!("users" / u32)
+//!     .and(protect(|method, _| format!("{}:/users/{{id}}", method)))
+//!     .map(|id, guard: SentinelGuard| guard.complete(format!("user {}", id)))
+//!     .recover(recover_blocked);
+//! ```
+//!
+//! The entry is exited by `SentinelGuard::complete()` when the reply is produced (the server errors are recorded),
+//! or when the guard is dropped, e.g., the handler rejects or the request is cancelled.
+//! The quota of the flow rules is reported by the `RateLimit-*` headers if the replies are completed by
+//! `SentinelGuard::complete_with_rate_limit_headers()`, and the rejections are recovered by `recover_blocked_with_rate_limit_headers`.
+use super::{
+    block_body, block_error_of, block_status_code, insert_headers, rate_limit_headers,
+    retry_after_secs,
+};
+use crate::base::{BlockError, EntryGuard, ResourceType, TrafficType};
+use crate::{EntryBuilder, Error};
+use std::sync::Arc;
+use warp::http::{header, HeaderName, HeaderValue, Method, StatusCode};
+use warp::path::FullPath;
+use warp::reject::{Reject, Rejection};
+use warp::reply::{Reply, Response};
+use warp::Filter;
+
+/// `BlockRejection` is the rejection of the blocked requests.
+#[derive(Debug)]
+pub struct BlockRejection(pub BlockError);
+
+impl Reject for BlockRejection {}
+
+/// `EntryRejection` is the rejection of the requests failing to build the entries by the errors other than `BlockError`,
+/// which is answered with 500 by warp if it is not recovered.
+#[derive(Debug)]
+pub struct EntryRejection(pub Error);
+
+impl Reject for EntryRejection {}
+
+/// `SentinelGuard` holds the entry of the request.
+pub struct SentinelGuard {
+    guard: EntryGuard,
+    resource: String,
+}
+
+impl SentinelGuard {
+    pub fn guard(&self) -> &EntryGuard {
+        &self.guard
+    }
+
+    pub fn resource(&self) -> &str {
+        &self.resource
+    }
+
+    /// `complete_with_rate_limit_headers` completes the reply like `complete()`,
+    /// with the headers of `rate_limit_headers()` taken when the reply is produced.
+    pub fn complete_with_rate_limit_headers(self, reply: impl Reply) -> Response {
+        let mut response = reply.into_response();
+        insert_headers(
+            rate_limit_headers(&self.resource, None),
+            |name: HeaderName, value| {
+                response.headers_mut().insert(name, value);
+            },
+        );
+        self.complete(response)
+    }
+
+    /// `complete` converts the reply into the response, records the server error and exits the entry.
+    pub fn complete(self, reply: impl Reply) -> Response {
+        let response = reply.into_response();
+        if response.status().is_server_error() {
+            self.guard.set_err(Error::msg(format!(
+                "the response status is {}",
+                response.status()
+            )));
+        }
+        self.guard.exit();
+        response
+    }
+}
+
+/// `protect` guards the request by the resource named by `resource_fn` from the method and the path.
+pub fn protect<R>(
+    resource_fn: R,
+) -> impl Filter<Extract = (SentinelGuard,), Error = Rejection> + Clone
+where
+    R: Fn(&Method, &str) -> String + Send + Sync + 'static,
+{
+    let resource_fn = Arc::new(resource_fn);
+    warp::method()
+        .and(warp::path::full())
+        .and_then(move |method: Method, path: FullPath| {
+            let resource = resource_fn(&method, path.as_str());
+            let result = EntryBuilder::new(resource.clone())
+                .with_resource_type(ResourceType::Web)
+                .with_traffic_type(TrafficType::Inbound)
+                .build_async_deferred()
+                .map(EntryGuard::new)
+                .map_err(|err| match block_error_of(err) {
+                    Ok(block_error) => warp::reject::custom(BlockRejection(block_error)),
+                    Err(err) => warp::reject::custom(EntryRejection(err)),
+                });
+            async move {
+                let guard = result?;
+                guard.entry().wait().await;
+                Ok::<_, Rejection>(SentinelGuard { guard, resource })
+            }
+        })
+}
+
+/// `block_response` is the response of the blocked requests,
+/// i.e., 429 (or 503 for circuit breaking) with the `Retry-After` header and the JSON details.
+pub fn block_response(block_error: &BlockError) -> Response {
+    let mut response = Response::new(block_body(block_error).into());
+    *response.status_mut() = StatusCode::from_u16(block_status_code(block_error))
+        .unwrap_or(StatusCode::TOO_MANY_REQUESTS);
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    if let Some(secs) = retry_after_secs(block_error) {
+        headers.insert(header::RETRY_AFTER, HeaderValue::from(secs));
+    }
+    response
+}
+
+/// `recover_blocked` converts `BlockRejection` into `block_response()`,
+/// the other rejections are passed to the next `recover`.
+pub async fn recover_blocked(rejection: Rejection) -> Result<Response, Rejection> {
+    match rejection.find::<BlockRejection>() {
+        Some(BlockRejection(block_error)) => Ok(block_response(block_error)),
+        None => Err(rejection),
+    }
+}
+
+/// `recover_blocked_with_rate_limit_headers` is `recover_blocked` with the headers of `rate_limit_headers()`.
+pub async fn recover_blocked_with_rate_limit_headers(
+    rejection: Rejection,
+) -> Result<Response, Rejection> {
+    match rejection.find::<BlockRejection>() {
+        Some(BlockRejection(block_error)) => {
+            let mut response = block_response(block_error);
+            insert_headers(
+                rate_limit_headers(&block_error.resource(), Some(block_error)),
+                |name: HeaderName, value| {
+                    response.headers_mut().insert(name, value);
+                },
+            );
+            Ok(response)
+        }
+        None => Err(rejection),
+    }
+}
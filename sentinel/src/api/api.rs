@@ -1,25 +1,30 @@
-use super::global_slot_chain;
+use super::{global_slot_chain, resource_slot_chain};
 use crate::base::{
-    EntryContext, EntryStrongPtr, ParamsList, ParamsMap, ResourceType, ResourceWrapper,
-    ResultStatus, SentinelEntry, SentinelInput, SlotChain, TokenResult, TrafficType,
+    pool, ContextGuard, EntryContext, EntryStrongPtr, ParamsList, ParamsMap, ResourceType,
+    ResourceWrapper, ResultStatus, SentinelContext, SentinelEntry, SentinelInput, SlotChain,
+    TokenResult, TrafficType,
 };
 use crate::utils::format_time_nanos_curr;
 use crate::{Error, Result};
 use std::any::Any;
 use std::collections::HashMap;
 use std::fmt;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 cfg_async! {
-    use std::sync::RwLock;
+    use crate::base::AsyncEntry;
 }
 
-cfg_not_async! {
-    use std::rc::Rc;
-    use std::cell::RefCell;
+/// `context_enter` enters the named context (the entrance of the invocation chain) in current thread,
+/// the entries built before the returned guard is dropped are accounted under the entrance,
+/// which can be limited by the flow rules with `RelationStrategy::Chain`.
+/// In async code, use `SentinelContext::named(..).scope_future(..)` instead.
+pub fn context_enter(name: String, origin: Option<String>) -> ContextGuard {
+    SentinelContext::named(name, origin).enter()
 }
 
 // EntryBuilder is the basic API of Sentinel.
+#[derive(Clone)]
 pub struct EntryBuilder {
     resource_name: String,
     resource_type: ResourceType,
@@ -27,6 +32,9 @@ pub struct EntryBuilder {
     batch_count: u32,
     flag: i32,
     slot_chain: Arc<SlotChain>,
+    origin: Option<String>,
+    priority_class: Option<String>,
+    context: Option<SentinelContext>,
     args: Option<ParamsList>,
     attachments: Option<ParamsMap>,
 }
@@ -42,6 +50,9 @@ impl Default for EntryBuilder {
             batch_count: 1,
             flag: 0,
             slot_chain: global_slot_chain(),
+            origin: None,
+            priority_class: None,
+            context: None,
             args: None,
             attachments: None,
         }
@@ -49,100 +60,114 @@ impl Default for EntryBuilder {
 }
 
 impl EntryBuilder {
+    /// `new` creates the builder of the resource,
+    /// the slot chain registered for the resource is used if there is one,
+    /// otherwise, the global slot chain is used.
     pub fn new(resource_name: String) -> Self {
         EntryBuilder {
+            slot_chain: resource_slot_chain(&resource_name),
             resource_name,
             ..EntryBuilder::default()
         }
     }
 
-    cfg_async! {
-        /// `build()` would consume EntryBuilder
-        pub fn build(self) -> Result<EntryStrongPtr> {
-            // get context from pool.
-            let mut ctx = EntryContext::new();
-
-            ctx.set_resource(ResourceWrapper::new(
-                self.resource_name,
-                self.resource_type,
-                self.traffic_type,
-            ));
-
-            let mut input = SentinelInput::new(self.batch_count, self.flag);
-            if let Some(args) = self.args {
-                input.set_args(args);
-            }
-            if let Some(attachments) = self.attachments {
-                input.set_attachments(attachments);
-            }
-            ctx.set_input(input);
-
-            let ctx = Arc::new(RwLock::new(ctx));
-            let entry =
-                Arc::new(RwLock::new(SentinelEntry::new(
-                    Arc::clone(&ctx),
-                    Arc::clone(&self.slot_chain),
-                )));
-            ctx.write().unwrap().set_entry(Arc::downgrade(&entry));
-
-            let r = self.slot_chain.entry(Arc::clone(&ctx));
-            if *r.status() == ResultStatus::Blocked {
-                // todo: here need fix
-                // if return block_error,
-                // must deep copy the error, since Arc only clone pointer
-                let block_err = r.block_err();
-
-                entry.read().unwrap().exit();
-                Err(Error::msg(r.to_string()))
-            } else {
-                Ok(entry)
-            }
+    /// `build()` would consume EntryBuilder,
+    /// if the entry is blocked, the returned error can be downcast to `BlockError`.
+    pub fn build(self) -> Result<EntryStrongPtr> {
+        self.try_build()
+            .map_err(|r| Error::new(r.block_err().unwrap_or_default()))
+    }
+
+    /// `try_build()` would consume EntryBuilder,
+    /// the blocked `TokenResult` is returned if the entry is blocked.
+    pub(crate) fn try_build(self) -> std::result::Result<EntryStrongPtr, TokenResult> {
+        self.try_build_with(false)
+    }
+
+    /// `try_build_with()` leaves the wait required by the rules to the caller if `wait_by_caller`,
+    /// see `AsyncEntry::wait()`.
+    pub(crate) fn try_build_with(
+        self,
+        wait_by_caller: bool,
+    ) -> std::result::Result<EntryStrongPtr, TokenResult> {
+        // get context from pool.
+        let ctx = pool::acquire_context();
+        let mut ctx_mut = ctx.write().unwrap();
+
+        ctx_mut.set_resource(ResourceWrapper::new(
+            self.resource_name,
+            self.resource_type,
+            self.traffic_type,
+        ));
+
+        // link the entry to the invocation chain
+        let sentinel_context = self.context.or_else(SentinelContext::current);
+
+        let mut input = SentinelInput::new(self.batch_count, self.flag);
+        // the origin of the context is used by default
+        let origin = self.origin.or_else(|| {
+            sentinel_context
+                .as_ref()
+                .and_then(|sc| sc.origin().cloned())
+        });
+        if let Some(origin) = origin {
+            input.set_origin(origin);
+        }
+        if let Some(priority_class) = self.priority_class {
+            input.set_priority_class(priority_class);
+        }
+        if let Some(args) = self.args {
+            input.set_args(args);
+        }
+        if let Some(attachments) = self.attachments {
+            input.set_attachments(attachments);
+        }
+        ctx_mut.set_input(input);
+        ctx_mut.set_wait_by_caller(wait_by_caller);
+        if let Some(sentinel_context) = sentinel_context {
+            ctx_mut.set_sentinel_context(sentinel_context);
+        }
+
+        drop(ctx_mut);
+        let entry = Arc::new(RwLock::new(SentinelEntry::new(
+            Arc::clone(&ctx),
+            Arc::clone(&self.slot_chain),
+        )));
+        ctx.write().unwrap().set_entry(Arc::downgrade(&entry));
+
+        // the context is only owned by the entry hereafter, thus it is recycled when the entry is dropped
+        let r = self.slot_chain.entry(ctx);
+        if *r.status() == ResultStatus::Blocked {
+            entry.read().unwrap().exit();
+            Err(r)
+        } else {
+            Ok(entry)
         }
     }
 
-    cfg_not_async! {
-        /// `build()` would consume EntryBuilder
-        pub fn build(self) -> Result<EntryStrongPtr> {
-            // get context from pool.
-            let mut ctx = EntryContext::new();
-
-            ctx.set_resource(ResourceWrapper::new(
-                self.resource_name,
-                self.resource_type,
-                self.traffic_type,
-            ));
-
-            let mut input = SentinelInput::new(self.batch_count, self.flag);
-            if let Some(args) = self.args {
-                input.set_args(args);
-            }
-            if let Some(attachments) = self.attachments {
-                input.set_attachments(attachments);
-            }
-            ctx.set_input(input);
-
-            let ctx = Rc::new(RefCell::new(ctx));
-            let entry = Rc::new(RefCell::new(SentinelEntry::new(
-                    Rc::clone(&ctx),
-                    Arc::clone(&self.slot_chain),
-                )));
-            ctx.borrow_mut().set_entry(Rc::downgrade(&entry));
-
-            let r = self.slot_chain.entry(Rc::clone(&ctx));
-            if *r.status() == ResultStatus::Blocked {
-                // todo: here need fix
-                // if return block_error,
-                // must deep copy the error, since Arc only clone pointer
-                let block_err = r.block_err();
-
-                entry.borrow().exit();
-                Err(Error::msg(r.to_string()))
-            } else {
-                Ok(entry)
-            }
+    cfg_async! {
+        /// `build_async()` would consume EntryBuilder,
+        /// the returned `AsyncEntry` is `Send + Sync` and can be exited in any task or thread.
+        /// Notice that the thread is blocked if the entry has to wait, e.g., queued by the throttling flow rules,
+        /// see `build_async_deferred()` for the non-blocking one.
+        pub fn build_async(self) -> Result<AsyncEntry> {
+            self.build().map(AsyncEntry::from)
+        }
+
+        /// `build_async_deferred()` would consume EntryBuilder, like `build_async()`,
+        /// but the wait required by the rules is left to `AsyncEntry::wait()`, which must be awaited before the invocation.
+        /// The waiting entry has been counted as passed (and concurrent) by the statistics.
+        pub fn build_async_deferred(self) -> Result<AsyncEntry> {
+            self.try_build_with(true)
+                .map(AsyncEntry::from)
+                .map_err(|r| Error::new(r.block_err().unwrap_or_default()))
         }
     }
 
+    pub fn resource_name(&self) -> &String {
+        &self.resource_name
+    }
+
     pub fn with_resource_type(mut self, resource_type: ResourceType) -> Self {
         self.resource_type = resource_type;
         self
@@ -153,6 +178,10 @@ impl EntryBuilder {
         self
     }
 
+    /// `with_batch_count` sets the number of tokens acquired by the entry, 1 by default.
+    /// An entry of a batch with N items consumes N tokens of the flow and hotspot rules,
+    /// and is counted as N passes (or blocks) and N completions in the statistics.
+    #[doc(alias = "acquire_count")]
     pub fn with_batch_count(mut self, batch_count: u32) -> Self {
         self.batch_count = batch_count;
         self
@@ -168,6 +197,26 @@ impl EntryBuilder {
         self
     }
 
+    /// `with_origin` sets the origin (caller) of the invocation, e.g., the upstream service name.
+    pub fn with_origin(mut self, origin: String) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// `with_priority_class` sets the priority class of the invocation, e.g., "interactive" or "batch",
+    /// see `priority_weights` of the flow rules.
+    pub fn with_priority_class(mut self, priority_class: String) -> Self {
+        self.priority_class = Some(priority_class);
+        self
+    }
+
+    /// `with_context` sets the invocation chain of the entry explicitly,
+    /// by default, the `SentinelContext` installed in current scope is used.
+    pub fn with_context(mut self, context: SentinelContext) -> Self {
+        self.context = Some(context);
+        self
+    }
+
     pub fn with_args(mut self, args: ParamsList) -> Self {
         self.args = Some(args);
         self
@@ -183,8 +232,8 @@ impl EntryBuilder {
 mod test {
     use super::*;
     use crate::base::{
-        BaseSlot, BlockType, MockRuleCheckSlot, MockStatNode, MockStatPrepareSlot, MockStatSlot,
-        RuleCheckSlot, StatPrepareSlot, StatSlot,
+        BaseSlot, BlockError, BlockType, MockRuleCheckSlot, MockStatNode, MockStatPrepareSlot,
+        MockStatSlot, RuleCheckSlot, StatPrepareSlot, StatSlot,
     };
     use mockall::predicate::*;
     use mockall::*;
@@ -240,10 +289,27 @@ mod test {
         sc.add_stat_slot(ssm.clone());
         let sc = Arc::new(sc);
 
-        let builder = EntryBuilder::new("abc".into()).with_slot_chain(sc);
+        let builder = EntryBuilder::new("abc".into())
+            .with_slot_chain(sc)
+            .with_traffic_type(TrafficType::Outbound)
+            .with_resource_type(ResourceType::RPC)
+            .with_origin("caller".into())
+            .with_priority_class("batch".into())
+            .with_batch_count(2)
+            .with_args(vec!["arg".into()]);
         let entry = builder.build().unwrap();
-        assert_eq!("abc", entry.borrow().context().borrow().resource().name());
-        entry.borrow().exit();
+        {
+            let entry = entry.read().unwrap();
+            let ctx = entry.context().read().unwrap();
+            assert_eq!("abc", ctx.resource().name());
+            assert_eq!(TrafficType::Outbound, *ctx.resource().traffic_type());
+            assert_eq!(ResourceType::RPC, *ctx.resource().resource_type());
+            assert_eq!(Some(&String::from("caller")), ctx.input().origin());
+            assert_eq!(Some(&String::from("batch")), ctx.input().priority_class());
+            assert_eq!(2, ctx.input().batch_count());
+            assert_eq!(1, ctx.input().args().unwrap().len());
+        }
+        entry.read().unwrap().exit();
     }
 
     #[test]
@@ -300,11 +366,80 @@ mod test {
         let rw = ResourceWrapper::new("abc".into(), ResourceType::Common, TrafficType::Inbound);
         ctx.set_resource(rw);
         ctx.set_stat_node(Arc::new(MockStatNode::new()));
-        let ctx = Rc::new(RefCell::new(ctx));
-        let entry = Rc::new(RefCell::new(SentinelEntry::new(ctx.clone(), sc.clone())));
-        ctx.borrow_mut().set_entry(Rc::downgrade(&entry));
+        let ctx = Arc::new(RwLock::new(ctx));
+        let entry = Arc::new(RwLock::new(SentinelEntry::new(ctx.clone(), sc.clone())));
+        ctx.write().unwrap().set_entry(Arc::downgrade(&entry));
 
         let builder = EntryBuilder::new("abc".into()).with_slot_chain(sc);
-        assert!(builder.build().is_err());
+        let err = builder.build().err().unwrap();
+        let block_err = err.downcast_ref::<BlockError>().unwrap();
+        assert_eq!(block_err.block_type(), BlockType::Flow);
+        assert_eq!(block_err.resource(), "abc");
+    }
+
+    #[test]
+    #[ignore]
+    fn batch_count() {
+        use crate::base::{MetricEvent, ReadStat};
+        use crate::{flow, stat};
+
+        let res = String::from("batch_count");
+        flow::load_rules_of_resource(
+            &res,
+            vec![Arc::new(flow::Rule {
+                resource: res.clone(),
+                threshold: 10.0,
+                ..Default::default()
+            })],
+        )
+        .unwrap();
+        let build = |batch_count| {
+            EntryBuilder::new(res.clone())
+                .with_batch_count(batch_count)
+                .build()
+        };
+        let first = build(6).unwrap();
+        assert!(build(6).is_err());
+        let second = build(4).unwrap();
+        first.read().unwrap().exit();
+        second.read().unwrap().exit();
+
+        let node = stat::get_resource_node(&res).unwrap();
+        assert_eq!(node.sum(MetricEvent::Pass), 10);
+        assert_eq!(node.sum(MetricEvent::Block), 6);
+        assert_eq!(node.sum(MetricEvent::Complete), 10);
+        flow::clear_rules_of_resource(&res);
+    }
+
+    #[test]
+    #[ignore]
+    fn batch_count_hotspot() {
+        use crate::hotspot;
+
+        let res = String::from("batch_count_hotspot");
+        hotspot::load_rules_of_resource(
+            &res,
+            vec![Arc::new(hotspot::Rule {
+                resource: res.clone(),
+                metric_type: hotspot::MetricType::QPS,
+                param_index: 0,
+                threshold: 10,
+                duration_in_sec: 1,
+                ..Default::default()
+            })],
+        )
+        .unwrap();
+        let build = |arg: &str, batch_count| {
+            EntryBuilder::new(res.clone())
+                .with_batch_count(batch_count)
+                .with_args(vec![arg.into()])
+                .build()
+        };
+        build("a", 6).unwrap().read().unwrap().exit();
+        assert!(build("a", 6).is_err());
+        build("a", 4).unwrap().read().unwrap().exit();
+        // the tokens are counted per parameter
+        build("b", 10).unwrap().read().unwrap().exit();
+        hotspot::clear_rules_of_resource(&res);
     }
 }
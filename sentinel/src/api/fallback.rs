@@ -0,0 +1,212 @@
+//! Fallback registry.
+//! A fallback produces a substitute value when the entry of a resource is blocked.
+//! Fallbacks can be registered per resource, or as the global default of a return type.
+//! The resource specific fallback takes precedence over the global default.
+use super::EntryBuilder;
+use crate::base::{BlockError, EntryStrongPtr};
+use lazy_static::lazy_static;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// `FallbackFn` produces the substitute value according to the `BlockError`.
+pub type FallbackFn<T> = Arc<dyn Fn(&BlockError) -> T + Send + Sync>;
+
+/// `EntryResult` is the result of `EntryBuilder::build_with_fallback()`.
+pub enum EntryResult<T> {
+    /// The entry passed, remember to exit it.
+    Passed(EntryStrongPtr),
+    /// The entry was blocked and the substitute value is produced by the registered fallback.
+    Fallback(T),
+    /// The entry was blocked and there is no fallback registered for the resource and the type.
+    Blocked(BlockError),
+}
+
+impl<T> EntryResult<T> {
+    pub fn is_passed(&self) -> bool {
+        matches!(self, EntryResult::Passed(_))
+    }
+
+    pub fn is_fallback(&self) -> bool {
+        matches!(self, EntryResult::Fallback(_))
+    }
+
+    pub fn is_blocked(&self) -> bool {
+        matches!(self, EntryResult::Blocked(_))
+    }
+}
+
+// The fallbacks are type-erased, the `Box<dyn Any>` is exactly a `FallbackFn<T>`.
+type ErasedFallback = Box<dyn Any + Send + Sync>;
+
+lazy_static! {
+    static ref FALLBACK_MAP: RwLock<HashMap<String, ErasedFallback>> = RwLock::new(HashMap::new());
+    static ref DEFAULT_FALLBACK_MAP: RwLock<HashMap<TypeId, ErasedFallback>> =
+        RwLock::new(HashMap::new());
+}
+
+/// `register_fallback` registers the fallback of the resource, the previous one will be replaced.
+pub fn register_fallback<T, F>(resource: String, fallback: F)
+where
+    T: 'static,
+    F: Fn(&BlockError) -> T + Send + Sync + 'static,
+{
+    let fallback: FallbackFn<T> = Arc::new(fallback);
+    FALLBACK_MAP
+        .write()
+        .unwrap()
+        .insert(resource, Box::new(fallback));
+}
+
+/// `register_default_fallback` registers the global default fallback of the return type `T`,
+/// which is used by the resources without their own fallbacks of type `T`.
+pub fn register_default_fallback<T, F>(fallback: F)
+where
+    T: 'static,
+    F: Fn(&BlockError) -> T + Send + Sync + 'static,
+{
+    let fallback: FallbackFn<T> = Arc::new(fallback);
+    DEFAULT_FALLBACK_MAP
+        .write()
+        .unwrap()
+        .insert(TypeId::of::<T>(), Box::new(fallback));
+}
+
+pub fn remove_fallback(resource: &String) {
+    FALLBACK_MAP.write().unwrap().remove(resource);
+}
+
+pub fn remove_default_fallback<T: 'static>() {
+    DEFAULT_FALLBACK_MAP
+        .write()
+        .unwrap()
+        .remove(&TypeId::of::<T>());
+}
+
+pub fn clear_fallbacks() {
+    FALLBACK_MAP.write().unwrap().clear();
+    DEFAULT_FALLBACK_MAP.write().unwrap().clear();
+}
+
+/// `get_fallback` returns the fallback of the resource with return type `T`,
+/// if there is no such one, the global default fallback of `T` is returned.
+pub fn get_fallback<T: 'static>(resource: &String) -> Option<FallbackFn<T>> {
+    let resource_fallback = FALLBACK_MAP
+        .read()
+        .unwrap()
+        .get(resource)
+        .and_then(|f| f.downcast_ref::<FallbackFn<T>>())
+        .cloned();
+    resource_fallback.or_else(|| {
+        DEFAULT_FALLBACK_MAP
+            .read()
+            .unwrap()
+            .get(&TypeId::of::<T>())
+            .and_then(|f| f.downcast_ref::<FallbackFn<T>>())
+            .cloned()
+    })
+}
+
+impl EntryBuilder {
+    /// `build_with_fallback()` would consume EntryBuilder,
+    /// if the entry is blocked, the registered fallback of the resource (or the global default of `T`) is invoked.
+    pub fn build_with_fallback<T: 'static>(self) -> EntryResult<T> {
+        let resource = self.resource_name().clone();
+        match self.try_build() {
+            Ok(entry) => EntryResult::Passed(entry),
+            Err(r) => {
+                let block_err = r.block_err().unwrap_or_default();
+                match get_fallback::<T>(&resource) {
+                    Some(fallback) => EntryResult::Fallback(fallback(&block_err)),
+                    None => EntryResult::Blocked(block_err),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::{BlockType, MockRuleCheckSlot, MockStatPrepareSlot, SlotChain, TokenResult};
+
+    fn blocked_slot_chain() -> Arc<SlotChain> {
+        let mut ps = Arc::new(MockStatPrepareSlot::new());
+        let mut rcs = Arc::new(MockRuleCheckSlot::new());
+        Arc::get_mut(&mut ps)
+            .unwrap()
+            .expect_prepare()
+            .return_const(());
+        Arc::get_mut(&mut rcs)
+            .unwrap()
+            .expect_check()
+            .returning(|_ctx| TokenResult::new_blocked(BlockType::Flow));
+        let mut sc = SlotChain::new();
+        sc.add_stat_prepare_slot(ps);
+        sc.add_rule_check_slot(rcs);
+        Arc::new(sc)
+    }
+
+    #[test]
+    fn passed() {
+        let entry = EntryBuilder::new("fallback_passed".into())
+            .with_slot_chain(Arc::new(SlotChain::new()))
+            .build_with_fallback::<u32>();
+        match entry {
+            EntryResult::Passed(entry) => entry.read().unwrap().exit(),
+            _ => panic!("the entry should pass"),
+        }
+    }
+
+    #[test]
+    fn resource_fallback() {
+        let resource = String::from("fallback_resource");
+        register_fallback(resource.clone(), |err: &BlockError| {
+            assert_eq!(err.block_type(), BlockType::Flow);
+            1u8
+        });
+        let r = EntryBuilder::new(resource.clone())
+            .with_slot_chain(blocked_slot_chain())
+            .build_with_fallback::<u8>();
+        assert!(matches!(r, EntryResult::Fallback(1)));
+        // the type mismatches
+        let r = EntryBuilder::new(resource.clone())
+            .with_slot_chain(blocked_slot_chain())
+            .build_with_fallback::<i8>();
+        assert!(r.is_blocked());
+
+        remove_fallback(&resource);
+        let r = EntryBuilder::new(resource)
+            .with_slot_chain(blocked_slot_chain())
+            .build_with_fallback::<u8>();
+        assert!(r.is_blocked());
+    }
+
+    #[test]
+    fn default_fallback() {
+        // a dedicated type, avoiding interference of other tests
+        #[derive(Debug, PartialEq)]
+        struct Substitute(&'static str);
+
+        let resource = String::from("fallback_default");
+        register_default_fallback(|_: &BlockError| Substitute("default"));
+        let r = EntryBuilder::new(resource.clone())
+            .with_slot_chain(blocked_slot_chain())
+            .build_with_fallback::<Substitute>();
+        assert!(matches!(r, EntryResult::Fallback(Substitute("default"))));
+
+        // resource fallback takes precedence
+        register_fallback(resource.clone(), |_: &BlockError| Substitute("resource"));
+        let r = EntryBuilder::new(resource.clone())
+            .with_slot_chain(blocked_slot_chain())
+            .build_with_fallback::<Substitute>();
+        assert!(matches!(r, EntryResult::Fallback(Substitute("resource"))));
+
+        remove_fallback(&resource);
+        remove_default_fallback::<Substitute>();
+        let r = EntryBuilder::new(resource)
+            .with_slot_chain(blocked_slot_chain())
+            .build_with_fallback::<Substitute>();
+        assert!(r.is_blocked());
+    }
+}
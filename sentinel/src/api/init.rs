@@ -1,11 +1,14 @@
 //! Initialization func initialize the Sentinel's runtime environment, including:
-//! 1. override global config, from manually config or yaml file or env variable
+//! 1. override global config, from manually config or YAML/TOML file or env variable
 //! 2. initialize global logger
 //! 3. initiate core component async task, including: metric log, system statistic...
+//! 4. reload the persisted rules, if the rule persistence is configured
 
-use super::{config, config::ConfigEntity};
-use crate::{log::metric, system_metric, utils, Error, Result};
-use serde_yaml;
+use super::{config, config::ConfigEntity, enable_rule_persistence};
+use crate::{system_metric, utils, Error, Result};
+cfg_metric_log! {
+    use crate::log::metric;
+}
 
 /// `init_default` initializes Sentinel using the configuration from system
 /// environment and the default value.
@@ -23,8 +26,9 @@ pub fn init_with_config(config_entity: ConfigEntity) -> Result<()> {
     init_core_compoents()
 }
 
-/// Init loads Sentinel general configuration from the given YAML file
-/// and initializes Sentinel.
+/// Init loads Sentinel general configuration from the given YAML or TOML file
+/// (resolved by the `.toml` extension) and initializes Sentinel.
+/// If `config_path` is empty, the path is resolved from the env `SENTINEL_CONFIG_FILE_PATH`.
 #[inline]
 pub fn init_with_config_file(config_path: &mut String) -> Result<()> {
     init_sentinel(config_path)
@@ -33,17 +37,45 @@ pub fn init_with_config_file(config_path: &mut String) -> Result<()> {
 #[inline]
 fn init_sentinel(config_path: &mut String) -> Result<()> {
     // Initialize general config and logging module.
-    config::init_config_with_yaml(config_path)?;
+    config::init_config_with_file(config_path)?;
     init_core_compoents()
 }
 
 // `init_core_compoents` init core components with global config
 #[inline]
 fn init_core_compoents() -> Result<()> {
+    // Sentinel may be initialized again after `shutdown()`
+    utils::background::resume_tasks();
+    // there are no threads for the background tasks on wasm32, e.g., the proxy-wasm filters
+    #[cfg(not(target_arch = "wasm32"))]
+    init_background_tasks()?;
+
+    let rule_persistence_path = config::rule_persistence_path();
+    if !utils::is_blank(&rule_persistence_path) {
+        enable_rule_persistence(rule_persistence_path)?;
+    }
+    Ok(())
+}
+
+// `init_background_tasks` starts the metric log task, the system metric collectors and the time ticker,
+// the metric log task and the collectors are only built with the `metric-log` and `system-metric` features
+#[cfg(not(target_arch = "wasm32"))]
+fn init_background_tasks() -> Result<()> {
+    #[cfg(feature = "metric-log")]
     if config::metric_log_flush_interval_sec() > 0 {
         metric::init_task()?;
     }
+    #[cfg(feature = "system-metric")]
+    init_system_metric_collectors();
 
+    if config::use_cache_time() {
+        utils::start_time_ticker();
+    }
+    Ok(())
+}
+
+#[cfg(all(feature = "system-metric", not(target_arch = "wasm32")))]
+fn init_system_metric_collectors() {
     let system_interval = config::system_stat_collect_interval_ms();
     let mut load_interval = system_interval;
     let mut cpu_interval = system_interval;
@@ -68,9 +100,4 @@ fn init_core_compoents() -> Result<()> {
     if mem_interval > 0 {
         system_metric::init_memory_collector(mem_interval);
     }
-
-    if config::use_cache_time() {
-        utils::start_time_ticker();
-    }
-    Ok(())
 }
@@ -0,0 +1,159 @@
+//! Runtime introspection of the resources, e.g., for the admin consoles rendering a "top" of Sentinel.
+//! A `ResourceStat` is the snapshot of the statistics of a resource in the sliding window of the metrics
+//! (see `config::metric_stat_interval_ms()`), together with the rules of the resource and the states of its breakers.
+//! The snapshots are `Serialize`, thus they could be exported as JSON by any transport directly.
+use crate::base::{MetricEvent, ReadStat, ResourceType};
+use crate::circuitbreaker::{self, BreakerStrategy, State};
+use crate::stat::{self, ResourceNode};
+use crate::{base, fault, flow, hotspot, isolation, retry, RuleSet};
+use serde::Serialize;
+use std::sync::Arc;
+
+/// `BreakerStatus` is the state of a circuit breaker of the resource.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BreakerStatus {
+    pub rule_id: Option<String>,
+    pub strategy: BreakerStrategy,
+    pub state: State,
+}
+
+/// `ResourceStat` is the runtime snapshot of a resource.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResourceStat {
+    pub resource: String,
+    pub resource_type: ResourceType,
+    pub pass_qps: f64,
+    pub block_qps: f64,
+    pub complete_qps: f64,
+    pub error_qps: f64,
+    pub avg_rt_ms: f64,
+    /// approximated by the histogram of the round trip time, see `rt_percentile()` of the statistic
+    pub p99_rt_ms: f64,
+    pub concurrency: u32,
+    /// the rules of the resource, the system rules are not bound to any resource thus never listed
+    pub rules: RuleSet,
+    pub breakers: Vec<BreakerStatus>,
+}
+
+/// `resource_stats` returns the snapshots of all the resources with statistics, sorted by the resource names.
+pub fn resource_stats() -> Vec<ResourceStat> {
+    let mut stats: Vec<ResourceStat> = stat::resource_node_list()
+        .iter()
+        .map(|node| resource_stat_of_node(node))
+        .collect();
+    stats.sort_by(|a, b| a.resource.cmp(&b.resource));
+    stats
+}
+
+/// `resource_stat` returns the snapshot of the resource, if it has been visited.
+pub fn resource_stat(resource: &String) -> Option<ResourceStat> {
+    stat::get_resource_node(resource).map(|node| resource_stat_of_node(&node))
+}
+
+fn resource_stat_of_node(node: &Arc<ResourceNode>) -> ResourceStat {
+    let resource = node.res_name();
+    ResourceStat {
+        resource: resource.clone(),
+        resource_type: node.resource_type(),
+        pass_qps: node.qps(MetricEvent::Pass),
+        block_qps: node.qps(MetricEvent::Block),
+        complete_qps: node.qps(MetricEvent::Complete),
+        error_qps: node.qps(MetricEvent::Error),
+        avg_rt_ms: node.avg_rt(),
+        p99_rt_ms: node.rt_percentile(0.99),
+        concurrency: base::ConcurrencyStat::current_concurrency(node.as_ref()),
+        rules: rules_of_resource(resource),
+        breakers: circuitbreaker::get_breakers_of_resource(resource)
+            .iter()
+            .map(|breaker| {
+                let rule = breaker.bound_rule();
+                BreakerStatus {
+                    rule_id: rule.id.clone(),
+                    strategy: rule.strategy,
+                    state: breaker.current_state(),
+                }
+            })
+            .collect(),
+    }
+}
+
+fn rules_of_resource(resource: &String) -> RuleSet {
+    // the rules of different types are read from the same snapshot, see `load_rule_set()`
+    let _snapshot = base::read_rule_snapshot();
+    RuleSet {
+        flow: flow::get_rules_of_resource(resource),
+        circuit_breaker: circuitbreaker::get_rules_of_resource(resource),
+        hotspot: hotspot::get_rules_of_resource(resource),
+        system: Vec::new(),
+        isolation: isolation::get_rules_of_resource(resource),
+        fault: fault::get_rules_of_resource(resource),
+        retry: retry::get_rules_of_resource(resource),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{EntryBuilder, Error};
+
+    #[test]
+    #[ignore]
+    fn snapshot() {
+        let resource = String::from("introspection");
+        flow::load_rules(vec![Arc::new(flow::Rule {
+            id: "introspection".into(),
+            resource: resource.clone(),
+            threshold: 2.0,
+            ..Default::default()
+        })]);
+        circuitbreaker::load_rules(vec![Arc::new(circuitbreaker::Rule {
+            id: Some("introspection".into()),
+            resource: resource.clone(),
+            strategy: BreakerStrategy::ErrorCount,
+            retry_timeout_ms: 1000,
+            stat_interval_ms: 1000,
+            threshold: 100.0,
+            ..Default::default()
+        })]);
+
+        let mut passed = Vec::new();
+        for _ in 0..5 {
+            if let Ok(entry) = EntryBuilder::new(resource.clone()).build() {
+                passed.push(entry);
+            }
+        }
+        assert_eq!(passed.len(), 2);
+        let stat = resource_stat(&resource).unwrap();
+        assert_eq!(stat.concurrency, 2);
+        assert_eq!(stat.rules.flow.len(), 1);
+        assert_eq!(stat.rules.circuit_breaker.len(), 1);
+        assert_eq!(
+            stat.breakers,
+            vec![BreakerStatus {
+                rule_id: Some("introspection".into()),
+                strategy: BreakerStrategy::ErrorCount,
+                state: State::Closed,
+            }]
+        );
+
+        for entry in passed {
+            let entry = entry.read().unwrap();
+            entry.set_round_trip(10);
+            entry.context().write().unwrap().set_err(Error::msg("biz"));
+            entry.exit();
+        }
+        let stat = resource_stat(&resource).unwrap();
+        assert_eq!(stat.concurrency, 0);
+        assert!(stat.pass_qps > 0.0);
+        assert!(stat.block_qps > 0.0);
+        assert!(stat.error_qps > 0.0);
+        assert_eq!(stat.avg_rt_ms, 10.0);
+        assert!((8.0..16.0).contains(&stat.p99_rt_ms));
+        assert!(resource_stats().iter().any(|s| s.resource == resource));
+        let json = serde_json::to_value(&stat).unwrap();
+        assert_eq!(json["breakers"][0]["state"], "Closed");
+
+        flow::clear_rules();
+        circuitbreaker::clear_rules();
+    }
+}
@@ -3,15 +3,33 @@
 //!
 //!  1. `init_default()`, using default config to initialize.
 //!  2. `init_with_config(config_entity: config::Entity)`, using customized config Entity to initialize.
-//!  3. `init_with_config_file(config_path: String)`, using yaml file to initialize.
+//!  3. `init_with_config_file(config_path: String)`, using YAML or TOML file to initialize.
+//! Before the process exits, `shutdown()` waits for the in-flight entries, stops the background tasks and flushes the logs.
 //! For the examples, visit the [Sentinel repository](https://github.com/sentinel-group/sentinel-rust)
 
 pub mod api;
+pub mod fallback;
 pub mod init;
+pub mod introspection;
+pub mod persistence;
+pub mod reload;
+pub mod rule_set;
+pub mod shutdown;
 pub mod slot_chain;
+// declared without `cfg_async!`, which is not visited by rustfmt
+#[cfg(feature = "async")]
+pub mod run;
 
 pub use api::*;
+pub use fallback::*;
 pub use init::*;
+pub use introspection::*;
+pub use persistence::*;
+pub use reload::*;
+pub use rule_set::*;
+#[cfg(feature = "async")]
+pub use run::*;
+pub use shutdown::*;
 pub use slot_chain::*;
 
 pub use crate::config;
@@ -0,0 +1,163 @@
+//! Local persistence of the effective rules.
+//! Once enabled, the effective rules of all types are written to a local JSON file on every change,
+//! and they are loaded from this file when the persistence is enabled at startup,
+//! thus a process restarted while the rule source is unreachable still enforces the last known rules.
+//! Enable it (or set `rule_persistence_path` in the config) before connecting to any datasource,
+//! so that the rules from the datasource replace the persisted ones rather than the opposite.
+use super::{load_rule_set, RuleSet};
+use crate::base::{RuleChange, RuleChangeListener};
+use crate::{
+    circuitbreaker, fault, flow, hotspot, isolation, logging, retry, system, Error, Result,
+};
+use lazy_static::lazy_static;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+lazy_static! {
+    // the path of the enabled persistence, `None` if disabled
+    static ref ACTIVE_PERSISTENCE: Mutex<Option<PathBuf>> = Mutex::new(None);
+    static ref RULE_PERSISTENCE: Arc<RulePersistence> = Arc::new(RulePersistence);
+    // serializes the writes, so that the file always ends with the latest rules
+    static ref PERSIST_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// `RulePersistence` is the listener registered to all the rule managers by `enable_rule_persistence()`,
+/// it writes the rules to the path of the enabled persistence.
+struct RulePersistence;
+
+impl<R> RuleChangeListener<R> for RulePersistence {
+    fn on_rules_changed(&self, _change: &RuleChange<R>) {
+        let path = match ACTIVE_PERSISTENCE.lock().unwrap().clone() {
+            Some(path) => path,
+            None => return,
+        };
+        if let Err(err) = persist_rules(&path) {
+            logging::error!(
+                "[RulePersistence] Failed to persist the rules to {:?}, error: {:?}",
+                path,
+                err
+            );
+        }
+    }
+}
+
+/// `persist_rules` writes the effective rules of all types to the file at `path`,
+/// the file is replaced atomically, a crash while writing never leaves a truncated file.
+pub fn persist_rules<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    let _guard = PERSIST_LOCK.lock().unwrap();
+    // `get_rule_set()` is not used here, the listeners may be notified inside `load_rule_set()`
+    let rule_set = RuleSet {
+        flow: flow::get_rules(),
+        circuit_breaker: circuitbreaker::get_rules(),
+        hotspot: hotspot::get_rules(),
+        system: system::get_rules(),
+        isolation: isolation::get_rules(),
+        fault: fault::get_rules(),
+        retry: retry::get_rules(),
+    };
+    let content = serde_json::to_string_pretty(&rule_set)?;
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// `load_persisted_rules` loads the rules persisted at `path` by `load_rule_set()`,
+/// it returns `false` if the file does not exist.
+pub fn load_persisted_rules<P: AsRef<Path>>(path: P) -> Result<bool> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(false);
+    }
+    let content = fs::read_to_string(path)?;
+    let rule_set: RuleSet = serde_json::from_str(&content)?;
+    load_rule_set(rule_set)?;
+    logging::info!("[RulePersistence] Persisted rules loaded from {:?}", path);
+    Ok(true)
+}
+
+/// `enable_rule_persistence` loads the rules persisted at `path` if there are,
+/// and then keeps the file in sync with the effective rules.
+/// If the file cannot be loaded, the persistence is not changed and the file is kept untouched.
+/// The persistence is stopped as well if the rule change listeners of the managers are cleared,
+/// until it is enabled again.
+pub fn enable_rule_persistence<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    // the previous persistence is kept if the file cannot be loaded,
+    // and it is not locked here, since the loaded rules are written by the listener
+    if !load_persisted_rules(path)? {
+        persist_rules(path)?;
+    }
+    *ACTIVE_PERSISTENCE.lock().unwrap() = Some(path.into());
+    // the registered listener is skipped by the managers, i.e., it is registered only once
+    let persistence = Arc::clone(&RULE_PERSISTENCE);
+    flow::register_rule_change_listeners(vec![persistence.clone()]);
+    circuitbreaker::register_rule_change_listeners(vec![persistence.clone()]);
+    hotspot::register_rule_change_listeners(vec![persistence.clone()]);
+    system::register_rule_change_listeners(vec![persistence.clone()]);
+    isolation::register_rule_change_listeners(vec![persistence.clone()]);
+    fault::register_rule_change_listeners(vec![persistence.clone()]);
+    retry::register_rule_change_listeners(vec![persistence]);
+    Ok(())
+}
+
+/// `disable_rule_persistence` stops writing the rules, the persisted file is kept.
+pub fn disable_rule_persistence() {
+    ACTIVE_PERSISTENCE.lock().unwrap().take();
+}
+
+/// `rule_persistence_path` returns the path of the enabled persistence.
+pub fn rule_persistence_path() -> Option<PathBuf> {
+    ACTIVE_PERSISTENCE.lock().unwrap().clone()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore]
+    fn persist_and_reload() {
+        let path = std::env::temp_dir()
+            .join(format!("sentinel-{}", uuid::Uuid::new_v4()))
+            .join("rules.json");
+        enable_rule_persistence(&path).unwrap();
+        assert_eq!(rule_persistence_path(), Some(path.clone()));
+        assert!(path.exists());
+
+        flow::load_rules(vec![Arc::new(flow::Rule {
+            resource: "persistence".into(),
+            threshold: 10.0,
+            ..Default::default()
+        })]);
+        isolation::load_rules(vec![Arc::new(isolation::Rule {
+            resource: "persistence".into(),
+            threshold: 5,
+            ..Default::default()
+        })]);
+        let persisted: RuleSet = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(persisted.flow.len(), 1);
+        assert_eq!(persisted.isolation[0].threshold, 5);
+
+        // the restarted process reloads the last known rules
+        disable_rule_persistence();
+        flow::clear_rules();
+        isolation::clear_rules();
+        assert!(load_persisted_rules(&path).unwrap());
+        assert_eq!(flow::get_rules()[0].threshold, 10.0);
+        assert_eq!(isolation::get_rules().len(), 1);
+
+        // the file is kept untouched after disabled
+        flow::clear_rules();
+        isolation::clear_rules();
+        let persisted: RuleSet = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(persisted.flow.len(), 1);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,207 @@
+//! Reloading the global configuration at runtime.
+//! The configuration is resolved again from the file and the system environment,
+//! the changeable items take effect at once, while the others are kept and reported as requiring restart:
+//!
+//!  - changeable: the level of the env logger, the metric and block log settings and the rule persistence path
+//!  - requiring restart: the app, the statistic windows and collectors, the cached time and the kind of logger
+//!
+//! On Unix, the `signal` feature provides `reload_config_on_sighup()`.
+use super::{config, disable_rule_persistence, enable_rule_persistence};
+use crate::{logging, utils, Result};
+use serde_json::Value;
+
+/// `ConfigReload` is the result of reloading, the items are the paths in the `config` section,
+/// e.g., `log.metric.max_file_count`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ConfigReload {
+    /// the changed items taking effect
+    pub applied: Vec<String>,
+    /// the changed items ignored until the process is restarted
+    pub requires_restart: Vec<String>,
+}
+
+impl ConfigReload {
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty() && self.requires_restart.is_empty()
+    }
+}
+
+/// `reload_config` reloads the configuration file resolved at initialization.
+#[inline]
+pub fn reload_config() -> Result<ConfigReload> {
+    reload_config_file(&mut String::new())
+}
+
+/// `reload_config_file` reloads the configuration from the given YAML or TOML file.
+/// Nothing changes if the configuration is invalid.
+pub fn reload_config_file(config_path: &mut String) -> Result<ConfigReload> {
+    let entity = config::resolve_config(config_path)?;
+    let old = config::global_config_value();
+    let mut new = serde_json::to_value(&entity)?;
+    let mut changed = Vec::new();
+    diff_items(&old["config"], &new["config"], "", &mut changed);
+
+    let mut reload = ConfigReload::default();
+    for item in changed {
+        if is_changeable(&item) {
+            reload.applied.push(item);
+        } else {
+            // keep the effective value
+            let pointer = format!("/config/{}", item.replace('.', "/"));
+            if let (Some(old_value), Some(new_value)) =
+                (old.pointer(&pointer), new.pointer_mut(&pointer))
+            {
+                *new_value = old_value.clone();
+            }
+            reload.requires_restart.push(item);
+        }
+    }
+    let entity: config::ConfigEntity = serde_json::from_value(new)?;
+
+    if reload
+        .applied
+        .iter()
+        .any(|item| item == "rule_persistence_path")
+    {
+        let path = entity.rule_persistence_path();
+        if utils::is_blank(path) {
+            disable_rule_persistence();
+        } else {
+            enable_rule_persistence(path)?;
+        }
+    }
+    if reload.applied.iter().any(|item| item == ENV_LOGGER_LEVEL) {
+        if let logging::Logger::EnvLogger(level) = entity.logger() {
+            logging::reload_env_logger_level(level);
+        }
+    }
+    config::reset_global_config(entity);
+    logging::info!(
+        "[Config] Configuration reloaded, applied: {:?}, requires restart: {:?}",
+        reload.applied,
+        reload.requires_restart
+    );
+    Ok(reload)
+}
+
+const ENV_LOGGER_LEVEL: &str = "log.logger.EnvLogger";
+
+fn is_changeable(item: &str) -> bool {
+    if item == ENV_LOGGER_LEVEL {
+        // only the level of the env logger can be changed, rather than the kind of logger
+        return logging::is_env_logger_in_use();
+    }
+    item.starts_with("log.metric.")
+        || item.starts_with("log.block.")
+        || item == "rule_persistence_path"
+}
+
+// diff_items collects the paths of the changed items,
+// a section is reported as a whole if its keys are changed (e.g., another kind of logger)
+fn diff_items(old: &Value, new: &Value, path: &str, changed: &mut Vec<String>) {
+    match (old, new) {
+        (Value::Object(old_items), Value::Object(new_items))
+            if old_items.len() == new_items.len()
+                && old_items.keys().all(|k| new_items.contains_key(k)) =>
+        {
+            for (key, old_item) in old_items.iter() {
+                let item_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                diff_items(old_item, &new_items[key], &item_path, changed);
+            }
+        }
+        _ if old != new => changed.push(path.into()),
+        _ => {}
+    }
+}
+
+cfg_signal! {
+    /// `reload_config_on_sighup` spawns a thread reloading the configuration on every SIGHUP,
+    /// the thread is stopped by `shutdown()`.
+    pub fn reload_config_on_sighup() -> Result<()> {
+        let mut signals = signal_hook::iterator::Signals::new(&[signal_hook::consts::SIGHUP])?;
+        // the iterator of the signals ends once closed
+        let handle = signals.handle();
+        utils::background::on_stop(move || handle.close());
+        utils::background::spawn_task("sentinel-config-reload", move || {
+            for _ in signals.forever() {
+                if let Err(err) = reload_config() {
+                    logging::error!("[Config] Failed to reload the configuration on SIGHUP, error: {:?}", err);
+                }
+            }
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn diff() {
+        let old = json!({
+            "app": {"app_name": "a", "app_type": "Common"},
+            "log": {"logger": {"EnvLogger": "info"}, "metric": {"max_file_count": 8}},
+        });
+        let new = json!({
+            "app": {"app_name": "a", "app_type": "Common"},
+            "log": {"logger": {"EnvLogger": "debug"}, "metric": {"max_file_count": 3}},
+        });
+        let mut changed = Vec::new();
+        diff_items(&old, &new, "", &mut changed);
+        assert_eq!(
+            changed,
+            vec!["log.logger.EnvLogger", "log.metric.max_file_count"]
+        );
+
+        let new = json!({
+            "app": {"app_name": "b", "app_type": "Common"},
+            "log": {"logger": {"Log4rs": "log4rs.yml"}, "metric": {"max_file_count": 8}},
+        });
+        let mut changed = Vec::new();
+        diff_items(&old, &new, "", &mut changed);
+        assert_eq!(changed, vec!["app.app_name", "log.logger"]);
+    }
+
+    #[test]
+    #[ignore]
+    fn reload() {
+        let dir = std::env::temp_dir().join(format!("sentinel-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sentinel.toml");
+        let write = |app_name: &str, max_file_count: u32| {
+            std::fs::write(
+                &path,
+                format!(
+                    "version = \"v1\"\n[config.app]\napp_name = \"{}\"\n[config.log.metric]\nmax_file_count = {}\n",
+                    app_name, max_file_count
+                ),
+            )
+            .unwrap();
+        };
+        write("reload", 8);
+        let mut config_path = path.to_str().unwrap().to_owned();
+        config::reset_global_config(config::resolve_config(&mut config_path).unwrap());
+        assert!(reload_config_file(&mut config_path).unwrap().is_empty());
+
+        write("reload_renamed", 3);
+        let reload = reload_config_file(&mut config_path).unwrap();
+        assert_eq!(reload.applied, vec!["log.metric.max_file_count"]);
+        assert_eq!(reload.requires_restart, vec!["app.app_name"]);
+        assert_eq!(config::metric_log_max_file_amount(), 3);
+        assert_eq!(config::app_name(), "reload");
+
+        // the invalid configuration changes nothing
+        write("reload", 0);
+        assert!(reload_config_file(&mut config_path).is_err());
+        assert_eq!(config::metric_log_max_file_amount(), 3);
+
+        config::reset_global_config(config::ConfigEntity::new());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
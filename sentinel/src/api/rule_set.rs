@@ -0,0 +1,390 @@
+//! Bulk update of rules.
+//! A `RuleSet` contains the rules of all types, it is applied atomically by `load_rule_set()`,
+//! the entries observe either the whole new set or the old one, never a mix of them.
+//!
+//! The rules can also be grouped by namespaces (e.g., per subsystem or per datasource),
+//! a namespace is loaded, replaced and cleared without affecting the others,
+//! and the rules of all namespaces take effect together.
+//! The rules are owned by the namespaces while there is any namespace, the APIs bypassing them
+//! (`load_rule_set()`, the `load_rules()` and `clear_rules()` of each type, etc.) are rejected meanwhile,
+//! and the first namespace is rejected if there are rules loaded by these APIs, thus neither side replaces the other.
+//! The rule changes of a namespace are notified to the `RuleChangeListener`s with the namespace as the source.
+use crate::base::{
+    check_direct_update, read_rule_snapshot, set_namespaced, validate_rules, with_namespace_update,
+    with_rule_source, write_rule_snapshot, RuleDiagnostics,
+};
+use crate::{circuitbreaker, fault, flow, hotspot, isolation, retry, system, Error, Result};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+lazy_static! {
+    static ref NAMESPACES: Mutex<BTreeMap<String, RuleSet>> = Mutex::new(BTreeMap::new());
+    // serializes the updates of the namespaces, `NAMESPACES` is not locked while the rules are loaded,
+    // thus the rule change listeners are free to read the namespaces
+    static ref NAMESPACE_UPDATE: Mutex<()> = Mutex::new(());
+}
+
+/// `RuleSet` is the full snapshot of rules,
+/// the empty rules of a type would clear the previous rules of this type when loading.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleSet {
+    pub flow: Vec<Arc<flow::Rule>>,
+    pub circuit_breaker: Vec<Arc<circuitbreaker::Rule>>,
+    pub hotspot: Vec<Arc<hotspot::Rule>>,
+    pub system: Vec<Arc<system::Rule>>,
+    pub isolation: Vec<Arc<isolation::Rule>>,
+    pub fault: Vec<Arc<fault::Rule>>,
+    pub retry: Vec<Arc<retry::Rule>>,
+}
+
+/// `RuleSetDiagnostics` is the diagnostics of the problematic rules in a `RuleSet`, grouped by types.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RuleSetDiagnostics {
+    pub flow: Vec<RuleDiagnostics>,
+    pub circuit_breaker: Vec<RuleDiagnostics>,
+    pub hotspot: Vec<RuleDiagnostics>,
+    pub system: Vec<RuleDiagnostics>,
+    pub isolation: Vec<RuleDiagnostics>,
+    pub fault: Vec<RuleDiagnostics>,
+    pub retry: Vec<RuleDiagnostics>,
+}
+
+impl RuleSetDiagnostics {
+    fn groups(&self) -> [(&'static str, &Vec<RuleDiagnostics>); 7] {
+        [
+            ("flow", &self.flow),
+            ("circuit_breaker", &self.circuit_breaker),
+            ("hotspot", &self.hotspot),
+            ("system", &self.system),
+            ("isolation", &self.isolation),
+            ("fault", &self.fault),
+            ("retry", &self.retry),
+        ]
+    }
+
+    /// `is_rejected` indicates there are invalid rules, thus the whole set would be rejected.
+    pub fn is_rejected(&self) -> bool {
+        self.groups()
+            .iter()
+            .any(|(_, group)| group.iter().any(RuleDiagnostics::is_rejected))
+    }
+}
+
+impl fmt::Display for RuleSetDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid rule set")?;
+        for (kind, group) in self.groups().iter() {
+            for rule in group.iter().filter(|r| r.is_rejected()) {
+                for diagnostic in rule.diagnostics.iter().filter(|d| d.is_error()) {
+                    write!(
+                        f,
+                        "; {}[{}]: {}: {}",
+                        kind, rule.index, diagnostic.field, diagnostic.reason
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RuleSetDiagnostics {}
+
+impl RuleSet {
+    pub fn validate(&self) -> RuleSetDiagnostics {
+        RuleSetDiagnostics {
+            flow: validate_rules(&self.flow),
+            circuit_breaker: validate_rules(&self.circuit_breaker),
+            hotspot: validate_rules(&self.hotspot),
+            system: validate_rules(&self.system),
+            isolation: validate_rules(&self.isolation),
+            fault: validate_rules(&self.fault),
+            retry: validate_rules(&self.retry),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.flow.is_empty()
+            && self.circuit_breaker.is_empty()
+            && self.hotspot.is_empty()
+            && self.system.is_empty()
+            && self.isolation.is_empty()
+            && self.fault.is_empty()
+            && self.retry.is_empty()
+    }
+
+    fn extend(&mut self, other: &RuleSet) {
+        self.flow.extend(other.flow.iter().cloned());
+        self.circuit_breaker
+            .extend(other.circuit_breaker.iter().cloned());
+        self.hotspot.extend(other.hotspot.iter().cloned());
+        self.system.extend(other.system.iter().cloned());
+        self.isolation.extend(other.isolation.iter().cloned());
+        self.fault.extend(other.fault.iter().cloned());
+        self.retry.extend(other.retry.iter().cloned());
+    }
+}
+
+/// `load_rule_set` replaces the rules of all types atomically.
+/// If any rule in the set is invalid, nothing is loaded,
+/// and the returned error can be downcast to `RuleSetDiagnostics`.
+/// It is rejected while the rules are owned by the namespaces.
+pub fn load_rule_set(rule_set: RuleSet) -> Result<()> {
+    check_direct_update("`load_rule_set()`")?;
+    let diagnostics = rule_set.validate();
+    if diagnostics.is_rejected() {
+        return Err(Error::new(diagnostics));
+    }
+    // the rule checking of entries waits until all types are switched
+    let _snapshot = write_rule_snapshot();
+    flow::load_rules(rule_set.flow);
+    circuitbreaker::load_rules(rule_set.circuit_breaker);
+    hotspot::load_rules(rule_set.hotspot);
+    system::load_rules(rule_set.system);
+    isolation::load_rules(rule_set.isolation);
+    fault::load_rules(rule_set.fault);
+    retry::load_rules(rule_set.retry);
+    Ok(())
+}
+
+/// `get_rule_set` returns a consistent snapshot of the rules of all types.
+pub fn get_rule_set() -> RuleSet {
+    let _snapshot = read_rule_snapshot();
+    RuleSet {
+        flow: flow::get_rules(),
+        circuit_breaker: circuitbreaker::get_rules(),
+        hotspot: hotspot::get_rules(),
+        system: system::get_rules(),
+        isolation: isolation::get_rules(),
+        fault: fault::get_rules(),
+        retry: retry::get_rules(),
+    }
+}
+
+/// `load_namespace_rules` replaces the rules of the namespace,
+/// the rules of other namespaces are kept. Nothing changes if any rule is invalid.
+pub fn load_namespace_rules(namespace: &str, rule_set: RuleSet) -> Result<()> {
+    update_namespace_rules(namespace, |current| *current = rule_set)
+}
+
+/// `update_namespace_rules` modifies the rules of the namespace in place,
+/// e.g., a datasource of flow rules only replaces `rule_set.flow` of its namespace.
+/// The first namespace is rejected if there are rules loaded bypassing the namespaces, clear them beforehand.
+pub fn update_namespace_rules(namespace: &str, f: impl FnOnce(&mut RuleSet)) -> Result<()> {
+    let _update = NAMESPACE_UPDATE.lock().unwrap();
+    let mut updated = NAMESPACES.lock().unwrap().clone();
+    let was_namespaced = !updated.is_empty();
+    if !was_namespaced && !get_rule_set().is_empty() {
+        return Err(Error::msg(
+            "the rules loaded bypassing the namespaces would be replaced, clear them before loading the namespaces",
+        ));
+    }
+    f(updated.entry(namespace.into()).or_default());
+    // the direct updates are rejected from now on, otherwise they could slip in before the namespace is stored
+    set_namespaced(true);
+    if let Err(err) = with_rule_source(namespace, || apply_namespaces(&updated)) {
+        set_namespaced(was_namespaced);
+        return Err(err);
+    }
+    *NAMESPACES.lock().unwrap() = updated;
+    Ok(())
+}
+
+/// `clear_namespace_rules` removes the namespace and its rules,
+/// the rules can be loaded bypassing the namespaces again once all the namespaces are cleared.
+pub fn clear_namespace_rules(namespace: &str) -> Result<()> {
+    let _update = NAMESPACE_UPDATE.lock().unwrap();
+    let mut updated = NAMESPACES.lock().unwrap().clone();
+    if updated.remove(namespace).is_some() {
+        with_rule_source(namespace, || apply_namespaces(&updated))?;
+        set_namespaced(!updated.is_empty());
+        *NAMESPACES.lock().unwrap() = updated;
+    }
+    Ok(())
+}
+
+pub fn get_namespace_rules(namespace: &str) -> Option<RuleSet> {
+    NAMESPACES.lock().unwrap().get(namespace).cloned()
+}
+
+pub fn namespaces() -> Vec<String> {
+    NAMESPACES.lock().unwrap().keys().cloned().collect()
+}
+
+fn apply_namespaces(namespaces: &BTreeMap<String, RuleSet>) -> Result<()> {
+    let mut union = RuleSet::default();
+    for rule_set in namespaces.values() {
+        union.extend(rule_set);
+    }
+    with_namespace_update(|| load_rule_set(union))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[ignore]
+    fn load_atomically() {
+        let valid = RuleSet {
+            flow: vec![Arc::new(flow::Rule {
+                resource: "rule_set".into(),
+                threshold: 10.0,
+                ..Default::default()
+            })],
+            isolation: vec![Arc::new(isolation::Rule {
+                resource: "rule_set".into(),
+                threshold: 10,
+                ..Default::default()
+            })],
+            ..Default::default()
+        };
+        load_rule_set(valid).unwrap();
+        let current = get_rule_set();
+        assert_eq!(current.flow.len(), 1);
+        assert_eq!(current.isolation.len(), 1);
+
+        // an invalid rule rejects the whole set
+        let invalid = RuleSet {
+            hotspot: vec![Arc::new(hotspot::Rule {
+                resource: "rule_set".into(),
+                ..Default::default()
+            })],
+            system: vec![Arc::new(system::Rule {
+                trigger_count: -1.0,
+                ..Default::default()
+            })],
+            ..Default::default()
+        };
+        let err = load_rule_set(invalid).unwrap_err();
+        let diagnostics = err.downcast_ref::<RuleSetDiagnostics>().unwrap();
+        assert!(diagnostics.hotspot.is_empty());
+        assert_eq!(diagnostics.system[0].index, 0);
+        assert!(err.to_string().contains("system[0]: trigger_count"));
+        let current = get_rule_set();
+        assert_eq!(current.flow.len(), 1);
+        assert_eq!(current.isolation.len(), 1);
+        assert!(current.system.is_empty());
+
+        load_rule_set(RuleSet::default()).unwrap();
+        let current = get_rule_set();
+        assert!(current.flow.is_empty());
+        assert!(current.isolation.is_empty());
+    }
+
+    #[test]
+    #[ignore]
+    fn load_while_waiting() {
+        let rule_set = RuleSet {
+            flow: vec![Arc::new(flow::Rule {
+                resource: "rule_set_waiting".into(),
+                threshold: 2.0,
+                control_strategy: flow::ControlStrategy::Throttling,
+                max_queueing_time_ms: 1000,
+                stat_interval_ms: 1000,
+                ..Default::default()
+            })],
+            ..Default::default()
+        };
+        load_rule_set(rule_set.clone()).unwrap();
+        let waiting = std::thread::spawn(|| {
+            let start = std::time::Instant::now();
+            for _ in 0..2 {
+                let entry = crate::EntryBuilder::new("rule_set_waiting".into())
+                    .build()
+                    .unwrap();
+                entry.read().unwrap().exit();
+            }
+            // the second entry is queued for 500 ms
+            start.elapsed()
+        });
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        // the rules are not locked by the waiting entry
+        let start = std::time::Instant::now();
+        load_rule_set(rule_set).unwrap();
+        assert!(start.elapsed() < std::time::Duration::from_millis(200));
+        assert!(waiting.join().unwrap() >= std::time::Duration::from_millis(400));
+        load_rule_set(RuleSet::default()).unwrap();
+    }
+
+    #[test]
+    #[ignore]
+    fn namespaces() {
+        let flow_rule = |threshold| {
+            Arc::new(flow::Rule {
+                resource: "namespace".into(),
+                threshold,
+                ..Default::default()
+            })
+        };
+        // the listeners are free to read the namespaces
+        struct Reader(Mutex<Vec<Vec<String>>>);
+        impl crate::base::RuleChangeListener<flow::Rule> for Reader {
+            fn on_rules_changed(&self, _change: &crate::base::RuleChange<flow::Rule>) {
+                get_namespace_rules("a");
+                self.0.lock().unwrap().push(super::namespaces());
+            }
+        }
+        let reader = Arc::new(Reader(Mutex::new(Vec::new())));
+        flow::register_rule_change_listeners(vec![reader.clone()]);
+
+        // the rules loaded bypassing the namespaces are not replaced
+        flow::load_rules(vec![flow_rule(5.0)]);
+        let only_flow = RuleSet {
+            flow: vec![flow_rule(1.0)],
+            ..Default::default()
+        };
+        assert!(load_namespace_rules("a", only_flow.clone()).is_err());
+        assert_eq!(flow::get_rules()[0].threshold, 5.0);
+        flow::clear_rules();
+
+        load_namespace_rules("a", only_flow).unwrap();
+        update_namespace_rules("b", |rule_set| rule_set.flow = vec![flow_rule(2.0)]).unwrap();
+        assert_eq!(super::namespaces(), vec!["a", "b"]);
+        assert_eq!(flow::get_rules().len(), 2);
+
+        // replacing a namespace keeps the others
+        load_namespace_rules(
+            "a",
+            RuleSet {
+                flow: vec![flow_rule(3.0), flow_rule(4.0)],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(flow::get_rules().len(), 3);
+
+        // an invalid update changes nothing
+        assert!(
+            update_namespace_rules("b", |rule_set| rule_set.flow = vec![flow_rule(-1.0)]).is_err()
+        );
+        assert_eq!(get_namespace_rules("b").unwrap().flow[0].threshold, 2.0);
+        assert_eq!(flow::get_rules().len(), 3);
+
+        // the namespaces are not replaced by the APIs bypassing them
+        assert!(!flow::load_rules(vec![flow_rule(5.0)]));
+        flow::clear_rules();
+        assert!(load_rule_set(RuleSet::default()).is_err());
+        assert_eq!(flow::get_rules().len(), 3);
+
+        clear_namespace_rules("a").unwrap();
+        assert_eq!(flow::get_rules().len(), 1);
+        clear_namespace_rules("b").unwrap();
+        assert!(flow::get_rules().is_empty());
+        assert!(super::namespaces().is_empty());
+        // the namespaces stored before the update are read inside it
+        let read = reader.0.lock().unwrap().clone();
+        assert_eq!(read.len(), 7);
+        assert_eq!(read[6], vec!["b"]);
+        flow::clear_rule_change_listeners();
+
+        // the rules can be loaded bypassing the namespaces once they are cleared
+        assert!(flow::load_rules(vec![flow_rule(5.0)]));
+        flow::clear_rules();
+    }
+}
@@ -0,0 +1,488 @@
+//! Combinators for asynchronous invocations.
+//! `run()` wraps an asynchronous task in the entry of the resource, the business error is recorded
+//! for the circuit breakers, the blocked or failed invocations can be retried with backoff,
+//! and a fallback can produce the substitute value finally, e.g.,
+//! `run(resource, || task()).retry(RetryPolicy::new(3)).fallback(|err| default).call().await`.
+//! The retries are bounded by the retry budgets of the resource as well, see mod `retry`.
+use super::EntryBuilder;
+use crate::base::{AsyncEntry, BlockError, EntryGuard};
+use crate::Error;
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+/// `RetryPolicy` describes the bounded retries with exponential backoff.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// `max_retries` is the max number of retries after the first attempt, 0 means no retry
+    pub max_retries: u32,
+    /// `initial_backoff` is the backoff before the first retry
+    pub initial_backoff: Duration,
+    /// `max_backoff` is the upper bound of the backoff
+    pub max_backoff: Duration,
+    /// `multiplier` is the growth factor of the backoff between two retries
+    pub multiplier: f64,
+    /// `retry_blocked` indicates whether the blocked invocations are retried,
+    /// the retry-after hint of `BlockError` is respected if present
+    pub retry_blocked: bool,
+    /// `retry_failed` indicates whether the failed invocations are retried
+    pub retry_failed: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 0,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2.0,
+            retry_blocked: true,
+            retry_failed: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32) -> Self {
+        RetryPolicy {
+            max_retries,
+            ..Default::default()
+        }
+    }
+
+    /// `backoff` returns the backoff before the `retry`-th (starting from 0) retry.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self.initial_backoff.as_secs_f64() * self.multiplier.powi(retry as i32);
+        if !backoff.is_finite() || backoff >= self.max_backoff.as_secs_f64() {
+            self.max_backoff
+        } else {
+            Duration::from_secs_f64(backoff.max(0.0))
+        }
+    }
+
+    fn should_retry<E>(&self, err: &RunError<E>) -> bool {
+        match err {
+            RunError::Blocked(_) => self.retry_blocked,
+            RunError::Failed(_) => self.retry_failed,
+        }
+    }
+}
+
+/// `RunError` is the error of the last attempt.
+#[derive(Debug)]
+pub enum RunError<E> {
+    /// The entry was blocked, the task was not invoked.
+    Blocked(BlockError),
+    /// The entry passed, but the task failed.
+    Failed(E),
+}
+
+impl<E> RunError<E> {
+    pub fn is_blocked(&self) -> bool {
+        matches!(self, RunError::Blocked(_))
+    }
+
+    pub fn is_failed(&self) -> bool {
+        matches!(self, RunError::Failed(_))
+    }
+
+    pub fn block_err(&self) -> Option<&BlockError> {
+        match self {
+            RunError::Blocked(err) => Some(err),
+            RunError::Failed(_) => None,
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for RunError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunError::Blocked(err) => write!(f, "{}", err),
+            RunError::Failed(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for RunError<E> {}
+
+type ErrorPredicate<E> = Box<dyn Fn(&E) -> bool + Send + Sync>;
+
+/// `Run` is created by `run()` or `run_with()`, call `call()` to execute it.
+pub struct Run<F, E> {
+    builder: EntryBuilder,
+    task: F,
+    policy: RetryPolicy,
+    error_predicate: Option<ErrorPredicate<E>>,
+}
+
+/// `run` executes the task in the entries of the resource,
+/// the task is a factory of futures, since each retry needs a new one.
+pub fn run<F, Fut, T, E>(resource: String, task: F) -> Run<F, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = std::result::Result<T, E>>,
+{
+    run_with(EntryBuilder::new(resource), task)
+}
+
+/// `run_with` is similar to `run()`, the entries are built by the clones of `builder`.
+pub fn run_with<F, Fut, T, E>(builder: EntryBuilder, task: F) -> Run<F, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = std::result::Result<T, E>>,
+{
+    Run {
+        builder,
+        task,
+        policy: RetryPolicy::default(),
+        error_predicate: None,
+    }
+}
+
+impl<F, Fut, T, E> Run<F, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = std::result::Result<T, E>>,
+    E: fmt::Display,
+{
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// `error_predicate` decides which errors are recorded for the circuit breakers,
+    /// by default, all the errors returned by the task are recorded.
+    pub fn error_predicate(
+        mut self,
+        predicate: impl Fn(&E) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.error_predicate = Some(Box::new(predicate));
+        self
+    }
+
+    pub fn fallback<G>(self, fallback: G) -> Fallback<F, E, G>
+    where
+        G: FnOnce(RunError<E>) -> T,
+    {
+        Fallback {
+            run: self,
+            fallback,
+        }
+    }
+
+    /// `call` executes the task until it succeeds, the retries are exhausted or the retry budget is used up,
+    /// the error of the last attempt is returned.
+    pub async fn call(mut self) -> std::result::Result<T, RunError<E>> {
+        let mut retry = 0;
+        loop {
+            let err = match self.attempt().await {
+                Ok(v) => return Ok(v),
+                Err(err) => err,
+            };
+            if retry >= self.policy.max_retries
+                || !self.policy.should_retry(&err)
+                || !crate::retry::try_acquire_retry(self.builder.resource_name())
+            {
+                return Err(err);
+            }
+            let mut backoff = self.policy.backoff(retry);
+            if let Some(retry_after) = err.block_err().and_then(|e| e.retry_after()) {
+                backoff = backoff.max(retry_after);
+            }
+            futures_timer::Delay::new(backoff).await;
+            retry += 1;
+        }
+    }
+
+    async fn attempt(&mut self) -> std::result::Result<T, RunError<E>> {
+        let entry = self
+            .builder
+            .clone()
+            .try_build_with(true)
+            .map_err(|r| RunError::Blocked(r.block_err().unwrap_or_default()))?;
+        // the entry is exited even if the task is cancelled, or panics (recorded as the error)
+        let guard = EntryGuard::new(AsyncEntry::from(entry));
+        // e.g., queued by the throttling flow rules, without blocking the thread
+        guard.entry().wait().await;
+        let result = guard.catch_panic((self.task)()).await;
+        if let Err(err) = &result {
+            let recorded = match &self.error_predicate {
+                Some(predicate) => predicate(err),
+                None => true,
+            };
+            if recorded {
+                guard.set_err(Error::msg(err.to_string()));
+            }
+        }
+        guard.exit();
+        result.map_err(RunError::Failed)
+    }
+}
+
+/// `Fallback` is created by `Run::fallback()`,
+/// the fallback is invoked with the error of the last attempt.
+pub struct Fallback<F, E, G> {
+    run: Run<F, E>,
+    fallback: G,
+}
+
+impl<F, Fut, T, E, G> Fallback<F, E, G>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = std::result::Result<T, E>>,
+    E: fmt::Display,
+    G: FnOnce(RunError<E>) -> T,
+{
+    pub async fn call(self) -> T {
+        match self.run.call().await {
+            Ok(v) => v,
+            Err(err) => (self.fallback)(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::{
+        BlockType, MockRuleCheckSlot, MockStatPrepareSlot, MockStatSlot, SlotChain, TokenResult,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn builder(blocked: bool) -> EntryBuilder {
+        let mut sc = SlotChain::new();
+        if blocked {
+            let mut ps = Arc::new(MockStatPrepareSlot::new());
+            let mut rcs = Arc::new(MockRuleCheckSlot::new());
+            Arc::get_mut(&mut ps)
+                .unwrap()
+                .expect_prepare()
+                .return_const(());
+            Arc::get_mut(&mut rcs)
+                .unwrap()
+                .expect_check()
+                .returning(|_ctx| TokenResult::new_blocked(BlockType::Flow));
+            sc.add_stat_prepare_slot(ps);
+            sc.add_rule_check_slot(rcs);
+        }
+        EntryBuilder::new("run".into()).with_slot_chain(Arc::new(sc))
+    }
+
+    fn policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn backoff() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(300),
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff(2), Duration::from_millis(300));
+        assert_eq!(policy.backoff(100), Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn retry_failed() {
+        let attempts = AtomicUsize::new(0);
+        let r = run_with(builder(false), || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err("biz error")
+            } else {
+                Ok(1)
+            }
+        })
+        .retry(policy(3))
+        .call()
+        .await;
+        assert_eq!(r.unwrap(), 1);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_exhausted() {
+        let attempts = AtomicUsize::new(0);
+        let r = run_with(builder(false), || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<u32, _>("biz error")
+        })
+        .retry(policy(2))
+        .call()
+        .await;
+        assert!(r.unwrap_err().is_failed());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn retry_budget_exhausted() {
+        let resource = String::from("run_retry_budget_exhausted");
+        crate::retry::load_rules_of_resource(
+            &resource,
+            vec![Arc::new(crate::retry::Rule {
+                resource: resource.clone(),
+                max_retry_ratio: 0.0,
+                min_retries_per_sec: 1,
+                ..Default::default()
+            })],
+        )
+        .unwrap();
+        let attempts = AtomicUsize::new(0);
+        let builder =
+            EntryBuilder::new(resource.clone()).with_slot_chain(Arc::new(SlotChain::new()));
+        let r = run_with(builder, || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<u32, _>("biz error")
+        })
+        .retry(policy(3))
+        .call()
+        .await;
+        assert!(r.unwrap_err().is_failed());
+        // only one retry is allowed by the budget
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        crate::retry::clear_rules_of_resource(&resource);
+    }
+
+    #[tokio::test]
+    async fn blocked_fallback() {
+        let attempts = AtomicUsize::new(0);
+        let v = run_with(builder(true), || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, String>(1)
+        })
+        .retry(RetryPolicy {
+            retry_blocked: false,
+            ..policy(3)
+        })
+        .fallback(|err| {
+            assert_eq!(err.block_err().unwrap().block_type(), BlockType::Flow);
+            0
+        })
+        .call()
+        .await;
+        assert_eq!(v, 0);
+        // blocked entries never invoke the task
+        assert_eq!(attempts.load(Ordering::SeqCst), 0);
+    }
+
+    // counts the completed entries and the ones with errors
+    fn counting_builder(
+        resource: &str,
+        completed: Arc<AtomicUsize>,
+        recorded: Arc<AtomicUsize>,
+    ) -> EntryBuilder {
+        let mut ss = Arc::new(MockStatSlot::new());
+        Arc::get_mut(&mut ss)
+            .unwrap()
+            .expect_on_entry_pass()
+            .return_const(());
+        Arc::get_mut(&mut ss)
+            .unwrap()
+            .expect_on_completed()
+            .returning(move |ctx| {
+                completed.fetch_add(1, Ordering::SeqCst);
+                if ctx.read().unwrap().get_err().is_some() {
+                    recorded.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+        let mut sc = SlotChain::new();
+        sc.add_stat_slot(ss);
+        EntryBuilder::new(resource.into()).with_slot_chain(Arc::new(sc))
+    }
+
+    #[tokio::test]
+    async fn cancelled() {
+        let completed = Arc::new(AtomicUsize::new(0));
+        let recorded = Arc::new(AtomicUsize::new(0));
+        let builder = counting_builder(
+            "run_cancelled",
+            Arc::clone(&completed),
+            Arc::clone(&recorded),
+        );
+        let fut = run_with(builder, || std::future::pending::<Result<u32, String>>()).call();
+        tokio::select! {
+            biased;
+            _ = fut => unreachable!(),
+            _ = async {} => {}
+        }
+        assert_eq!(completed.load(Ordering::SeqCst), 1);
+        assert_eq!(recorded.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn panicked() {
+        let completed = Arc::new(AtomicUsize::new(0));
+        let recorded = Arc::new(AtomicUsize::new(0));
+        let builder = counting_builder(
+            "run_panicked",
+            Arc::clone(&completed),
+            Arc::clone(&recorded),
+        );
+        let handle = tokio::spawn(
+            run_with(builder, || async { panic!("boom") as Result<u32, String> }).call(),
+        );
+        assert!(handle.await.unwrap_err().is_panic());
+        assert_eq!(completed.load(Ordering::SeqCst), 1);
+        assert_eq!(recorded.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn wait_without_blocking() {
+        let mut ps = Arc::new(MockStatPrepareSlot::new());
+        let mut rcs = Arc::new(MockRuleCheckSlot::new());
+        Arc::get_mut(&mut ps)
+            .unwrap()
+            .expect_prepare()
+            .return_const(());
+        Arc::get_mut(&mut rcs)
+            .unwrap()
+            .expect_check()
+            .returning(|ctx| {
+                let mut ctx = ctx.write().unwrap();
+                ctx.defer_wait(Duration::from_millis(300).as_nanos() as u64);
+                ctx.result().clone()
+            });
+        let mut sc = SlotChain::new();
+        sc.add_stat_prepare_slot(ps);
+        sc.add_rule_check_slot(rcs);
+        let builder = EntryBuilder::new("run_wait".into()).with_slot_chain(Arc::new(sc));
+
+        // the runtime of `tokio::test` is single-threaded, the ticker is stalled if the thread sleeps
+        let start = std::time::Instant::now();
+        let (result, ticked) = tokio::join!(
+            run_with(builder, || async { Ok::<_, String>(start.elapsed()) }).call(),
+            async {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                start.elapsed()
+            }
+        );
+        assert!(result.unwrap() >= Duration::from_millis(300));
+        assert!(ticked < Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn record_error() {
+        let recorded = Arc::new(AtomicUsize::new(0));
+        let builder = counting_builder(
+            "run_record_error",
+            Arc::new(AtomicUsize::new(0)),
+            Arc::clone(&recorded),
+        );
+
+        let fut = run_with(builder, || async { Err::<u32, _>(String::from("timeout")) })
+            .retry(policy(2))
+            .error_predicate(|err: &String| err != "ignored")
+            .call();
+        fn assert_send<T: Send>(_: &T) {}
+        assert_send(&fut);
+        assert!(fut.await.is_err());
+        assert_eq!(recorded.load(Ordering::SeqCst), 3);
+    }
+}
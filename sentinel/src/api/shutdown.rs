@@ -0,0 +1,94 @@
+//! Shutting down Sentinel gracefully, e.g., at the end of the short-lived jobs or before the rolling restarts.
+//! The shutdown
+//!
+//!  1. waits for the in-flight entries to exit, thus their statistics and block logs are not lost,
+//!  2. stops the background tasks, i.e., the time ticker, the aggregation of the block log and the reload watcher,
+//!  3. flushes the last aggregate lines of the block log, the metric log and the logger.
+//!
+//! Notice that the metric log writer is not implemented yet (see `log::metric`), thus there is nothing to flush for it,
+//! and the system metric collectors are not background tasks, since each of them exits after a single collection.
+//! Both waits are bounded by the timeout. Sentinel could be initialized again after the shutdown.
+use crate::base::inflight_entries;
+use crate::log::block;
+#[cfg(feature = "metric-log")]
+use crate::log::metric;
+use crate::utils::background;
+use crate::{Error, Result};
+use std::time::{Duration, Instant};
+
+/// the default timeout of `shutdown()`
+pub const DEFAULT_SHUTDOWN_TIMEOUT_MS: u64 = 3000;
+
+const INFLIGHT_POLL_INTERVAL_MS: u64 = 5;
+
+/// `shutdown` shuts down Sentinel in `DEFAULT_SHUTDOWN_TIMEOUT_MS`, see `shutdown_with_timeout()`.
+pub fn shutdown() -> Result<()> {
+    shutdown_with_timeout(Duration::from_millis(DEFAULT_SHUTDOWN_TIMEOUT_MS))
+}
+
+/// `shutdown_with_timeout` waits for the in-flight entries, stops the background tasks and flushes the logs.
+/// The logs are flushed even if the timeout elapses, then the error reports the entries and the tasks left.
+pub fn shutdown_with_timeout(timeout: Duration) -> Result<()> {
+    // the real time is waited, regardless of the clocks of Sentinel
+    let deadline = Instant::now() + timeout;
+    while inflight_entries() > 0 && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(INFLIGHT_POLL_INTERVAL_MS));
+    }
+    let inflight = inflight_entries();
+    let unfinished_tasks = background::stop_tasks(deadline);
+
+    block::log_summaries();
+    #[cfg(feature = "metric-log")]
+    metric::flush();
+    log::logger().flush();
+
+    if inflight == 0 && unfinished_tasks.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::msg(format!(
+            "[Shutdown] Timed out in {:?}, in-flight entries: {}, unfinished tasks: {:?}",
+            timeout, inflight, unfinished_tasks
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::EntryBuilder;
+
+    #[test]
+    #[ignore]
+    fn wait_inflight_entries() {
+        crate::utils::start_time_ticker();
+        let entry = EntryBuilder::new("shutdown_wait".into()).build().unwrap();
+        let exiting = std::thread::spawn(move || {
+            crate::utils::sleep_for_ms(50);
+            entry.read().unwrap().exit();
+        });
+        // the ticker is joined as well
+        shutdown_with_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(inflight_entries(), 0);
+        assert!(background::is_stopped());
+        exiting.join().unwrap();
+
+        // the tasks could be started again, e.g., by `init_default()`
+        background::resume_tasks();
+        assert!(!background::is_stopped());
+    }
+
+    #[test]
+    #[ignore]
+    fn timeout() {
+        let entry = EntryBuilder::new("shutdown_timeout".into())
+            .build()
+            .unwrap();
+        let start = Instant::now();
+        let err = shutdown_with_timeout(Duration::from_millis(50)).unwrap_err();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(err.to_string().contains("in-flight entries: 1"));
+        entry.read().unwrap().exit();
+        assert_eq!(inflight_entries(), 0);
+        background::resume_tasks();
+    }
+}
@@ -1,28 +1,278 @@
-use crate::{base::SlotChain, circuitbreaker, flow, hotspot, isolation, stat, system};
+//! The global slot chain.
+//! Besides the built-in slots, the user-defined slots can be registered with an explicit order,
+//! the built-in slots take the orders of 1000, 2000, ..., 6000 in each bucket (see `SlotChainBuilder`),
+//! e.g., a `RuleCheckSlot` with order 1500 is checked between the system and the flow slots.
+//! The slots with the same order are executed in the order of registration.
+//! A group of resources can use its own slot chain built by `SlotChainBuilder`,
+//! e.g., skipping the system slot for the internal resources.
+use crate::base::{OrderedSlot, RuleCheckSlot, SlotChain, StatPrepareSlot, StatSlot};
+use crate::{circuitbreaker, fault, flow, hotspot, isolation, stat, system};
 use lazy_static::lazy_static;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 
 lazy_static! {
-    pub static ref GLOBAL_SLOT_CHAIN: Arc<SlotChain> = {
-        let mut sc = SlotChain::new();
+    /// The registration is copy-on-write,
+    /// the entries built before keep the slot chain at that time.
+    pub static ref GLOBAL_SLOT_CHAIN: RwLock<Arc<SlotChain>> =
+        RwLock::new(SlotChainBuilder::default_slots().build());
+    /// The slot chains of specific resources, which take the place of the global one.
+    static ref RESOURCE_SLOT_CHAINS: RwLock<HashMap<String, Arc<SlotChain>>> =
+        RwLock::new(HashMap::new());
+}
+
+/// `SlotChainBuilder` builds the slot chain with the selected built-in slots and the customized slots.
+/// The built-in rule checking slots rely on the statistic structures prepared by `with_stat()`.
+/// The slots registered by `register_*_slot()` belong to the global slot chain only,
+/// they are not included in the chains built here.
+pub struct SlotChainBuilder {
+    sc: SlotChain,
+}
+
+impl Default for SlotChainBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SlotChainBuilder {
+    /// `new` creates a builder of the empty slot chain.
+    pub fn new() -> Self {
+        SlotChainBuilder {
+            sc: SlotChain::new(),
+        }
+    }
+
+    /// `default_slots` creates a builder with all the built-in slots, the same as the global slot chain.
+    pub fn default_slots() -> Self {
+        Self::new()
+            .with_stat()
+            .with_log()
+            .with_system()
+            .with_flow()
+            .with_isolation()
+            .with_hotspot()
+            .with_circuit_breaker()
+            .with_fault()
+    }
+
+    /// the resource node preparing slot and the resource statistic slot (order 1000)
+    pub fn with_stat(mut self) -> Self {
+        self.sc
+            .add_stat_prepare_slot(stat::default_resource_node_prepare_slot());
+        self.sc.add_stat_slot(stat::default_resource_stat_slot());
+        self
+    }
+
+    /// the block log slot (order 2000)
+    pub fn with_log(mut self) -> Self {
+        self.sc.add_stat_slot(crate::log::default_stat_slot());
+        self
+    }
+
+    /// the system adaptive checking slot (order 1000)
+    pub fn with_system(mut self) -> Self {
+        self.sc.add_rule_check_slot(system::default_slot());
+        self
+    }
+
+    /// the flow checking slot (order 2000) and its standalone statistic slot (order 3000)
+    pub fn with_flow(mut self) -> Self {
+        self.sc.add_rule_check_slot(flow::default_slot());
+        self.sc.add_stat_slot(flow::default_stand_alone_stat_slot());
+        self
+    }
 
-        sc.add_stat_prepare_slot(stat::default_resource_node_prepare_slot());
+    /// the concurrency isolation checking slot (order 3000)
+    pub fn with_isolation(mut self) -> Self {
+        self.sc.add_rule_check_slot(isolation::default_slot());
+        self
+    }
 
-        sc.add_rule_check_slot(system::default_slot()); // 1000
-        sc.add_rule_check_slot(flow::default_slot()); // 2000
-        sc.add_rule_check_slot(isolation::default_slot()); // 3000
-        sc.add_rule_check_slot(hotspot::default_slot()); // 4000
-        sc.add_rule_check_slot(circuitbreaker::default_slot()); // 5000
+    /// the hotspot parameter checking slot (order 4000) and its standalone statistic slot (order 4000)
+    pub fn with_hotspot(mut self) -> Self {
+        self.sc.add_rule_check_slot(hotspot::default_slot());
+        self.sc
+            .add_stat_slot(hotspot::default_stand_alone_stat_slot());
+        self
+    }
 
-        sc.add_stat_slot(stat::default_resource_stat_slot()); // 1000
-        sc.add_stat_slot(crate::log::default_stat_slot()); // 2000
-        sc.add_stat_slot(flow::default_stand_alone_stat_slot()); // 3000
-        sc.add_stat_slot(hotspot::default_stand_alone_stat_slot()); // 4000
-        sc.add_stat_slot(circuitbreaker::default_metric_stat_slot()); // 5000
-        Arc::new(sc)
-    };
+    /// the circuit breaking checking slot (order 5000) and its metric statistic slot (order 5000)
+    pub fn with_circuit_breaker(mut self) -> Self {
+        self.sc.add_rule_check_slot(circuitbreaker::default_slot());
+        self.sc
+            .add_stat_slot(circuitbreaker::default_metric_stat_slot());
+        self
+    }
+
+    /// the fault injecting slot (order 6000)
+    pub fn with_fault(mut self) -> Self {
+        self.sc.add_rule_check_slot(fault::default_slot());
+        self
+    }
+
+    pub fn with_stat_prepare_slot(mut self, slot: Arc<dyn StatPrepareSlot>) -> Self {
+        self.sc.add_stat_prepare_slot(slot);
+        self
+    }
+
+    pub fn with_rule_check_slot(mut self, slot: Arc<dyn RuleCheckSlot>) -> Self {
+        self.sc.add_rule_check_slot(slot);
+        self
+    }
+
+    pub fn with_stat_slot(mut self, slot: Arc<dyn StatSlot>) -> Self {
+        self.sc.add_stat_slot(slot);
+        self
+    }
+
+    pub fn build(self) -> Arc<SlotChain> {
+        Arc::new(self.sc)
+    }
 }
 
 pub fn global_slot_chain() -> Arc<SlotChain> {
-    GLOBAL_SLOT_CHAIN.clone()
+    GLOBAL_SLOT_CHAIN.read().unwrap().clone()
+}
+
+/// `register_resource_slot_chain` makes the resources use the slot chain instead of the global one,
+/// it takes effect on the entries built afterwards.
+pub fn register_resource_slot_chain(resources: Vec<String>, slot_chain: Arc<SlotChain>) {
+    let mut chains = RESOURCE_SLOT_CHAINS.write().unwrap();
+    for resource in resources {
+        chains.insert(resource, Arc::clone(&slot_chain));
+    }
+}
+
+pub fn remove_resource_slot_chain(resource: &String) {
+    RESOURCE_SLOT_CHAINS.write().unwrap().remove(resource);
+}
+
+pub fn clear_resource_slot_chains() {
+    RESOURCE_SLOT_CHAINS.write().unwrap().clear();
+}
+
+/// `resource_slot_chain` returns the slot chain registered for the resource,
+/// or the global slot chain if there is no such one.
+pub fn resource_slot_chain(resource: &String) -> Arc<SlotChain> {
+    RESOURCE_SLOT_CHAINS
+        .read()
+        .unwrap()
+        .get(resource)
+        .cloned()
+        .unwrap_or_else(global_slot_chain)
+}
+
+fn update_global_slot_chain(f: impl FnOnce(&mut SlotChain)) {
+    let mut global = GLOBAL_SLOT_CHAIN.write().unwrap();
+    let mut sc = SlotChain::clone(&global);
+    f(&mut sc);
+    *global = Arc::new(sc);
+}
+
+/// `register_stat_prepare_slot` adds the `StatPrepareSlot` to the global slot chain,
+/// the `order` overrides the `order()` of the slot.
+pub fn register_stat_prepare_slot(slot: Arc<dyn StatPrepareSlot>, order: u32) {
+    update_global_slot_chain(|sc| {
+        sc.add_stat_prepare_slot(Arc::new(OrderedSlot::new(slot, order)))
+    });
+}
+
+/// `register_rule_check_slot` adds the `RuleCheckSlot` to the global slot chain,
+/// the `order` overrides the `order()` of the slot.
+pub fn register_rule_check_slot(slot: Arc<dyn RuleCheckSlot>, order: u32) {
+    update_global_slot_chain(|sc| sc.add_rule_check_slot(Arc::new(OrderedSlot::new(slot, order))));
+}
+
+/// `register_stat_slot` adds the `StatSlot` to the global slot chain,
+/// the `order` overrides the `order()` of the slot.
+pub fn register_stat_slot(slot: Arc<dyn StatSlot>, order: u32) {
+    update_global_slot_chain(|sc| sc.add_stat_slot(Arc::new(OrderedSlot::new(slot, order))));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::{BaseSlot, BlockType, ContextPtr, TokenResult};
+    use crate::EntryBuilder;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const BLOCKED_RES: &str = "custom_slot_blocked";
+
+    struct AuthQuotaSlot;
+    impl BaseSlot for AuthQuotaSlot {}
+    impl RuleCheckSlot for AuthQuotaSlot {
+        fn check(&self, ctx: &ContextPtr) -> TokenResult {
+            let ctx = ctx.read().unwrap();
+            if ctx.resource().name() == BLOCKED_RES {
+                TokenResult::new_blocked_with_msg(BlockType::Other(1), "quota exhausted".into())
+            } else {
+                ctx.result().clone()
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct CountSlot {
+        blocked: AtomicUsize,
+    }
+    impl BaseSlot for CountSlot {}
+    impl StatSlot for CountSlot {
+        fn on_entry_blocked(&self, ctx: ContextPtr, _: Option<crate::base::BlockError>) {
+            if ctx.read().unwrap().resource().name() == BLOCKED_RES {
+                self.blocked.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    #[test]
+    fn register_custom_slots() {
+        let before = global_slot_chain();
+        register_rule_check_slot(Arc::new(AuthQuotaSlot), 1500);
+        let counter = Arc::new(CountSlot::default());
+        register_stat_slot(counter.clone(), 500);
+
+        let err = EntryBuilder::new(BLOCKED_RES.into()).build();
+        assert!(err.err().unwrap().to_string().contains("quota exhausted"));
+        assert_eq!(counter.blocked.load(Ordering::SeqCst), 1);
+        let entry = EntryBuilder::new("custom_slot_passed".into())
+            .build()
+            .unwrap();
+        entry.read().unwrap().exit();
+
+        // the slot chain obtained before is not affected
+        let entry = EntryBuilder::new(BLOCKED_RES.into())
+            .with_slot_chain(before)
+            .build()
+            .unwrap();
+        entry.read().unwrap().exit();
+    }
+
+    #[test]
+    fn resource_slot_chain_without_flow() {
+        let resource = String::from("resource_slot_chain");
+        crate::flow::load_rules_of_resource(
+            &resource,
+            vec![Arc::new(crate::flow::Rule {
+                resource: resource.clone(),
+                threshold: 0.0,
+                ..Default::default()
+            })],
+        )
+        .unwrap();
+        assert!(EntryBuilder::new(resource.clone()).build().is_err());
+
+        let sc = SlotChainBuilder::new()
+            .with_stat()
+            .with_circuit_breaker()
+            .build();
+        register_resource_slot_chain(vec![resource.clone()], Arc::clone(&sc));
+        assert!(Arc::ptr_eq(&resource_slot_chain(&resource), &sc));
+        let entry = EntryBuilder::new(resource.clone()).build().unwrap();
+        entry.read().unwrap().exit();
+
+        remove_resource_slot_chain(&resource);
+        assert!(EntryBuilder::new(resource.clone()).build().is_err());
+        crate::flow::clear_rules_of_resource(&resource);
+    }
 }
@@ -3,6 +3,7 @@ use crate::utils;
 use std::any::Any;
 use std::fmt;
 use std::sync::Arc;
+use std::time::Duration;
 
 // todo: use String instead of Any to record snapshots?
 pub trait SnapshotTrait: Any + fmt::Debug + utils::AsAny + Send + Sync {}
@@ -18,6 +19,11 @@ pub struct BlockError {
     rule: Option<Arc<dyn SentinelRule>>,
     // snapshotValue represents the triggered "snapshot" value
     snapshot_value: Option<Arc<Snapshot>>,
+    // resource is the name of the blocked resource, it is filled by the slot chain
+    resource: String,
+    // retry_after is the estimated duration after which the request may pass,
+    // it is provided in throttling and circuit breaking cases
+    retry_after: Option<Duration>,
 }
 
 impl BlockError {
@@ -47,6 +53,7 @@ impl BlockError {
             block_msg,
             rule: Some(rule),
             snapshot_value: Some(snapshot_value),
+            ..Self::default()
         }
     }
 
@@ -65,22 +72,44 @@ impl BlockError {
     pub fn triggered_value(&self) -> Option<Arc<Snapshot>> {
         self.snapshot_value.clone()
     }
+
+    pub fn set_resource(&mut self, resource: String) {
+        self.resource = resource;
+    }
+
+    pub fn resource(&self) -> String {
+        self.resource.clone()
+    }
+
+    pub fn set_retry_after(&mut self, retry_after: Duration) {
+        self.retry_after = Some(retry_after);
+    }
+
+    /// `retry_after` returns the estimated duration after which the request may pass,
+    /// which can be used in the `Retry-After` headers of HTTP responses.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.retry_after
+    }
 }
 
 impl fmt::Display for BlockError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if self.block_msg.len() == 0 {
-            write!(f, "SentinelBlockError: {}", self.block_type)
-        } else {
-            write!(
-                f,
-                "NoBlockError: {}, message: {}",
-                self.block_type, self.block_msg
-            )
+        write!(f, "SentinelBlockError: {}", self.block_type)?;
+        if self.block_msg.len() > 0 {
+            write!(f, ", message: {}", self.block_msg)?;
+        }
+        if self.resource.len() > 0 {
+            write!(f, ", resource: {}", self.resource)?;
+        }
+        if let Some(retry_after) = self.retry_after {
+            write!(f, ", retry after: {:?}", retry_after)?;
         }
+        Ok(())
     }
 }
 
+impl std::error::Error for BlockError {}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -141,6 +170,23 @@ mod test {
         }
     }
 
+    #[test]
+    fn error_display() {
+        let mut block_err = BlockError::new_with_msg(BlockType::Flow, "mock msg".into());
+        assert_eq!(
+            block_err.to_string(),
+            "SentinelBlockError: Flow, message: mock msg"
+        );
+        block_err.set_resource("abc".into());
+        block_err.set_retry_after(Duration::from_millis(100));
+        assert_eq!(block_err.resource(), "abc");
+        assert_eq!(block_err.retry_after(), Some(Duration::from_millis(100)));
+        assert_eq!(
+            block_err.to_string(),
+            "SentinelBlockError: Flow, message: mock msg, resource: abc, retry after: 100ms"
+        );
+    }
+
     #[test]
     fn error_create() {
         testcase(BlockType::Flow, None, None, None);
@@ -1,42 +1,47 @@
 //! Context
 //!
-use super::{EntryStrongPtr, EntryWeakPtr, ResourceWrapper, StatNode, TokenResult};
+use super::{
+    EntryStrongPtr, EntryWeakPtr, ResourceWrapper, SentinelContext, StatNode, TokenResult,
+};
 use crate::utils::time::curr_time_millis;
 use crate::Error;
 use std::any::Any;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
-cfg_async! {
-    use std::sync::RwLock;
-    pub type ContextPtr = Arc<RwLock<EntryContext>>;
-}
-
-cfg_not_async! {
-    use std::rc::Rc;
-    use std::cell::RefCell;
-    use std::rc::Weak;
-    pub type ContextPtr = Rc<RefCell<EntryContext>>;
-}
+/// `ContextPtr` is `Send + Sync`, so that a context created on one worker thread
+/// can be completed on another, e.g., in work-stealing executors or thread pools.
+pub type ContextPtr = Arc<RwLock<EntryContext>>;
 
 #[derive(Default)]
 pub struct EntryContext {
-    /// entry and context do not need to be `Send/Sync`
     /// entry<->context, cycled reference, so need Weak
-    /// context should not change entry, so here we do not use RefCell
     entry: Option<EntryWeakPtr>,
     /// Use to calculate RT
     start_time: u64,
-    /// The round trip time of this transaction
-    round_trip: u64,
+    /// The round trip time of this transaction,
+    /// it is measured when exiting unless the caller has set it
+    round_trip: Option<u64>,
     resource: ResourceWrapper,
     // todo: is it neccessary to keep using trait object here?
     // consider replacing by `crate::core::stat::ResourceNode`
     stat_node: Option<Arc<dyn StatNode>>,
+    /// the statistic node of the resource invoked through the entrance (named context)
+    chain_node: Option<Arc<dyn StatNode>>,
     input: SentinelInput,
     /// the result of rule slots check
     rule_check_result: TokenResult,
     err: Option<Error>,
+    /// the error injected by the fault rules, which is recorded when the entry exits
+    /// unless the application has set its own error
+    injected_err: Option<Error>,
+    /// the nanoseconds to wait before passing, deferred by the rule check slots (e.g., of the throttling rules),
+    /// thus the slot chain waits after the rules and the context are unlocked
+    nanos_to_wait: u64,
+    /// whether the deferred wait is left to the caller (e.g., awaited by `AsyncEntry::wait()`) rather than the slot chain
+    wait_by_caller: bool,
+    /// the invocation chain where the entry is built
+    sentinel_context: Option<SentinelContext>,
 }
 
 impl EntryContext {
@@ -63,12 +68,24 @@ impl EntryContext {
         self.rule_check_result.is_blocked()
     }
 
+    /// `set_round_trip` overrides the round trip time measured by Sentinel,
+    /// for the callers measuring it themselves. It should be called before exiting.
     pub fn set_round_trip(&mut self, round_trip: u64) {
-        self.round_trip = round_trip
+        self.round_trip = Some(round_trip)
     }
 
+    /// `round_trip` returns 0 before the round trip time is set or measured.
     pub fn round_trip(&self) -> u64 {
-        self.round_trip
+        self.round_trip.unwrap_or_default()
+    }
+
+    /// `complete_round_trip` measures the round trip time since the entry creation,
+    /// unless it has been set by `set_round_trip()`, the final one is returned.
+    pub fn complete_round_trip(&mut self) -> u64 {
+        let start_time = self.start_time;
+        *self
+            .round_trip
+            .get_or_insert_with(|| curr_time_millis().saturating_sub(start_time))
     }
 
     pub fn set_resource(&mut self, resource: ResourceWrapper) {
@@ -95,6 +112,14 @@ impl EntryContext {
         self.stat_node.clone()
     }
 
+    pub fn set_chain_node(&mut self, chain_node: Arc<dyn StatNode>) {
+        self.chain_node = Some(chain_node);
+    }
+
+    pub fn chain_node(&self) -> Option<Arc<dyn StatNode>> {
+        self.chain_node.clone()
+    }
+
     pub fn set_result(&mut self, result: TokenResult) {
         self.rule_check_result = result;
     }
@@ -107,10 +132,67 @@ impl EntryContext {
         &self.rule_check_result
     }
 
+    /// `defer_wait` requires the entry to wait before passing, the longest of the waits is taken,
+    /// since the waits required by the rule check slots start at the same time.
+    pub fn defer_wait(&mut self, nanos_to_wait: u64) {
+        self.nanos_to_wait = self.nanos_to_wait.max(nanos_to_wait);
+    }
+
+    pub fn nanos_to_wait(&self) -> u64 {
+        self.nanos_to_wait
+    }
+
+    /// `take_nanos_to_wait` returns the deferred wait and resets it, thus it is waited only once.
+    pub fn take_nanos_to_wait(&mut self) -> u64 {
+        std::mem::take(&mut self.nanos_to_wait)
+    }
+
+    pub fn set_wait_by_caller(&mut self, wait_by_caller: bool) {
+        self.wait_by_caller = wait_by_caller;
+    }
+
+    pub fn wait_by_caller(&self) -> bool {
+        self.wait_by_caller
+    }
+
     pub fn set_err(&mut self, err: Error) {
         self.err = Some(err);
     }
 
+    /// `inject_err` defers the error (e.g., of the fault rules) to the exit of the entry, see `apply_injected_err`.
+    pub fn inject_err(&mut self, err: Error) {
+        self.injected_err = Some(err);
+    }
+
+    /// `apply_injected_err` records the injected error, unless the application has set its own error.
+    pub fn apply_injected_err(&mut self) {
+        if let Some(err) = self.injected_err.take() {
+            if self.err.is_none() {
+                self.err = Some(err);
+            }
+        }
+    }
+
+    pub fn set_sentinel_context(&mut self, sentinel_context: SentinelContext) {
+        self.sentinel_context = Some(sentinel_context);
+    }
+
+    pub fn sentinel_context(&self) -> Option<&SentinelContext> {
+        self.sentinel_context.as_ref()
+    }
+
+    /// `entrance` returns the name of the entrance (named context) where the entry is built.
+    pub fn entrance(&self) -> Option<&String> {
+        self.sentinel_context.as_ref().and_then(|sc| sc.name())
+    }
+
+    /// `parent_entry` returns the innermost alive entry of the invocation chain.
+    pub fn parent_entry(&self) -> Option<EntryStrongPtr> {
+        self.sentinel_context
+            .as_ref()
+            .and_then(|sc| sc.parent_entry())
+    }
+
     pub fn get_err(&self) -> &Option<Error> {
         &self.err
     }
@@ -125,6 +207,11 @@ pub type ParamsMap = HashMap<String, ParamKey>;
 pub struct SentinelInput {
     batch_count: u32,
     flag: i32,
+    /// the origin (caller) of this invocation, e.g., the upstream service name
+    origin: Option<String>,
+    /// the priority class of this invocation, e.g., "interactive" or "batch",
+    /// the queued requests of the throttling flow rules are drained by the weights of the classes
+    priority_class: Option<String>,
     /// following input items are used in hotspot module
     args: Option<ParamsList>,
     attachments: Option<ParamsMap>,
@@ -135,6 +222,8 @@ impl Default for SentinelInput {
         SentinelInput {
             batch_count: 1,
             flag: 0,
+            origin: None,
+            priority_class: None,
             args: None,
             attachments: None,
         }
@@ -166,6 +255,22 @@ impl SentinelInput {
         self.flag
     }
 
+    pub fn set_origin(&mut self, origin: String) {
+        self.origin = Some(origin);
+    }
+
+    pub fn origin(&self) -> Option<&String> {
+        self.origin.as_ref()
+    }
+
+    pub fn set_priority_class(&mut self, priority_class: String) {
+        self.priority_class = Some(priority_class);
+    }
+
+    pub fn priority_class(&self) -> Option<&String> {
+        self.priority_class.as_ref()
+    }
+
     pub fn set_args(&mut self, args: ParamsList) {
         self.args = Some(args);
     }
@@ -194,4 +299,58 @@ mod test {
         ctx.set_result(TokenResult::new_blocked(BlockType::Other(1)));
         assert_eq!(ctx.is_blocked(), true);
     }
+
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ContextPtr>();
+        assert_send_sync::<EntryStrongPtr>();
+    }
+
+    #[test]
+    fn complete_on_another_thread() {
+        let ctx: ContextPtr = Arc::new(RwLock::new(EntryContext::new()));
+        let ctx_cloned = Arc::clone(&ctx);
+        std::thread::spawn(move || {
+            let mut ctx = ctx_cloned.write().unwrap();
+            ctx.set_round_trip(10);
+            ctx.set_err(Error::msg("biz error"));
+        })
+        .join()
+        .unwrap();
+        let ctx = ctx.read().unwrap();
+        assert_eq!(ctx.round_trip(), 10);
+        assert!(ctx.get_err().is_some());
+    }
+
+    #[test]
+    fn complete_round_trip() {
+        let mut ctx = EntryContext::new();
+        assert_eq!(ctx.round_trip(), 0);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let rt = ctx.complete_round_trip();
+        assert!(rt >= 20);
+        // measured only once
+        assert_eq!(ctx.complete_round_trip(), rt);
+
+        let mut ctx = EntryContext::new();
+        ctx.set_round_trip(5);
+        assert_eq!(ctx.complete_round_trip(), 5);
+    }
+
+    #[test]
+    fn apply_injected_err() {
+        let mut ctx = EntryContext::new();
+        ctx.inject_err(Error::msg("injected"));
+        assert!(ctx.get_err().is_none());
+        ctx.apply_injected_err();
+        assert_eq!(ctx.get_err().as_ref().unwrap().to_string(), "injected");
+
+        // the error of the application takes precedence
+        let mut ctx = EntryContext::new();
+        ctx.inject_err(Error::msg("injected"));
+        ctx.set_err(Error::msg("biz error"));
+        ctx.apply_injected_err();
+        assert_eq!(ctx.get_err().as_ref().unwrap().to_string(), "biz error");
+    }
 }
@@ -1,42 +1,46 @@
-use super::{ContextPtr, EntryContext, ResourceWrapper, SlotChain};
+use super::{pool, ContextPtr, EntryContext, ResourceWrapper, SlotChain};
 use crate::logging;
 use crate::{Error, Result};
-use std::sync::Arc;
+use std::mem::ManuallyDrop;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock, Weak};
 use std::vec::Vec;
 
-type ExitHandler = Box<dyn Send + Sync + Fn(&SentinelEntry, ContextPtr) -> Result<()>>;
+pub type ExitHandler = Box<dyn Send + Sync + Fn(&SentinelEntry, ContextPtr) -> Result<()>>;
 
-cfg_async! {
-    use std::sync::{RwLock, Weak};
-    pub type EntryStrongPtr = Arc<RwLock<SentinelEntry>>;
-    pub type EntryWeakPtr = Weak<RwLock<SentinelEntry>>;
-}
+pub type EntryStrongPtr = Arc<RwLock<SentinelEntry>>;
+pub type EntryWeakPtr = Weak<RwLock<SentinelEntry>>;
 
-cfg_not_async! {
-    use std::rc::{Rc,Weak};
-    use std::cell::RefCell;
-    pub type EntryStrongPtr = Rc<RefCell<SentinelEntry>>;
-    pub type EntryWeakPtr = Weak<RefCell<SentinelEntry>>;
+// the entries built but not exited yet, including the ones being checked by the slot chains
+static INFLIGHT_ENTRIES: AtomicUsize = AtomicUsize::new(0);
+
+/// `inflight_entries` returns the amount of the entries not exited yet,
+/// the entries dropped without exiting are not counted.
+pub fn inflight_entries() -> usize {
+    INFLIGHT_ENTRIES.load(Ordering::SeqCst)
 }
 
 pub struct SentinelEntry {
-    // todo: it is assumed that entry and context is visited in a single thread,
-    // is it neccessary to consider concurrency?
-    // Then Rc and RefCell is not suitable...
-    /// inner context may need mutability in ExitHandlers, thus, RefCell is used
-    ctx: ContextPtr,
+    /// inner context may need mutability in ExitHandlers, thus, RwLock is used,
+    /// it is taken to be recycled when the entry is dropped
+    ctx: ManuallyDrop<ContextPtr>,
     exit_handlers: Vec<ExitHandler>,
     /// each entry traverses a slot chain,
     /// global slot chain is wrapped by Arc, thus here we use Arc
     sc: Arc<SlotChain>,
+    /// guarantees the exit handlers and the slot chain exit are triggered only once,
+    /// even if the entry is exited on several threads
+    exited: AtomicBool,
 }
 
 impl SentinelEntry {
     pub fn new(ctx: ContextPtr, sc: Arc<SlotChain>) -> Self {
+        INFLIGHT_ENTRIES.fetch_add(1, Ordering::SeqCst);
         SentinelEntry {
-            ctx,
-            exit_handlers: Vec::new(),
+            ctx: ManuallyDrop::new(ctx),
+            exit_handlers: pool::acquire_exit_handlers(),
             sc,
+            exited: AtomicBool::new(false),
         }
     }
 
@@ -48,26 +52,138 @@ impl SentinelEntry {
         &self.ctx
     }
 
+    pub fn is_exited(&self) -> bool {
+        self.exited.load(Ordering::SeqCst)
+    }
+
+    /// `set_round_trip` overrides the round trip time measured automatically,
+    /// which is fed to the statistic and the circuit breakers when exiting.
+    pub fn set_round_trip(&self, round_trip: u64) {
+        self.ctx.write().unwrap().set_round_trip(round_trip);
+    }
+
+    /// `exit_with_round_trip` exits the entry with the round trip time measured by the caller.
+    pub fn exit_with_round_trip(&self, round_trip: u64) {
+        if !self.is_exited() {
+            self.set_round_trip(round_trip);
+        }
+        self.exit();
+    }
+
     // todo: cleanup
     pub fn exit(&self) {
+        if self.exited.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        // the entry is counted as exited only after the statistics are updated by the slot chain
+        let _exiting = InflightGuard;
+        // the exit handlers and the statistic slots see the error injected by the fault rules
+        self.ctx.write().unwrap().apply_injected_err();
         for handler in &self.exit_handlers {
-            handler(&self, self.ctx.clone()) // Rc/Arc clone
+            handler(&self, ContextPtr::clone(&self.ctx)) // Arc clone
                 .map_err(|err: Error| {
                     logging::error!("ERROR: {}", err);
                 })
                 .unwrap();
         }
-        self.sc.exit(self.ctx.clone()); // Rc/Arc clone
+        self.sc.exit(ContextPtr::clone(&self.ctx)); // Arc clone
+    }
+}
+
+// decrements the in-flight entries when dropped, even if the exit handlers panic
+struct InflightGuard;
+
+impl Drop for InflightGuard {
+    fn drop(&mut self) {
+        INFLIGHT_ENTRIES.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// the context and the buffer of the exit handlers are returned to the pool of current thread
+impl Drop for SentinelEntry {
+    fn drop(&mut self) {
+        if !*self.exited.get_mut() {
+            INFLIGHT_ENTRIES.fetch_sub(1, Ordering::SeqCst);
+        }
+        pool::recycle_exit_handlers(std::mem::take(&mut self.exit_handlers));
+        // safety: the context is never accessed after being taken
+        let ctx = unsafe { ManuallyDrop::take(&mut self.ctx) };
+        pool::recycle_context(ctx);
+    }
+}
+
+cfg_async! {
+    /// `AsyncEntry` is a `Send + Sync` handle of a passed `SentinelEntry`.
+    /// It can be held across `.await` points, cloned into spawned tasks,
+    /// and exited on a thread different from the one that built it.
+    #[derive(Clone)]
+    pub struct AsyncEntry {
+        inner: EntryStrongPtr,
+    }
+
+    impl AsyncEntry {
+        pub fn new(inner: EntryStrongPtr) -> Self {
+            AsyncEntry { inner }
+        }
+
+        pub fn entry(&self) -> &EntryStrongPtr {
+            &self.inner
+        }
+
+        pub fn context(&self) -> ContextPtr {
+            self.inner.read().unwrap().context().clone()
+        }
+
+        /// `set_err` records the business error of this invocation,
+        /// which would be consumed by the circuit breakers when exiting.
+        pub fn set_err(&self, err: Error) {
+            self.context().write().unwrap().set_err(err);
+        }
+
+        pub fn is_exited(&self) -> bool {
+            self.inner.read().unwrap().is_exited()
+        }
+
+        /// `set_round_trip` overrides the round trip time measured automatically.
+        pub fn set_round_trip(&self, round_trip: u64) {
+            self.inner.read().unwrap().set_round_trip(round_trip);
+        }
+
+        /// `exit` is idempotent, only the first call takes effect.
+        pub fn exit(&self) {
+            self.inner.read().unwrap().exit();
+        }
+
+        pub fn exit_with_round_trip(&self, round_trip: u64) {
+            self.inner.read().unwrap().exit_with_round_trip(round_trip);
+        }
+
+        /// `wait` awaits the wait required by the rules (e.g., the queueing of the throttling flow rules
+        /// and the latency faults) of the entry built by `EntryBuilder::build_async_deferred()`,
+        /// it returns immediately if there is nothing to wait for, or it has been waited.
+        pub async fn wait(&self) {
+            let nanos_to_wait = self.context().write().unwrap().take_nanos_to_wait();
+            if nanos_to_wait > 0 {
+                futures_timer::Delay::new(std::time::Duration::from_nanos(nanos_to_wait)).await;
+            }
+        }
+    }
+
+    impl From<EntryStrongPtr> for AsyncEntry {
+        fn from(inner: EntryStrongPtr) -> Self {
+            AsyncEntry::new(inner)
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::cell::RefCell;
     std::thread_local! {
         static EXIT_FLAG: RefCell<u8> = RefCell::new(0);
     }
-    fn exit_handler_mock(_entry: &SentinelEntry, _ctx: Rc<RefCell<EntryContext>>) -> Result<()> {
+    fn exit_handler_mock(_entry: &SentinelEntry, _ctx: ContextPtr) -> Result<()> {
         EXIT_FLAG.with(|f| {
             *f.borrow_mut() += 1;
         });
@@ -77,15 +193,34 @@ mod test {
     #[test]
     fn exit() {
         let sc = Arc::new(SlotChain::new());
-        let ctx = Rc::new(RefCell::new(EntryContext::new()));
+        let ctx = Arc::new(RwLock::new(EntryContext::new()));
         let mut entry = SentinelEntry::new(ctx.clone(), sc);
 
         entry.when_exit(Box::new(exit_handler_mock));
-        let entry = Rc::new(RefCell::new(entry));
-        ctx.borrow_mut().set_entry(Rc::downgrade(&entry));
-        entry.borrow().exit();
+        let entry = Arc::new(RwLock::new(entry));
+        ctx.write().unwrap().set_entry(Arc::downgrade(&entry));
+        entry.read().unwrap().exit();
         EXIT_FLAG.with(|f| {
             assert_eq!(*f.borrow(), 1);
         });
+        // exit again takes no effect
+        entry.read().unwrap().exit();
+        assert!(entry.read().unwrap().is_exited());
+        EXIT_FLAG.with(|f| {
+            assert_eq!(*f.borrow(), 1);
+        });
+    }
+
+    #[test]
+    fn exit_with_round_trip() {
+        let sc = Arc::new(SlotChain::new());
+        let ctx = Arc::new(RwLock::new(EntryContext::new()));
+        let entry = Arc::new(RwLock::new(SentinelEntry::new(ctx.clone(), sc)));
+        ctx.write().unwrap().set_entry(Arc::downgrade(&entry));
+        entry.read().unwrap().exit_with_round_trip(30);
+        assert_eq!(ctx.read().unwrap().round_trip(), 30);
+        // the round trip time is not changed after exiting
+        entry.read().unwrap().exit_with_round_trip(50);
+        assert_eq!(ctx.read().unwrap().round_trip(), 30);
     }
 }
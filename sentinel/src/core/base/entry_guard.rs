@@ -0,0 +1,66 @@
+use super::AsyncEntry;
+use crate::Error;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// `EntryGuard` exits the passed entry when it is dropped,
+/// thus the entry is completed even if the request future is cancelled.
+pub struct EntryGuard {
+    entry: AsyncEntry,
+}
+
+impl EntryGuard {
+    pub fn new(entry: AsyncEntry) -> Self {
+        EntryGuard { entry }
+    }
+
+    pub fn entry(&self) -> &AsyncEntry {
+        &self.entry
+    }
+
+    /// `set_err` records the business error of the request.
+    pub fn set_err(&self, err: Error) {
+        self.entry.set_err(err);
+    }
+
+    /// `exit` completes the entry, the round trip time is measured since the entry was built.
+    pub fn exit(&self) {
+        self.entry.exit();
+    }
+
+    /// `catch_panic` polls the future, if it panics,
+    /// the panic is recorded as the error and the entry is exited before the panic is resumed.
+    pub async fn catch_panic<F: Future>(&self, future: F) -> F::Output {
+        match CatchUnwind(Box::pin(future)).await {
+            Ok(output) => output,
+            Err(payload) => {
+                self.set_err(Error::msg("the handler panicked"));
+                self.exit();
+                panic::resume_unwind(payload)
+            }
+        }
+    }
+}
+
+impl Drop for EntryGuard {
+    fn drop(&mut self) {
+        self.entry.exit();
+    }
+}
+
+struct CatchUnwind<F>(Pin<Box<F>>);
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = std::thread::Result<F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let inner = &mut self.get_mut().0;
+        match panic::catch_unwind(AssertUnwindSafe(|| inner.as_mut().poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
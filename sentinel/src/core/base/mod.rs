@@ -2,20 +2,29 @@ pub mod block_error;
 pub mod constant;
 pub mod context;
 pub mod entry;
+#[cfg(feature = "async")]
+pub mod entry_guard;
 pub mod metric_item;
+pub mod pool;
 pub mod resource;
 pub mod result;
 pub mod rule;
+pub mod sentinel_context;
 pub mod slot_chain;
 pub mod stat;
 
+// `EntryBuilder` is defined in `crate::api`
+pub use crate::api::EntryBuilder;
 pub use block_error::*;
 pub use constant::*;
 pub use context::*;
 pub use entry::*;
+#[cfg(feature = "async")]
+pub use entry_guard::*;
 pub use metric_item::*;
 pub use resource::*;
 pub use result::*;
 pub use rule::*;
+pub use sentinel_context::*;
 pub use slot_chain::*;
 pub use stat::*;
@@ -0,0 +1,161 @@
+//! The thread-local pools of the per-entry allocations, i.e., the `ContextPtr` of `EntryContext`
+//! and the buffer of the exit handlers of `SentinelEntry`.
+//! The entry built by `EntryBuilder` takes them from the pool of current thread,
+//! and returns them to the pool of the thread where the entry is dropped,
+//! thus the allocations are reused at a high entry rate, e.g., 100k+ entries per second.
+//! The context is recycled only if it is not referenced elsewhere, e.g., the context cloned by `AsyncEntry::context()`
+//! is left to be freed as usual.
+use super::{ContextPtr, EntryContext, ExitHandler};
+use std::cell::RefCell;
+use std::sync::{Arc, RwLock};
+
+/// the max number of the pooled objects of each type per thread
+pub const MAX_POOLED_PER_THREAD: usize = 128;
+
+std::thread_local! {
+    static CONTEXTS: RefCell<Vec<ContextPtr>> = RefCell::new(Vec::new());
+    static EXIT_HANDLERS: RefCell<Vec<Vec<ExitHandler>>> = RefCell::new(Vec::new());
+}
+
+/// `acquire_context` returns a context as `EntryContext::new()`, reusing the pooled allocation if there is one.
+pub fn acquire_context() -> ContextPtr {
+    let pooled = CONTEXTS
+        .try_with(|contexts| contexts.borrow_mut().pop())
+        .ok()
+        .flatten();
+    match pooled {
+        Some(mut ctx) => {
+            // the pooled contexts are exclusively owned, see `recycle_context()`
+            *Arc::get_mut(&mut ctx).unwrap().get_mut().unwrap() = EntryContext::new();
+            ctx
+        }
+        None => Arc::new(RwLock::new(EntryContext::new())),
+    }
+}
+
+/// `recycle_context` pools the context if it is exclusively owned (and not poisoned),
+/// otherwise it is simply dropped. The fields of the context are released at once.
+pub fn recycle_context(mut ctx: ContextPtr) {
+    let reusable = match Arc::get_mut(&mut ctx) {
+        Some(lock) => match lock.get_mut() {
+            Ok(inner) => {
+                *inner = EntryContext::default();
+                true
+            }
+            Err(_) => false,
+        },
+        None => false,
+    };
+    if reusable {
+        // the pool may have been destroyed if the thread is exiting
+        let _ = CONTEXTS.try_with(|contexts| {
+            let mut contexts = contexts.borrow_mut();
+            if contexts.len() < MAX_POOLED_PER_THREAD {
+                contexts.push(ctx);
+            }
+        });
+    }
+}
+
+/// `acquire_exit_handlers` returns an empty buffer of the exit handlers.
+pub fn acquire_exit_handlers() -> Vec<ExitHandler> {
+    EXIT_HANDLERS
+        .try_with(|buffers| buffers.borrow_mut().pop())
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+/// `recycle_exit_handlers` drops the exit handlers and pools the buffer.
+pub fn recycle_exit_handlers(mut handlers: Vec<ExitHandler>) {
+    if handlers.capacity() == 0 {
+        return;
+    }
+    handlers.clear();
+    let _ = EXIT_HANDLERS.try_with(|buffers| {
+        let mut buffers = buffers.borrow_mut();
+        if buffers.len() < MAX_POOLED_PER_THREAD {
+            buffers.push(handlers);
+        }
+    });
+}
+
+/// `pooled_contexts` returns the number of the contexts pooled in current thread.
+pub fn pooled_contexts() -> usize {
+    CONTEXTS
+        .try_with(|contexts| contexts.borrow().len())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::{SentinelEntry, SlotChain, TokenResult};
+
+    #[test]
+    fn reuse_context() {
+        std::thread::spawn(|| {
+            let ctx = acquire_context();
+            ctx.write().unwrap().set_round_trip(10);
+            let ptr = Arc::as_ptr(&ctx);
+            recycle_context(ctx);
+            assert!(pooled_contexts() > 0);
+
+            let ctx = acquire_context();
+            assert_eq!(Arc::as_ptr(&ctx), ptr);
+            // the recycled context is reset
+            let ctx = ctx.read().unwrap();
+            assert_eq!(ctx.round_trip(), 0);
+            assert!(ctx.start_time() > 0);
+            assert!(ctx.entry().is_none());
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn shared_context_not_recycled() {
+        std::thread::spawn(|| {
+            let ctx = acquire_context();
+            let cloned = Arc::clone(&ctx);
+            recycle_context(ctx);
+            assert_eq!(pooled_contexts(), 0);
+            drop(cloned);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn bounded_pool() {
+        std::thread::spawn(|| {
+            let contexts: Vec<_> = (0..MAX_POOLED_PER_THREAD + 10)
+                .map(|_| acquire_context())
+                .collect();
+            contexts.into_iter().for_each(recycle_context);
+            assert_eq!(pooled_contexts(), MAX_POOLED_PER_THREAD);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn recycle_on_drop() {
+        std::thread::spawn(|| {
+            let sc = Arc::new(SlotChain::new());
+            let ctx = acquire_context();
+            let mut entry = SentinelEntry::new(Arc::clone(&ctx), sc);
+            entry.when_exit(Box::new(|_, _| Ok(())));
+            let entry = Arc::new(RwLock::new(entry));
+            ctx.write().unwrap().set_entry(Arc::downgrade(&entry));
+            ctx.write().unwrap().set_result(TokenResult::new_pass());
+            drop(ctx);
+            entry.read().unwrap().exit();
+            drop(entry);
+            assert_eq!(pooled_contexts(), 1);
+            assert!(acquire_exit_handlers().capacity() > 0);
+        })
+        .join()
+        .unwrap();
+    }
+}
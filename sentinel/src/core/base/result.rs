@@ -6,6 +6,7 @@ use lazy_static::lazy_static;
 use std::collections::HashMap;
 use std::fmt;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 type OtherBlockType = u8;
 
@@ -17,6 +18,8 @@ pub enum BlockType {
     CircuitBreaking,
     SystemFlow,
     HotSpotParamFlow,
+    /// the block injected by the fault rules
+    Fault,
     Other(OtherBlockType),
 }
 
@@ -190,6 +193,17 @@ impl TokenResult {
     pub fn block_err(&self) -> Option<BlockError> {
         self.block_err.clone()
     }
+    pub fn block_err_mut(&mut self) -> Option<&mut BlockError> {
+        self.block_err.as_mut()
+    }
+    /// `with_retry_after` attaches the estimated retry-after duration to the block error,
+    /// it takes no effect if the result is not blocked.
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        if let Some(block_err) = self.block_err.as_mut() {
+            block_err.set_retry_after(retry_after);
+        }
+        self
+    }
     pub fn nanos_to_wait(&self) -> u64 {
         self.nanos_to_wait
     }
@@ -1,9 +1,427 @@
-use crate::Result;
+use crate::{logging, Error, Result};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
 use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+lazy_static! {
+    /// The rule checking of an entry holds the read lock,
+    /// and the bulk update of rules holds the write lock,
+    /// thus an entry observes either all the updated rules or none of them.
+    static ref RULE_SNAPSHOT_LOCK: RwLock<()> = RwLock::new(());
+}
+
+pub(crate) fn read_rule_snapshot() -> RwLockReadGuard<'static, ()> {
+    RULE_SNAPSHOT_LOCK
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub(crate) fn write_rule_snapshot() -> RwLockWriteGuard<'static, ()> {
+    RULE_SNAPSHOT_LOCK
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// `Severity` indicates whether the problem makes the rule invalid.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Severity {
+    /// The rule takes effect, but the setting is not recommended or would be adjusted.
+    Warning,
+    /// The rule is invalid and would be rejected by the rule managers.
+    Error,
+}
+
+/// `Diagnostic` describes a problem on a field of the rule.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub field: String,
+    pub reason: String,
+    pub severity: Severity,
+}
+
+impl Diagnostic {
+    pub fn error(field: &str, reason: impl Into<String>) -> Self {
+        Diagnostic {
+            field: field.into(),
+            reason: reason.into(),
+            severity: Severity::Error,
+        }
+    }
+
+    pub fn warning(field: &str, reason: impl Into<String>) -> Self {
+        Diagnostic {
+            field: field.into(),
+            reason: reason.into(),
+            severity: Severity::Warning,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{:?}] {}: {}", self.severity, self.field, self.reason)
+    }
+}
 
 pub trait SentinelRule: fmt::Debug + Send + Sync {
     fn resource_name(&self) -> String;
+
+    /// `rule_id` returns the unique ID of the rule, if there is.
+    fn rule_id(&self) -> Option<String> {
+        None
+    }
+
+    /// `diagnose` returns all the problems of the rule.
+    fn diagnose(&self) -> Vec<Diagnostic> {
+        Vec::new()
+    }
+
+    /// `is_valid` returns the first error of `diagnose()`, the warnings are logged.
     fn is_valid(&self) -> Result<()> {
-        Ok(())
+        let mut result = Ok(());
+        for diagnostic in self.diagnose() {
+            if !diagnostic.is_error() {
+                logging::warn!("[Rule Validation] {}, rule: {:?}", diagnostic, self);
+            } else if result.is_ok() {
+                result = Err(Error::msg(diagnostic.reason));
+            }
+        }
+        result
+    }
+}
+
+/// `RuleDiagnostics` is the diagnostics of the rule at `index` of the payload.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleDiagnostics {
+    pub index: usize,
+    pub resource: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl RuleDiagnostics {
+    /// `is_rejected` indicates the rule is invalid, thus it would be ignored when loading.
+    pub fn is_rejected(&self) -> bool {
+        self.diagnostics.iter().any(Diagnostic::is_error)
+    }
+}
+
+/// `validate_rules` validates the rules of any type,
+/// only the rules with problems are reported, the rejected ones are ignored by `load_rules()`,
+/// while the valid remainder is still applied.
+pub fn validate_rules<R: SentinelRule>(rules: &[Arc<R>]) -> Vec<RuleDiagnostics> {
+    rules
+        .iter()
+        .enumerate()
+        .filter_map(|(index, rule)| {
+            let diagnostics = rule.diagnose();
+            if diagnostics.is_empty() {
+                None
+            } else {
+                Some(RuleDiagnostics {
+                    index,
+                    resource: rule.resource_name(),
+                    diagnostics,
+                })
+            }
+        })
+        .collect()
+}
+
+std::thread_local! {
+    static RULE_SOURCE: RefCell<Option<String>> = RefCell::new(None);
+    // the updates nested in an update are not notified separately
+    static IN_RULE_UPDATE: Cell<bool> = Cell::new(false);
+    static IN_NAMESPACE_UPDATE: Cell<bool> = Cell::new(false);
+}
+
+// whether the rules are owned by the namespaces, see `load_namespace_rules()`
+static NAMESPACED: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_namespaced(namespaced: bool) {
+    NAMESPACED.store(namespaced, Ordering::SeqCst);
+}
+
+/// `with_namespace_update` runs the update of the rules on behalf of the namespaces.
+pub(crate) fn with_namespace_update<T>(f: impl FnOnce() -> T) -> T {
+    struct Restore(bool);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            IN_NAMESPACE_UPDATE.with(|u| u.set(self.0));
+        }
+    }
+    let _restore = Restore(IN_NAMESPACE_UPDATE.with(|u| u.replace(true)));
+    f()
+}
+
+/// `check_direct_update` rejects the updates of the rules bypassing the namespaces while the rules are owned by them,
+/// since the rules of the namespaces would be replaced (and the other way round), the rejection is logged as well.
+pub(crate) fn check_direct_update(api: &str) -> Result<()> {
+    if !NAMESPACED.load(Ordering::SeqCst) || IN_NAMESPACE_UPDATE.with(|u| u.get()) {
+        return Ok(());
+    }
+    let err = Error::msg(format!(
+        "{} is rejected since the rules are owned by the namespaces, update them by the namespaces instead",
+        api
+    ));
+    logging::error!("[RuleManager] {}", err);
+    Err(err)
+}
+
+/// `with_rule_source` runs `f`, the rule changes made by `f` in current thread
+/// are notified to the listeners with the `source`, e.g., the name of the datasource.
+pub fn with_rule_source<T>(source: &str, f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<String>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let prev = self.0.take();
+            RULE_SOURCE.with(|s| *s.borrow_mut() = prev);
+        }
+    }
+    let _restore = Restore(RULE_SOURCE.with(|s| s.borrow_mut().replace(source.into())));
+    f()
+}
+
+/// `RuleChange` describes an update of the effective rules of a type,
+/// it holds the rules of the resource only if the rules are loaded or cleared per resource
+/// (e.g., `flow::load_rules_of_resource()`).
+#[derive(Debug, Clone)]
+pub struct RuleChange<R> {
+    pub old_rules: Vec<Arc<R>>,
+    pub new_rules: Vec<Arc<R>>,
+    /// the source set by `with_rule_source()`, `None` if unknown
+    pub source: Option<String>,
+}
+
+impl<R: PartialEq> RuleChange<R> {
+    /// `added` returns the new rules which are absent in the old ones.
+    pub fn added(&self) -> Vec<&Arc<R>> {
+        difference(&self.new_rules, &self.old_rules)
+    }
+
+    /// `removed` returns the old rules which are absent in the new ones.
+    pub fn removed(&self) -> Vec<&Arc<R>> {
+        difference(&self.old_rules, &self.new_rules)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added().is_empty() && self.removed().is_empty()
+    }
+}
+
+fn difference<'a, R: PartialEq>(a: &'a [Arc<R>], b: &[Arc<R>]) -> Vec<&'a Arc<R>> {
+    a.iter()
+        .filter(|r| !b.iter().any(|o| **o == ***r))
+        .collect()
+}
+
+/// `RuleChangeListener` is notified after the rules are loaded or cleared,
+/// e.g., writing the changes to an audit log.
+pub trait RuleChangeListener<R>: Send + Sync {
+    fn on_rules_changed(&self, change: &RuleChange<R>);
+}
+
+/// `RuleChangeListeners` holds the listeners of a rule manager.
+pub struct RuleChangeListeners<R> {
+    listeners: RwLock<Vec<Arc<dyn RuleChangeListener<R>>>>,
+}
+
+impl<R: PartialEq + SentinelRule> RuleChangeListeners<R> {
+    pub fn new() -> Self {
+        RuleChangeListeners {
+            listeners: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// `register` appends the listeners, the ones registered already (the same `Arc`) are skipped.
+    pub fn register(&self, listeners: Vec<Arc<dyn RuleChangeListener<R>>>) {
+        let mut registered = self.listeners.write().unwrap();
+        for listener in listeners {
+            let ptr = Arc::as_ptr(&listener) as *const ();
+            if !registered
+                .iter()
+                .any(|other| Arc::as_ptr(other) as *const () == ptr)
+            {
+                registered.push(listener);
+            }
+        }
+    }
+
+    pub fn clear(&self) {
+        self.listeners.write().unwrap().clear();
+    }
+
+    fn is_watched(&self) -> bool {
+        !self.listeners.read().unwrap().is_empty()
+            || logging::event_enabled(logging::EventKind::RuleUpdate, logging::Level::Info)
+    }
+
+    /// `watch` runs the `update` of rules, and notifies the listeners
+    /// if the effective rules (returned by `get_rules`) are changed,
+    /// the added and removed rules are logged as the structured events as well.
+    /// The `get_rules` must not be called with the locks of the rule manager held,
+    /// it returns the rules of the resource for the updates per resource, thus the diff is kept small.
+    /// The rules are not compared if there is neither listener nor the enabled events of the rule updates.
+    pub fn watch<T>(&self, get_rules: impl Fn() -> Vec<Arc<R>>, update: impl FnOnce() -> T) -> T {
+        if IN_RULE_UPDATE.with(|u| u.get()) || !self.is_watched() {
+            return update();
+        }
+        struct Updating;
+        impl Drop for Updating {
+            fn drop(&mut self) {
+                IN_RULE_UPDATE.with(|u| u.set(false));
+            }
+        }
+        let old_rules = get_rules();
+        IN_RULE_UPDATE.with(|u| u.set(true));
+        let updating = Updating;
+        let result = update();
+        drop(updating);
+        let change = RuleChange {
+            old_rules,
+            new_rules: get_rules(),
+            source: RULE_SOURCE.with(|s| s.borrow().clone()),
+        };
+        if !change.is_empty() {
+            log_rule_change(&change);
+            let listeners = self.listeners.read().unwrap().clone();
+            for listener in listeners {
+                listener.on_rules_changed(&change);
+            }
+        }
+        result
+    }
+}
+
+fn log_rule_change<R: PartialEq + SentinelRule>(change: &RuleChange<R>) {
+    if !logging::event_enabled(logging::EventKind::RuleUpdate, logging::Level::Info) {
+        return;
+    }
+    let source = match &change.source {
+        Some(source) => format!(" by {}", source),
+        None => String::new(),
+    };
+    for (rules, state) in [(change.added(), "added"), (change.removed(), "removed")].iter() {
+        for rule in rules {
+            let event = logging::Event::new(
+                logging::EventKind::RuleUpdate,
+                rule.resource_name(),
+                format!("[RuleManager] Rule {}{}", state, source),
+            )
+            .with_rule_id(rule.rule_id())
+            .with_state(*state);
+            logging::log_event(logging::Level::Info, &event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug)]
+    struct MockRule(Vec<Diagnostic>);
+
+    impl SentinelRule for MockRule {
+        fn resource_name(&self) -> String {
+            "mock".into()
+        }
+        fn diagnose(&self) -> Vec<Diagnostic> {
+            self.0.clone()
+        }
+    }
+
+    #[test]
+    fn validate() {
+        let rules = vec![
+            Arc::new(MockRule(Vec::new())),
+            Arc::new(MockRule(vec![Diagnostic::warning("a", "not recommended")])),
+            Arc::new(MockRule(vec![
+                Diagnostic::warning("a", "not recommended"),
+                Diagnostic::error("b", "first"),
+                Diagnostic::error("c", "second"),
+            ])),
+        ];
+        assert!(rules[1].is_valid().is_ok());
+        assert_eq!(rules[2].is_valid().unwrap_err().to_string(), "first");
+
+        let report = validate_rules(&rules);
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].index, 1);
+        assert!(!report[0].is_rejected());
+        assert_eq!(report[1].index, 2);
+        assert!(report[1].is_rejected());
+        assert_eq!(report[1].diagnostics.len(), 3);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Rule(u32);
+
+    impl SentinelRule for Rule {
+        fn resource_name(&self) -> String {
+            "mock".into()
+        }
+    }
+
+    struct Recorder(std::sync::Mutex<Vec<(Vec<u32>, Vec<u32>, Option<String>)>>);
+
+    impl RuleChangeListener<Rule> for Recorder {
+        fn on_rules_changed(&self, change: &RuleChange<Rule>) {
+            self.0.lock().unwrap().push((
+                change.added().iter().map(|r| r.0).collect(),
+                change.removed().iter().map(|r| r.0).collect(),
+                change.source.clone(),
+            ));
+        }
+    }
+
+    #[test]
+    fn rule_change_listeners() {
+        let rules = RwLock::new(vec![Arc::new(Rule(1)), Arc::new(Rule(2))]);
+        let get_rules = || rules.read().unwrap().clone();
+        let listeners = RuleChangeListeners::new();
+        if !logging::event_enabled(logging::EventKind::RuleUpdate, logging::Level::Info) {
+            // the rules are not compared without the listeners
+            let scanned = std::cell::Cell::new(0);
+            let get_rules = || {
+                scanned.set(scanned.get() + 1);
+                rules.read().unwrap().clone()
+            };
+            listeners.watch(get_rules, || rules.write().unwrap().push(Arc::new(Rule(5))));
+            rules.write().unwrap().pop();
+            assert_eq!(scanned.get(), 0);
+        }
+        let recorder = Arc::new(Recorder(Default::default()));
+        listeners.register(vec![recorder.clone()]);
+        // the registered listener is notified only once
+        listeners.register(vec![recorder.clone()]);
+
+        listeners.watch(get_rules, || {
+            *rules.write().unwrap() = vec![Arc::new(Rule(2)), Arc::new(Rule(3))]
+        });
+        // nothing changed
+        with_rule_source("datasource", || {
+            listeners.watch(get_rules, || {
+                // nested updates are notified as a whole
+                listeners.watch(get_rules, || rules.write().unwrap().push(Arc::new(Rule(4))));
+                rules.write().unwrap().pop();
+            })
+        });
+        with_rule_source("datasource", || {
+            listeners.watch(get_rules, || rules.write().unwrap().clear())
+        });
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                (vec![3], vec![1], None),
+                (vec![], vec![2, 3], Some("datasource".into()))
+            ]
+        );
     }
 }
@@ -0,0 +1,212 @@
+//! SentinelContext
+//!
+//! `SentinelContext` records the entries opened along the invocation chain.
+//! It is installed in a scope, the entries built within the scope are linked to it,
+//! thus the nested resources can be accounted with their parents.
+//! Under the `async` feature, it can be propagated to the spawned tasks by `scope_future()`,
+//! which works as a task-local storage and is agnostic to the async runtimes.
+//! A named context represents an entrance of the invocation chain,
+//! the resources invoked through it are also accounted by the chain statistics.
+use super::{EntryStrongPtr, EntryWeakPtr};
+use std::cell::RefCell;
+use std::marker::PhantomData;
+
+std::thread_local! {
+    static CURRENT: RefCell<Option<SentinelContext>> = RefCell::new(None);
+}
+
+#[derive(Clone, Default)]
+pub struct SentinelContext {
+    /// the name of the entrance, `None` for anonymous contexts
+    name: Option<String>,
+    /// the origin (caller) of the invocation chain
+    origin: Option<String>,
+    /// the entries opened in this context, the last one is the innermost
+    entries: Vec<EntryWeakPtr>,
+}
+
+impl SentinelContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `named` creates the context of an entrance.
+    pub fn named(name: String, origin: Option<String>) -> Self {
+        SentinelContext {
+            name: Some(name),
+            origin,
+            ..Self::default()
+        }
+    }
+
+    pub fn name(&self) -> Option<&String> {
+        self.name.as_ref()
+    }
+
+    pub fn origin(&self) -> Option<&String> {
+        self.origin.as_ref()
+    }
+
+    /// `current` returns the context installed in the current scope.
+    pub fn current() -> Option<SentinelContext> {
+        CURRENT.with(|c| c.borrow().clone())
+    }
+
+    /// `with_entry` derives a child context, in which the `entry` is the innermost one.
+    pub fn with_entry(&self, entry: &EntryStrongPtr) -> SentinelContext {
+        let mut entries = self.entries.clone();
+        entries.push(std::sync::Arc::downgrade(entry));
+        SentinelContext {
+            entries,
+            ..self.clone()
+        }
+    }
+
+    pub fn entries(&self) -> &Vec<EntryWeakPtr> {
+        &self.entries
+    }
+
+    /// `parent_entry` returns the innermost entry which is still alive.
+    pub fn parent_entry(&self) -> Option<EntryStrongPtr> {
+        self.entries.iter().rev().find_map(|entry| entry.upgrade())
+    }
+
+    /// `scope` installs the context while running `f`, the previous one is restored afterwards.
+    pub fn scope<R>(self, f: impl FnOnce() -> R) -> R {
+        let _guard = self.enter();
+        f()
+    }
+
+    /// `enter` installs the context in current thread until the returned guard is dropped.
+    /// In async code, use `scope_future()` instead, since the task may be moved to other threads.
+    pub fn enter(self) -> ContextGuard {
+        let prev = CURRENT.with(|c| c.borrow_mut().replace(self));
+        ContextGuard {
+            prev,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+/// `ContextGuard` restores the previous context when dropped, even if the scope panics.
+/// It is bound to the thread where the context is entered.
+pub struct ContextGuard {
+    prev: Option<SentinelContext>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        let prev = self.prev.take();
+        CURRENT.with(|c| *c.borrow_mut() = prev);
+    }
+}
+
+cfg_async! {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    /// `Scoped` installs the context each time the inner future is polled,
+    /// thus the context follows the task across threads of the executor.
+    pub struct Scoped<F> {
+        ctx: SentinelContext,
+        fut: Pin<Box<F>>,
+    }
+
+    impl<F: Future> Future for Scoped<F> {
+        type Output = F::Output;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let this = self.get_mut();
+            let _guard = this.ctx.clone().enter();
+            this.fut.as_mut().poll(cx)
+        }
+    }
+
+    impl SentinelContext {
+        /// `scope_future` installs the context while polling `fut`.
+        pub fn scope_future<F: Future>(self, fut: F) -> Scoped<F> {
+            Scoped {
+                ctx: self,
+                fut: Box::pin(fut),
+            }
+        }
+    }
+
+    /// `in_current_context` wraps `fut` in the current context (if any),
+    /// it is used for propagating the context to the spawned tasks, e.g.,
+    /// `tokio::spawn(in_current_context(async { ... }))`.
+    pub fn in_current_context<F: Future>(fut: F) -> Scoped<F> {
+        SentinelContext::current()
+            .unwrap_or_default()
+            .scope_future(fut)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::{EntryContext, SentinelEntry, SlotChain};
+    use std::sync::{Arc, RwLock};
+
+    fn new_entry() -> EntryStrongPtr {
+        let ctx = Arc::new(RwLock::new(EntryContext::new()));
+        let entry = Arc::new(RwLock::new(SentinelEntry::new(
+            ctx.clone(),
+            Arc::new(SlotChain::new()),
+        )));
+        ctx.write().unwrap().set_entry(Arc::downgrade(&entry));
+        entry
+    }
+
+    #[test]
+    fn scope() {
+        assert!(SentinelContext::current().is_none());
+        let entry = new_entry();
+        let ctx = SentinelContext::new().with_entry(&entry);
+        ctx.scope(|| {
+            let current = SentinelContext::current().unwrap();
+            assert!(Arc::ptr_eq(&current.parent_entry().unwrap(), &entry));
+            let inner = new_entry();
+            current.with_entry(&inner).scope(|| {
+                let current = SentinelContext::current().unwrap();
+                assert_eq!(current.entries().len(), 2);
+                assert!(Arc::ptr_eq(&current.parent_entry().unwrap(), &inner));
+            });
+            // restored
+            assert_eq!(SentinelContext::current().unwrap().entries().len(), 1);
+        });
+        assert!(SentinelContext::current().is_none());
+    }
+
+    #[test]
+    fn restore_on_panic() {
+        let r = std::panic::catch_unwind(|| {
+            SentinelContext::new().scope(|| panic!("mock panic"));
+        });
+        assert!(r.is_err());
+        assert!(SentinelContext::current().is_none());
+    }
+
+    #[test]
+    fn named() {
+        let entry = new_entry();
+        {
+            let _guard = SentinelContext::named("entrance".into(), Some("caller".into())).enter();
+            let ctx = SentinelContext::current().unwrap().with_entry(&entry);
+            assert_eq!(ctx.name().unwrap(), "entrance");
+            assert_eq!(ctx.origin().unwrap(), "caller");
+            assert_eq!(ctx.entries().len(), 1);
+        }
+        assert!(SentinelContext::current().is_none());
+    }
+
+    #[test]
+    fn dropped_parent() {
+        let entry = new_entry();
+        let ctx = SentinelContext::new().with_entry(&entry);
+        drop(entry);
+        assert!(ctx.parent_entry().is_none());
+    }
+}
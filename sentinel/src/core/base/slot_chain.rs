@@ -1,9 +1,8 @@
-use super::{BlockError, ContextPtr, EntryContext, TokenResult, SLOT_INIT};
+use super::{read_rule_snapshot, BlockError, ContextPtr, EntryContext, TokenResult, SLOT_INIT};
 use crate::logging;
+use crate::utils;
 use crate::utils::AsAny;
 use std::any::Any;
-use std::cell::RefCell;
-use std::rc::Rc;
 use std::sync::Arc;
 
 /// trait `PartialOrd` is not object safe
@@ -16,7 +15,7 @@ pub trait BaseSlot: Any + AsAny + Sync + Send {
     }
 }
 
-// todo: replace `Rc` of ctx to `&Rc` in these slots
+// todo: replace `Arc` of ctx to `&Arc` in these slots
 
 /// StatPrepareSlot is responsible for some preparation before statistic
 /// For example: init structure and so on
@@ -37,10 +36,7 @@ pub trait RuleCheckSlot: BaseSlot {
     // Each TokenResult will return check result
     // The upper logic will control pipeline according to SlotResult.
     fn check(&self, ctx: &ContextPtr) -> TokenResult {
-        cfg_if_async! {
-            let ctx = ctx.read().unwrap(),
-            let ctx = ctx.borrow()
-        };
+        let ctx = ctx.read().unwrap();
         ctx.result().clone()
     }
 }
@@ -62,8 +58,56 @@ pub trait StatSlot: BaseSlot {
     fn on_completed(&self, ctx: ContextPtr) {}
 }
 
+/// `OrderedSlot` overrides the order of the wrapped slot,
+/// so a slot can be placed anywhere in the chain without modifying its `order()`.
+pub struct OrderedSlot<S: ?Sized> {
+    order: u32,
+    inner: Arc<S>,
+}
+
+impl<S: ?Sized> OrderedSlot<S> {
+    pub fn new(inner: Arc<S>, order: u32) -> Self {
+        OrderedSlot { order, inner }
+    }
+
+    pub fn inner(&self) -> &Arc<S> {
+        &self.inner
+    }
+}
+
+impl<S: ?Sized + Send + Sync + 'static> BaseSlot for OrderedSlot<S> {
+    fn order(&self) -> u32 {
+        self.order
+    }
+}
+
+impl StatPrepareSlot for OrderedSlot<dyn StatPrepareSlot> {
+    fn prepare(&self, ctx: ContextPtr) {
+        self.inner.prepare(ctx)
+    }
+}
+
+impl RuleCheckSlot for OrderedSlot<dyn RuleCheckSlot> {
+    fn check(&self, ctx: &ContextPtr) -> TokenResult {
+        self.inner.check(ctx)
+    }
+}
+
+impl StatSlot for OrderedSlot<dyn StatSlot> {
+    fn on_entry_pass(&self, ctx: ContextPtr) {
+        self.inner.on_entry_pass(ctx)
+    }
+    fn on_entry_blocked(&self, ctx: ContextPtr, block_error: Option<BlockError>) {
+        self.inner.on_entry_blocked(ctx, block_error)
+    }
+    fn on_completed(&self, ctx: ContextPtr) {
+        self.inner.on_completed(ctx)
+    }
+}
+
 /// SlotChain hold all system slots and customized slot.
 /// SlotChain support plug-in slots developed by developer.
+#[derive(Clone)]
 pub struct SlotChain {
     /// statPres is in ascending order by StatPrepareSlot.order() value.
     pub(self) stat_pres: Vec<Arc<dyn StatPrepareSlot>>,
@@ -82,45 +126,28 @@ impl SlotChain {
         }
     }
 
-    cfg_async! {
-        pub fn exit(&self, ctx: ContextPtr) {
-            if ctx.read().unwrap().entry().is_none() {
-                logging::error!("SentinelEntry is nil in SlotChain.exit()");
-                return;
-            }
-            if ctx.read().unwrap().is_blocked() {
-                return;
-            }
-            // The on_completed is called only when entry passed
-            for s in &self.stats {
-                s.on_completed(ctx.clone()); // Rc/Arc clone
-            }
+    pub fn exit(&self, ctx: ContextPtr) {
+        if ctx.read().unwrap().entry().is_none() {
+            logging::error!("SentinelEntry is nil in SlotChain.exit()");
+            return;
         }
-    }
-
-    cfg_not_async! {
-        pub fn exit(&self, ctx: ContextPtr) {
-            if ctx.borrow().entry().is_none() {
-                logging::error!("SentinelEntry is nil in SlotChain.exit()");
-                return;
-            }
-            if ctx.borrow().is_blocked() {
-                return;
-            }
-            // The on_completed is called only when entry passed
-            for s in &self.stats {
-                s.on_completed(ctx.clone()); // Rc/Arc clone
-            }
+        if ctx.read().unwrap().is_blocked() {
+            return;
+        }
+        // The on_completed is called only when entry passed
+        for s in &self.stats {
+            s.on_completed(ctx.clone()); // Arc clone
         }
     }
 
     /// add_stat_prepare_slot adds the StatPrepareSlot slot to the StatPrepareSlot list of the SlotChain.
-    /// All StatPrepareSlot in the list will be sorted according to StatPrepareSlot.order() in ascending order.
+    /// All StatPrepareSlot in the list will be sorted according to StatPrepareSlot.order() in ascending order,
+    /// the slots with the same order keep the order of addition.
     /// add_stat_prepare_slot is non-thread safe,
     /// In concurrency scenario, add_stat_prepare_slot must be guarded by SlotChain.RWMutex#Lock
     pub fn add_stat_prepare_slot(&mut self, s: Arc<dyn StatPrepareSlot>) {
         self.stat_pres.push(s);
-        self.stat_pres.sort_unstable_by_key(|a| a.order());
+        self.stat_pres.sort_by_key(|a| a.order());
     }
 
     // add_rule_check_slot adds the RuleCheckSlot to the RuleCheckSlot list of the SlotChain.
@@ -129,7 +156,7 @@ impl SlotChain {
     // In concurrency scenario, add_rule_check_slot must be guarded by SlotChain.RWMutex#Lock
     pub fn add_rule_check_slot(&mut self, s: Arc<dyn RuleCheckSlot>) {
         self.rule_checks.push(s);
-        self.rule_checks.sort_unstable_by_key(|a| a.order());
+        self.rule_checks.sort_by_key(|a| a.order());
     }
 
     // add_stat_slot adds the StatSlot to the StatSlot list of the SlotChain.
@@ -138,7 +165,7 @@ impl SlotChain {
     // In concurrency scenario, add_stat_slot must be guarded by SlotChain.RWMutex#Lock
     pub fn add_stat_slot(&mut self, s: Arc<dyn StatSlot>) {
         self.stats.push(s);
-        self.stats.sort_unstable_by_key(|a| a.order());
+        self.stats.sort_by_key(|a| a.order());
     }
 
     /// The entrance of slot chain
@@ -146,40 +173,53 @@ impl SlotChain {
     pub fn entry(&self, ctx: ContextPtr) -> TokenResult {
         // execute prepare slot
         for s in &self.stat_pres {
-            s.prepare(ctx.clone()); // Rc/Arc clone
+            s.prepare(ctx.clone()); // Arc clone
         }
 
-        // execute rule based checking slot
-        cfg_if_async! {
-            ctx.write().unwrap().reset_result_to_pass(),
-            ctx.borrow_mut().reset_result_to_pass()
-        };
+        // execute rule based checking slot,
+        // the rules are not switched by `load_rule_set()` in the middle of checking
+        ctx.write().unwrap().reset_result_to_pass();
+        let rule_snapshot = read_rule_snapshot();
         for s in &self.rule_checks {
-            let res = s.check(&ctx);
+            let mut res = s.check(&ctx);
             // check slot result
             if res.is_blocked() {
-                cfg_if_async! {
-                    ctx.write().unwrap().set_result(res.clone()),
-                    ctx.borrow_mut().set_result(res.clone())
-                };
+                let mut ctx = ctx.write().unwrap();
+                if let Some(block_err) = res.block_err_mut() {
+                    block_err.set_resource(ctx.resource().name().clone());
+                }
+                ctx.set_result(res);
             }
         }
 
-        cfg_if_async! {
-            let ctx_ptr = ctx.read().unwrap(),
-            let ctx_ptr = ctx.borrow()
+        drop(rule_snapshot);
+
+        // the lock of ctx is released before executing statistic slots,
+        // since statistic slots would acquire it again
+        let (result, nanos_to_wait) = {
+            let mut ctx = ctx.write().unwrap();
+            let nanos_to_wait = if ctx.wait_by_caller() {
+                0
+            } else {
+                ctx.take_nanos_to_wait()
+            };
+            (ctx.result().clone(), nanos_to_wait)
         };
+        // wait out of the locks, thus neither the loading of rules nor the other entries are stalled
+        if result.is_pass() && nanos_to_wait > 0 {
+            utils::sleep_for_ns(nanos_to_wait);
+        }
         // execute statistic slot
         for s in &self.stats {
             // indicate the result of rule based checking slot.
-            if ctx_ptr.result().is_pass() {
-                s.on_entry_pass(ctx.clone()) // Rc/Arc clone
+            if result.is_pass() {
+                s.on_entry_pass(ctx.clone()) // Arc clone
             } else {
                 // The block error should not be nil.
-                s.on_entry_blocked(ctx.clone(), ctx_ptr.result().block_err()) // Rc/Arc clone
+                s.on_entry_blocked(ctx.clone(), result.block_err()) // Arc clone
             }
         }
-        ctx_ptr.result().clone()
+        result
     }
 }
 
@@ -195,7 +235,7 @@ mod test {
     };
     use super::*;
     use crate::Result;
-    use std::sync::Arc;
+    use std::sync::{Arc, RwLock};
 
     // here we test three kinds of slots one by one
     mod single {
@@ -210,6 +250,48 @@ mod test {
             }
         }
         impl StatPrepareSlot for StatPrepareSlotMock {}
+
+        #[test]
+        fn ordered_slot() {
+            let mut sc = SlotChain::new();
+            let mock = |name: &str| {
+                Arc::new(StatPrepareSlotMock {
+                    name: name.into(),
+                    order: 0,
+                })
+            };
+            sc.add_stat_prepare_slot(Arc::new(OrderedSlot::new(
+                mock("second") as Arc<dyn StatPrepareSlot>,
+                20,
+            )));
+            sc.add_stat_prepare_slot(Arc::new(OrderedSlot::new(
+                mock("first") as Arc<dyn StatPrepareSlot>,
+                10,
+            )));
+            sc.add_stat_prepare_slot(Arc::new(OrderedSlot::new(
+                mock("third") as Arc<dyn StatPrepareSlot>,
+                20,
+            )));
+            let names: Vec<String> = sc
+                .stat_pres
+                .into_iter()
+                .map(|s| {
+                    let s = s
+                        .as_any_arc()
+                        .downcast::<OrderedSlot<dyn StatPrepareSlot>>()
+                        .unwrap();
+                    s.inner()
+                        .clone()
+                        .as_any_arc()
+                        .downcast::<StatPrepareSlotMock>()
+                        .unwrap()
+                        .name
+                        .clone()
+                })
+                .collect();
+            assert_eq!(names, vec!["first", "second", "third"]);
+        }
+
         #[test]
         fn add_stat_prepare_slot() {
             let mut sc = SlotChain::new();
@@ -393,13 +475,13 @@ mod test {
             let rw = ResourceWrapper::new("abc".into(), ResourceType::Common, TrafficType::Inbound);
             ctx.set_resource(rw);
             ctx.set_stat_node(Arc::new(MockStatNode::new()));
-            let ctx = Rc::new(RefCell::new(ctx));
-            let entry = Rc::new(RefCell::new(SentinelEntry::new(ctx.clone(), sc.clone())));
-            ctx.borrow_mut().set_entry(Rc::downgrade(&entry));
+            let ctx = Arc::new(RwLock::new(ctx));
+            let entry = Arc::new(RwLock::new(SentinelEntry::new(ctx.clone(), sc.clone())));
+            ctx.write().unwrap().set_entry(Arc::downgrade(&entry));
 
-            let r = sc.entry(Rc::clone(&ctx));
+            let r = sc.entry(Arc::clone(&ctx));
             assert_eq!(ResultStatus::Pass, *r.status(), "should pass but blocked");
-            sc.exit(Rc::clone(&ctx));
+            sc.exit(Arc::clone(&ctx));
         }
 
         #[test]
@@ -456,14 +538,14 @@ mod test {
             let rw = ResourceWrapper::new("abc".into(), ResourceType::Common, TrafficType::Inbound);
             ctx.set_resource(rw);
             ctx.set_stat_node(Arc::new(MockStatNode::new()));
-            let ctx = Rc::new(RefCell::new(ctx));
-            let entry = Rc::new(RefCell::new(SentinelEntry::new(
-                Rc::clone(&ctx),
+            let ctx = Arc::new(RwLock::new(ctx));
+            let entry = Arc::new(RwLock::new(SentinelEntry::new(
+                Arc::clone(&ctx),
                 sc.clone(),
             )));
-            ctx.borrow_mut().set_entry(Rc::downgrade(&entry));
+            ctx.write().unwrap().set_entry(Arc::downgrade(&entry));
 
-            let r = sc.entry(Rc::clone(&ctx));
+            let r = sc.entry(Arc::clone(&ctx));
             assert_eq!(
                 ResultStatus::Blocked,
                 *r.status(),
@@ -474,7 +556,7 @@ mod test {
                 r.block_err().unwrap().block_type(),
                 "should blocked by BlockType Flow"
             );
-            sc.exit(Rc::clone(&ctx));
+            sc.exit(Arc::clone(&ctx));
         }
 
         struct StatPrepareSlotBadMock {}
@@ -531,14 +613,14 @@ mod test {
             let rw = ResourceWrapper::new("abc".into(), ResourceType::Common, TrafficType::Inbound);
             ctx.set_resource(rw);
             ctx.set_stat_node(Arc::new(MockStatNode::new()));
-            let ctx = Rc::new(RefCell::new(ctx));
-            let entry = Rc::new(RefCell::new(SentinelEntry::new(
-                Rc::clone(&ctx),
+            let ctx = Arc::new(RwLock::new(ctx));
+            let entry = Arc::new(RwLock::new(SentinelEntry::new(
+                Arc::clone(&ctx),
                 sc.clone(),
             )));
-            ctx.borrow_mut().set_entry(Rc::downgrade(&entry));
+            ctx.write().unwrap().set_entry(Arc::downgrade(&entry));
 
-            let r = sc.entry(Rc::clone(&ctx));
+            let r = sc.entry(Arc::clone(&ctx));
         }
     }
 }
@@ -2,8 +2,6 @@ use super::*;
 use crate::Result;
 use crate::{base::ContextPtr, logging};
 use lazy_static::lazy_static;
-use std::cell::RefCell;
-use std::rc::Rc;
 use std::sync::{
     atomic::{AtomicU64, Ordering},
     Arc, Mutex,
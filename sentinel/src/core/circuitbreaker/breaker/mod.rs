@@ -36,9 +36,7 @@ use crate::{
 };
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use std::cell::RefCell;
 use std::hash::Hash;
-use std::rc::Rc;
 use std::sync::{
     atomic::{AtomicU64, Ordering},
     Arc, Mutex,
@@ -64,7 +62,7 @@ impl Default for BreakerStrategy {
 }
 
 /// States of Circuit Breaker State Machine
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum State {
     Closed,
     HalfOpen,
@@ -102,11 +100,9 @@ pub trait StateChangeListener: Sync + Send {
 // todo: consider removing BreakerBase struct. Or keep it for simpler trait implementations
 pub trait CircuitBreakerTrait: Send + Sync {
     /// `breaker` returns the associated inner breaker.
-    #[inline]
     fn breaker(&self) -> &BreakerBase;
 
     /// `stat` returns the associated statistic data structure.
-    #[inline]
     fn stat(&self) -> &Arc<CounterLeapArray>;
 
     /// `try_pass` acquires permission of an invocation only if it is available at the time of invocation.
@@ -188,7 +184,7 @@ pub struct BreakerBase {
     /// During the open period, no requests are permitted until the timeout has elapsed.
     /// After that, the circuit breaker will transform to half-open state for trying a few "trial" requests.
     retry_timeout_ms: u32,
-    /// next_retry_timestamp_ms is the time circuit breaker could probe
+    /// next_retry_timestamp_ms is the time (of the monotonic clock, see `utils::curr_time_millis()`) circuit breaker could probe
     next_retry_timestamp_ms: AtomicU64,
     /// state is the state machine of circuit breaker
     // todo: test `AtomicPtr`
@@ -226,6 +222,7 @@ impl BreakerBase {
         if *state == State::Closed {
             *state = State::Open;
             self.update_next_retry_timestamp();
+            log_transition(State::Closed, State::Open, &self.rule, Some(&snapshot));
             let listeners = state_change_listeners().lock().unwrap();
             for listener in &*listeners {
                 listener.on_transform_to_open(
@@ -240,89 +237,44 @@ impl BreakerBase {
         }
     }
 
-    cfg_async! {
     /// from_open_to_half_open updates circuit breaker state machine from open to half-open.
-        /// Return true only if current goroutine successfully accomplished the transformation.
-        pub fn from_open_to_half_open(&self, ctx: ContextPtr) -> bool {
-            let mut state = self.state.lock().unwrap();
-            if *state == State::Open {
-                *state = State::HalfOpen;
-                let listeners = state_change_listeners().lock().unwrap();
-                for listener in &*listeners {
-                    listener.on_transform_to_half_open(State::Open, Arc::clone(&self.rule));
-                }
-
-                let ctx = ctx.read().unwrap();
-                let entry = ctx.entry();
-                if entry.is_none() {
-                    logging::error!(
-                        "Entry is None in BreakerBase::from_open_to_half_open(), rule: {:?}",
-                        self.rule,
-                    );
-                } else {
-                    // add hook for entry exit
-                    // if the current circuit breaker performs the probe through this entry, but the entry was blocked,
-                    // this hook will guarantee current circuit breaker state machine will rollback to Open from Half-Open
-                    drop(state);
-                    let entry = entry.unwrap();
-                    let rule = Arc::clone(&self.rule);
-                    let state = Arc::clone(&self.state);
-                    entry.upgrade().unwrap().write().unwrap().when_exit(Box::new(
-                        move |entry: &SentinelEntry, ctx: ContextPtr| -> Result<()> {
-                            let mut state = state.lock().unwrap();
-                            if ctx.read().unwrap().is_blocked() && *state == State::HalfOpen {
-                                *state = State::Open;
-                                let listeners = state_change_listeners().lock().unwrap();
-                                for listener in &*listeners {
-                                    listener.on_transform_to_open(
-                                        State::HalfOpen,
-                                        Arc::clone(&rule),
-                                        Some(Arc::new(1.0)),
-                                    );
-                                }
-                            }
-                            Ok(())
-                        },
-                    ))
-                }
-                true
-            } else {
-                false
+    /// Return true only if current goroutine successfully accomplished the transformation.
+    pub fn from_open_to_half_open(&self, ctx: ContextPtr) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if *state == State::Open {
+            *state = State::HalfOpen;
+            log_transition(State::Open, State::HalfOpen, &self.rule, None);
+            let listeners = state_change_listeners().lock().unwrap();
+            for listener in &*listeners {
+                listener.on_transform_to_half_open(State::Open, Arc::clone(&self.rule));
             }
-        }
-    }
 
-    cfg_not_async! {
-    /// from_open_to_half_open updates circuit breaker state machine from open to half-open.
-        /// Return true only if current goroutine successfully accomplished the transformation.
-        pub fn from_open_to_half_open(&self, ctx: ContextPtr) -> bool {
-            let mut state = self.state.lock().unwrap();
-            if *state == State::Open {
-                *state = State::HalfOpen;
-                let listeners = state_change_listeners().lock().unwrap();
-                for listener in &*listeners {
-                    listener.on_transform_to_half_open(State::Open, Arc::clone(&self.rule));
-                }
-                let ctx = ctx.borrow();
-                let entry = ctx.entry();
-                if entry.is_none() {
-                    logging::error!(
-                        "Entry is None in BreakerBase::from_open_to_half_open(), rule: {:?}",
-                        self.rule,
-                    );
-                } else {
-                    // add hook for entry exit
-                    // if the current circuit breaker performs the probe through this entry, but the entry was blocked,
-                    // this hook will guarantee current circuit breaker state machine will rollback to Open from Half-Open
-                    drop(state);
-                    let entry = entry.unwrap();
-                    let rule = Arc::clone(&self.rule);
-                    let state = Arc::clone(&self.state);
-                    entry.upgrade().unwrap().borrow_mut().when_exit(Box::new(
+            let ctx = ctx.read().unwrap();
+            let entry = ctx.entry();
+            if entry.is_none() {
+                logging::error!(
+                    "Entry is None in BreakerBase::from_open_to_half_open(), rule: {:?}",
+                    self.rule,
+                );
+            } else {
+                // add hook for entry exit
+                // if the current circuit breaker performs the probe through this entry, but the entry was blocked,
+                // this hook will guarantee current circuit breaker state machine will rollback to Open from Half-Open
+                drop(state);
+                let entry = entry.unwrap();
+                let rule = Arc::clone(&self.rule);
+                let state = Arc::clone(&self.state);
+                entry
+                    .upgrade()
+                    .unwrap()
+                    .write()
+                    .unwrap()
+                    .when_exit(Box::new(
                         move |entry: &SentinelEntry, ctx: ContextPtr| -> Result<()> {
                             let mut state = state.lock().unwrap();
-                            if ctx.borrow().is_blocked() && *state == State::HalfOpen {
+                            if ctx.read().unwrap().is_blocked() && *state == State::HalfOpen {
                                 *state = State::Open;
+                                log_transition(State::HalfOpen, State::Open, &rule, None);
                                 let listeners = state_change_listeners().lock().unwrap();
                                 for listener in &*listeners {
                                     listener.on_transform_to_open(
@@ -335,11 +287,10 @@ impl BreakerBase {
                             Ok(())
                         },
                     ))
-                }
-                true
-            } else {
-                false
             }
+            true
+        } else {
+            false
         }
     }
 
@@ -350,6 +301,7 @@ impl BreakerBase {
         if *state == State::HalfOpen {
             *state = State::Open;
             self.update_next_retry_timestamp();
+            log_transition(State::HalfOpen, State::Open, &self.rule, Some(&snapshot));
             let listeners = state_change_listeners().lock().unwrap();
             for listener in &*listeners {
                 listener.on_transform_to_open(
@@ -370,6 +322,7 @@ impl BreakerBase {
         let mut state = self.state.lock().unwrap();
         if *state == State::HalfOpen {
             *state = State::Closed;
+            log_transition(State::HalfOpen, State::Closed, &self.rule, None);
             let listeners = state_change_listeners().lock().unwrap();
             for listener in &*listeners {
                 listener.on_transform_to_closed(State::HalfOpen, Arc::clone(&self.rule));
@@ -381,6 +334,21 @@ impl BreakerBase {
     }
 }
 
+fn log_transition(prev: State, state: State, rule: &Arc<Rule>, snapshot: Option<&Arc<Snapshot>>) {
+    let event = logging::Event::new(
+        logging::EventKind::BreakerTransition,
+        rule.resource.clone(),
+        format!(
+            "[CircuitBreaker] State changed from {:?} to {:?}",
+            prev, state
+        ),
+    )
+    .with_rule_id(rule.id.clone())
+    .with_state(format!("{:?}", state))
+    .with_snapshot(snapshot.map(|s| format!("{:?}", s)));
+    logging::log_event(logging::Level::Info, &event);
+}
+
 #[cfg(test)]
 pub(crate) use test::{MockCircuitBreaker, MockStateListener};
 
@@ -390,6 +358,7 @@ pub(crate) mod test {
     use crate::base::{ResourceType, ResourceWrapper, SentinelInput, SlotChain, TrafficType};
     use mockall::predicate::*;
     use mockall::*;
+    use std::sync::RwLock;
 
     /// MockCircuitBreaker
     mock! {
@@ -399,13 +368,13 @@ pub(crate) mod test {
             fn stat(&self) -> &Arc<CounterLeapArray>;
             fn bound_rule(&self) -> &Arc<Rule>;
             fn next_retry_timestamp_ms(&self)->u64;
-            fn try_pass(&self, ctx: Rc<RefCell<EntryContext>>) -> bool;
+            fn try_pass(&self, ctx: ContextPtr) -> bool;
             fn set_state(&self, state:State);
             fn current_state(&self) -> State;
             fn on_request_complete(&self, rt: u64, error: &Option<Error>);
             fn reset_metric(&self);
             fn from_closed_to_open(&self, snapshot: Arc<Snapshot>) -> bool;
-            fn from_open_to_half_open(&self, ctx: Rc<RefCell<EntryContext>>) -> bool;
+            fn from_open_to_half_open(&self, ctx: ContextPtr) -> bool;
             fn from_half_open_to_open(&self, snapshot: Arc<Snapshot>) -> bool;
             fn from_half_open_to_closed(&self) -> bool;
         }
@@ -448,7 +417,7 @@ pub(crate) mod test {
             ..Default::default()
         });
         let breaker = SlowRtBreaker::new(Arc::clone(&rule));
-        let token = breaker.try_pass(Rc::new(RefCell::new(EntryContext::new())));
+        let token = breaker.try_pass(Arc::new(RwLock::new(EntryContext::new())));
         clear_state_change_listeners();
         assert!(token);
     }
@@ -485,12 +454,12 @@ pub(crate) mod test {
         let mut ctx = EntryContext::new();
         let res = ResourceWrapper::new("abc".into(), ResourceType::Common, TrafficType::Inbound);
         ctx.set_resource(res);
-        let ctx = Rc::new(RefCell::new(ctx));
-        let entry = Rc::new(RefCell::new(SentinelEntry::new(
-            Rc::clone(&ctx),
+        let ctx = Arc::new(RwLock::new(ctx));
+        let entry = Arc::new(RwLock::new(SentinelEntry::new(
+            Arc::clone(&ctx),
             Arc::clone(&sc),
         )));
-        ctx.borrow_mut().set_entry(Rc::downgrade(&entry));
+        ctx.write().unwrap().set_entry(Arc::downgrade(&entry));
         let token = breaker.try_pass(ctx);
         clear_state_change_listeners();
         assert!(token);
@@ -511,7 +480,7 @@ pub(crate) mod test {
             ..Default::default()
         });
         let breaker = SlowRtBreaker::new(Arc::clone(&rule));
-        let token = breaker.try_pass(Rc::new(RefCell::new(EntryContext::new())));
+        let token = breaker.try_pass(Arc::new(RwLock::new(EntryContext::new())));
         assert!(token);
     }
 
@@ -534,12 +503,12 @@ pub(crate) mod test {
         let mut ctx = EntryContext::new();
         let res = ResourceWrapper::new("abc".into(), ResourceType::Common, TrafficType::Inbound);
         ctx.set_resource(res);
-        let ctx = Rc::new(RefCell::new(ctx));
-        let entry = Rc::new(RefCell::new(SentinelEntry::new(
-            Rc::clone(&ctx),
+        let ctx = Arc::new(RwLock::new(ctx));
+        let entry = Arc::new(RwLock::new(SentinelEntry::new(
+            Arc::clone(&ctx),
             Arc::clone(&sc),
         )));
-        ctx.borrow_mut().set_entry(Rc::downgrade(&entry));
+        ctx.write().unwrap().set_entry(Arc::downgrade(&entry));
         let token = breaker.try_pass(ctx);
         assert!(token);
         assert_eq!(breaker.current_state(), State::HalfOpen);
@@ -588,7 +557,7 @@ pub(crate) mod test {
             ..Default::default()
         });
         let breaker = ErrorCountBreaker::new(Arc::clone(&rule));
-        let token = breaker.try_pass(Rc::new(RefCell::new(EntryContext::new())));
+        let token = breaker.try_pass(Arc::new(RwLock::new(EntryContext::new())));
         assert!(token);
     }
 
@@ -610,12 +579,12 @@ pub(crate) mod test {
         let mut ctx = EntryContext::new();
         let res = ResourceWrapper::new("abc".into(), ResourceType::Common, TrafficType::Inbound);
         ctx.set_resource(res);
-        let ctx = Rc::new(RefCell::new(ctx));
-        let entry = Rc::new(RefCell::new(SentinelEntry::new(
-            Rc::clone(&ctx),
+        let ctx = Arc::new(RwLock::new(ctx));
+        let entry = Arc::new(RwLock::new(SentinelEntry::new(
+            Arc::clone(&ctx),
             Arc::clone(&sc),
         )));
-        ctx.borrow_mut().set_entry(Rc::downgrade(&entry));
+        ctx.write().unwrap().set_entry(Arc::downgrade(&entry));
         let token = breaker.try_pass(ctx);
         assert!(token);
         assert_eq!(breaker.current_state(), State::HalfOpen);
@@ -663,7 +632,7 @@ pub(crate) mod test {
             ..Default::default()
         });
         let breaker = ErrorCountBreaker::new(Arc::clone(&rule));
-        let token = breaker.try_pass(Rc::new(RefCell::new(EntryContext::new())));
+        let token = breaker.try_pass(Arc::new(RwLock::new(EntryContext::new())));
         assert!(token);
     }
 
@@ -685,12 +654,12 @@ pub(crate) mod test {
         let mut ctx = EntryContext::new();
         let res = ResourceWrapper::new("abc".into(), ResourceType::Common, TrafficType::Inbound);
         ctx.set_resource(res);
-        let ctx = Rc::new(RefCell::new(ctx));
-        let entry = Rc::new(RefCell::new(SentinelEntry::new(
-            Rc::clone(&ctx),
+        let ctx = Arc::new(RwLock::new(ctx));
+        let entry = Arc::new(RwLock::new(SentinelEntry::new(
+            Arc::clone(&ctx),
             Arc::clone(&sc),
         )));
-        ctx.borrow_mut().set_entry(Rc::downgrade(&entry));
+        ctx.write().unwrap().set_entry(Arc::downgrade(&entry));
         let token = breaker.try_pass(ctx);
         assert!(token);
         assert_eq!(breaker.current_state(), State::HalfOpen);
@@ -790,4 +759,27 @@ pub(crate) mod test {
         assert!(changed);
         assert!(breaker.next_retry_timestamp_ms() > 0);
     }
+
+    #[test]
+    fn retry_timeout_with_mock_clock() {
+        let clock = Arc::new(utils::MockClock::starting_at(1_600_000_000_000));
+        let _guard = clock.install();
+        let rule = Arc::new(Rule {
+            resource: "abc".into(),
+            strategy: BreakerStrategy::SlowRequestRatio,
+            retry_timeout_ms: 3000,
+            min_request_amount: 10,
+            stat_interval_ms: 10000,
+            max_allowed_rt_ms: 50,
+            threshold: 0.5,
+            ..Default::default()
+        });
+        let breaker = SlowRtBreaker::new(rule);
+        breaker.breaker().update_next_retry_timestamp();
+        assert!(!breaker.breaker().retry_timeout_arrived());
+        clock.advance_millis(2999);
+        assert!(!breaker.breaker().retry_timeout_arrived());
+        clock.advance_millis(1);
+        assert!(breaker.breaker().retry_timeout_arrived());
+    }
 }
@@ -1,8 +1,6 @@
 use super::*;
 use crate::{base::EntryContext, logging, Result};
 use lazy_static::lazy_static;
-use std::cell::RefCell;
-use std::rc::Rc;
 use std::sync::{
     atomic::{AtomicU64, Ordering},
     Arc, Mutex,
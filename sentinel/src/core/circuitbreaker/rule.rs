@@ -1,5 +1,8 @@
 use super::*;
-use crate::{base::SentinelRule, logging, system_metric, Error, Result};
+use crate::{
+    base::{Diagnostic, SentinelRule},
+    logging, system_metric, Error, Result,
+};
 use serde::{Deserialize, Serialize};
 use serde_json;
 use std::fmt;
@@ -61,31 +64,45 @@ impl SentinelRule for Rule {
         self.resource.clone()
     }
 
-    fn is_valid(&self) -> Result<()> {
+    fn rule_id(&self) -> Option<String> {
+        self.id.clone()
+    }
+
+    fn diagnose(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
         if self.resource.len() == 0 {
-            return Err(Error::msg("empty resource name"));
+            diagnostics.push(Diagnostic::error("resource", "empty resource name"));
         }
         if self.stat_interval_ms == 0 {
-            return Err(Error::msg("invalid stat_interval_ms"));
+            diagnostics.push(Diagnostic::error(
+                "stat_interval_ms",
+                "invalid stat_interval_ms",
+            ));
         }
         if self.retry_timeout_ms == 0 {
-            return Err(Error::msg("invalid retry_timeout_ms"));
+            diagnostics.push(Diagnostic::error(
+                "retry_timeout_ms",
+                "invalid retry_timeout_ms",
+            ));
         }
         if self.threshold < 0.0 {
-            return Err(Error::msg("invalid threshold"));
+            diagnostics.push(Diagnostic::error("threshold", "invalid threshold"));
         }
         if self.strategy != BreakerStrategy::ErrorCount && self.threshold > 1.0 {
-            return Err(Error::msg(format!(
-                "invalid {:?} ratio threshold (valid range: [0.0, 1.0])",
-                self.strategy
-            )));
+            diagnostics.push(Diagnostic::error(
+                "threshold",
+                format!(
+                    "invalid {:?} ratio threshold (valid range: [0.0, 1.0])",
+                    self.strategy
+                ),
+            ));
         }
         if self.stat_sliding_window_bucket_count != 0
             && self.stat_interval_ms % self.stat_sliding_window_bucket_count != 0
         {
-            logging::warn!("[CircuitBreaker IsValidRule] The following must be true: stat_interval_ms % stat_sliding_window_bucket_count == 0. stat_sliding_window_bucket_count will be replaced by 1, rule {:?}", self);
+            diagnostics.push(Diagnostic::warning("stat_sliding_window_bucket_count", "The following must be true: stat_interval_ms % stat_sliding_window_bucket_count == 0. stat_sliding_window_bucket_count will be replaced by 1"));
         }
-        Ok(())
+        diagnostics
     }
 }
 
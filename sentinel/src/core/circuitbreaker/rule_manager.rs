@@ -1,5 +1,6 @@
 use super::*;
-use crate::{base::rule::SentinelRule, logging, utils, Error, Result};
+use crate::base::{check_direct_update, RuleChangeListener, RuleChangeListeners};
+use crate::{base::rule::SentinelRule, logging, utils, utils::ShardedMap, Error, Result};
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 use std::hash::Hash;
@@ -23,10 +24,24 @@ lazy_static! {
     };
     pub static ref STATE_CHANGE_LISTERNERS: Mutex<Vec<Arc<dyn StateChangeListener>>> =
         Mutex::new(Vec::new());
-    pub static ref BREAKER_MAP: RwLock<HashMap<String, Vec<Arc<dyn CircuitBreakerTrait>>>> =
-        RwLock::new(HashMap::new());
+    pub static ref BREAKER_MAP: ShardedMap<String, Vec<Arc<dyn CircuitBreakerTrait>>> =
+        ShardedMap::new();
     pub static ref CURRENT_RULES: Mutex<RuleMap> = Mutex::new(HashMap::new());
-    pub static ref BREAKER_RULES: RwLock<RuleMap> = RwLock::new(HashMap::new());
+    /// the valid rules of the breakers
+    pub static ref BREAKER_RULES: ShardedMap<String, Vec<Arc<Rule>>> = ShardedMap::new();
+}
+
+lazy_static! {
+    static ref RULE_CHANGE_LISTENERS: RuleChangeListeners<Rule> = RuleChangeListeners::new();
+}
+
+/// `register_rule_change_listeners` registers the listeners notified after the rules are changed.
+pub fn register_rule_change_listeners(listeners: Vec<Arc<dyn RuleChangeListener<Rule>>>) {
+    RULE_CHANGE_LISTENERS.register(listeners);
+}
+
+pub fn clear_rule_change_listeners() {
+    RULE_CHANGE_LISTENERS.clear();
 }
 
 pub fn state_change_listeners() -> &'static Mutex<Vec<Arc<dyn StateChangeListener>>> {
@@ -78,30 +93,20 @@ mod gen_fns {
 }
 
 /// `get_rules_of_resource` returns specific resource's rules
-// This func acquires read locks on global `BREAKER_RULES`,
-// please release your write locks on them before calling this func
+// This func acquires the read lock on a shard of global `BREAKER_RULES`
 pub fn get_rules_of_resource(res: &String) -> Vec<Arc<Rule>> {
-    let breaker_rules = BREAKER_RULES.read().unwrap();
-    let placeholder = Vec::new();
-    let res_rules = breaker_rules.get(res).unwrap_or(&placeholder);
-    let mut rules = Vec::with_capacity(res_rules.len());
-    for r in res_rules {
-        rules.push(Arc::clone(r));
-    }
-    rules
+    BREAKER_RULES.get(res).unwrap_or_default()
 }
 
 /// `get_rules` returns all the rules
-// This func acquires read locks on global `BREAKER_RULES`,
-// please release your write locks on them before calling this func
+// This func acquires the read locks on the shards of global `BREAKER_RULES` one by one
 pub fn get_rules() -> Vec<Arc<Rule>> {
     let mut rules = Vec::new();
-    let breaker_rules = BREAKER_RULES.read().unwrap();
-    for (_, res_rules) in &*breaker_rules {
+    BREAKER_RULES.for_each(|_, res_rules| {
         for r in res_rules {
             rules.push(Arc::clone(r));
         }
-    }
+    });
     rules
 }
 
@@ -109,9 +114,17 @@ pub fn get_rules() -> Vec<Arc<Rule>> {
 // This func acquires locks on global `BREAKER_RULES`, `CURRENT_RULES` and `BREAKER_MAP`,
 // please release your locks on them before calling this func
 pub fn clear_rules() {
-    CURRENT_RULES.lock().unwrap().clear();
-    BREAKER_RULES.write().unwrap().clear();
-    BREAKER_MAP.write().unwrap().clear();
+    if check_direct_update("`circuitbreaker::clear_rules()`").is_err() {
+        return;
+    }
+    RULE_CHANGE_LISTENERS.watch(get_rules, do_clear_rules)
+}
+
+fn do_clear_rules() {
+    let mut global_rule_map = CURRENT_RULES.lock().unwrap();
+    global_rule_map.clear();
+    BREAKER_RULES.clear();
+    BREAKER_MAP.clear();
 }
 
 fn log_rule_update(map: &RuleMap) {
@@ -130,6 +143,13 @@ fn log_rule_update(map: &RuleMap) {
 // This func acquires locks on global `CURRENT_RULES`, `BREAKER_RULES` and `BREAKER_MAP`,
 // please release your locks on them before calling this func
 pub fn load_rules(rules: Vec<Arc<Rule>>) -> bool {
+    if check_direct_update("`circuitbreaker::load_rules()`").is_err() {
+        return false;
+    }
+    RULE_CHANGE_LISTENERS.watch(get_rules, || do_load_rules(rules))
+}
+
+fn do_load_rules(rules: Vec<Arc<Rule>>) -> bool {
     let mut rule_map: RuleMap = HashMap::new();
     // todo: validate rules here,
     // neglect invalid rules,
@@ -169,27 +189,22 @@ pub fn load_rules(rules: Vec<Arc<Rule>>) -> bool {
     }
 
     let start = utils::curr_time_nanos();
-    let mut global_breaker_map = BREAKER_MAP.write().unwrap();
     let mut valid_breaker_map = HashMap::with_capacity(valid_rules_map.len());
 
-    // build global_breaker_map according to valid rules
+    // build global_breaker_map according to valid rules,
+    // the breakers are built without locking `BREAKER_MAP`, thus the traffic is not stalled
     for (res, rules) in valid_rules_map.iter() {
-        let mut placeholder = Vec::new();
-        let new_cbs_of_res = build_resource_circuit_breaker(
-            res,
-            &rules,
-            global_breaker_map.get_mut(res).unwrap_or(&mut placeholder),
-        );
+        let mut old_res_cbs = BREAKER_MAP.get(res).unwrap_or_default();
+        let new_cbs_of_res = build_resource_circuit_breaker(res, &rules, &mut old_res_cbs);
         if new_cbs_of_res.len() > 0 {
             valid_breaker_map.insert(res.clone(), new_cbs_of_res);
         }
     }
     log_rule_update(&valid_rules_map);
-    *BREAKER_RULES.write().unwrap() = valid_rules_map;
-    *global_breaker_map = valid_breaker_map;
+    BREAKER_RULES.replace(valid_rules_map);
+    BREAKER_MAP.replace(valid_breaker_map);
     *global_rule_map = rule_map;
     drop(global_rule_map);
-    drop(global_breaker_map);
     logging::debug!(
         "[CircuitBreakerTrait load_rules] Time statistic(ns) for updating flow rule, time cost {}",
         utils::curr_time_nanos() - start
@@ -203,16 +218,23 @@ pub fn load_rules(rules: Vec<Arc<Rule>>) -> bool {
 // This func acquires locks on global `CURRENT_RULES`, `BREAKER_RULES` and `BREAKER_MAP`,
 // please release your locks on them before calling this func
 pub fn load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<bool> {
+    check_direct_update("`circuitbreaker::load_rules_of_resource()`")?;
+    RULE_CHANGE_LISTENERS.watch(
+        || get_rules_of_resource(res),
+        || do_load_rules_of_resource(res, rules),
+    )
+}
+
+fn do_load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<bool> {
     if res.len() == 0 {
         return Err(Error::msg("empty resource"));
     }
     let mut global_rule_map = CURRENT_RULES.lock().unwrap();
-    let mut global_breaker_map = BREAKER_MAP.write().unwrap();
     // clear resource rules
     if rules.len() == 0 {
         global_rule_map.remove(res);
-        global_breaker_map.remove(res);
-        BREAKER_RULES.write().unwrap().remove(res);
+        BREAKER_MAP.remove(res);
+        BREAKER_RULES.remove(res);
         logging::info!(
             "[CircuitBreakerTrait] clear resource level rules, resource {}",
             res
@@ -238,21 +260,17 @@ pub fn load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<boo
     }
     // the `res` related rules changes, have to update
     let start = utils::curr_time_nanos();
-    let mut placeholder = Vec::new();
-    let mut old_res_tcs = global_breaker_map.get_mut(res).unwrap_or(&mut placeholder);
+    let mut old_res_tcs = BREAKER_MAP.get(res).unwrap_or_default();
 
     let valid_res_rules_string = format!("{:?}", &valid_res_rules);
     let new_res_tcs = build_resource_circuit_breaker(res, &valid_res_rules, &mut old_res_tcs);
 
     if new_res_tcs.len() == 0 {
-        global_breaker_map.remove(res);
-        BREAKER_RULES.write().unwrap().remove(res);
+        BREAKER_MAP.remove(res);
+        BREAKER_RULES.remove(res);
     } else {
-        global_breaker_map.insert(res.clone(), new_res_tcs);
-        BREAKER_RULES
-            .write()
-            .unwrap()
-            .insert(res.clone(), valid_res_rules);
+        BREAKER_MAP.insert(res.clone(), new_res_tcs);
+        BREAKER_RULES.insert(res.clone(), valid_res_rules);
     }
 
     global_rule_map.insert(res.clone(), rules);
@@ -269,17 +287,10 @@ pub fn load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<boo
     Ok(true)
 }
 
-// This func acquires read locks on global `BREAKER_MAP`,
-// please release your write locks on them before calling this func
+// This func only acquires the read lock on a shard of global `BREAKER_MAP`,
+// thus it never waits for the loading of the rules
 pub fn get_breakers_of_resource(resource: &String) -> Vec<Arc<dyn CircuitBreakerTrait>> {
-    let breakers_map = BREAKER_MAP.read().unwrap();
-    let placeholder = Vec::new();
-    let res_cbs = breakers_map.get(resource).unwrap_or(&placeholder);
-    let mut breakers = Vec::with_capacity(res_cbs.len());
-    for b in res_cbs {
-        breakers.push(Arc::clone(b));
-    }
-    breakers
+    BREAKER_MAP.get(resource).unwrap_or_default()
 }
 
 /// register_state_change_listeners registers the global state change listener for all circuit breakers
@@ -329,9 +340,20 @@ pub fn remove_circuit_breaker_generator(s: &BreakerStrategy) -> Result<()> {
 
 /// `clear_rules_of_resource` clears resource level rules in circuitBreaker module.
 pub fn clear_rules_of_resource(res: &String) {
-    BREAKER_RULES.write().unwrap().remove(res);
-    CURRENT_RULES.lock().unwrap().remove(res);
-    BREAKER_MAP.write().unwrap().remove(res);
+    if check_direct_update("`circuitbreaker::clear_rules_of_resource()`").is_err() {
+        return;
+    }
+    RULE_CHANGE_LISTENERS.watch(
+        || get_rules_of_resource(res),
+        || do_clear_rules_of_resource(res),
+    )
+}
+
+fn do_clear_rules_of_resource(res: &String) {
+    let mut global_rule_map = CURRENT_RULES.lock().unwrap();
+    global_rule_map.remove(res);
+    BREAKER_RULES.remove(res);
+    BREAKER_MAP.remove(res);
 }
 
 pub fn calculate_reuse_index_for(
@@ -463,7 +485,7 @@ mod test {
             ..Default::default()
         })]);
 
-        let breaker_map = BREAKER_MAP.write().unwrap();
+        let breaker_map = BREAKER_MAP.snapshot();
 
         assert!(GEN_FUN_MAP.read().unwrap().contains_key(&key));
         assert!(breaker_map[&resource].len() > 0);
@@ -507,7 +529,7 @@ mod test {
         });
         let sucess = load_rules(vec![Arc::clone(&r0), Arc::clone(&r1), Arc::clone(&r2)]);
         assert!(sucess);
-        let breaker_map = BREAKER_MAP.read().unwrap();
+        let breaker_map = BREAKER_MAP.snapshot();
         let b2 = &breaker_map["abc"][1];
         assert_eq!(breaker_map.len(), 1);
         assert_eq!(breaker_map["abc"].len(), 3);
@@ -561,7 +583,7 @@ mod test {
             Arc::clone(&r6),
         ]);
         assert!(sucess);
-        let breaker_map = BREAKER_MAP.read().unwrap();
+        let breaker_map = BREAKER_MAP.snapshot();
         let b2 = &breaker_map["abc"][1];
         assert_eq!(breaker_map.len(), 1);
         assert_eq!(breaker_map["abc"].len(), 4);
@@ -645,8 +667,8 @@ mod test {
         assert!(success.unwrap());
         let success = load_rules_of_resource(&"abc2".into(), vec![Arc::clone(&r2)]);
         assert!(success.unwrap());
-        let breaker_map = BREAKER_MAP.read().unwrap();
-        let breaker_rules = BREAKER_RULES.read().unwrap();
+        let breaker_map = BREAKER_MAP.snapshot();
+        let breaker_rules = BREAKER_RULES.snapshot();
         let current_rules = CURRENT_RULES.lock().unwrap();
         assert_eq!(2, breaker_map["abc1"].len());
         assert_eq!(2, breaker_rules["abc1"].len());
@@ -662,14 +684,14 @@ mod test {
         let success =
             load_rules_of_resource(&"abc1".into(), vec![Arc::clone(&r0), Arc::clone(&r1)]);
         assert!(!success.unwrap());
-        assert_eq!(2, BREAKER_MAP.read().unwrap()["abc1"].len());
-        assert_eq!(2, BREAKER_RULES.read().unwrap()["abc1"].len());
+        assert_eq!(2, BREAKER_MAP.snapshot()["abc1"].len());
+        assert_eq!(2, BREAKER_RULES.snapshot()["abc1"].len());
         assert_eq!(2, CURRENT_RULES.lock().unwrap()["abc1"].len());
 
         let success = load_rules_of_resource(&"abc1".into(), Vec::new());
         assert!(success.unwrap());
-        assert!(!BREAKER_MAP.read().unwrap().contains_key("abc1"));
-        assert!(!BREAKER_RULES.read().unwrap().contains_key("abc1"));
+        assert!(!BREAKER_MAP.snapshot().contains_key("abc1"));
+        assert!(!BREAKER_RULES.snapshot().contains_key("abc1"));
         assert!(!CURRENT_RULES.lock().unwrap().contains_key("abc1"));
 
         clear_rules();
@@ -756,8 +778,8 @@ mod test {
         assert!(success);
 
         clear_rules_of_resource(&"abc1".into());
-        let breaker_map = BREAKER_MAP.read().unwrap();
-        let breaker_rules = BREAKER_RULES.read().unwrap();
+        let breaker_map = BREAKER_MAP.snapshot();
+        let breaker_rules = BREAKER_RULES.snapshot();
         let current_rules = CURRENT_RULES.lock().unwrap();
         assert_eq!(0, breaker_map.get("abc1").unwrap_or(&Vec::new()).len());
         assert_eq!(0, breaker_rules.get("abc1").unwrap_or(&Vec::new()).len());
@@ -771,4 +793,50 @@ mod test {
 
         clear_rules();
     }
+
+    #[test]
+    #[ignore]
+    fn test_rule_change_listeners() {
+        use crate::base::{with_rule_source, RuleChange};
+
+        // the old rules are counted to check the change is of the resource only
+        struct DisabledAlert(Mutex<Vec<(String, Option<String>, usize)>>);
+        impl RuleChangeListener<Rule> for DisabledAlert {
+            fn on_rules_changed(&self, change: &RuleChange<Rule>) {
+                for rule in change.removed() {
+                    self.0.lock().unwrap().push((
+                        rule.resource.clone(),
+                        change.source.clone(),
+                        change.old_rules.len(),
+                    ));
+                }
+            }
+        }
+
+        clear_rules();
+        let alert = Arc::new(DisabledAlert(Mutex::new(Vec::new())));
+        register_rule_change_listeners(vec![alert.clone()]);
+        let rule = Arc::new(Rule {
+            resource: "critical".into(),
+            strategy: BreakerStrategy::ErrorCount,
+            retry_timeout_ms: 1000,
+            min_request_amount: 5,
+            stat_interval_ms: 1000,
+            threshold: 10.0,
+            ..Default::default()
+        });
+        let other = Arc::new(Rule {
+            resource: "other".into(),
+            ..(*rule).clone()
+        });
+        load_rules(vec![rule, other]);
+        assert!(alert.0.lock().unwrap().is_empty());
+        with_rule_source("datasource", || clear_rules_of_resource(&"critical".into()));
+        assert_eq!(
+            *alert.0.lock().unwrap(),
+            vec![("critical".into(), Some("datasource".into()), 1)]
+        );
+        clear_rule_change_listeners();
+        clear_rules();
+    }
 }
@@ -1,17 +1,16 @@
 use super::*;
 use crate::{
     base::{
-        BaseSlot, BlockType, ContextPtr, MetricEvent, ResultStatus, RuleCheckSlot, StatNode,
-        StatSlot, TokenResult,
+        BaseSlot, BlockType, ContextPtr, MetricEvent, ResultStatus, RuleCheckSlot, SentinelRule,
+        StatNode, StatSlot, TokenResult,
     },
     logging, stat, utils,
     utils::AsAny,
 };
 use lazy_static::lazy_static;
 use std::any::Any;
-use std::cell::RefCell;
-use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Duration;
 
 const RULE_CHECK_SLOT_ORDER: u32 = 5000;
 
@@ -33,48 +32,39 @@ impl BaseSlot for Slot {
 }
 
 impl RuleCheckSlot for Slot {
-    cfg_async! {
-        fn check(&self, ctx: &ContextPtr) -> TokenResult {
-            let res = ctx.read().unwrap().resource().name().clone();
-            if res.len() == 0 {
-                return ctx.read().unwrap().result().clone();
-            }
-            if let Some(rule) = can_pass_check(&ctx, &res) {
-                ctx.write().unwrap()
-                    .set_result(TokenResult::new_blocked_with_msg(
-                        BlockType::CircuitBreaking,
-                        "circuit breaker check blocked".into(),
-                    ));
-            }
+    fn check(&self, ctx: &ContextPtr) -> TokenResult {
+        let res = ctx.read().unwrap().resource().name().clone();
+        if res.len() == 0 {
             return ctx.read().unwrap().result().clone();
         }
-    }
-
-    cfg_not_async! {
-        fn check(&self, ctx: &ContextPtr) -> TokenResult {
-            let res = ctx.borrow().resource().name().clone();
-            if res.len() == 0 {
-                return ctx.borrow().result().clone();
-            }
-            if let Some(rule) = can_pass_check(&ctx, &res) {
-                ctx.borrow_mut()
-                    .set_result(TokenResult::new_blocked_with_msg(
-                        BlockType::CircuitBreaking,
-                        "circuit breaker check blocked".into(),
-                    ));
+        if let Some(breaker) = can_pass_check(&ctx, &res) {
+            let rule: Arc<dyn SentinelRule> = Arc::clone(breaker.bound_rule()) as _;
+            let mut result = TokenResult::new_blocked_with_cause(
+                BlockType::CircuitBreaking,
+                "circuit breaker check blocked".into(),
+                rule,
+                Arc::new(breaker.current_state()),
+            );
+            // the circuit breaker would probe after the retry timeout
+            let retry_after = breaker
+                .next_retry_timestamp_ms()
+                .saturating_sub(utils::curr_time_millis());
+            if retry_after > 0 {
+                result = result.with_retry_after(Duration::from_millis(retry_after));
             }
-            return ctx.borrow().result().clone();
+            ctx.write().unwrap().set_result(result);
         }
+        return ctx.read().unwrap().result().clone();
     }
 }
 
 /// `None` indicates it passes
-/// `Some(rule)` indicates it is broke by the rule
-fn can_pass_check(ctx: &ContextPtr, res: &String) -> Option<Arc<Rule>> {
+/// `Some(breaker)` indicates it is broke by the breaker
+fn can_pass_check(ctx: &ContextPtr, res: &String) -> Option<Arc<dyn CircuitBreakerTrait>> {
     let breakers = get_breakers_of_resource(res);
     for breaker in breakers {
         if !breaker.try_pass(ctx.clone()) {
-            return Some(Arc::clone(breaker.bound_rule()));
+            return Some(breaker);
         }
     }
     return None;
@@ -84,6 +74,7 @@ fn can_pass_check(ctx: &ContextPtr, res: &String) -> Option<Arc<Rule>> {
 mod test {
     use super::*;
     use crate::base::{EntryContext, ResourceType, ResourceWrapper, SentinelInput, TrafficType};
+    use std::sync::RwLock;
 
     #[test]
     #[ignore]
@@ -117,6 +108,10 @@ mod test {
                     };
                     breaker.expect_try_pass().return_const(false);
                     breaker.expect_bound_rule().return_const(Arc::new(rule));
+                    breaker.expect_current_state().return_const(State::Open);
+                    breaker
+                        .expect_next_retry_timestamp_ms()
+                        .return_const(utils::curr_time_millis() + 3000);
                     Arc::new(breaker)
                 },
             ),
@@ -130,9 +125,13 @@ mod test {
         let mut ctx = EntryContext::new();
         let res = ResourceWrapper::new(res_name, ResourceType::Common, TrafficType::Inbound);
         ctx.set_resource(res);
-        let ctx = Rc::new(RefCell::new(ctx));
+        let ctx = Arc::new(RwLock::new(ctx));
         let token = slot.check(&ctx);
         assert!(token.is_blocked());
+        let block_err = token.block_err().unwrap();
+        assert_eq!(block_err.block_type(), BlockType::CircuitBreaking);
+        assert!(block_err.triggered_rule().is_some());
+        assert!(block_err.retry_after().unwrap() <= Duration::from_millis(3000));
         clear_rules();
     }
 
@@ -170,10 +169,10 @@ mod test {
         let mut ctx = EntryContext::new();
         let res = ResourceWrapper::new(res_name, ResourceType::Common, TrafficType::Inbound);
         ctx.set_resource(res);
-        let ctx = Rc::new(RefCell::new(ctx));
+        let ctx = Arc::new(RwLock::new(ctx));
         let token = slot.check(&ctx);
         assert!(token.is_pass());
-        assert!(ctx.borrow().result().is_pass());
+        assert!(ctx.read().unwrap().result().is_pass());
         clear_rules();
     }
 }
@@ -1,8 +1,6 @@
 use super::*;
 use crate::base::{BaseSlot, BlockError, ContextPtr, EntryContext, MetricEvent, StatSlot};
 use lazy_static::lazy_static;
-use std::cell::RefCell;
-use std::rc::Rc;
 use std::sync::Arc;
 
 const STAT_SLOT_ORDER: u32 = 5000;
@@ -31,15 +29,67 @@ impl StatSlot for MetricStatSlot {
     fn on_entry_blocked(&self, _ctx: ContextPtr, _block_error: Option<BlockError>) {}
 
     fn on_completed(&self, ctx: ContextPtr) {
-        cfg_if_async! {
-            let ctx = ctx.read().unwrap(),
-            let ctx = ctx.borrow()
-        };
+        // the round trip is measured here as well,
+        // since the `ResourceNodeStatSlot` may be absent from the slot chain of the resource
+        let rt = ctx.write().unwrap().complete_round_trip();
+        let ctx = ctx.read().unwrap();
 
         let res = ctx.resource().name();
-        let rt = ctx.round_trip();
         for cb in get_breakers_of_resource(res) {
             cb.on_request_complete(rt, ctx.get_err());
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::SlotChain;
+    use crate::EntryBuilder;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    #[ignore]
+    fn round_trip_of_breaker_only_chain() {
+        static ROUND_TRIP: AtomicU64 = AtomicU64::new(0);
+        set_circuit_breaker_generator(
+            BreakerStrategy::Custom(102),
+            Box::new(
+                move |_: Arc<Rule>,
+                      _: Option<Arc<CounterLeapArray>>|
+                      -> Arc<dyn CircuitBreakerTrait> {
+                    let mut breaker = MockCircuitBreaker::new();
+                    breaker.expect_try_pass().return_const(true);
+                    breaker
+                        .expect_on_request_complete()
+                        .returning(|rt, _| ROUND_TRIP.store(rt, Ordering::SeqCst));
+                    Arc::new(breaker)
+                },
+            ),
+        )
+        .unwrap();
+        load_rules(vec![Arc::new(Rule {
+            resource: "breaker_only".into(),
+            strategy: BreakerStrategy::Custom(102),
+            retry_timeout_ms: 3000,
+            min_request_amount: 10,
+            stat_interval_ms: 10000,
+            threshold: 0.5,
+            ..Default::default()
+        })]);
+
+        // without the `ResourceNodeStatSlot`, which measures the round trip of the default chain
+        let mut sc = SlotChain::new();
+        sc.add_rule_check_slot(default_slot());
+        sc.add_stat_slot(default_metric_stat_slot());
+        let entry = EntryBuilder::new("breaker_only".into())
+            .with_slot_chain(Arc::new(sc))
+            .build()
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        entry.read().unwrap().exit();
+        assert!(ROUND_TRIP.load(Ordering::SeqCst) >= 20);
+        clear_rules();
+    }
+}
@@ -1,8 +1,6 @@
 use super::{constant::*, ConfigEntity};
 use crate::{base::ResourceType, logging, utils, Error, Result};
-use directories::UserDirs;
 use lazy_static::lazy_static;
-use serde_yaml;
 use std::env;
 use std::fs::File;
 use std::io::prelude::*;
@@ -11,6 +9,7 @@ use std::sync::RwLock;
 
 lazy_static! {
     static ref GLOBAL_CONFIG: RwLock<ConfigEntity> = RwLock::new(ConfigEntity::new());
+    static ref CONFIG_FILE_PATH: RwLock<String> = RwLock::new(String::new());
 }
 
 pub fn reset_global_config(entity: ConfigEntity) {
@@ -18,49 +17,129 @@ pub fn reset_global_config(entity: ConfigEntity) {
     *cfg = entity;
 }
 
-// init_config_with_yaml loads general configuration from the YAML file under provided path.
-pub fn init_config_with_yaml(config_path: &mut String) -> Result<()> {
+// init_config_with_file loads general configuration from the YAML or TOML file under provided path.
+pub fn init_config_with_file(config_path: &mut String) -> Result<()> {
     // Initialize general config and logging module.
-    apply_yaml_config_file(config_path)?;
+    apply_config_file(config_path)?;
     override_config_from_env_and_init_log()?;
     Ok(())
 }
 
-// apply_yaml_config_file loads general configuration from the given YAML file.
-fn apply_yaml_config_file(config_path: &mut String) -> Result<()> {
-    // Priority: system environment > YAML file > default config
+// apply_config_file loads general configuration from the given YAML or TOML file.
+fn apply_config_file(config_path: &mut String) -> Result<()> {
+    // Priority: system environment > configuration file > default config
+    resolve_config_path(config_path);
+    // First Sentinel will try to load config from the given file.
+    // If the path is empty (not set), Sentinel will use the default config.
+    load_global_config_from_file(&config_path)?;
+    *CONFIG_FILE_PATH.write().unwrap() = config_path.clone();
+    Ok(())
+}
+
+fn resolve_config_path(config_path: &mut String) {
     if utils::is_blank(&config_path) {
         // If the config file path is absent, Sentinel will try to resolve it from the system env.
         *config_path = env::var(CONF_FILE_PATH_ENV_KEY).unwrap_or(CONFIG_FILENAME.into());
     }
-    // First Sentinel will try to load config from the given file.
-    // If the path is empty (not set), Sentinel will use the default config.
-    load_global_config_from_yaml_file(&config_path)?;
+}
+
+fn load_global_config_from_file(path_str: &String) -> Result<()> {
+    if let Some(entity) = load_config_from_file(path_str)? {
+        logging::info!(
+            "[Config] Resolving Sentinel config from file, file {}",
+            path_str
+        );
+        reset_global_config(entity);
+    }
     Ok(())
 }
 
-fn load_global_config_from_yaml_file(path_str: &String) -> Result<()> {
+// load_config_from_file returns `None` if the default configuration file is absent
+fn load_config_from_file(path_str: &String) -> Result<Option<ConfigEntity>> {
     let path = Path::new(path_str);
-    if path_str == CONFIG_FILENAME && path.exists() {
+    if path_str == CONFIG_FILENAME && !path.exists() {
         //use default globalCfg.
-        return Ok(());
+        return Ok(None);
     }
     if !path.exists() {
-        return Err(Error::msg(
-            "Sentinel YAML configuration file does not exist!",
-        ));
+        return Err(Error::msg("Sentinel configuration file does not exist!"));
     }
     let mut file = File::open(path)?;
     let mut content = String::new();
     file.read_to_string(&mut content)?;
-    let entity: ConfigEntity = serde_yaml::from_str(&content)?;
+    let entity = parse_config(&content, ConfigFormat::from_path(path))?;
     entity.check()?;
-    logging::info!(
-        "[Config] Resolving Sentinel config from file, file {}",
-        path_str
-    );
-    reset_global_config(entity);
-    Ok(())
+    Ok(Some(entity))
+}
+
+/// `config_file_path` returns the configuration file resolved at initialization,
+/// it is empty if Sentinel is initialized by `init_with_config()`.
+pub fn config_file_path() -> String {
+    CONFIG_FILE_PATH.read().unwrap().clone()
+}
+
+/// `resolve_config` resolves the configuration from the file and the system environment
+/// in the same way as the initialization, but the global configuration is not changed.
+/// If `config_path` is empty, the file resolved at initialization is used.
+pub fn resolve_config(config_path: &mut String) -> Result<ConfigEntity> {
+    if utils::is_blank(&config_path) {
+        *config_path = config_file_path();
+    }
+    resolve_config_path(config_path);
+    let entity = load_config_from_file(config_path)?.unwrap_or_default();
+    let entity = override_items_from_env(&entity)?;
+    entity.check()?;
+    Ok(entity)
+}
+
+/// `global_config_value` returns the serialized global configuration.
+pub fn global_config_value() -> serde_json::Value {
+    serde_json::to_value(&*GLOBAL_CONFIG.read().unwrap()).unwrap()
+}
+
+/// The format of the configuration file, resolved from the extension of the file.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum ConfigFormat {
+    Yaml,
+    Toml,
+}
+
+impl ConfigFormat {
+    // the files other than `*.toml` are regarded as YAML files
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Yaml,
+        }
+    }
+}
+
+// the items absent in the content take the default values
+#[cfg(feature = "config-file")]
+fn parse_config(content: &str, format: ConfigFormat) -> Result<ConfigEntity> {
+    Ok(match format {
+        ConfigFormat::Yaml => serde_yaml::from_str(content)?,
+        ConfigFormat::Toml => toml::from_str(content)?,
+    })
+}
+
+#[cfg(not(feature = "config-file"))]
+fn parse_config(_content: &str, format: ConfigFormat) -> Result<ConfigEntity> {
+    Err(Error::msg(format!(
+        "the {:?} configuration file requires the `config-file` feature",
+        format
+    )))
+}
+
+// the values of the environment variables are YAML, or JSON without the `config-file` feature
+#[cfg(feature = "config-file")]
+fn parse_env_value(raw: &str) -> Result<serde_json::Value> {
+    Ok(serde_yaml::from_str(raw)?)
+}
+
+#[cfg(not(feature = "config-file"))]
+fn parse_env_value(raw: &str) -> Result<serde_json::Value> {
+    Ok(serde_json::from_str(raw)?)
 }
 
 pub fn override_config_from_env_and_init_log() -> Result<()> {
@@ -69,7 +148,7 @@ pub fn override_config_from_env_and_init_log() -> Result<()> {
     override_items_from_system_env()?;
 
     let config_logger = logger();
-    logging::logger_init(config_logger);
+    logging::logger_init_with_format(config_logger, log_format());
     logging::info!("[Config] App name resolved, appName {}", app_name());
     logging::info!(
         "[Config] Print effective global config, globalConfig {}",
@@ -81,18 +160,76 @@ pub fn override_config_from_env_and_init_log() -> Result<()> {
 
 fn override_items_from_system_env() -> Result<()> {
     let mut cfg = GLOBAL_CONFIG.write().unwrap();
-    let app_name = env::var(APP_NAME_ENV_KEY).unwrap_or(DEFAULT_APP_NAME.into());
-    let app_type: ResourceType = env::var(APP_TYPE_ENV_KEY)
-        .unwrap_or(format!("{}", DEFAULT_APP_TYPE))
-        .parse::<u8>()
-        .unwrap_or(DEFAULT_APP_TYPE)
-        .into();
-
-    if !utils::is_blank(&app_name) {
-        cfg.config.app.app_name = app_name;
+    let entity = override_items_from_env(&cfg)?;
+    entity.check()?;
+    *cfg = entity;
+    Ok(())
+}
+
+fn override_items_from_env(entity: &ConfigEntity) -> Result<ConfigEntity> {
+    let mut entity = override_items_from(entity, |key| env::var(key).ok())?;
+    // the legacy keys take the precedence
+    if let Ok(app_name) = env::var(APP_NAME_ENV_KEY) {
+        if !utils::is_blank(&app_name) {
+            entity.config.app.app_name = app_name;
+        }
+    }
+    if let Ok(app_type) = env::var(APP_TYPE_ENV_KEY) {
+        let app_type: ResourceType = app_type.parse::<u8>().unwrap_or(DEFAULT_APP_TYPE).into();
+        entity.config.app.app_type = app_type;
+    }
+    Ok(entity)
+}
+
+// override_items_from overrides every item of the `config` section by the value of `lookup`,
+// the key is the path of the item in upper case, joined by `_` and prefixed with `SENTINEL`,
+// e.g., `log.metric.max_file_count` is overridden by `SENTINEL_LOG_METRIC_MAX_FILE_COUNT`.
+// The values of the non-string items are parsed as YAML (JSON without the `config-file` feature), thus a section can be overridden as a whole,
+// e.g., `SENTINEL_LOG_LOGGER="EnvLogger: debug"`.
+fn override_items_from(
+    entity: &ConfigEntity,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<ConfigEntity> {
+    let mut value = serde_json::to_value(entity)?;
+    if let Some(serde_json::Value::Object(items)) = value.get_mut("config") {
+        for (name, item) in items.iter_mut() {
+            override_item(
+                item,
+                format!("{}_{}", ENV_KEY_PREFIX, name.to_uppercase()),
+                &lookup,
+            )?;
+        }
+    }
+    serde_json::from_value(value).map_err(|err| {
+        Error::msg(format!(
+            "invalid config from environment variables: {}",
+            err
+        ))
+    })
+}
+
+fn override_item(
+    item: &mut serde_json::Value,
+    key: String,
+    lookup: &impl Fn(&str) -> Option<String>,
+) -> Result<()> {
+    if let Some(raw) = lookup(&key) {
+        *item = match item {
+            serde_json::Value::String(_) => serde_json::Value::String(raw),
+            _ => parse_env_value(&raw)
+                .map_err(|err| Error::msg(format!("invalid value of {}: {}", key, err)))?,
+        };
+        logging::info!(
+            "[Config] Config item overridden by environment variable {}",
+            key
+        );
+        return Ok(());
+    }
+    if let serde_json::Value::Object(items) = item {
+        for (name, child) in items.iter_mut() {
+            override_item(child, format!("{}_{}", key, name.to_uppercase()), lookup)?;
+        }
     }
-    cfg.config.app.app_type = app_type;
-    cfg.check()?;
     Ok(())
 }
 
@@ -114,6 +251,12 @@ pub fn logger() -> logging::Logger {
     cfg.logger().clone()
 }
 
+#[inline]
+pub fn log_format() -> logging::LogFormat {
+    let cfg = GLOBAL_CONFIG.read().unwrap();
+    cfg.log_format()
+}
+
 #[inline]
 pub fn metric_log_flush_interval_sec() -> u32 {
     let cfg = GLOBAL_CONFIG.read().unwrap();
@@ -132,6 +275,18 @@ pub fn metric_log_max_file_amount() -> u32 {
     cfg.metric_log_max_file_amount()
 }
 
+#[inline]
+pub fn block_log_sample_per_sec() -> u32 {
+    let cfg = GLOBAL_CONFIG.read().unwrap();
+    cfg.block_log_sample_per_sec()
+}
+
+#[inline]
+pub fn block_log_aggregate_interval_sec() -> u32 {
+    let cfg = GLOBAL_CONFIG.read().unwrap();
+    cfg.block_log_aggregate_interval_sec()
+}
+
 #[inline]
 pub fn system_stat_collect_interval_ms() -> u32 {
     let cfg = GLOBAL_CONFIG.read().unwrap();
@@ -162,6 +317,12 @@ pub fn use_cache_time() -> bool {
     cfg.use_cache_time()
 }
 
+#[inline]
+pub fn rule_persistence_path() -> String {
+    let cfg = GLOBAL_CONFIG.read().unwrap();
+    cfg.rule_persistence_path().clone()
+}
+
 #[inline]
 pub fn global_stat_interval_ms_total() -> u32 {
     let cfg = GLOBAL_CONFIG.read().unwrap();
@@ -191,3 +352,116 @@ pub fn metric_stat_sample_count() -> u32 {
     let cfg = GLOBAL_CONFIG.read().unwrap();
     cfg.metric_stat_sample_count()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn config_format() {
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("conf/sentinel.TOML")),
+            ConfigFormat::Toml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("sentinel.yaml")),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("sentinel")),
+            ConfigFormat::Yaml
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "config-file")]
+    fn parse_yaml_and_toml() {
+        let yaml = r#"
+version: v1
+config:
+  app:
+    app_name: yaml_app
+  log:
+    logger:
+      EnvLogger: info
+  stat:
+    system:
+      cpu_interval_ms: 500
+"#;
+        let toml = r#"
+version = "v1"
+[config.app]
+app_name = "toml_app"
+app_type = "Web"
+[config.log]
+logger = { EnvLogger = "info" }
+[config.stat.system]
+cpu_interval_ms = 500
+"#;
+        for (content, format, app_name) in [
+            (yaml, ConfigFormat::Yaml, "yaml_app"),
+            (toml, ConfigFormat::Toml, "toml_app"),
+        ]
+        .iter()
+        {
+            let entity = parse_config(content, *format).unwrap();
+            entity.check().unwrap();
+            assert_eq!(entity.app_name(), app_name);
+            assert_eq!(entity.cpu_stat_collec_interval_ms(), 500);
+            // the absent items take the default values
+            assert_eq!(entity.memory_stat_collec_interval_ms(), MEMORY_INTERVAL_MS);
+            assert_eq!(entity.metric_log_max_file_amount(), MAX_FILE_AMOUNT);
+        }
+        assert!(parse_config("version = ", ConfigFormat::Toml).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "config-file")]
+    fn override_items() {
+        let vars: std::collections::HashMap<&str, &str> = [
+            ("SENTINEL_APP_APP_NAME", "env_app"),
+            ("SENTINEL_APP_APP_TYPE", "Web"),
+            ("SENTINEL_LOG_LOGGER", "EnvLogger: debug"),
+            ("SENTINEL_LOG_METRIC_MAX_FILE_COUNT", "3"),
+            ("SENTINEL_STAT_SYSTEM_CPU_INTERVAL_MS", "500"),
+            ("SENTINEL_USE_CACHE_TIME", "false"),
+            ("SENTINEL_RULE_PERSISTENCE_PATH", "/tmp/rules.json"),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        let lookup = |key: &str| vars.get(key).map(|v| v.to_string());
+        let entity = override_items_from(&ConfigEntity::new(), lookup).unwrap();
+        assert_eq!(entity.app_name(), "env_app");
+        assert_eq!(*entity.app_type(), ResourceType::Web);
+        assert!(matches!(entity.logger(), logging::Logger::EnvLogger(level) if level == "debug"));
+        assert_eq!(entity.metric_log_max_file_amount(), 3);
+        assert_eq!(entity.cpu_stat_collec_interval_ms(), 500);
+        // the items without environment variables are kept
+        assert_eq!(entity.memory_stat_collec_interval_ms(), MEMORY_INTERVAL_MS);
+        assert!(!entity.use_cache_time());
+        assert_eq!(entity.rule_persistence_path(), "/tmp/rules.json");
+
+        let lookup = |key: &str| match key {
+            "SENTINEL_STAT_INTERVAL_MS" => Some("one second".into()),
+            _ => None,
+        };
+        let err = override_items_from(&ConfigEntity::new(), lookup).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("invalid config from environment variables"));
+    }
+
+    // the JSON values are accepted with or without the `config-file` feature
+    #[test]
+    fn override_items_by_json() {
+        let lookup = |key: &str| match key {
+            "SENTINEL_LOG_LOGGER" => Some(r#"{"EnvLogger": "debug"}"#.into()),
+            "SENTINEL_LOG_METRIC_MAX_FILE_COUNT" => Some("3".into()),
+            _ => None,
+        };
+        let entity = override_items_from(&ConfigEntity::new(), lookup).unwrap();
+        assert!(matches!(entity.logger(), logging::Logger::EnvLogger(level) if level == "debug"));
+        assert_eq!(entity.metric_log_max_file_amount(), 3);
+    }
+}
@@ -4,6 +4,8 @@ use crate::base::ResourceType;
 pub const SENTINEL_VERSION: &str = "v1";
 pub const DEFAULT_APP_NAME: &str = "unknown_service";
 pub const DEFAULT_APP_TYPE: u8 = ResourceType::Common as _;
+// the prefix of the environment variables overriding the config items, e.g., `SENTINEL_LOG_METRIC_MAX_FILE_COUNT`
+pub const ENV_KEY_PREFIX: &str = "SENTINEL";
 pub const APP_NAME_ENV_KEY: &str = "SENTINEL_APP_NAME";
 pub const APP_TYPE_ENV_KEY: &str = "SENTINEL_APP_TYPE";
 pub const CONF_FILE_PATH_ENV_KEY: &str = "SENTINEL_CONFIG_FILE_PATH";
@@ -14,6 +16,10 @@ pub const FLUSH_INTERVAL_SEC: u32 = 1;
 pub const SINGLE_FILE_MAX_SIZE: u64 = 1024 * 1024 * 50;
 pub const MAX_FILE_AMOUNT: u32 = 8;
 
+// default block log settings
+pub const BLOCK_LOG_SAMPLE_PER_SEC: u32 = 10;
+pub const BLOCK_LOG_AGGREGATE_INTERVAL_SEC: u32 = 1;
+
 // default statistic settings
 pub const SYSTEM_INTERVAL_MS: u32 = 1000;
 pub const LOAD_INTERVAL_MS: u32 = 1000;
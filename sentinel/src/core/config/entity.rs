@@ -1,7 +1,7 @@
 use super::{config, constant::*};
 use crate::{
     base::{check_validity_for_reuse_statistic, constant::*, ResourceType},
-    logging::{Logger, DEFAULT_LOG_LEVEL},
+    logging::{LogFormat, Logger, DEFAULT_LOG_LEVEL},
     Error, Result,
 };
 use serde::{Deserialize, Serialize};
@@ -10,6 +10,7 @@ use std::fmt;
 use std::path::PathBuf;
 
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub(super) struct AppConfig {
     // app_name represents the name of current running service.
     pub(super) app_name: String,
@@ -28,6 +29,7 @@ impl Default for AppConfig {
 
 // LogMetricConfig represents the configuration items of the metric log.
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub(super) struct LogMetricConfig {
     pub(super) single_file_max_size: u64,
     pub(super) max_file_count: u32,
@@ -44,26 +46,53 @@ impl Default for LogMetricConfig {
     }
 }
 
+// LogBlockConfig represents the configuration items of the block log.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub(super) struct LogBlockConfig {
+    // sample_per_sec is the max number of the block events logged per resource per second, 0 means none.
+    pub(super) sample_per_sec: u32,
+    // aggregate_interval_sec is the interval of the aggregate lines of the blocked entries, 0 means disabled.
+    pub(super) aggregate_interval_sec: u32,
+}
+
+impl Default for LogBlockConfig {
+    fn default() -> Self {
+        LogBlockConfig {
+            sample_per_sec: BLOCK_LOG_SAMPLE_PER_SEC,
+            aggregate_interval_sec: BLOCK_LOG_AGGREGATE_INTERVAL_SEC,
+        }
+    }
+}
+
 // LogConfig represent the configuration of logging in Sentinel.
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub(super) struct LogConfig {
     // logger indicates that using logger to replace default logging.
     pub(super) logger: Logger,
+    // format indicates the output format of the env logger, `Text` or `Json`.
+    pub(super) format: LogFormat,
     // metric represents the configuration items of the metric log.
     pub(super) metric: LogMetricConfig,
+    // block represents the configuration items of the block log.
+    pub(super) block: LogBlockConfig,
 }
 
 impl Default for LogConfig {
     fn default() -> Self {
         LogConfig {
             logger: Logger::EnvLogger(DEFAULT_LOG_LEVEL.into()),
+            format: LogFormat::default(),
             metric: LogMetricConfig::default(),
+            block: LogBlockConfig::default(),
         }
     }
 }
 
 // SystemStatConfig represents the configuration items of system statistic collector
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub(super) struct SystemStatConfig {
     // interval_ms represents the collecting interval of the system metrics collector.
     pub(super) system_interval_ms: u32,
@@ -88,6 +117,7 @@ impl Default for SystemStatConfig {
 
 // StatConfig represents configuration items related to statistics.
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub(super) struct StatConfig {
     // sample_count_total and interval_ms_total is the per resource's global default statistic sliding window config
     pub(super) sample_count_total: u32,
@@ -113,12 +143,15 @@ impl Default for StatConfig {
 
 // SentinelConfig represent the general configuration of Sentinel.
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub(super) struct SentinelConfig {
     pub(super) app: AppConfig,
     pub(super) log: LogConfig,
     pub(super) stat: StatConfig,
     // use_cache_time indicates whether to cache time(ms), it is false by default
     pub(super) use_cache_time: bool,
+    // rule_persistence_path is the file persisting the effective rules, the persistence is disabled if it is empty
+    pub(super) rule_persistence_path: String,
 }
 
 impl Default for SentinelConfig {
@@ -128,11 +161,13 @@ impl Default for SentinelConfig {
             app: AppConfig::default(),
             log: LogConfig::default(),
             stat: StatConfig::default(),
+            rule_persistence_path: String::new(),
         }
     }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct ConfigEntity {
     pub(super) version: String,
     pub(super) config: SentinelConfig,
@@ -159,12 +194,12 @@ impl ConfigEntity {
         if self.config.app.app_name.len() == 0 {
             return Err(Error::msg("empty app name"));
         }
-        if self.config.log.metric.max_file_count <= 0 {
+        if self.config.log.metric.max_file_count == 0 {
             return Err(Error::msg(
                 "illegal metric log configuration: max_file_count < 0",
             ));
         }
-        if self.config.log.metric.single_file_max_size <= 0 {
+        if self.config.log.metric.single_file_max_size == 0 {
             return Err(Error::msg(
                 "illegal metric log configuration: single_file_max_size < 0",
             ));
@@ -190,6 +225,10 @@ impl ConfigEntity {
         &self.config.log.logger
     }
 
+    pub fn log_format(&self) -> LogFormat {
+        self.config.log.format
+    }
+
     pub fn metric_log_flush_interval_sec(&self) -> u32 {
         self.config.log.metric.flush_interval_sec
     }
@@ -202,6 +241,14 @@ impl ConfigEntity {
         self.config.log.metric.max_file_count
     }
 
+    pub fn block_log_sample_per_sec(&self) -> u32 {
+        self.config.log.block.sample_per_sec
+    }
+
+    pub fn block_log_aggregate_interval_sec(&self) -> u32 {
+        self.config.log.block.aggregate_interval_sec
+    }
+
     pub fn system_stat_collect_interval_ms(&self) -> u32 {
         self.config.stat.system.system_interval_ms
     }
@@ -222,6 +269,10 @@ impl ConfigEntity {
         self.config.use_cache_time
     }
 
+    pub fn rule_persistence_path(&self) -> &String {
+        &self.config.rule_persistence_path
+    }
+
     pub fn global_stat_interval_ms_total(&self) -> u32 {
         self.config.stat.interval_ms_total
     }
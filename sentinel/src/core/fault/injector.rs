@@ -0,0 +1,62 @@
+use super::Rule;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// `FaultInjector` decides which requests of the resource are injected by the rule.
+#[derive(Debug)]
+pub struct FaultInjector {
+    rule: Arc<Rule>,
+    // the amount of the requests matched by the rule
+    matched: AtomicU64,
+}
+
+impl FaultInjector {
+    pub fn new(rule: Arc<Rule>) -> Self {
+        FaultInjector {
+            rule,
+            matched: AtomicU64::new(0),
+        }
+    }
+
+    pub fn rule(&self) -> &Arc<Rule> {
+        &self.rule
+    }
+
+    /// `should_inject` counts the request and returns whether the fault is injected,
+    /// the injected requests are spread evenly by the ratio of the rule.
+    pub fn should_inject(&self) -> bool {
+        let ratio = self.rule.ratio;
+        if ratio >= 1.0 {
+            return true;
+        }
+        let n = self.matched.fetch_add(1, Ordering::SeqCst) as f64;
+        ((n + 1.0) * ratio).floor() > (n * ratio).floor()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn spread_by_ratio() {
+        let injector = FaultInjector::new(Arc::new(Rule {
+            resource: "abc".into(),
+            ratio: 0.25,
+            ..Default::default()
+        }));
+        let injected: Vec<bool> = (0..8).map(|_| injector.should_inject()).collect();
+        assert_eq!(
+            injected,
+            vec![false, false, false, true, false, false, false, true]
+        );
+
+        let injector = FaultInjector::new(Arc::new(Rule {
+            resource: "abc".into(),
+            ratio: 0.3,
+            ..Default::default()
+        }));
+        let injected = (0..1000).filter(|_| injector.should_inject()).count();
+        assert_eq!(injected, 300);
+    }
+}
@@ -0,0 +1,25 @@
+//! mod fault provides the fault injection (chaos) rules, which inject the faults for the matched resources,
+//! so the resilience of the fallbacks and the circuit breakers can be exercised in staging
+//! without touching the application code.
+//!
+//! The faults are injected by the slot checked after all the other rules (order 6000),
+//! i.e., only the requests passing the other rules are injected:
+//!  1. `FaultType::Error` completes the request with a business error, as if the call failed,
+//!     which is counted by the circuit breakers when the entry exits,
+//!  2. `FaultType::Latency` delays the request before it runs, the caller thread sleeps,
+//!     unless the entry is built by `EntryBuilder::build_async_deferred()` (e.g., by `run()` and the adapters),
+//!     of which the delay is awaited by `AsyncEntry::wait()`,
+//!  3. `FaultType::Block` blocks the request with `BlockType::Fault`, the fallbacks take over.
+//!
+//! The faults are spread evenly by the `ratio` of the rule, e.g., 0.25 injects every fourth request.
+//! The rules are loaded by `load_rules()` or in the `RuleSet` like the other rule types.
+
+pub mod injector;
+pub mod rule;
+pub mod rule_manager;
+pub mod slot;
+
+pub use injector::*;
+pub use rule::*;
+pub use rule_manager::*;
+pub use slot::*;
@@ -0,0 +1,141 @@
+use crate::base::{Diagnostic, SentinelRule};
+use serde::{Deserialize, Serialize};
+use serde_json;
+use std::fmt;
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize, Hash, Eq)]
+pub enum FaultType {
+    /// `Error` completes the passed requests with a business error.
+    Error,
+    /// `Latency` delays the passed requests by `latency_ms`.
+    Latency,
+    /// `Block` blocks the requests.
+    Block,
+}
+
+impl Default for FaultType {
+    fn default() -> FaultType {
+        FaultType::Error
+    }
+}
+
+/// `Rule` describes the fault injected into the requests of the resource.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Rule {
+    /// `id` represents the unique ID of the rule (optional).
+    pub id: Option<String>,
+    /// `resource` represents the target resource definition
+    pub resource: String,
+    pub fault_type: FaultType,
+    /// `ratio` is the ratio of the requests injected, in `(0.0, 1.0]`, 1.0 for all the requests.
+    pub ratio: f64,
+    /// `latency_ms` only takes effect when `fault_type` is `Latency`.
+    pub latency_ms: u64,
+    /// `message` is the message of the injected errors and blocks,
+    /// a default one is used if it is empty.
+    pub message: String,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Rule {
+            id: None,
+            resource: String::default(),
+            fault_type: FaultType::default(),
+            ratio: 1.0,
+            latency_ms: 0,
+            message: String::default(),
+        }
+    }
+}
+
+impl Rule {
+    /// `message_or_default` returns the message of the injected errors and blocks.
+    pub fn message_or_default(&self) -> String {
+        if self.message.is_empty() {
+            format!("injected {:?} fault", self.fault_type).to_lowercase()
+        } else {
+            self.message.clone()
+        }
+    }
+}
+
+impl SentinelRule for Rule {
+    fn resource_name(&self) -> String {
+        self.resource.clone()
+    }
+
+    fn rule_id(&self) -> Option<String> {
+        self.id.clone()
+    }
+
+    fn diagnose(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        if self.resource.len() == 0 {
+            diagnostics.push(Diagnostic::error(
+                "resource",
+                "empty resource of fault rule",
+            ));
+        }
+        if !(self.ratio > 0.0 && self.ratio <= 1.0) {
+            diagnostics.push(Diagnostic::error("ratio", "ratio must be in (0.0, 1.0]"));
+        }
+        if self.fault_type == FaultType::Latency && self.latency_ms == 0 {
+            diagnostics.push(Diagnostic::error(
+                "latency_ms",
+                "latency_ms must be great than 0 when fault_type is FaultType::Latency",
+            ));
+        }
+        if self.fault_type != FaultType::Latency && self.latency_ms > 0 {
+            diagnostics.push(Diagnostic::warning(
+                "latency_ms",
+                "latency_ms only takes effect when fault_type is FaultType::Latency",
+            ));
+        }
+        diagnostics
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fmtted = serde_json::to_string_pretty(self).unwrap();
+        write!(f, "{}", fmtted)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "ratio must be in (0.0, 1.0]")]
+    fn invalid_ratio() {
+        let rule = Rule {
+            resource: "invalid_ratio".into(),
+            ratio: 0.0,
+            ..Default::default()
+        };
+        rule.is_valid().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "latency_ms must be great than 0")]
+    fn invalid_latency() {
+        let rule = Rule {
+            resource: "invalid_latency".into(),
+            fault_type: FaultType::Latency,
+            ..Default::default()
+        };
+        rule.is_valid().unwrap();
+    }
+
+    #[test]
+    fn deserialize_with_default() {
+        let rule: Rule =
+            serde_json::from_str(r#"{"resource": "abc", "fault_type": "Block"}"#).unwrap();
+        assert_eq!(rule.ratio, 1.0);
+        assert!(rule.is_valid().is_ok());
+        assert_eq!(rule.message_or_default(), "injected block fault");
+    }
+}
@@ -0,0 +1,245 @@
+use super::*;
+use crate::base::{check_direct_update, RuleChangeListener, RuleChangeListeners};
+use crate::{base::SentinelRule, logging, utils, utils::ShardedMap};
+use crate::{Error, Result};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+pub type RuleMap = HashMap<String, Vec<Arc<Rule>>>;
+
+lazy_static! {
+    static ref INJECTOR_MAP: ShardedMap<String, Vec<Arc<FaultInjector>>> = ShardedMap::new();
+    static ref CURRENT_RULES: Mutex<RuleMap> = Mutex::new(RuleMap::new());
+}
+
+lazy_static! {
+    static ref RULE_CHANGE_LISTENERS: RuleChangeListeners<Rule> = RuleChangeListeners::new();
+}
+
+/// `register_rule_change_listeners` registers the listeners notified after the rules are changed.
+pub fn register_rule_change_listeners(listeners: Vec<Arc<dyn RuleChangeListener<Rule>>>) {
+    RULE_CHANGE_LISTENERS.register(listeners);
+}
+
+pub fn clear_rule_change_listeners() {
+    RULE_CHANGE_LISTENERS.clear();
+}
+
+/// `get_rules` returns all the effective fault rules
+// This func acquires the read locks on the shards of global `INJECTOR_MAP` one by one
+pub fn get_rules() -> Vec<Arc<Rule>> {
+    let mut rules = Vec::new();
+    INJECTOR_MAP.for_each(|_, injectors| {
+        rules.extend(injectors.iter().map(|injector| Arc::clone(injector.rule())))
+    });
+    rules
+}
+
+/// `get_rules_of_resource` returns the effective fault rules of the resource
+pub fn get_rules_of_resource(res: &String) -> Vec<Arc<Rule>> {
+    get_injectors_of_resource(res)
+        .iter()
+        .map(|injector| Arc::clone(injector.rule()))
+        .collect()
+}
+
+// This func only acquires the read lock on a shard of global `INJECTOR_MAP`,
+// thus it never waits for the loading of the rules
+pub(crate) fn get_injectors_of_resource(res: &String) -> Vec<Arc<FaultInjector>> {
+    INJECTOR_MAP.get(res).unwrap_or_default()
+}
+
+fn build_injectors(res: &String, rules: &[Arc<Rule>]) -> Vec<Arc<FaultInjector>> {
+    let mut injectors = Vec::with_capacity(rules.len());
+    for rule in rules {
+        match rule.is_valid() {
+            Ok(_) => injectors.push(Arc::new(FaultInjector::new(Arc::clone(rule)))),
+            Err(err) => logging::warn!(
+                "[Fault build_injectors] Ignoring invalid fault rule {:?} of resource {}, reason: {:?}",
+                rule,
+                res,
+                err
+            ),
+        }
+    }
+    injectors
+}
+
+/// `load_rules` loads given fault rules to the rule manager, while all previous rules will be replaced.
+// This func acquires the locks on global `CURRENT_RULES` and `INJECTOR_MAP`,
+// please release the locks before calling this func
+pub fn load_rules(rules: Vec<Arc<Rule>>) {
+    if check_direct_update("`fault::load_rules()`").is_err() {
+        return;
+    }
+    RULE_CHANGE_LISTENERS.watch(get_rules, || do_load_rules(rules))
+}
+
+fn do_load_rules(rules: Vec<Arc<Rule>>) {
+    let mut res_rules_map = RuleMap::new();
+    for rule in rules {
+        res_rules_map
+            .entry(rule.resource.clone())
+            .or_insert(Vec::new())
+            .push(rule);
+    }
+    let mut current_rules = CURRENT_RULES.lock().unwrap();
+    if &*current_rules == &res_rules_map {
+        logging::info!(
+            "[Fault] Load rules is the same with current rules, so ignore load operation."
+        );
+        return;
+    }
+
+    // ignore invalid rules
+    let mut injector_map = HashMap::with_capacity(res_rules_map.len());
+    for (res, rules) in &res_rules_map {
+        let injectors = build_injectors(res, rules);
+        if injectors.len() > 0 {
+            injector_map.insert(res.clone(), injectors);
+        }
+    }
+
+    let start = utils::curr_time_nanos();
+    INJECTOR_MAP.replace(injector_map);
+    *current_rules = res_rules_map;
+
+    logging::debug!(
+        "[Fault load_rules] Time statistic(ns) for updating fault rule, timeCost {:?}",
+        utils::curr_time_nanos() - start
+    );
+    logging::info!(
+        "[FaultRuleManager] Fault rules loaded, rules {:?}",
+        *INJECTOR_MAP
+    );
+}
+
+/// `load_rules_of_resource` loads the given resource's fault rules to the rule manager, while all previous resource's rules will be replaced.
+// This func acquires the locks on global `CURRENT_RULES` and `INJECTOR_MAP`,
+// please release the locks before calling this func
+pub fn load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<bool> {
+    check_direct_update("`fault::load_rules_of_resource()`")?;
+    RULE_CHANGE_LISTENERS.watch(
+        || get_rules_of_resource(res),
+        || do_load_rules_of_resource(res, rules),
+    )
+}
+
+fn do_load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<bool> {
+    if res.len() == 0 {
+        return Err(Error::msg("empty resource"));
+    }
+    let mut current_rules = CURRENT_RULES.lock().unwrap();
+    if rules.len() == 0 {
+        current_rules.remove(res);
+        INJECTOR_MAP.remove(res);
+        logging::info!("[Fault] clear resource level rules, resource {}", res);
+        return Ok(true);
+    }
+    if current_rules.get(res) == Some(&rules) {
+        logging::info!(
+            "[Fault] Load resource level rules is the same with current resource level rules, so ignore load operation."
+        );
+        return Ok(false);
+    }
+
+    let injectors = build_injectors(res, &rules);
+    if injectors.len() == 0 {
+        INJECTOR_MAP.remove(res);
+    } else {
+        INJECTOR_MAP.insert(res.clone(), injectors);
+    }
+    logging::info!(
+        "[FaultRuleManager] Fault rules of resource {} loaded, rules {:?}",
+        res,
+        rules
+    );
+    current_rules.insert(res.clone(), rules);
+    Ok(true)
+}
+
+/// `clear_rules` clears all the rules in fault module
+// This func acquires the locks on global `CURRENT_RULES` and `INJECTOR_MAP`,
+// please release the locks before calling this func
+pub fn clear_rules() {
+    if check_direct_update("`fault::clear_rules()`").is_err() {
+        return;
+    }
+    RULE_CHANGE_LISTENERS.watch(get_rules, do_clear_rules)
+}
+
+fn do_clear_rules() {
+    let mut current_rules = CURRENT_RULES.lock().unwrap();
+    current_rules.clear();
+    INJECTOR_MAP.clear();
+}
+
+/// `clear_rules_of_resource` clears resource level rules in fault module.
+// This func acquires the locks on global `CURRENT_RULES` and `INJECTOR_MAP`,
+// please release the locks before calling this func
+pub fn clear_rules_of_resource(res: &String) {
+    if check_direct_update("`fault::clear_rules_of_resource()`").is_err() {
+        return;
+    }
+    RULE_CHANGE_LISTENERS.watch(
+        || get_rules_of_resource(res),
+        || do_clear_rules_of_resource(res),
+    )
+}
+
+fn do_clear_rules_of_resource(res: &String) {
+    let mut current_rules = CURRENT_RULES.lock().unwrap();
+    current_rules.remove(res);
+    INJECTOR_MAP.remove(res);
+}
+
+#[cfg(test)]
+mod test {
+    //! Some tests cannot run in parallel, since we cannot promise that
+    //! the global data structs are not modified before assertion.
+    use super::*;
+
+    #[test]
+    fn rules_of_resource() {
+        let res = "fault_rules_of_resource".to_string();
+        let valid = Arc::new(Rule {
+            resource: res.clone(),
+            fault_type: FaultType::Block,
+            ..Default::default()
+        });
+        let invalid = Arc::new(Rule {
+            resource: res.clone(),
+            ratio: 2.0,
+            ..Default::default()
+        });
+        let rules = vec![Arc::clone(&valid), invalid];
+        assert!(load_rules_of_resource(&res, rules.clone()).unwrap());
+        assert_eq!(get_rules_of_resource(&res), vec![Arc::clone(&valid)]);
+        // the same rules are ignored
+        assert!(!load_rules_of_resource(&res, rules).unwrap());
+        clear_rules_of_resource(&res);
+        assert!(get_rules_of_resource(&res).is_empty());
+        assert!(load_rules_of_resource(&String::new(), vec![valid]).is_err());
+    }
+
+    #[test]
+    #[ignore]
+    fn load_and_clear() {
+        let r1 = Arc::new(Rule {
+            resource: "abc1".into(),
+            ..Default::default()
+        });
+        let r2 = Arc::new(Rule {
+            resource: "abc2".into(),
+            fault_type: FaultType::Latency,
+            latency_ms: 10,
+            ..Default::default()
+        });
+        load_rules(vec![Arc::clone(&r1), Arc::clone(&r2)]);
+        assert_eq!(get_rules().len(), 2);
+        assert_eq!(get_rules_of_resource(&"abc2".into()), vec![r2]);
+        clear_rules();
+        assert!(get_rules().is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+use super::*;
+use crate::{
+    base::{BaseSlot, BlockType, ContextPtr, RuleCheckSlot, TokenResult},
+    utils, Error,
+};
+use lazy_static::lazy_static;
+use std::sync::Arc;
+
+// checked after all the other rules, only the passed requests are injected
+const RULE_CHECK_SLOT_ORDER: u32 = 6000;
+
+/// A RuleSlot injecting the faults
+pub struct Slot {}
+
+lazy_static! {
+    pub static ref DEFAULT_SLOT: Arc<Slot> = Arc::new(Slot {});
+}
+
+pub fn default_slot() -> Arc<Slot> {
+    DEFAULT_SLOT.clone()
+}
+
+impl BaseSlot for Slot {
+    fn order(&self) -> u32 {
+        RULE_CHECK_SLOT_ORDER
+    }
+}
+
+impl RuleCheckSlot for Slot {
+    fn check(&self, ctx: &ContextPtr) -> TokenResult {
+        let res = ctx.read().unwrap().resource().name().clone();
+        for injector in get_injectors_of_resource(&res) {
+            if !injector.should_inject() {
+                continue;
+            }
+            let rule = injector.rule();
+            match rule.fault_type {
+                // the error is recorded when the entry exits, thus counted by the circuit breakers,
+                // unless the application sets its own error
+                FaultType::Error => ctx
+                    .write()
+                    .unwrap()
+                    .inject_err(Error::msg(rule.message_or_default())),
+                // the latency is injected after the waits of the other rules
+                FaultType::Latency => {
+                    let mut ctx = ctx.write().unwrap();
+                    let nanos_to_wait =
+                        ctx.nanos_to_wait() + utils::milli2nano(rule.latency_ms) as u64;
+                    ctx.defer_wait(nanos_to_wait);
+                }
+                FaultType::Block => {
+                    ctx.write()
+                        .unwrap()
+                        .set_result(TokenResult::new_blocked_with_cause(
+                            BlockType::Fault,
+                            rule.message_or_default(),
+                            Arc::clone(rule) as Arc<_>,
+                            Arc::new(rule.ratio),
+                        ));
+                    break;
+                }
+            }
+        }
+        ctx.read().unwrap().result().clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::{EntryContext, ResourceType, ResourceWrapper, TrafficType};
+    use std::sync::RwLock;
+
+    fn context_of(res: &str) -> ContextPtr {
+        let mut ctx = EntryContext::new();
+        ctx.set_resource(ResourceWrapper::new(
+            res.into(),
+            ResourceType::Common,
+            TrafficType::Inbound,
+        ));
+        Arc::new(RwLock::new(ctx))
+    }
+
+    #[test]
+    fn inject() {
+        let slot = Slot {};
+        let res = "fault_slot_inject".to_string();
+        load_rules_of_resource(
+            &res,
+            vec![
+                Arc::new(Rule {
+                    resource: res.clone(),
+                    message: "broken".into(),
+                    ..Default::default()
+                }),
+                Arc::new(Rule {
+                    resource: res.clone(),
+                    fault_type: FaultType::Block,
+                    ratio: 0.5,
+                    ..Default::default()
+                }),
+            ],
+        )
+        .unwrap();
+
+        let ctx = context_of(&res);
+        assert!(slot.check(&ctx).is_pass());
+        assert!(ctx.read().unwrap().get_err().is_none());
+        ctx.write().unwrap().apply_injected_err();
+        assert_eq!(
+            ctx.read().unwrap().get_err().as_ref().unwrap().to_string(),
+            "broken"
+        );
+
+        let ctx = context_of(&res);
+        let result = slot.check(&ctx);
+        assert!(result.is_blocked());
+        let block_err = result.block_err().unwrap();
+        assert_eq!(block_err.block_type(), BlockType::Fault);
+        assert_eq!(block_err.block_msg(), "injected block fault");
+        clear_rules_of_resource(&res);
+
+        let ctx = context_of(&res);
+        assert!(slot.check(&ctx).is_pass());
+        assert!(ctx.read().unwrap().get_err().is_none());
+    }
+}
@@ -1,7 +1,11 @@
-use crate::{base::SentinelRule, logging, system_metric};
+use crate::{
+    base::{Diagnostic, SentinelRule},
+    logging, system_metric,
+};
 use crate::{Error, Result};
 use serde::{Deserialize, Serialize};
 use serde_json;
+use std::collections::BTreeMap;
 use std::fmt;
 
 pub type Id = String;
@@ -13,6 +17,9 @@ pub enum RelationStrategy {
     CurrentResource,
     /// AssociatedResource means flow control by the associated resource rather than current resource.
     AssociatedResource,
+    /// Chain means flow control by current resource only when it is invoked through the entrance `ref_resource`,
+    /// the statistics of the resource under the entrance are used.
+    Chain,
 }
 
 impl Default for RelationStrategy {
@@ -70,6 +77,13 @@ pub struct Rule {
     /// When `max_queueing_time_ms` is 0, it means Throttling only controls interval of requests,
     /// and requests exceeding the threshold will be rejected directly.
     pub max_queueing_time_ms: u32,
+    /// `priority_weights` only takes effect when `control_strategy` is Throttling.
+    /// It maps the priority classes of the requests (see `EntryBuilder::with_priority_class()`) to their weights,
+    /// e.g., {"interactive": 70, "batch": 30}, then the backlogged classes share the threshold by the weights
+    /// rather than queueing in FIFO order. The requests of unlisted classes (or without class) belong to the class "",
+    /// whose weight is 1 unless listed. By default (empty), all the requests are queued in FIFO order.
+    #[serde(default)]
+    pub priority_weights: BTreeMap<String, u32>,
     /// stat_interval_ms indicates the statistic interval and it's the optional setting for flow Rule.
     /// If user doesn't set stat_interval_ms, that means using default metric statistic of resource.
     /// If the stat_interval_ms user specifies can not reuse the global statistic of resource,
@@ -100,6 +114,7 @@ impl Default for Rule {
             warm_up_period_sec: 0,
             warm_up_cold_factor: 0,
             max_queueing_time_ms: 0,
+            priority_weights: BTreeMap::new(),
             stat_interval_ms: 0,
             low_mem_usage_threshold: 0,
             high_mem_usage_threshold: 0,
@@ -130,30 +145,61 @@ impl SentinelRule for Rule {
         self.resource.clone()
     }
 
-    fn is_valid(&self) -> Result<()> {
+    fn rule_id(&self) -> Option<String> {
+        Some(self.id.clone())
+    }
+
+    fn diagnose(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
         if self.resource.len() == 0 {
-            return Err(Error::msg("empty resource name"));
+            diagnostics.push(Diagnostic::error("resource", "empty resource name"));
         }
         if self.threshold < 0.0 {
-            return Err(Error::msg("negative threshold"));
+            diagnostics.push(Diagnostic::error("threshold", "negative threshold"));
         }
         if self.relation_strategy == RelationStrategy::AssociatedResource
             && self.ref_resource.len() == 0
         {
-            return Err(Error::msg("ref_resource must be non empty when relation_strategy is RelationStrategy::AssociatedResource"));
+            diagnostics.push(Diagnostic::error("ref_resource", "ref_resource must be non empty when relation_strategy is RelationStrategy::AssociatedResource"));
+        }
+        if self.relation_strategy == RelationStrategy::Chain && self.ref_resource.len() == 0 {
+            diagnostics.push(Diagnostic::error(
+                "ref_resource",
+                "ref_resource must be non empty when relation_strategy is RelationStrategy::Chain",
+            ));
         }
         if self.calculate_strategy == CalculateStrategy::WarmUp {
             if self.warm_up_period_sec == 0 {
-                return Err(Error::msg("warm_up_period_sec must be great than 0"));
+                diagnostics.push(Diagnostic::error(
+                    "warm_up_period_sec",
+                    "warm_up_period_sec must be great than 0",
+                ));
             }
             if self.warm_up_cold_factor == 1 {
-                return Err(Error::msg("warm_up_cold_factor must be great than 1"));
+                diagnostics.push(Diagnostic::error(
+                    "warm_up_cold_factor",
+                    "warm_up_cold_factor must be great than 1",
+                ));
             }
         }
+        if self.priority_weights.values().any(|weight| *weight == 0) {
+            diagnostics.push(Diagnostic::error(
+                "priority_weights",
+                "the weights of the priority classes must be great than 0",
+            ));
+        }
+        if !self.priority_weights.is_empty() && self.control_strategy != ControlStrategy::Throttling
+        {
+            diagnostics.push(Diagnostic::warning(
+                "priority_weights",
+                "priority_weights only takes effect when control_strategy is ControlStrategy::Throttling",
+            ));
+        }
         if self.stat_interval_ms > 10 * 60 * 1000 {
-            logging::info!(
-                "stat_interval_ms is great than 10 minutes, less than 10 minutes is recommended."
-            )
+            diagnostics.push(Diagnostic::warning(
+                "stat_interval_ms",
+                "stat_interval_ms is great than 10 minutes, less than 10 minutes is recommended.",
+            ));
         }
         if self.calculate_strategy == CalculateStrategy::MemoryAdaptive {
             if self.mem_low_water_mark == 0
@@ -161,26 +207,32 @@ impl SentinelRule for Rule {
                 || self.high_mem_usage_threshold == 0
                 || self.low_mem_usage_threshold == 0
             {
-                return Err(Error::msg(
+                diagnostics.push(Diagnostic::error(
+                    "mem_low_water_mark",
                     "memory water mark or usage threshold setting to 0",
                 ));
-            }
-            if self.high_mem_usage_threshold >= self.low_mem_usage_threshold {
-                return Err(Error::msg(
-                    "self.high_mem_usage_threshold >= self.low_mem_usage_threshold",
-                ));
-            }
-            if self.mem_high_water_mark > system_metric::get_total_memory_size() {
-                return Err(Error::msg("self.mem_high_water_mark should not be greater than current system's total memory size"));
-            }
-            if self.mem_low_water_mark >= self.mem_high_water_mark {
-                // can not be equal to defeat from zero overflow
-                return Err(Error::msg(
-                    "self.mem_low_water_mark >= self.mem_high_water_mark",
-                ));
+            } else {
+                if self.high_mem_usage_threshold >= self.low_mem_usage_threshold {
+                    diagnostics.push(Diagnostic::error(
+                        "high_mem_usage_threshold",
+                        "self.high_mem_usage_threshold >= self.low_mem_usage_threshold",
+                    ));
+                }
+                // the total memory is unknown (0) if the system metrics are not collected
+                let total_memory_size = system_metric::get_total_memory_size();
+                if total_memory_size > 0 && self.mem_high_water_mark > total_memory_size {
+                    diagnostics.push(Diagnostic::error("mem_high_water_mark", "self.mem_high_water_mark should not be greater than current system's total memory size"));
+                }
+                if self.mem_low_water_mark >= self.mem_high_water_mark {
+                    // can not be equal to defeat from zero overflow
+                    diagnostics.push(Diagnostic::error(
+                        "mem_low_water_mark",
+                        "self.mem_low_water_mark >= self.mem_high_water_mark",
+                    ));
+                }
             }
         }
-        Ok(())
+        diagnostics
     }
 }
 
@@ -196,6 +248,7 @@ impl PartialEq for Rule {
             && self.warm_up_period_sec == other.warm_up_period_sec
             && self.warm_up_cold_factor == other.warm_up_cold_factor
             && self.max_queueing_time_ms == other.max_queueing_time_ms
+            && self.priority_weights == other.priority_weights
             && self.stat_interval_ms == other.stat_interval_ms
             && self.low_mem_usage_threshold == other.low_mem_usage_threshold
             && self.high_mem_usage_threshold == other.high_mem_usage_threshold
@@ -399,6 +452,59 @@ mod test {
         assert!(r61.is_stat_reusable(&r62));
     }
 
+    #[test]
+    fn diagnose() {
+        let rule = Rule {
+            threshold: -1.0,
+            resource: "".into(),
+            calculate_strategy: CalculateStrategy::WarmUp,
+            warm_up_period_sec: 10,
+            warm_up_cold_factor: 3,
+            stat_interval_ms: 6000000,
+            ..Default::default()
+        };
+        let diagnostics = rule.diagnose();
+        let fields: Vec<&str> = diagnostics.iter().map(|d| d.field.as_str()).collect();
+        assert_eq!(fields, vec!["resource", "threshold", "stat_interval_ms"]);
+        assert!(diagnostics[0].is_error());
+        assert!(diagnostics[1].is_error());
+        assert!(!diagnostics[2].is_error());
+        assert_eq!(
+            rule.is_valid().unwrap_err().to_string(),
+            "empty resource name"
+        );
+    }
+
+    #[test]
+    fn diagnose_priority_weights() {
+        let mut rule = Rule {
+            resource: "test".into(),
+            threshold: 10.0,
+            control_strategy: ControlStrategy::Throttling,
+            priority_weights: vec![("interactive".into(), 70), ("batch".into(), 30)]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        assert!(rule.diagnose().is_empty());
+
+        rule.control_strategy = ControlStrategy::Reject;
+        let diagnostics = rule.diagnose();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(!diagnostics[0].is_error());
+
+        rule.control_strategy = ControlStrategy::Throttling;
+        rule.priority_weights.insert("batch".into(), 0);
+        assert!(rule.is_valid().is_err());
+
+        // the rules without the weights are still deserialized
+        let rule: Rule = serde_json::from_str(
+            r#"{"id":"1","resource":"test","ref_resource":"","calculate_strategy":"Direct","control_strategy":"Throttling","relation_strategy":"CurrentResource","threshold":10.0,"warm_up_period_sec":0,"warm_up_cold_factor":0,"max_queueing_time_ms":0,"stat_interval_ms":0,"low_mem_usage_threshold":0,"high_mem_usage_threshold":0,"mem_low_water_mark":0,"mem_high_water_mark":0}"#,
+        )
+        .unwrap();
+        assert!(rule.priority_weights.is_empty());
+    }
+
     #[test]
     fn is_valid_flow_rule1() {
         let bad_rule1 = Rule {
@@ -1,4 +1,5 @@
 use super::*;
+use crate::base::{check_direct_update, RuleChangeListener, RuleChangeListeners};
 use crate::{
     core::{
         base,
@@ -7,7 +8,9 @@ use crate::{
         stat::{ResourceNode, SlidingWindowMetric},
         system_metric,
     },
-    logging, utils, Error, Result,
+    logging, utils,
+    utils::ShardedMap,
+    Error, Result,
 };
 use lazy_static::lazy_static;
 use std::collections::{HashMap, HashSet};
@@ -87,7 +90,7 @@ lazy_static! {
 
         RwLock::new(gen_fun_map)
     };
-    static ref CONTROLLER_MAP: Mutex<ControllerMap> = Mutex::new(HashMap::new());
+    static ref CONTROLLER_MAP: ShardedMap<String, Vec<Arc<Controller>>> = ShardedMap::new();
     static ref NOP_STAT: Arc<StandaloneStat> = Arc::new(StandaloneStat::new(
         false,
         nop_read_stat(),
@@ -96,6 +99,19 @@ lazy_static! {
     static ref RULE_MAP: Mutex<RuleMap> = Mutex::new(HashMap::new());
 }
 
+lazy_static! {
+    static ref RULE_CHANGE_LISTENERS: RuleChangeListeners<Rule> = RuleChangeListeners::new();
+}
+
+/// `register_rule_change_listeners` registers the listeners notified after the rules are changed.
+pub fn register_rule_change_listeners(listeners: Vec<Arc<dyn RuleChangeListener<Rule>>>) {
+    RULE_CHANGE_LISTENERS.register(listeners);
+}
+
+pub fn clear_rule_change_listeners() {
+    RULE_CHANGE_LISTENERS.clear();
+}
+
 fn log_rule_update(map: &RuleMap) {
     if map.len() == 0 {
         logging::info!("[FlowRuleManager] Flow rules were cleared")
@@ -112,6 +128,13 @@ fn log_rule_update(map: &RuleMap) {
 // This func acquires locks on global `RULE_MAP` and `CONTROLLER_MAP`,
 // please release your locks on them before calling this func
 pub fn load_rules(rules: Vec<Arc<Rule>>) -> bool {
+    if check_direct_update("`flow::load_rules()`").is_err() {
+        return false;
+    }
+    RULE_CHANGE_LISTENERS.watch(get_rules, || do_load_rules(rules))
+}
+
+fn do_load_rules(rules: Vec<Arc<Rule>>) -> bool {
     let mut rule_map: RuleMap = HashMap::new();
     // todo: validate rules here,
     // neglect invalid rules,
@@ -150,25 +173,21 @@ pub fn load_rules(rules: Vec<Arc<Rule>>) -> bool {
     }
 
     let start = utils::curr_time_nanos();
-    let mut controller_map = CONTROLLER_MAP.lock().unwrap();
     let mut valid_controller_map = HashMap::with_capacity(valid_rules_map.len());
 
-    // build controller_map according to valid rules
+    // build controller_map according to valid rules,
+    // the controllers are built without locking `CONTROLLER_MAP`, thus the traffic is not stalled
     for (res, rules) in valid_rules_map.iter() {
-        let mut placeholder = Vec::new();
-        let new_tcs_of_res = build_resource_traffic_shaping_controller(
-            res,
-            rules.clone(),
-            controller_map.get_mut(res).unwrap_or(&mut placeholder),
-        );
+        let mut old_res_tcs = CONTROLLER_MAP.get(res).unwrap_or_default();
+        let new_tcs_of_res =
+            build_resource_traffic_shaping_controller(res, rules.clone(), &mut old_res_tcs);
         if new_tcs_of_res.len() > 0 {
             valid_controller_map.insert(res.clone(), new_tcs_of_res);
         }
     }
-    *controller_map = valid_controller_map;
+    CONTROLLER_MAP.replace(valid_controller_map);
     *global_rule_map = rule_map;
     drop(global_rule_map);
-    drop(controller_map);
     logging::debug!(
         "[Flow load_rules] Time statistic(ns) for updating flow rule, time cost {}",
         utils::curr_time_nanos() - start
@@ -182,15 +201,22 @@ pub fn load_rules(rules: Vec<Arc<Rule>>) -> bool {
 // This func acquires locks on global `RULE_MAP` and `CONTROLLER_MAP`,
 // please release your locks on them before calling this func
 pub fn load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<bool> {
+    check_direct_update("`flow::load_rules_of_resource()`")?;
+    RULE_CHANGE_LISTENERS.watch(
+        || get_rules_of_resource(res),
+        || do_load_rules_of_resource(res, rules),
+    )
+}
+
+fn do_load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<bool> {
     if res.len() == 0 {
         return Err(Error::msg("empty resource"));
     }
     let mut global_rule_map = RULE_MAP.lock().unwrap();
-    let mut global_controller_map = CONTROLLER_MAP.lock().unwrap();
     // clear resource rules
     if rules.len() == 0 {
         global_rule_map.remove(res);
-        global_controller_map.remove(res);
+        CONTROLLER_MAP.remove(res);
         logging::info!("[Flow] clear resource level rules, resource {}", res);
         return Ok(true);
     }
@@ -213,19 +239,16 @@ pub fn load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<boo
     }
     // the `res` related rules changes, have to update
     let start = utils::curr_time_nanos();
-    let mut placeholder = Vec::new();
-    let mut old_res_tcs = global_controller_map
-        .get_mut(res)
-        .unwrap_or(&mut placeholder);
+    let mut old_res_tcs = CONTROLLER_MAP.get(res).unwrap_or_default();
 
     let valid_res_rules_string = format!("{:?}", &valid_res_rules);
     let new_res_tcs =
         build_resource_traffic_shaping_controller(res, valid_res_rules, &mut old_res_tcs);
 
     if new_res_tcs.len() == 0 {
-        global_controller_map.remove(res);
+        CONTROLLER_MAP.remove(res);
     } else {
-        global_controller_map.insert(res.clone(), new_res_tcs);
+        CONTROLLER_MAP.insert(res.clone(), new_res_tcs);
     }
 
     global_rule_map.insert(res.clone(), rules);
@@ -247,12 +270,11 @@ pub fn load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<boo
 // please release your lock on it before calling this func
 pub fn get_rules() -> Vec<Arc<Rule>> {
     let mut rules = Vec::new();
-    let controller_map = CONTROLLER_MAP.lock().unwrap();
-    for (_, controllers) in controller_map.iter() {
+    CONTROLLER_MAP.for_each(|_, controllers| {
         for c in controllers {
             rules.push(Arc::clone(c.rule()));
         }
-    }
+    });
     rules
 }
 
@@ -260,41 +282,52 @@ pub fn get_rules() -> Vec<Arc<Rule>> {
 // This func acquires the lock on global `CONTROLLER_MAP`,
 // please release your locks on them before calling this func
 pub fn get_rules_of_resource(res: &String) -> Vec<Arc<Rule>> {
-    let controller_map = CONTROLLER_MAP.lock().unwrap();
-    let placeholder = Vec::new();
-    let controllers = controller_map.get(res).unwrap_or(&placeholder);
-    let mut rules = Vec::with_capacity(controllers.len());
-    for c in controllers {
-        rules.push(Arc::clone(c.rule()));
-    }
-    rules
+    CONTROLLER_MAP.with(res, |controllers| {
+        controllers
+            .map(|controllers| controllers.iter().map(|c| Arc::clone(c.rule())).collect())
+            .unwrap_or_default()
+    })
 }
 
 /// clear_rules clears all the rules in flow module.
 // This func acquires locks on global `RULE_MAP` and `CONTROLLER_MAP`,
 // please release your locks on them before calling this func
 pub fn clear_rules() {
-    RULE_MAP.lock().unwrap().clear();
-    CONTROLLER_MAP.lock().unwrap().clear();
+    if check_direct_update("`flow::clear_rules()`").is_err() {
+        return;
+    }
+    RULE_CHANGE_LISTENERS.watch(get_rules, do_clear_rules)
+}
+
+fn do_clear_rules() {
+    let mut global_rule_map = RULE_MAP.lock().unwrap();
+    global_rule_map.clear();
+    CONTROLLER_MAP.clear();
 }
 
 /// `clear_rules_of_resource` clears resource level rules in flow module.
 // This func acquires locks on global `RULE_MAP` and `CONTROLLER_MAP`,
 // please release your locks on them before calling this func
 pub fn clear_rules_of_resource(res: &String) {
-    RULE_MAP.lock().unwrap().remove(res);
-    CONTROLLER_MAP.lock().unwrap().remove(res);
+    if check_direct_update("`flow::clear_rules_of_resource()`").is_err() {
+        return;
+    }
+    RULE_CHANGE_LISTENERS.watch(
+        || get_rules_of_resource(res),
+        || do_clear_rules_of_resource(res),
+    )
 }
 
-// This func acquires the lock on global `CONTROLLER_MAP`,
-// please release your lock on it before calling this func
+fn do_clear_rules_of_resource(res: &String) {
+    let mut global_rule_map = RULE_MAP.lock().unwrap();
+    global_rule_map.remove(res);
+    CONTROLLER_MAP.remove(res);
+}
+
+// This func only acquires the read lock on a shard of global `CONTROLLER_MAP`,
+// thus it never waits for the loading of the rules
 pub fn get_traffic_controller_list_for(name: &String) -> Vec<Arc<Controller>> {
-    let controller_map = CONTROLLER_MAP.lock().unwrap();
-    let controllers = controller_map.get(name);
-    match controllers {
-        Some(controllers) => controllers.clone(),
-        None => Vec::new(),
-    }
+    CONTROLLER_MAP.get(name).unwrap_or_default()
 }
 
 /// `generate_stat_for` generates a `StandaloneStat` according to the rule,
@@ -312,6 +345,13 @@ fn generate_stat_for(rule: &Arc<Rule>) -> Result<Arc<StandaloneStat>> {
         if rule.relation_strategy == RelationStrategy::AssociatedResource {
             // use associated statistic
             stat::get_or_create_resource_node(&rule.ref_resource, &ResourceType::Common)
+        } else if rule.relation_strategy == RelationStrategy::Chain {
+            // use statistic of the resource under the entrance
+            stat::get_or_create_chain_node(
+                &rule.ref_resource,
+                &rule.resource,
+                &ResourceType::Common,
+            )
         } else {
             stat::get_or_create_resource_node(&rule.resource, &ResourceType::Common)
         }
@@ -547,7 +587,7 @@ mod test {
             control_strategy: ControlStrategy::Custom(STRATEGY),
         };
 
-        let controller_map = CONTROLLER_MAP.lock().unwrap();
+        let controller_map = CONTROLLER_MAP.snapshot();
 
         assert!(GEN_FUN_MAP.read().unwrap().contains_key(&key));
         assert!(controller_map[&resource].len() > 0);
@@ -622,7 +662,7 @@ mod test {
             assert_eq!(rs[1], r1);
         }
 
-        let controller_map = CONTROLLER_MAP.lock().unwrap();
+        let controller_map = CONTROLLER_MAP.snapshot();
 
         assert_eq!(1, controller_map["abc2"].len());
         assert_eq!(false, controller_map["abc2"][0].stat().reuse_global());
@@ -721,7 +761,7 @@ mod test {
             ..Default::default()
         });
 
-        let mut controller_map = CONTROLLER_MAP.lock().unwrap();
+        let mut controller_map = CONTROLLER_MAP.snapshot();
         assert_eq!(
             0,
             controller_map
@@ -836,7 +876,7 @@ mod test {
         assert_eq!(false, stat4.reuse_global());
         assert!(stat4.write_only_metric().is_some());
 
-        let mut controller_map = CONTROLLER_MAP.lock().unwrap();
+        let mut controller_map = CONTROLLER_MAP.snapshot();
 
         controller_map.insert(
             "abc1".into(),
@@ -973,7 +1013,7 @@ mod test {
         assert!(result.unwrap());
 
         let rule_map = RULE_MAP.lock().unwrap();
-        let controller_map = CONTROLLER_MAP.lock().unwrap();
+        let controller_map = CONTROLLER_MAP.snapshot();
 
         assert_eq!(0, controller_map.get("abc1").unwrap_or(&Vec::new()).len());
         assert_eq!(0, rule_map.get("abc1").unwrap_or(&Vec::new()).len());
@@ -1017,7 +1057,7 @@ mod test {
         clear_rules_of_resource(&String::from("abc1"));
 
         let rule_map = RULE_MAP.lock().unwrap();
-        let controller_map = CONTROLLER_MAP.lock().unwrap();
+        let controller_map = CONTROLLER_MAP.snapshot();
 
         assert_eq!(0, controller_map.get("abc1").unwrap_or(&Vec::new()).len());
         assert_eq!(0, rule_map.get("abc1").unwrap_or(&Vec::new()).len());
@@ -4,13 +4,11 @@ use crate::{
         BaseSlot, ContextPtr, EntryContext, MetricEvent, ResultStatus, RuleCheckSlot, StatNode,
         StatSlot, TokenResult,
     },
-    logging, stat, utils,
+    logging, stat,
     utils::AsAny,
 };
 use lazy_static::lazy_static;
 use std::any::Any;
-use std::cell::RefCell;
-use std::rc::Rc;
 use std::sync::Arc;
 
 const RULE_CHECK_SLOT_ORDER: u32 = 2000;
@@ -33,54 +31,46 @@ impl BaseSlot for Slot {
 }
 
 impl RuleCheckSlot for Slot {
-    cfg_async! {
-        fn check(&self, ctx: &ContextPtr) -> TokenResult {
-            let mut ctx = ctx.write().unwrap();
-            let res = ctx.resource().name();
-            let stat_node = ctx.stat_node();
-            let input = ctx.input();
-            let tcs = get_traffic_controller_list_for(res);
-            for tc in tcs {
-                let r = can_pass_check(tc, stat_node.clone(), input.batch_count());
-                match r.status() {
-                    ResultStatus::Pass => {}
-                    ResultStatus::Blocked => {
-                        ctx.set_result(r);
-                        return ctx.result().clone();
-                    }
-                    ResultStatus::ShouldWait => {
-                        let nanos_to_wait = r.nanos_to_wait();
-                        utils::sleep_for_ns(nanos_to_wait);
+    fn check(&self, ctx: &ContextPtr) -> TokenResult {
+        let mut ctx = ctx.write().unwrap();
+        let res = ctx.resource().name();
+        let stat_node = ctx.stat_node();
+        let chain_node = ctx.chain_node();
+        let entrance = ctx.entrance().cloned();
+        let input = ctx.input();
+        let tcs = get_traffic_controller_list_for(res);
+        let mut nanos_to_wait = 0;
+        for tc in tcs {
+            let actual_node = match tc.rule().relation_strategy {
+                // the rule only takes effect when the resource is invoked through the entrance
+                RelationStrategy::Chain => {
+                    if entrance.as_ref() != Some(&tc.rule().ref_resource) {
+                        continue;
                     }
+                    chain_node.clone()
                 }
-            }
-            ctx.result().clone()
-        }
-    }
-
-    cfg_not_async! {
-        fn check(&self, ctx: &ContextPtr) -> TokenResult {
-            let mut ctx = ctx.borrow_mut();
-            let res = ctx.resource().name();
-            let stat_node = ctx.stat_node();
-            let input = ctx.input();
-            let tcs = get_traffic_controller_list_for(res);
-            for tc in tcs {
-                let r = can_pass_check(tc, stat_node.clone(), input.batch_count());
-                match r.status() {
-                    ResultStatus::Pass => {}
-                    ResultStatus::Blocked => {
-                        ctx.set_result(r);
-                        return ctx.result().clone();
-                    }
-                    ResultStatus::ShouldWait => {
-                        let nanos_to_wait = r.nanos_to_wait();
-                        utils::sleep_for_ns(nanos_to_wait);
-                    }
+                _ => stat_node.clone(),
+            };
+            let r = can_pass_check(
+                tc,
+                actual_node,
+                input.batch_count(),
+                input.priority_class().map(String::as_str),
+            );
+            match r.status() {
+                ResultStatus::Pass => {}
+                ResultStatus::Blocked => {
+                    ctx.set_result(r);
+                    return ctx.result().clone();
+                }
+                ResultStatus::ShouldWait => {
+                    // the slot chain waits after checking, see `EntryContext::defer_wait()`
+                    nanos_to_wait = nanos_to_wait.max(r.nanos_to_wait());
                 }
             }
-            ctx.result().clone()
         }
+        ctx.defer_wait(nanos_to_wait);
+        ctx.result().clone()
     }
 }
 
@@ -88,6 +78,7 @@ fn can_pass_check(
     tc: Arc<Controller>,
     given_node: Option<Arc<dyn StatNode>>,
     batch_count: u32,
+    priority_class: Option<&str>,
 ) -> TokenResult {
     let actual_node = {
         match tc.rule().relation_strategy {
@@ -101,7 +92,7 @@ fn can_pass_check(
         }
     };
     match actual_node {
-        Some(node) => tc.perform_checking(node, batch_count, 0),
+        Some(node) => tc.perform_checking_with_class(node, batch_count, 0, priority_class),
         None => {
             logging::FREQUENT_ERROR_ONCE.call_once(|| {
                 logging::error!(
@@ -121,8 +112,7 @@ mod test {
         base::{ResourceType, ResourceWrapper, SentinelInput, TrafficType},
         flow::StandaloneStat,
     };
-    use std::cell::RefCell;
-    use std::rc::Rc;
+    use std::sync::RwLock;
 
     #[test]
     fn rule_check_slot() {
@@ -136,7 +126,7 @@ mod test {
         ctx.set_input(SentinelInput::new(1, 0));
         ctx.set_stat_node(res_node);
         ctx.set_resource(res);
-        let ctx = Rc::new(RefCell::new(ctx));
+        let ctx = Arc::new(RwLock::new(ctx));
 
         slot.check(&ctx);
 
@@ -154,7 +144,7 @@ mod test {
 
         for _ in 0..50 {
             slot.check(&ctx);
-            stat_slot.on_entry_pass(Rc::clone(&ctx));
+            stat_slot.on_entry_pass(Arc::clone(&ctx));
         }
         assert_eq!(
             get_traffic_controller_list_for(&res_name)[0]
@@ -164,4 +154,49 @@ mod test {
             50
         );
     }
+
+    #[test]
+    #[ignore]
+    fn chain_relation_strategy() {
+        let res_name = String::from("chain_relation_res");
+        load_rules_of_resource(
+            &res_name,
+            vec![Arc::new(Rule {
+                resource: res_name.clone(),
+                ref_resource: "entrance_a".into(),
+                relation_strategy: RelationStrategy::Chain,
+                threshold: 1.0,
+                ..Default::default()
+            })],
+        )
+        .unwrap();
+
+        let build = || crate::EntryBuilder::new(res_name.clone()).build();
+        // the rule does not take effect without the entrance
+        for _ in 0..3 {
+            build().unwrap().read().unwrap().exit();
+        }
+        {
+            let _guard = crate::context_enter("entrance_b".into(), None);
+            build().unwrap().read().unwrap().exit();
+        }
+        {
+            let _guard = crate::context_enter("entrance_a".into(), Some("caller".into()));
+            let entry = build().unwrap();
+            assert_eq!(
+                entry
+                    .read()
+                    .unwrap()
+                    .context()
+                    .read()
+                    .unwrap()
+                    .input()
+                    .origin(),
+                Some(&String::from("caller"))
+            );
+            entry.read().unwrap().exit();
+            assert!(build().is_err());
+        }
+        clear_rules_of_resource(&res_name);
+    }
 }
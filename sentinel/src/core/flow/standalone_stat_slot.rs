@@ -1,8 +1,6 @@
 use super::*;
 use crate::base::{BaseSlot, BlockError, ContextPtr, MetricEvent, StatNode, StatSlot};
 use lazy_static::lazy_static;
-use std::cell::RefCell;
-use std::rc::Rc;
 use std::sync::Arc;
 
 const STAT_SLOT_ORDER: u32 = 3000;
@@ -26,15 +24,17 @@ impl BaseSlot for StandaloneStatSlot {
 
 impl StatSlot for StandaloneStatSlot {
     fn on_entry_pass(&self, ctx: ContextPtr) {
-        cfg_if_async! {
-            let ctx = ctx.read().unwrap(),
-            let ctx = ctx.borrow()
-        };
+        let ctx = ctx.read().unwrap();
 
         let res = ctx.resource().name();
         let input = ctx.input();
         let tcs = get_traffic_controller_list_for(res);
         for tc in tcs {
+            if tc.rule().relation_strategy == RelationStrategy::Chain
+                && ctx.entrance() != Some(&tc.rule().ref_resource)
+            {
+                continue;
+            }
             if !tc.stat().reuse_global() {
                 tc.stat()
                     .write_only_metric()
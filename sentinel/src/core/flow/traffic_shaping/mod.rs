@@ -37,6 +37,17 @@ pub trait Checker: Send + Sync + std::fmt::Debug {
         batch_count: u32,
         threshold: f64,
     ) -> TokenResult;
+    /// `do_check_with_class` performs checking for the request of the priority class,
+    /// the class is ignored by default.
+    fn do_check_with_class(
+        &self,
+        stat_node: Option<Arc<dyn StatNode>>,
+        batch_count: u32,
+        threshold: f64,
+        _priority_class: Option<&str>,
+    ) -> TokenResult {
+        self.do_check(stat_node, batch_count, threshold)
+    }
 }
 
 /// StandaloneStat indicates the independent statistic for each Traffic Shaping Controller
@@ -133,6 +144,18 @@ impl Controller {
         res_stat: Arc<dyn StatNode>,
         batch_count: u32,
         flag: i32,
+    ) -> TokenResult {
+        self.perform_checking_with_class(res_stat, batch_count, flag, None)
+    }
+
+    /// `perform_checking_with_class` performs checking for the request of the priority class,
+    /// see `priority_weights` of the rule.
+    pub fn perform_checking_with_class(
+        &self,
+        res_stat: Arc<dyn StatNode>,
+        batch_count: u32,
+        flag: i32,
+        priority_class: Option<&str>,
     ) -> TokenResult {
         let calculator = self.calculator.as_ref().unwrap();
         let calculator = calculator.lock().unwrap();
@@ -142,6 +165,11 @@ impl Controller {
 
         let checker = self.checker.as_ref().unwrap();
         let checker = checker.lock().unwrap();
-        checker.do_check(Some(res_stat), batch_count, allowed_threshold)
+        checker.do_check_with_class(
+            Some(res_stat),
+            batch_count,
+            allowed_threshold,
+            priority_class,
+        )
     }
 }
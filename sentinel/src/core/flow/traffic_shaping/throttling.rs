@@ -1,23 +1,58 @@
 //! Throttling indicates that pending requests will be throttled,
 //! wait in queue (until free capacity is available)
+//!
+//! With the `priority_weights` of the rule, the classes share the clock of the whole rule,
+//! i.e., the requests of all classes pass at most at the threshold, and each class is spaced by its own interval
+//! stretched by its share among the backlogged classes,
+//! e.g., the interactive (70) and batch (30) classes pass at 70% and 30% of the threshold when both are backlogged,
+//! and either passes at the whole threshold alone. The requests already queued keep their expected pass time,
+//! thus a class becoming backlogged is queued after them.
 
 use super::{Calculator, Checker, Controller, Rule};
 use crate::base::{BlockType, MetricEvent, StatNode, TokenResult};
 use crate::utils;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::TryInto;
 use std::sync::{
     atomic::{AtomicI64, Ordering},
     Arc, Mutex, Weak,
 };
+use std::time::Duration;
 
 static BLOCK_MSG_QUEUEING: &'static str = "flow throttling check blocked, threshold is <= 0.0";
 
+/// the class of the requests whose priority classes are not listed in the weights
+const DEFAULT_PRIORITY_CLASS: &str = "";
+const DEFAULT_PRIORITY_WEIGHT: u32 = 1;
+
+// the queue of a priority class
+#[derive(Debug, Default, Clone, Copy)]
+struct ClassSchedule {
+    // the finish time of the last request of the class, i.e., its start time plus the interval of the class
+    finish_time: i64,
+    // the check time of the last request of the class, including the blocked ones
+    last_requested_time: i64,
+    // the interval of the class at the last request
+    interval_ns: i64,
+}
+
+impl ClassSchedule {
+    // the class is backlogged until the last request finishes, or within an interval since it is checked
+    fn is_backlogged(&self, curr_nano: i64) -> bool {
+        self.finish_time
+            .max(self.last_requested_time + self.interval_ns)
+            > curr_nano
+    }
+}
+
 #[derive(Debug)]
 pub struct ThrottlingChecker {
     owner: Weak<Controller>,
     max_queueing_time_ns: i64,
     stat_interval_ns: i64,
     last_passed_time: AtomicI64,
+    priority_weights: BTreeMap<String, u32>,
+    class_schedules: Mutex<HashMap<String, ClassSchedule>>,
 }
 
 impl ThrottlingChecker {
@@ -39,6 +74,32 @@ impl ThrottlingChecker {
             max_queueing_time_ns: utils::milli2nano(timeout_ms).try_into().unwrap(),
             stat_interval_ns,
             last_passed_time: AtomicI64::new(0),
+            priority_weights: rule.priority_weights.clone(),
+            class_schedules: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn weight_of(&self, class: &str) -> u32 {
+        self.priority_weights
+            .get(class)
+            .copied()
+            .unwrap_or(DEFAULT_PRIORITY_WEIGHT)
+    }
+
+    fn blocked_by_queueing(&self, estimated_queue_duration: i64) -> TokenResult {
+        // the request may pass once the queueing time is acceptable
+        let retry_after =
+            Duration::from_nanos((estimated_queue_duration - self.max_queueing_time_ns) as u64);
+        match self.owner.upgrade() {
+            Some(owner) => TokenResult::new_blocked_with_cause(
+                BlockType::Flow,
+                BLOCK_MSG_QUEUEING.into(),
+                owner.rule().clone(),
+                Arc::new(estimated_queue_duration),
+            )
+            .with_retry_after(retry_after),
+            None => TokenResult::new_blocked_with_msg(BlockType::Flow, BLOCK_MSG_QUEUEING.into())
+                .with_retry_after(retry_after),
         }
     }
 }
@@ -115,22 +176,7 @@ impl Checker for ThrottlingChecker {
         let estimated_queue_duration =
             self.last_passed_time.load(Ordering::SeqCst) + interval_ns - curr_nano;
         if estimated_queue_duration > self.max_queueing_time_ns {
-            match owner {
-                Some(owner) => {
-                    return TokenResult::new_blocked_with_cause(
-                        BlockType::Flow,
-                        BLOCK_MSG_QUEUEING.into(),
-                        owner.rule().clone(),
-                        Arc::new(estimated_queue_duration),
-                    );
-                }
-                None => {
-                    return TokenResult::new_blocked_with_msg(
-                        BlockType::Flow,
-                        BLOCK_MSG_QUEUEING.into(),
-                    );
-                }
-            }
+            return self.blocked_by_queueing(estimated_queue_duration);
         }
         // It is expected to run at `expected_time`
         let expected_time = self
@@ -142,22 +188,7 @@ impl Checker for ThrottlingChecker {
             // Subtract the interval.
             self.last_passed_time
                 .fetch_sub(interval_ns, Ordering::SeqCst);
-            match owner {
-                Some(owner) => {
-                    return TokenResult::new_blocked_with_cause(
-                        BlockType::Flow,
-                        BLOCK_MSG_QUEUEING.into(),
-                        owner.rule().clone(),
-                        Arc::new(estimated_queue_duration),
-                    );
-                }
-                None => {
-                    return TokenResult::new_blocked_with_msg(
-                        BlockType::Flow,
-                        BLOCK_MSG_QUEUEING.into(),
-                    );
-                }
-            }
+            return self.blocked_by_queueing(estimated_queue_duration);
         }
         if estimated_queue_duration > 0 {
             return TokenResult::new_should_wait(estimated_queue_duration.try_into().unwrap());
@@ -165,13 +196,73 @@ impl Checker for ThrottlingChecker {
             return TokenResult::new_should_wait(0);
         }
     }
+
+    fn do_check_with_class(
+        &self,
+        stat_node: Option<Arc<dyn StatNode>>,
+        batch_count: u32,
+        threshold: f64,
+        priority_class: Option<&str>,
+    ) -> TokenResult {
+        // the requests are queued in FIFO order without the weights,
+        // and the invalid requests or thresholds are checked regardless of the classes
+        if self.priority_weights.is_empty()
+            || batch_count == 0
+            || threshold <= 0.0
+            || batch_count as f64 > threshold
+        {
+            return self.do_check(stat_node, batch_count, threshold);
+        }
+        let class = priority_class
+            .filter(|class| self.priority_weights.contains_key(*class))
+            .unwrap_or(DEFAULT_PRIORITY_CLASS);
+        let weight = self.weight_of(class);
+
+        let curr_nano: i64 = utils::curr_time_nanos().try_into().unwrap();
+        let mut schedules = self.class_schedules.lock().unwrap();
+        // the threshold is shared with the other backlogged classes by the weights
+        let backlogged_weight: u64 = schedules
+            .iter()
+            .filter(|(other, schedule)| {
+                other.as_str() != class && schedule.is_backlogged(curr_nano)
+            })
+            .map(|(other, _)| self.weight_of(other) as u64)
+            .sum();
+        let share = weight as f64 / (weight as u64 + backlogged_weight) as f64;
+        // the interval of the whole rule, and the one of the class by its share
+        let interval_ns =
+            ((batch_count as f64).ceil() / threshold * (self.stat_interval_ns as f64)) as i64;
+        let class_interval_ns = (interval_ns as f64 / share) as i64;
+
+        let schedule = schedules.entry(class.into()).or_default();
+        schedule.last_requested_time = curr_nano;
+        schedule.interval_ns = class_interval_ns;
+        // the request takes the next slot of the clock shared by all classes,
+        // and it is admitted only if its class has not used up the share within the queueing time
+        let passed_time =
+            (self.last_passed_time.load(Ordering::SeqCst) + interval_ns).max(curr_nano);
+        let start_time = schedule.finish_time.max(curr_nano);
+        let estimated_queue_duration = passed_time.max(start_time) - curr_nano;
+        if estimated_queue_duration > self.max_queueing_time_ns {
+            return self.blocked_by_queueing(estimated_queue_duration);
+        }
+        // the shared clock is only advanced with the lock of the schedules held
+        self.last_passed_time.store(passed_time, Ordering::SeqCst);
+        schedule.finish_time = start_time + class_interval_ns;
+        let estimated_queue_duration = passed_time - curr_nano;
+        if estimated_queue_duration > 0 {
+            TokenResult::new_should_wait(estimated_queue_duration as u64)
+        } else {
+            TokenResult::new_pass()
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::base::ResultStatus;
-    use crate::utils::unix_time_unit_offset;
+    use crate::utils::{unix_time_unit_offset, MockClock};
     use std::sync::atomic::{AtomicU32, Ordering};
 
     #[test]
@@ -245,6 +336,9 @@ mod test {
         }
         for i in wait_count as usize..req_count {
             assert!(result_list[i].is_blocked());
+            // the queueing time exceeds at most one interval
+            let retry_after = result_list[i].block_err().unwrap().retry_after().unwrap();
+            assert!(retry_after <= Duration::from_millis((interval_ms as f64 / threshold) as u64));
         }
     }
 
@@ -296,6 +390,125 @@ mod test {
         );
     }
 
+    fn weighted_rule(max_queueing_time_ms: u32) -> Arc<Rule> {
+        Arc::new(Rule {
+            max_queueing_time_ms,
+            stat_interval_ms: 1000,
+            priority_weights: vec![("interactive".into(), 70), ("batch".into(), 30)]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn weighted_queueing() {
+        let clock = Arc::new(MockClock::starting_at(1_600_000_000_000));
+        let _guard = clock.install();
+        let threshold = 10.0;
+        let tc = ThrottlingChecker::new(Weak::new(), weighted_rule(1000));
+
+        // the batch class alone is queued at the whole threshold
+        let batch: Vec<TokenResult> = (0..20)
+            .map(|_| tc.do_check_with_class(None, 1, threshold, Some("batch")))
+            .collect();
+        assert!(batch[0].is_pass());
+        assert_eq!(batch.iter().filter(|r| r.is_wait()).count(), 10);
+        assert!(batch[19].is_blocked());
+
+        // the requests already queued keep their pass time, the interactive class is queued after them
+        clock.advance_millis(500);
+        let interactive: Vec<TokenResult> = (0..10)
+            .map(|_| tc.do_check_with_class(None, 1, threshold, Some("interactive")))
+            .collect();
+        assert_eq!(interactive[0].nanos_to_wait(), 600_000_000);
+        assert_eq!(interactive[4].nanos_to_wait(), 1_000_000_000);
+        assert!(interactive[5].is_blocked());
+
+        // the unlisted classes share the default class
+        clock.advance_millis(2000);
+        assert!(tc.do_check_with_class(None, 1, threshold, None).is_pass());
+        assert_eq!(
+            tc.do_check_with_class(None, 1, threshold, Some("unknown"))
+                .nanos_to_wait(),
+            100_000_000
+        );
+    }
+
+    #[test]
+    fn weighted_shares() {
+        let clock = Arc::new(MockClock::starting_at(1_600_000_000_000));
+        let _guard = clock.install();
+        let threshold = 10.0;
+        let tc = ThrottlingChecker::new(Weak::new(), weighted_rule(500));
+
+        // both classes are saturated, the queued requests are counted
+        let (mut interactive, mut batch) = (0, 0);
+        for _ in 0..10_000 {
+            clock.advance_millis(1);
+            if !tc
+                .do_check_with_class(None, 1, threshold, Some("interactive"))
+                .is_blocked()
+            {
+                interactive += 1;
+            }
+            if !tc
+                .do_check_with_class(None, 1, threshold, Some("batch"))
+                .is_blocked()
+            {
+                batch += 1;
+            }
+        }
+        assert!(
+            (70..=76).contains(&interactive),
+            "{} {}",
+            interactive,
+            batch
+        );
+        assert!((29..=33).contains(&batch), "{} {}", interactive, batch);
+
+        // the interactive class alone passes at the whole threshold
+        clock.advance_millis(2000);
+        let mut interactive = 0;
+        for _ in 0..10_000 {
+            clock.advance_millis(1);
+            if !tc
+                .do_check_with_class(None, 1, threshold, Some("interactive"))
+                .is_blocked()
+            {
+                interactive += 1;
+            }
+        }
+        // plus the requests queued within `max_queueing_time_ms`
+        assert!((100..=106).contains(&interactive), "{}", interactive);
+    }
+
+    #[test]
+    fn weighted_threshold() {
+        let clock = Arc::new(MockClock::starting_at(1_600_000_000_000));
+        let _guard = clock.install();
+        let threshold = 10.0;
+        let tc = ThrottlingChecker::new(Weak::new(), weighted_rule(500));
+
+        // all classes, including the default one, are saturated
+        let mut passed_times = Vec::new();
+        for _ in 0..10_000 {
+            clock.advance_millis(1);
+            for class in &[Some("interactive"), Some("batch"), None] {
+                let result = tc.do_check_with_class(None, 1, threshold, *class);
+                if !result.is_blocked() {
+                    passed_times.push(utils::curr_time_nanos() + result.nanos_to_wait() as i128);
+                }
+            }
+        }
+        passed_times.sort_unstable();
+        assert!(passed_times.len() >= 100);
+        // at most `threshold` requests pass within any second
+        for window in passed_times.windows(threshold as usize + 1) {
+            assert!(window[threshold as usize] - window[0] >= utils::milli2nano(1000));
+        }
+    }
+
     #[test]
     #[ignore]
     // todo: this test should not be ignored for single-thread,
@@ -118,3 +118,37 @@ impl Calculator for WarmUpCalculator {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sync_token_with_mock_clock() {
+        let clock = Arc::new(utils::MockClock::starting_at(1_600_000_000_000));
+        let _guard = clock.install();
+        let rule = Arc::new(Rule {
+            threshold: 10.0,
+            warm_up_period_sec: 10,
+            warm_up_cold_factor: 3,
+            ..Default::default()
+        });
+        let tc = WarmUpCalculator::new(Weak::new(), rule);
+        assert_eq!(tc.warning_token, 50);
+        assert_eq!(tc.max_token, 100);
+
+        // the cold system starts with the full bucket
+        tc.sync_token(0.0);
+        assert_eq!(tc.stored_tokens.load(Ordering::SeqCst), tc.max_token);
+        // the tokens are synchronized at most once per second
+        clock.advance_millis(500);
+        tc.sync_token(10.0);
+        assert_eq!(tc.stored_tokens.load(Ordering::SeqCst), tc.max_token);
+        // the passed requests consume the tokens until the warning line
+        for expected in &[90, 80, 70, 60, 50] {
+            clock.advance_millis(1000);
+            tc.sync_token(10.0);
+            assert_eq!(tc.stored_tokens.load(Ordering::SeqCst), *expected);
+        }
+    }
+}
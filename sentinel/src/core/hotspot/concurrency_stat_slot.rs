@@ -4,8 +4,6 @@ use crate::{
     logging,
 };
 use lazy_static::lazy_static;
-use std::cell::RefCell;
-use std::rc::Rc;
 use std::sync::{atomic::Ordering, Arc};
 
 const STAT_SLOT_ORDER: u32 = 4000;
@@ -30,19 +28,13 @@ impl BaseSlot for ConcurrencyStatSlot {
 
 impl StatSlot for ConcurrencyStatSlot {
     fn on_entry_pass(&self, ctx: ContextPtr) {
-        let ctx_ref = &ctx;
-        cfg_if_async! {
-            let ctx = ctx.read().unwrap(),
-            let ctx = ctx.borrow()
-        };
-        let res = ctx.resource().name();
-        let input = ctx.input();
-        let tcs = get_traffic_controller_list_for(res);
+        let res = ctx.read().unwrap().resource().name().clone();
+        let tcs = get_traffic_controller_list_for(&res);
         for tc in tcs {
             if tc.rule().metric_type != MetricType::Concurrency {
                 continue;
             }
-            if let Some(arg) = tc.extract_args(ctx_ref) {
+            if let Some(arg) = tc.extract_args(&ctx) {
                 let metric = tc.metric();
                 match metric.concurrency_counter.get(&arg) {
                     Some(counter) => {
@@ -59,18 +51,13 @@ impl StatSlot for ConcurrencyStatSlot {
     fn on_entry_blocked(&self, _ctx: ContextPtr, _block_error: Option<BlockError>) {}
 
     fn on_completed(&self, ctx: ContextPtr) {
-        let ctx_ref = &ctx;
-        cfg_if_async! {
-            let ctx = ctx.read().unwrap(),
-            let ctx = ctx.borrow()
-        };
-        let res = ctx.resource().name();
-        let tcs = get_traffic_controller_list_for(res);
+        let res = ctx.read().unwrap().resource().name().clone();
+        let tcs = get_traffic_controller_list_for(&res);
         for tc in tcs {
             if tc.rule().metric_type != MetricType::Concurrency {
                 continue;
             }
-            if let Some(arg) = tc.extract_args(ctx_ref) {
+            if let Some(arg) = tc.extract_args(&ctx) {
                 let metric = tc.metric();
                 match metric.concurrency_counter.get(&arg) {
                     Some(counter) => {
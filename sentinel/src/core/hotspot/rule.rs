@@ -1,5 +1,5 @@
 use crate::{
-    base::{ParamKey, SentinelRule},
+    base::{Diagnostic, ParamKey, SentinelRule},
     logging, system_metric, Error, Result,
 };
 use serde::{Deserialize, Serialize};
@@ -91,19 +91,25 @@ impl SentinelRule for Rule {
         self.resource.clone()
     }
 
-    fn is_valid(&self) -> Result<()> {
+    fn rule_id(&self) -> Option<String> {
+        self.id.clone()
+    }
+
+    fn diagnose(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
         if self.resource.len() == 0 {
-            return Err(Error::msg("empty resource name"));
+            diagnostics.push(Diagnostic::error("resource", "empty resource name"));
         }
         if self.metric_type == MetricType::QPS && self.duration_in_sec == 0 {
-            return Err(Error::msg("invalid duration"));
+            diagnostics.push(Diagnostic::error("duration_in_sec", "invalid duration"));
         }
         if self.param_index > 0 && self.param_key.len() != 0 {
-            return Err(Error::msg(
+            diagnostics.push(Diagnostic::error(
+                "param_key",
                 "param index and param key are mutually exclusive",
             ));
         }
-        Ok(())
+        diagnostics
     }
 }
 
@@ -1,6 +1,7 @@
 use super::*;
 use crate::base::ParamKey;
-use crate::{base::SentinelRule, logging, utils, Error, Result};
+use crate::base::{check_direct_update, RuleChangeListener, RuleChangeListeners};
+use crate::{base::SentinelRule, logging, utils, utils::ShardedMap, Error, Result};
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex, RwLock};
@@ -33,10 +34,23 @@ lazy_static! {
 
         RwLock::new(gen_fun_map)
     };
-    static ref CONTROLLER_MAP: RwLock<ControllerMap> = RwLock::new(HashMap::new());
+    static ref CONTROLLER_MAP: ShardedMap<String, Vec<Arc<Controller>>> = ShardedMap::new();
     static ref RULE_MAP: Mutex<RuleMap> = Mutex::new(HashMap::new());
 }
 
+lazy_static! {
+    static ref RULE_CHANGE_LISTENERS: RuleChangeListeners<Rule> = RuleChangeListeners::new();
+}
+
+/// `register_rule_change_listeners` registers the listeners notified after the rules are changed.
+pub fn register_rule_change_listeners(listeners: Vec<Arc<dyn RuleChangeListener<Rule>>>) {
+    RULE_CHANGE_LISTENERS.register(listeners);
+}
+
+pub fn clear_rule_change_listeners() {
+    RULE_CHANGE_LISTENERS.clear();
+}
+
 pub(super) use gen_fns::*;
 
 mod gen_fns {
@@ -76,13 +90,10 @@ mod gen_fns {
     }
 }
 
+// This func only acquires the read lock on a shard of global `CONTROLLER_MAP`,
+// thus it never waits for the loading of the rules
 pub fn get_traffic_controller_list_for(res: &String) -> Vec<Arc<Controller>> {
-    CONTROLLER_MAP
-        .read()
-        .unwrap()
-        .get(res)
-        .unwrap_or(&Vec::new())
-        .clone()
+    CONTROLLER_MAP.get(res).unwrap_or_default()
 }
 
 fn log_rule_update(map: &RuleMap) {
@@ -101,6 +112,13 @@ fn log_rule_update(map: &RuleMap) {
 // This func acquires locks on global `RULE_MAP` and `CONTROLLER_MAP`,
 // please release your locks on them before calling this func
 pub fn load_rules(rules: Vec<Arc<Rule>>) -> bool {
+    if check_direct_update("`hotspot::load_rules()`").is_err() {
+        return false;
+    }
+    RULE_CHANGE_LISTENERS.watch(get_rules, || do_load_rules(rules))
+}
+
+fn do_load_rules(rules: Vec<Arc<Rule>>) -> bool {
     let mut rule_map: RuleMap = HashMap::new();
     for rule in rules {
         let entry = rule_map.entry(rule.resource.clone()).or_insert(Vec::new());
@@ -135,25 +153,21 @@ pub fn load_rules(rules: Vec<Arc<Rule>>) -> bool {
     }
 
     let start = utils::curr_time_nanos();
-    let mut controller_map = CONTROLLER_MAP.write().unwrap();
     let mut valid_controller_map = HashMap::with_capacity(valid_rules_map.len());
 
-    // build controller_map according to valid rules
+    // build controller_map according to valid rules,
+    // the controllers are built without locking `CONTROLLER_MAP`, thus the traffic is not stalled
     for (res, rules) in valid_rules_map.iter() {
-        let mut placeholder = Vec::new();
-        let new_tcs_of_res = build_resource_traffic_shaping_controller(
-            res,
-            rules.clone(),
-            controller_map.get_mut(res).unwrap_or(&mut placeholder),
-        );
+        let mut old_res_tcs = CONTROLLER_MAP.get(res).unwrap_or_default();
+        let new_tcs_of_res =
+            build_resource_traffic_shaping_controller(res, rules.clone(), &mut old_res_tcs);
         if new_tcs_of_res.len() > 0 {
             valid_controller_map.insert(res.clone(), new_tcs_of_res);
         }
     }
-    *controller_map = valid_controller_map;
+    CONTROLLER_MAP.replace(valid_controller_map);
     *global_rule_map = rule_map;
     drop(global_rule_map);
-    drop(controller_map);
     logging::debug!(
         "[HotSpot load_rules] Time statistic(ns) for updating hotspot param flow rule, time cost {}",
         utils::curr_time_nanos() - start
@@ -168,15 +182,22 @@ pub fn load_rules(rules: Vec<Arc<Rule>>) -> bool {
 // This func acquires locks on global `RULE_MAP` and `CONTROLLER_MAP`,
 // please release your locks on them before calling this func
 pub fn load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<bool> {
+    check_direct_update("`hotspot::load_rules_of_resource()`")?;
+    RULE_CHANGE_LISTENERS.watch(
+        || get_rules_of_resource(res),
+        || do_load_rules_of_resource(res, rules),
+    )
+}
+
+fn do_load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<bool> {
     if res.len() == 0 {
         return Err(Error::msg("empty resource"));
     }
     let mut global_rule_map = RULE_MAP.lock().unwrap();
-    let mut global_controller_map = CONTROLLER_MAP.write().unwrap();
     // clear resource rules
     if rules.len() == 0 {
         global_rule_map.remove(res);
-        global_controller_map.remove(res);
+        CONTROLLER_MAP.remove(res);
         logging::info!("[HotSpot] clear resource level rules, resource {}", res);
         return Ok(true);
     }
@@ -199,19 +220,16 @@ pub fn load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<boo
     }
     // the `res` related rules changes, have to update
     let start = utils::curr_time_nanos();
-    let mut placeholder = Vec::new();
-    let mut old_res_tcs = global_controller_map
-        .get_mut(res)
-        .unwrap_or(&mut placeholder);
+    let mut old_res_tcs = CONTROLLER_MAP.get(res).unwrap_or_default();
 
     let valid_res_rules_string = format!("{:?}", &valid_res_rules);
     let new_res_tcs =
         build_resource_traffic_shaping_controller(res, valid_res_rules, &mut old_res_tcs);
 
     if new_res_tcs.len() == 0 {
-        global_controller_map.remove(res);
+        CONTROLLER_MAP.remove(res);
     } else {
-        global_controller_map.insert(res.clone(), new_res_tcs);
+        CONTROLLER_MAP.insert(res.clone(), new_res_tcs);
     }
 
     global_rule_map.insert(res.clone(), rules);
@@ -233,12 +251,11 @@ pub fn load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<boo
 // please release your lock on it before calling this func
 pub fn get_rules() -> Vec<Arc<Rule>> {
     let mut rules = Vec::new();
-    let controller_map = CONTROLLER_MAP.read().unwrap();
-    for (_, controllers) in controller_map.iter() {
+    CONTROLLER_MAP.for_each(|_, controllers| {
         for c in controllers {
             rules.push(Arc::clone(c.rule()));
         }
-    }
+    });
     rules
 }
 
@@ -246,30 +263,46 @@ pub fn get_rules() -> Vec<Arc<Rule>> {
 // This func acquires the lock on global `CONTROLLER_MAP`,
 // please release your locks on them before calling this func
 pub fn get_rules_of_resource(res: &String) -> Vec<Arc<Rule>> {
-    let controller_map = CONTROLLER_MAP.read().unwrap();
-    let placeholder = Vec::new();
-    let controllers = controller_map.get(res).unwrap_or(&placeholder);
-    let mut rules = Vec::with_capacity(controllers.len());
-    for c in controllers {
-        rules.push(Arc::clone(c.rule()));
-    }
-    rules
+    CONTROLLER_MAP.with(res, |controllers| {
+        controllers
+            .map(|controllers| controllers.iter().map(|c| Arc::clone(c.rule())).collect())
+            .unwrap_or_default()
+    })
 }
 
 /// clear_rules clears all the rules in hotspot param flow module.
 // This func acquires locks on global `RULE_MAP` and `CONTROLLER_MAP`,
 // please release your locks on them before calling this func
 pub fn clear_rules() {
-    RULE_MAP.lock().unwrap().clear();
-    CONTROLLER_MAP.write().unwrap().clear();
+    if check_direct_update("`hotspot::clear_rules()`").is_err() {
+        return;
+    }
+    RULE_CHANGE_LISTENERS.watch(get_rules, do_clear_rules)
+}
+
+fn do_clear_rules() {
+    let mut global_rule_map = RULE_MAP.lock().unwrap();
+    global_rule_map.clear();
+    CONTROLLER_MAP.clear();
 }
 
 /// `clear_rules_of_resource` clears resource level rules in hotspot param flow module.
 // This func acquires locks on global `RULE_MAP` and `CONTROLLER_MAP`,
 // please release your locks on them before calling this func
 pub fn clear_rules_of_resource(res: &String) {
-    RULE_MAP.lock().unwrap().remove(res);
-    CONTROLLER_MAP.write().unwrap().remove(res);
+    if check_direct_update("`hotspot::clear_rules_of_resource()`").is_err() {
+        return;
+    }
+    RULE_CHANGE_LISTENERS.watch(
+        || get_rules_of_resource(res),
+        || do_clear_rules_of_resource(res),
+    )
+}
+
+fn do_clear_rules_of_resource(res: &String) {
+    let mut global_rule_map = RULE_MAP.lock().unwrap();
+    global_rule_map.remove(res);
+    CONTROLLER_MAP.remove(res);
 }
 
 /// `set_traffic_shaping_generator` sets the traffic controller generator for the given CalculateStrategy and ControlStrategy.
@@ -468,7 +501,7 @@ mod test {
         let success = load_rules(vec![rule]);
         assert!(!success);
 
-        let controller_map = CONTROLLER_MAP.read().unwrap();
+        let controller_map = CONTROLLER_MAP.snapshot();
         let rule_map = RULE_MAP.lock().unwrap();
 
         assert_eq!(1, rule_map["abc"].len());
@@ -548,7 +581,7 @@ mod test {
         let success = load_rules_of_resource(&"abc1".into(), vec![]);
         assert!(success.unwrap());
 
-        let controller_map = CONTROLLER_MAP.read().unwrap();
+        let controller_map = CONTROLLER_MAP.snapshot();
         let rule_map = RULE_MAP.lock().unwrap();
 
         assert_eq!(0, rule_map.get("abc1").unwrap_or(&Vec::new()).len());
@@ -629,8 +662,7 @@ mod test {
         assert_eq!(
             0,
             CONTROLLER_MAP
-                .read()
-                .unwrap()
+                .snapshot()
                 .get("abc1")
                 .unwrap_or(&Vec::new())
                 .len()
@@ -647,8 +679,7 @@ mod test {
         assert_eq!(
             2,
             CONTROLLER_MAP
-                .read()
-                .unwrap()
+                .snapshot()
                 .get("abc2")
                 .unwrap_or(&Vec::new())
                 .len()
@@ -4,13 +4,11 @@ use crate::{
         BaseSlot, ContextPtr, EntryContext, MetricEvent, ResultStatus, RuleCheckSlot, StatNode,
         StatSlot, TokenResult,
     },
-    logging, stat, utils,
+    logging, stat,
     utils::AsAny,
 };
 use lazy_static::lazy_static;
 use std::any::Any;
-use std::cell::RefCell;
-use std::rc::Rc;
 use std::sync::Arc;
 
 const RULE_CHECK_SLOT_ORDER: u32 = 4000;
@@ -34,32 +32,31 @@ impl BaseSlot for Slot {
 
 impl RuleCheckSlot for Slot {
     fn check(&self, ctx: &ContextPtr) -> TokenResult {
-        let ctx_cloned = ctx.clone(); // Rc/Arc clone
-        cfg_if_async! {
-            let mut ctx = ctx.write().unwrap(),
-            let mut ctx = ctx.borrow_mut()
+        // the lock of ctx should not be held here,
+        // since `extract_args()` would acquire it again
+        let (res, batch) = {
+            let ctx = ctx.read().unwrap();
+            (ctx.resource().name().clone(), ctx.input().batch_count())
         };
-        let res = ctx.resource().name();
-        let input = ctx.input();
-        let batch = input.batch_count();
 
-        let tcs = get_traffic_controller_list_for(res);
+        let tcs = get_traffic_controller_list_for(&res);
         for tc in tcs {
-            if let Some(arg) = tc.extract_args(&ctx_cloned) {
+            if let Some(arg) = tc.extract_args(ctx) {
                 let r = tc.perform_checking(arg, batch);
                 match r.status() {
                     ResultStatus::Pass => {}
                     ResultStatus::Blocked => {
+                        let mut ctx = ctx.write().unwrap();
                         ctx.set_result(r);
                         return ctx.result().clone();
                     }
                     ResultStatus::ShouldWait => {
-                        let nanos_to_wait = r.nanos_to_wait();
-                        utils::sleep_for_ns(nanos_to_wait);
+                        // the slot chain waits after checking, see `EntryContext::defer_wait()`
+                        ctx.write().unwrap().defer_wait(r.nanos_to_wait());
                     }
                 }
             }
         }
-        ctx.result().clone()
+        ctx.read().unwrap().result().clone()
     }
 }
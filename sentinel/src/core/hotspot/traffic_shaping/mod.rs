@@ -11,11 +11,9 @@ use crate::{
 };
 use lazy_static::lazy_static;
 use std::any::Any;
-use std::cell::RefCell;
 use std::cmp::min;
 use std::collections::HashMap;
 use std::hash::Hash;
-use std::rc::Rc;
 use std::sync::{atomic::Ordering, Arc, Mutex, RwLock, Weak};
 
 /// Traffic Shaping `Checker` performs checking according to current metrics and the traffic
@@ -164,10 +162,7 @@ where
     }
 
     fn extract_list_args(&self, ctx: &ContextPtr) -> Option<ParamKey> {
-        cfg_if_async! {
-            let ctx = ctx.read().unwrap(),
-            let ctx = ctx.borrow()
-        };
+        let ctx = ctx.read().unwrap();
         let args = ctx.input().args();
         match args {
             Some(args) => {
@@ -193,10 +188,7 @@ where
     }
 
     fn extract_kv_args(&self, ctx: &ContextPtr) -> Option<ParamKey> {
-        cfg_if_async! {
-            let ctx = ctx.read().unwrap(),
-            let ctx = ctx.borrow()
-        };
+        let ctx = ctx.read().unwrap();
         let attachments = ctx.input().attachments();
         match attachments {
             Some(attachments) => {
@@ -317,7 +309,7 @@ pub(crate) mod test {
         input.set_args(args);
         input.set_attachments(attachments);
         ctx.set_input(input);
-        let ctx = Rc::new(RefCell::new(ctx));
+        let ctx = Arc::new(RwLock::new(ctx));
 
         // no data
         let extracted = controller.extract_args(&ctx);
@@ -347,7 +339,7 @@ pub(crate) mod test {
         input.set_args(args);
         input.set_attachments(attachments);
         ctx.set_input(input);
-        let ctx = Rc::new(RefCell::new(ctx));
+        let ctx = Arc::new(RwLock::new(ctx));
 
         let extracted = controller.extract_args(&ctx);
         assert_eq!("v1", &extracted.unwrap());
@@ -376,7 +368,7 @@ pub(crate) mod test {
         input.set_args(args);
         input.set_attachments(attachments);
         ctx.set_input(input);
-        let ctx = Rc::new(RefCell::new(ctx));
+        let ctx = Arc::new(RwLock::new(ctx));
 
         let extracted = controller.extract_args(&ctx);
         assert_eq!("v1", &extracted.unwrap());
@@ -405,7 +397,7 @@ pub(crate) mod test {
         input.set_args(args);
         input.set_attachments(attachments);
         ctx.set_input(input);
-        let ctx = Rc::new(RefCell::new(ctx));
+        let ctx = Arc::new(RwLock::new(ctx));
 
         let extracted = controller.extract_args(&ctx);
         assert_eq!("2", &extracted.unwrap());
@@ -434,7 +426,7 @@ pub(crate) mod test {
         input.set_args(args);
         input.set_attachments(attachments);
         ctx.set_input(input);
-        let ctx = Rc::new(RefCell::new(ctx));
+        let ctx = Arc::new(RwLock::new(ctx));
 
         let extracted = controller.extract_args(&ctx);
         assert!(extracted.is_none());
@@ -691,6 +683,58 @@ pub(crate) mod test {
                 assert!(last_add_token_time.load(Ordering::SeqCst) > curr_time);
                 assert!(old_qps.load(Ordering::SeqCst) > 30);
             }
+
+            #[test]
+            fn refill_insufficient_token() {
+                let clock = Arc::new(utils::MockClock::starting_at(1_600_000_000_000));
+                let _guard = clock.install();
+                let rule = Arc::new(Rule {
+                    resource: "abc".into(),
+                    metric_type: MetricType::QPS,
+                    control_strategy: ControlStrategy::Reject,
+                    threshold: 10,
+                    duration_in_sec: 1,
+                    burst_count: 5,
+                    ..Default::default()
+                });
+                // the burst is used up
+                let old_qps = Arc::new(AtomicU64::new(0));
+                let last_add_token_time =
+                    Arc::new(AtomicU64::new(utils::curr_time_millis() - 1001));
+                let mut rule_time_counter: MockCounter<ParamKey> = MockCounter::new();
+                rule_time_counter
+                    .expect_add_if_absent()
+                    .once()
+                    .return_const(Some(Arc::clone(&last_add_token_time)));
+                rule_time_counter
+                    .expect_cap()
+                    .once()
+                    .return_const(PARAMS_MAX_CAPACITY);
+                let mut rule_token_counter: MockCounter<ParamKey> = MockCounter::new();
+                rule_token_counter
+                    .expect_add_if_absent()
+                    .once()
+                    .return_const(Some(Arc::clone(&old_qps)));
+                rule_token_counter
+                    .expect_cap()
+                    .once()
+                    .return_const(PARAMS_MAX_CAPACITY);
+                let metric = Arc::new(ParamsMetric {
+                    rule_time_counter,
+                    rule_token_counter,
+                    ..Default::default()
+                });
+
+                let controller = gen_reject(rule, Some(metric));
+                // 10 tokens are refilled, less than the batch within the burst
+                let token = controller.perform_checking(010110.to_string(), 15);
+                assert!(token.is_blocked());
+                assert_eq!(0, old_qps.load(Ordering::SeqCst));
+                assert_eq!(
+                    utils::curr_time_millis() - 1001,
+                    last_add_token_time.load(Ordering::SeqCst)
+                );
+            }
         }
     }
 
@@ -92,15 +92,7 @@ impl<C: CounterTrait> Checker<C> for RejectChecker<C> {
                 let rest_qps = old_qps_arc.load(Ordering::SeqCst);
                 let to_add_token_num =
                     pass_time as u64 * token_count / (owner.rule().duration_in_sec * 1000);
-                let mut new_qps = {
-                    if to_add_token_num + rest_qps > max_count {
-                        max_count - batch_count as u64
-                    } else {
-                        to_add_token_num + rest_qps - batch_count as u64
-                    }
-                };
-
-                if new_qps < 0 {
+                if to_add_token_num + rest_qps < batch_count as u64 {
                     let msg = format!("hotspot reject check blocked, request batch count is more than available token count, arg: {:?}", arg);
                     return TokenResult::new_blocked_with_cause(
                         BlockType::HotSpotParamFlow,
@@ -109,6 +101,13 @@ impl<C: CounterTrait> Checker<C> for RejectChecker<C> {
                         Arc::new(token_count),
                     );
                 }
+                let new_qps = {
+                    if to_add_token_num + rest_qps > max_count {
+                        max_count - batch_count as u64
+                    } else {
+                        to_add_token_num + rest_qps - batch_count as u64
+                    }
+                };
                 if old_qps_arc
                     .compare_exchange(rest_qps, new_qps, Ordering::SeqCst, Ordering::Relaxed)
                     .is_ok()
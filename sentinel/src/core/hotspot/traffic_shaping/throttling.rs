@@ -12,6 +12,7 @@ use std::sync::{
     atomic::{AtomicI64, Ordering},
     Arc, Mutex, Weak,
 };
+use std::time::Duration;
 
 static BLOCK_MSG_QUEUEING: &'static str = "flow throttling check blocked, threshold is <= 0.0";
 
@@ -93,12 +94,17 @@ impl<C: CounterTrait> Checker<C> for ThrottlingChecker<C> {
                 }
             } else {
                 let msg = format!("hotspot throttling check blocked, wait time exceedes max queueing time, arg: {:?}", arg);
+                // the request may pass once the queueing time is acceptable
+                let retry_after = Duration::from_millis(
+                    expected_time - current_time_in_ms - owner.rule().max_queueing_time_ms,
+                );
                 return TokenResult::new_blocked_with_cause(
                     BlockType::HotSpotParamFlow,
                     msg,
                     owner.rule.clone(),
                     Arc::new(token_count),
-                );
+                )
+                .with_retry_after(retry_after);
             }
         }
     }
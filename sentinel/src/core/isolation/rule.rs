@@ -1,4 +1,7 @@
-use crate::{base::SentinelRule, logging, system_metric};
+use crate::{
+    base::{Diagnostic, SentinelRule},
+    logging, system_metric,
+};
 use crate::{Error, Result};
 use serde::{Deserialize, Serialize};
 use serde_json;
@@ -46,16 +49,22 @@ impl SentinelRule for Rule {
         format!("{:?}", self.metric_type)
     }
 
-    fn is_valid(&self) -> Result<()> {
+    fn rule_id(&self) -> Option<String> {
+        self.id.clone()
+    }
+
+    fn diagnose(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
         if self.resource.len() == 0 {
-            return Err(Error::msg("empty resource of isolation rule"));
+            diagnostics.push(Diagnostic::error(
+                "resource",
+                "empty resource of isolation rule",
+            ));
         }
-
         if self.threshold == 0 {
-            return Err(Error::msg("zero threshold"));
+            diagnostics.push(Diagnostic::error("threshold", "zero threshold"));
         }
-
-        Ok(())
+        diagnostics
     }
 }
 
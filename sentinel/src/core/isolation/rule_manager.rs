@@ -1,5 +1,6 @@
 use super::*;
-use crate::{base::SentinelRule, logging, utils};
+use crate::base::{check_direct_update, RuleChangeListener, RuleChangeListeners};
+use crate::{base::SentinelRule, logging, utils, utils::ShardedMap};
 use crate::{Error, Result};
 use lazy_static::lazy_static;
 use std::collections::HashMap;
@@ -9,37 +10,49 @@ use std::sync::{Arc, Mutex, RwLock, Weak};
 pub type RuleMap = HashMap<String, Vec<Arc<Rule>>>;
 
 lazy_static! {
-    static ref RULE_MAP: RwLock<RuleMap> = RwLock::new(RuleMap::new());
+    static ref RULE_MAP: ShardedMap<String, Vec<Arc<Rule>>> = ShardedMap::new();
     static ref CURRENT_RULES: Mutex<RuleMap> = Mutex::new(RuleMap::new());
 }
 
+lazy_static! {
+    static ref RULE_CHANGE_LISTENERS: RuleChangeListeners<Rule> = RuleChangeListeners::new();
+}
+
+/// `register_rule_change_listeners` registers the listeners notified after the rules are changed.
+pub fn register_rule_change_listeners(listeners: Vec<Arc<dyn RuleChangeListener<Rule>>>) {
+    RULE_CHANGE_LISTENERS.register(listeners);
+}
+
+pub fn clear_rule_change_listeners() {
+    RULE_CHANGE_LISTENERS.clear();
+}
+
 /// `get_rules` returns all the rules in the global `RULE_MAP`
-// This func acquires a read lock on global `RULE_MAP`,
-// please release the lock before calling this func
+// This func acquires the read locks on the shards of global `RULE_MAP` one by one
 pub fn get_rules() -> Vec<Arc<Rule>> {
-    let rule_map = RULE_MAP.read().unwrap();
-    let mut rules = Vec::with_capacity(rule_map.len());
-    for r in rule_map.values() {
-        rules.append(&mut r.clone());
-    }
+    let mut rules = Vec::new();
+    RULE_MAP.for_each(|_, r| rules.extend(r.iter().cloned()));
     rules
 }
 
 // `get_rules_of_resource` returns specific resource's rules
-// This func acquires a read lock on global `RULE_MAP`,
-// please release the lock before calling this func
+// This func only acquires the read lock on a shard of global `RULE_MAP`,
+// thus it never waits for the loading of the rules
 pub fn get_rules_of_resource(res: &String) -> Vec<Arc<Rule>> {
-    let mut placeholder = Vec::new();
-    let rule_map = RULE_MAP.read().unwrap();
-    let res_rules = rule_map.get(res).unwrap_or(&mut placeholder);
-    let mut rules = res_rules.clone();
-    rules
+    RULE_MAP.get(res).unwrap_or_default()
 }
 
 /// `load_rules` loads given isolation rules to the rule manager, while all previous rules will be replaced.
 // This func acquires the locks on global `CURRENT_RULES` and `RULE_MAP`,
 // please release the locks before calling this func
 pub fn load_rules(rules: Vec<Arc<Rule>>) {
+    if check_direct_update("`isolation::load_rules()`").is_err() {
+        return;
+    }
+    RULE_CHANGE_LISTENERS.watch(get_rules, || do_load_rules(rules))
+}
+
+fn do_load_rules(rules: Vec<Arc<Rule>>) {
     let mut res_rules_map = RuleMap::new();
     for rule in rules {
         let mut val = res_rules_map
@@ -76,8 +89,7 @@ pub fn load_rules(rules: Vec<Arc<Rule>>) {
     }
 
     let start = utils::curr_time_nanos();
-    let mut rule_map = RULE_MAP.write().unwrap();
-    *rule_map = valid_res_rule_map;
+    RULE_MAP.replace(valid_res_rule_map);
     *current_rules = res_rules_map;
 
     logging::debug!(
@@ -86,7 +98,7 @@ pub fn load_rules(rules: Vec<Arc<Rule>>) {
     );
     logging::info!(
         "[SystemRuleManager] Isolation rules loaded, rules {:?}",
-        rule_map
+        *RULE_MAP
     );
 }
 
@@ -94,6 +106,14 @@ pub fn load_rules(rules: Vec<Arc<Rule>>) {
 // This func acquires the locks on global `CURRENT_RULES` and `RULE_MAP`,
 // please release the locks before calling this func
 pub fn load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<bool> {
+    check_direct_update("`isolation::load_rules_of_resource()`")?;
+    RULE_CHANGE_LISTENERS.watch(
+        || get_rules_of_resource(res),
+        || do_load_rules_of_resource(res, rules),
+    )
+}
+
+fn do_load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<bool> {
     if res.len() == 0 {
         return Err(Error::msg("empty resource"));
     }
@@ -134,12 +154,9 @@ pub fn load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<boo
     let valid_res_rules_string = format!("{:?}", &valid_res_rules);
     let start = utils::curr_time_nanos();
     if valid_res_rules.len() == 0 {
-        RULE_MAP.write().unwrap().remove(res);
+        RULE_MAP.remove(res);
     } else {
-        RULE_MAP
-            .write()
-            .unwrap()
-            .insert(res.clone(), valid_res_rules);
+        RULE_MAP.insert(res.clone(), valid_res_rules);
     }
     CURRENT_RULES.lock().unwrap().insert(res.clone(), rules);
 
@@ -158,16 +175,35 @@ pub fn load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<boo
 // This func acquires the locks on global `CURRENT_RULES` and `RULE_MAP`,
 // please release the locks before calling this func
 pub fn clear_rules() {
-    CURRENT_RULES.lock().unwrap().clear();
-    RULE_MAP.write().unwrap().clear();
+    if check_direct_update("`isolation::clear_rules()`").is_err() {
+        return;
+    }
+    RULE_CHANGE_LISTENERS.watch(get_rules, do_clear_rules)
+}
+
+fn do_clear_rules() {
+    let mut current_rules = CURRENT_RULES.lock().unwrap();
+    current_rules.clear();
+    RULE_MAP.clear();
 }
 
 /// ClearRulesOfResource clears resource level rules in isolation module.
 // This func acquires the locks on global `CURRENT_RULES` and `RULE_MAP`,
 // please release the locks before calling this func
 pub fn clear_rules_of_resource(res: &String) {
-    CURRENT_RULES.lock().unwrap().remove(res);
-    RULE_MAP.write().unwrap().remove(res);
+    if check_direct_update("`isolation::clear_rules_of_resource()`").is_err() {
+        return;
+    }
+    RULE_CHANGE_LISTENERS.watch(
+        || get_rules_of_resource(res),
+        || do_clear_rules_of_resource(res),
+    )
+}
+
+fn do_clear_rules_of_resource(res: &String) {
+    let mut current_rules = CURRENT_RULES.lock().unwrap();
+    current_rules.remove(res);
+    RULE_MAP.remove(res);
 }
 
 #[cfg(test)]
@@ -219,7 +255,7 @@ mod test {
             r4,
             Arc::clone(&r5),
         ]);
-        let rule_map = RULE_MAP.read().unwrap();
+        let rule_map = RULE_MAP.snapshot();
         let current_rules = CURRENT_RULES.lock().unwrap();
         assert_eq!(2, rule_map.len());
         assert_eq!(2, rule_map["abc1"].len());
@@ -233,7 +269,7 @@ mod test {
         drop(current_rules);
 
         clear_rules();
-        assert_eq!(0, RULE_MAP.read().unwrap().len());
+        assert_eq!(0, RULE_MAP.snapshot().len());
         assert_eq!(0, CURRENT_RULES.lock().unwrap().len());
     }
 
@@ -246,7 +282,7 @@ mod test {
             ..Default::default()
         });
         let result = load_rules_of_resource(&"".into(), vec![r1]);
-        assert_eq!(0, RULE_MAP.read().unwrap().len());
+        assert_eq!(0, RULE_MAP.snapshot().len());
         result.unwrap();
     }
 
@@ -280,7 +316,7 @@ mod test {
         // that is, rule of "abc3" cannot be loaded to "abc1"
         load_rules_of_resource(&"abc1".into(), vec![Arc::clone(&r1), Arc::clone(&r2)]);
         load_rules_of_resource(&"abc3".into(), vec![Arc::clone(&r3), Arc::clone(&r4)]);
-        let rule_map = RULE_MAP.read().unwrap();
+        let rule_map = RULE_MAP.snapshot();
         let current_rules = CURRENT_RULES.lock().unwrap();
         assert_eq!(2, rule_map.len());
         assert_eq!(2, rule_map["abc1"].len());
@@ -294,10 +330,10 @@ mod test {
         drop(current_rules);
 
         clear_rules_of_resource(&"abc1".into());
-        assert_eq!(1, RULE_MAP.read().unwrap().len());
+        assert_eq!(1, RULE_MAP.snapshot().len());
         assert_eq!(1, CURRENT_RULES.lock().unwrap().len());
         clear_rules_of_resource(&"abc3".into());
-        assert_eq!(0, RULE_MAP.read().unwrap().len());
+        assert_eq!(0, RULE_MAP.snapshot().len());
         assert_eq!(0, CURRENT_RULES.lock().unwrap().len());
     }
 }
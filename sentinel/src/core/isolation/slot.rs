@@ -9,8 +9,6 @@ use crate::{
 };
 use lazy_static::lazy_static;
 use std::any::Any;
-use std::cell::RefCell;
-use std::rc::Rc;
 use std::sync::Arc;
 
 const RULE_CHECK_SLOT_ORDER: u32 = 3000;
@@ -33,46 +31,24 @@ impl BaseSlot for AdaptiveSlot {
 }
 
 impl RuleCheckSlot for AdaptiveSlot {
-    cfg_async! {
-        fn check(&self, ctx: &ContextPtr) -> TokenResult {
-            let res_name = ctx.read().unwrap().resource().name().clone();
-            if res_name.len() == 0 {
-                return ctx.read().unwrap().result().clone();
-            }
-            let (passed, rule, snapshot) = can_pass_check(ctx, &res_name);
-            if !passed {
-                // never panic
-                ctx.write().unwrap()
-                    .set_result(TokenResult::new_blocked_with_cause(
-                        BlockType::SystemFlow,
-                        "concurrency exceeds threshold".into(),
-                        rule.unwrap(),
-                        snapshot.unwrap(),
-                    ));
-            }
+    fn check(&self, ctx: &ContextPtr) -> TokenResult {
+        let res_name = ctx.read().unwrap().resource().name().clone();
+        if res_name.len() == 0 {
             return ctx.read().unwrap().result().clone();
         }
-    }
-
-    cfg_not_async! {
-        fn check(&self, ctx: &ContextPtr) -> TokenResult {
-            let res_name = ctx.borrow().resource().name().clone();
-            if res_name.len() == 0 {
-                return ctx.borrow().result().clone();
-            }
-            let (passed, rule, snapshot) = can_pass_check(ctx, &res_name);
-            if !passed {
-                // never panic
-                ctx.borrow_mut()
-                    .set_result(TokenResult::new_blocked_with_cause(
-                        BlockType::SystemFlow,
-                        "concurrency exceeds threshold".into(),
-                        rule.unwrap(),
-                        snapshot.unwrap(),
-                    ));
-            }
-            return ctx.borrow().result().clone();
+        let (passed, rule, snapshot) = can_pass_check(ctx, &res_name);
+        if !passed {
+            // never panic
+            ctx.write()
+                .unwrap()
+                .set_result(TokenResult::new_blocked_with_cause(
+                    BlockType::SystemFlow,
+                    "concurrency exceeds threshold".into(),
+                    rule.unwrap(),
+                    snapshot.unwrap(),
+                ));
         }
+        return ctx.read().unwrap().result().clone();
     }
 }
 
@@ -80,10 +56,7 @@ fn can_pass_check(
     ctx: &ContextPtr,
     res: &String,
 ) -> (bool, Option<Arc<Rule>>, Option<Arc<Snapshot>>) {
-    cfg_if_async! {
-        let ctx = ctx.read().unwrap(),
-        let ctx = ctx.borrow()
-    };
+    let ctx = ctx.read().unwrap();
     let stat_node = ctx.stat_node().unwrap();
     let batch_count = ctx.input().batch_count();
     for rule in get_rules_of_resource(res) {
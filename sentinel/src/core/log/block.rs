@@ -0,0 +1,200 @@
+//! The block log.
+//! Under heavy rejection, logging every blocked entry floods the disks and amplifies the overload,
+//! thus at most `sample_per_sec` block events are logged per resource per second,
+//! and an aggregate line of each blocked resource is logged every `aggregate_interval_sec`.
+//! The events are logged to the target `logging::BLOCK_LOG_TARGET`.
+use crate::base::BlockError;
+use crate::utils::{self, background};
+use crate::{config, logging};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+lazy_static! {
+    static ref BLOCK_LOG: BlockLog = BlockLog::new();
+}
+static AGGREGATE_STARTED: AtomicBool = AtomicBool::new(false);
+
+const SAMPLE_WINDOW_MS: u64 = 1000;
+
+#[derive(Debug, Default)]
+struct BlockStat {
+    window_start_ms: u64,
+    // the logged events in current sampling window
+    sampled: u32,
+    // the blocked entries since the last aggregate line
+    blocked: u64,
+    suppressed: u64,
+}
+
+/// `BlockSummary` is the aggregation of the blocked entries of a resource.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockSummary {
+    pub resource: String,
+    pub blocked: u64,
+    /// the blocked entries which were not logged separately
+    pub suppressed: u64,
+}
+
+pub struct BlockLog {
+    stats: Mutex<HashMap<String, BlockStat>>,
+}
+
+impl BlockLog {
+    pub fn new() -> Self {
+        BlockLog {
+            stats: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// `sample` counts the blocked entry, and returns whether it should be logged.
+    pub fn sample(&self, resource: &str, now: u64, sample_per_sec: u32) -> bool {
+        let mut stats = self.stats.lock().unwrap();
+        if !stats.contains_key(resource) {
+            stats.insert(resource.into(), BlockStat::default());
+        }
+        let stat = stats.get_mut(resource).unwrap();
+        stat.blocked += 1;
+        if now.saturating_sub(stat.window_start_ms) >= SAMPLE_WINDOW_MS {
+            stat.window_start_ms = now;
+            stat.sampled = 0;
+        }
+        if stat.sampled < sample_per_sec {
+            stat.sampled += 1;
+            true
+        } else {
+            stat.suppressed += 1;
+            false
+        }
+    }
+
+    /// `take_summaries` returns and resets the aggregations of all the blocked resources.
+    pub fn take_summaries(&self) -> Vec<BlockSummary> {
+        let stats = std::mem::take(&mut *self.stats.lock().unwrap());
+        let mut summaries: Vec<BlockSummary> = stats
+            .into_iter()
+            .map(|(resource, stat)| BlockSummary {
+                resource,
+                blocked: stat.blocked,
+                suppressed: stat.suppressed,
+            })
+            .collect();
+        summaries.sort_by(|a, b| a.resource.cmp(&b.resource));
+        summaries
+    }
+}
+
+impl Default for BlockLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `log_blocked` logs the blocked entry of the resource if it is sampled.
+pub fn log_blocked(resource: &str, block_error: &BlockError) {
+    if !logging::event_enabled(logging::EventKind::Block, logging::Level::Info) {
+        return;
+    }
+    init_aggregate_task();
+    let sample_per_sec = config::block_log_sample_per_sec();
+    if !BLOCK_LOG.sample(resource, utils::curr_time_millis(), sample_per_sec) {
+        return;
+    }
+    let event = logging::Event::new(
+        logging::EventKind::Block,
+        resource.into(),
+        format!("[Block] {}", block_error),
+    )
+    .with_rule_id(block_error.triggered_rule().and_then(|r| r.rule_id()))
+    .with_state(block_error.block_type().to_string())
+    .with_snapshot(block_error.triggered_value().map(|s| format!("{:?}", s)));
+    logging::log_event(logging::Level::Info, &event);
+}
+
+/// `log_summaries` logs the aggregate lines, and resets the aggregations.
+pub fn log_summaries() {
+    let summaries = BLOCK_LOG.take_summaries();
+    let aggregate_interval_sec = config::block_log_aggregate_interval_sec();
+    // the stats are still reset if the aggregate lines are disabled
+    if aggregate_interval_sec == 0 {
+        return;
+    }
+    for summary in summaries {
+        let event = logging::Event::new(
+            logging::EventKind::BlockSummary,
+            summary.resource,
+            format!(
+                "[Block] Blocked in the last {}s, {} of them were not logged",
+                aggregate_interval_sec, summary.suppressed
+            ),
+        )
+        .with_count(summary.blocked);
+        logging::log_event(logging::Level::Info, &event);
+    }
+}
+
+// the task is stopped by `api::shutdown()`, which logs the last aggregate lines itself
+fn init_aggregate_task() {
+    if background::is_stopped() || AGGREGATE_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let spawned = background::spawn_task("sentinel-block-log", || {
+        loop {
+            // the interval is resolved in every round, since the config can be reloaded
+            let interval_sec = config::block_log_aggregate_interval_sec().max(1);
+            if !background::sleep_unless_stopped(interval_sec as u64 * 1000) {
+                break;
+            }
+            log_summaries();
+        }
+        AGGREGATE_STARTED.store(false, Ordering::SeqCst);
+    });
+    // it is not retried, e.g., there are no threads on wasm32
+    if let Err(err) = spawned {
+        logging::error!(
+            "[Block] Failed to start the aggregation of the block log, error: {:?}",
+            err
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample() {
+        let log = BlockLog::new();
+        let sampled = (0..5).filter(|_| log.sample("a", 1000, 2)).count();
+        assert_eq!(sampled, 2);
+        assert!(log.sample("b", 1500, 2));
+        assert!(!log.sample("a", 1999, 2));
+        // a new sampling window
+        assert!(log.sample("a", 2000, 2));
+        // no event is logged
+        assert!(!log.sample("c", 2000, 0));
+
+        assert_eq!(
+            log.take_summaries(),
+            vec![
+                BlockSummary {
+                    resource: "a".into(),
+                    blocked: 7,
+                    suppressed: 4,
+                },
+                BlockSummary {
+                    resource: "b".into(),
+                    blocked: 1,
+                    suppressed: 0,
+                },
+                BlockSummary {
+                    resource: "c".into(),
+                    blocked: 1,
+                    suppressed: 1,
+                },
+            ]
+        );
+        assert!(log.take_summaries().is_empty());
+    }
+}
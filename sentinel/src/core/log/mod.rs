@@ -1,5 +1,11 @@
-pub mod metric;
+pub mod block;
+cfg_metric_log! {
+    pub mod metric;
+}
 pub mod slot;
 
-pub use metric::*;
+pub use block::*;
+cfg_metric_log! {
+    pub use metric::*;
+}
 pub use slot::*;
@@ -1,7 +1,5 @@
 use crate::base::{BaseSlot, BlockError, ContextPtr, StatSlot};
 use lazy_static::lazy_static;
-use std::cell::RefCell;
-use std::rc::Rc;
 use std::sync::Arc;
 
 const STAT_SLOT_ORDER: u32 = 2000;
@@ -25,8 +23,10 @@ impl BaseSlot for Slot {
 impl StatSlot for Slot {
     fn on_entry_pass(&self, _ctx: ContextPtr) {}
 
-    // todo: write sentinel-block.log here
-    fn on_entry_blocked(&self, _ctx: ContextPtr, _block_error: Option<BlockError>) {}
+    fn on_entry_blocked(&self, ctx: ContextPtr, block_error: Option<BlockError>) {
+        let resource = ctx.read().unwrap().resource().name().clone();
+        super::log_blocked(&resource, &block_error.unwrap_or_default());
+    }
 
     fn on_completed(&self, _ctx: ContextPtr) {}
 }
@@ -10,5 +10,7 @@ pub mod config;
 pub mod flow;
 pub mod hotspot;
 // rule check slots
+pub mod fault;
 pub mod isolation;
+pub mod retry;
 pub mod system;
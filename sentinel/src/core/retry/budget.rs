@@ -0,0 +1,162 @@
+use super::{get_budgets_of_resource, Rule};
+use crate::base::{self, MetricEvent, ReadStat, ResourceType, StatNode};
+use crate::stat::{self, BucketLeapArray, SlidingWindowMetric};
+use crate::utils::curr_time_millis;
+use crate::{config, Error, Result};
+use std::sync::Arc;
+
+/// `RetryBudget` counts the retries of the resource in the sliding window of the rule,
+/// and compares them with the passed requests in the same window of the resource statistic.
+#[derive(Debug)]
+pub struct RetryBudget {
+    rule: Arc<Rule>,
+    // the passed requests of the resource, i.e., the global statistic of the resource node
+    requests: Arc<dyn ReadStat>,
+    // only `MetricEvent::Pass` is counted, as the retries
+    retries: Arc<BucketLeapArray>,
+    // read in the same window as the requests
+    retries_metric: SlidingWindowMetric,
+}
+
+impl RetryBudget {
+    pub fn new(rule: Arc<Rule>) -> Result<Self> {
+        let interval_ms = rule.stat_interval_ms;
+        let res_node = stat::get_or_create_resource_node(&rule.resource, &ResourceType::Common);
+        let (sample_count, requests) = if interval_ms == config::metric_stat_interval_ms() {
+            // default case, use the resource's default statistic
+            (
+                config::metric_stat_sample_count(),
+                res_node.default_metric(),
+            )
+        } else {
+            let bucket_length_ms = config::global_stat_bucket_length_ms();
+            let sample_count =
+                if interval_ms > bucket_length_ms && interval_ms % bucket_length_ms == 0 {
+                    interval_ms / bucket_length_ms
+                } else {
+                    1
+                };
+            base::check_validity_for_reuse_statistic(
+                sample_count,
+                interval_ms,
+                config::global_stat_sample_count_total(),
+                config::global_stat_interval_ms_total(),
+            )
+            .map_err(|_| {
+                Error::msg(format!(
+                    "{} stat_interval_ms: {}",
+                    base::GLOBAL_STATISTIC_NON_REUSABLE_ERROR,
+                    interval_ms
+                ))
+            })?;
+            (
+                sample_count,
+                res_node.generate_read_stat(sample_count, interval_ms)?,
+            )
+        };
+        let retries = Arc::new(BucketLeapArray::new(sample_count, interval_ms)?);
+        let retries_metric =
+            SlidingWindowMetric::new(sample_count, interval_ms, Arc::clone(&retries))?;
+        Ok(RetryBudget {
+            rule,
+            requests,
+            retries,
+            retries_metric,
+        })
+    }
+
+    pub fn rule(&self) -> &Arc<Rule> {
+        &self.rule
+    }
+
+    /// `retries` returns the retries in current window.
+    pub fn retries(&self) -> u64 {
+        self.retries_metric.sum(MetricEvent::Pass)
+    }
+
+    /// `allowed_retries` returns the max retries in current window,
+    /// the larger of the ratio of the passed requests and the floor.
+    pub fn allowed_retries(&self) -> f64 {
+        let interval_sec = self.rule.stat_interval_ms as f64 / 1000.0;
+        let floor = self.rule.min_retries_per_sec as f64 * interval_sec;
+        let by_ratio = self.requests.sum(MetricEvent::Pass) as f64 * self.rule.max_retry_ratio;
+        floor.max(by_ratio)
+    }
+
+    /// `has_budget` returns whether one more retry is allowed in current window.
+    pub fn has_budget(&self) -> bool {
+        (self.retries() + 1) as f64 <= self.allowed_retries()
+    }
+
+    /// `record_retry` counts a retry in current window.
+    pub fn record_retry(&self) {
+        if let Err(err) = self
+            .retries
+            .add_count_with_time(curr_time_millis(), MetricEvent::Pass, 1)
+        {
+            crate::logging::error!("[RetryBudget] Failed to record the retry, error: {:?}", err);
+        }
+    }
+}
+
+/// `try_acquire_retry` returns whether the failed call of the resource could be retried, and counts the retry if so.
+/// The retry is allowed only if all the budgets of the resource allow it, and always allowed without any budget.
+/// Notice that the budgets are checked and counted separately, thus they may be exceeded slightly under concurrency.
+pub fn try_acquire_retry(resource: &String) -> bool {
+    let budgets = get_budgets_of_resource(resource);
+    if !budgets.iter().all(|budget| budget.has_budget()) {
+        return false;
+    }
+    for budget in &budgets {
+        budget.record_retry();
+    }
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::WriteStat;
+    use crate::utils::MockClock;
+
+    #[test]
+    fn ratio_and_floor() {
+        let clock = Arc::new(MockClock::starting_at(1_600_000_000_000));
+        let _guard = clock.install();
+        let resource = String::from("retry_budget_ratio_and_floor");
+        let budget = RetryBudget::new(Arc::new(Rule {
+            resource: resource.clone(),
+            max_retry_ratio: 0.1,
+            min_retries_per_sec: 2,
+            ..Default::default()
+        }))
+        .unwrap();
+        // the floor
+        assert_eq!(budget.allowed_retries(), 2.0);
+        budget.record_retry();
+        assert!(budget.has_budget());
+        budget.record_retry();
+        assert!(!budget.has_budget());
+
+        // the ratio of the passed requests
+        let node = stat::get_or_create_resource_node(&resource, &ResourceType::Common);
+        node.add_count(MetricEvent::Pass, 50);
+        assert_eq!(budget.allowed_retries(), 5.0);
+        assert!(budget.has_budget());
+
+        // the next window
+        clock.advance_millis(1000);
+        assert_eq!(budget.retries(), 0);
+        assert_eq!(budget.allowed_retries(), 2.0);
+    }
+
+    #[test]
+    fn non_reusable_interval() {
+        let budget = RetryBudget::new(Arc::new(Rule {
+            resource: "retry_budget_non_reusable_interval".into(),
+            stat_interval_ms: 1234,
+            ..Default::default()
+        }));
+        assert!(budget.is_err());
+    }
+}
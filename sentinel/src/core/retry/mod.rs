@@ -0,0 +1,20 @@
+//! mod retry provides the retry budgets, which bound the retries of the failed calls of the resources,
+//! so the client retries cannot amplify an outage that the circuit breakers are trying to contain.
+//!
+//! The callers consult `try_acquire_retry()` before retrying a call of the resource,
+//! a retry is allowed while the retries in the sliding window of the rule stay below both
+//!  1. `max_retry_ratio` of the passed requests of the resource in the window,
+//!     which are the entry statistics of the resource, including the retries themselves,
+//!  2. and the floor `min_retries_per_sec`, thus the resources of low traffic can still retry.
+//!
+//! When the requests are blocked (e.g., the breaker is open), the budget shrinks to the floor.
+//! The retry combinator of `api::run()` consults the budgets automatically.
+//! The rules are loaded by `load_rules()` or in the `RuleSet` like the other rule types.
+
+pub mod budget;
+pub mod rule;
+pub mod rule_manager;
+
+pub use budget::*;
+pub use rule::*;
+pub use rule_manager::*;
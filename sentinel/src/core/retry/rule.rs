@@ -0,0 +1,104 @@
+use crate::base::{Diagnostic, SentinelRule};
+use serde::{Deserialize, Serialize};
+use serde_json;
+use std::fmt;
+
+/// `Rule` describes the retry budget of the resource.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Rule {
+    /// `id` represents the unique ID of the rule (optional).
+    pub id: Option<String>,
+    /// `resource` represents the target resource definition
+    pub resource: String,
+    /// `max_retry_ratio` is the max ratio of the retries to the passed requests in the window, 0.1 by default.
+    pub max_retry_ratio: f64,
+    /// `min_retries_per_sec` is the retries always allowed per second, regardless of the ratio, 10 by default.
+    pub min_retries_per_sec: u32,
+    /// `stat_interval_ms` is the length of the sliding window, 1000 ms by default,
+    /// it must be able to reuse the global statistic of the resource.
+    pub stat_interval_ms: u32,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Rule {
+            id: None,
+            resource: String::default(),
+            max_retry_ratio: 0.1,
+            min_retries_per_sec: 10,
+            stat_interval_ms: 1000,
+        }
+    }
+}
+
+impl SentinelRule for Rule {
+    fn resource_name(&self) -> String {
+        self.resource.clone()
+    }
+
+    fn rule_id(&self) -> Option<String> {
+        self.id.clone()
+    }
+
+    fn diagnose(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        if self.resource.len() == 0 {
+            diagnostics.push(Diagnostic::error(
+                "resource",
+                "empty resource of retry rule",
+            ));
+        }
+        if !self.max_retry_ratio.is_finite() || self.max_retry_ratio < 0.0 {
+            diagnostics.push(Diagnostic::error(
+                "max_retry_ratio",
+                "max_retry_ratio must be a finite number not less than 0.0",
+            ));
+        }
+        if self.stat_interval_ms == 0 {
+            diagnostics.push(Diagnostic::error(
+                "stat_interval_ms",
+                "invalid stat_interval_ms",
+            ));
+        }
+        if self.max_retry_ratio == 0.0 && self.min_retries_per_sec == 0 {
+            diagnostics.push(Diagnostic::warning(
+                "max_retry_ratio",
+                "all the retries are rejected, since both max_retry_ratio and min_retries_per_sec are 0",
+            ));
+        }
+        diagnostics
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fmtted = serde_json::to_string_pretty(self).unwrap();
+        write!(f, "{}", fmtted)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "max_retry_ratio must be a finite number")]
+    fn invalid_ratio() {
+        let rule = Rule {
+            resource: "invalid_ratio".into(),
+            max_retry_ratio: -0.1,
+            ..Default::default()
+        };
+        rule.is_valid().unwrap();
+    }
+
+    #[test]
+    fn deserialize_with_default() {
+        let rule: Rule =
+            serde_json::from_str(r#"{"resource": "abc", "max_retry_ratio": 0.2}"#).unwrap();
+        assert_eq!(rule.min_retries_per_sec, 10);
+        assert_eq!(rule.stat_interval_ms, 1000);
+        assert!(rule.is_valid().is_ok());
+    }
+}
@@ -0,0 +1,315 @@
+use super::*;
+use crate::base::{check_direct_update, RuleChangeListener, RuleChangeListeners};
+use crate::{base::SentinelRule, logging, utils, utils::ShardedMap};
+use crate::{Error, Result};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+pub type RuleMap = HashMap<String, Vec<Arc<Rule>>>;
+
+lazy_static! {
+    static ref BUDGET_MAP: ShardedMap<String, Vec<Arc<RetryBudget>>> = ShardedMap::new();
+    static ref CURRENT_RULES: Mutex<RuleMap> = Mutex::new(RuleMap::new());
+}
+
+lazy_static! {
+    static ref RULE_CHANGE_LISTENERS: RuleChangeListeners<Rule> = RuleChangeListeners::new();
+}
+
+/// `register_rule_change_listeners` registers the listeners notified after the rules are changed.
+pub fn register_rule_change_listeners(listeners: Vec<Arc<dyn RuleChangeListener<Rule>>>) {
+    RULE_CHANGE_LISTENERS.register(listeners);
+}
+
+pub fn clear_rule_change_listeners() {
+    RULE_CHANGE_LISTENERS.clear();
+}
+
+/// `get_rules` returns all the effective retry rules
+// This func acquires the read locks on the shards of global `BUDGET_MAP` one by one
+pub fn get_rules() -> Vec<Arc<Rule>> {
+    let mut rules = Vec::new();
+    BUDGET_MAP.for_each(|_, budgets| {
+        rules.extend(budgets.iter().map(|budget| Arc::clone(budget.rule())))
+    });
+    rules
+}
+
+/// `get_rules_of_resource` returns the effective retry rules of the resource
+pub fn get_rules_of_resource(res: &String) -> Vec<Arc<Rule>> {
+    get_budgets_of_resource(res)
+        .iter()
+        .map(|budget| Arc::clone(budget.rule()))
+        .collect()
+}
+
+// This func only acquires the read lock on a shard of global `BUDGET_MAP`,
+// thus it never waits for the loading of the rules
+pub(crate) fn get_budgets_of_resource(res: &String) -> Vec<Arc<RetryBudget>> {
+    BUDGET_MAP.get(res).unwrap_or_default()
+}
+
+/// `build_budgets` builds the budgets of the resource from the rules,
+/// the old budget of an equivalent rule is reused, so that the retries counted in it are kept.
+fn build_budgets(
+    res: &String,
+    rules: &[Arc<Rule>],
+    old_budgets: &mut Vec<Arc<RetryBudget>>,
+) -> Vec<Arc<RetryBudget>> {
+    let mut budgets = Vec::with_capacity(rules.len());
+    for rule in rules {
+        if let Some(eq_idx) = old_budgets
+            .iter()
+            .position(|old_budget| old_budget.rule() == rule)
+        {
+            // reuse the old budget and remove it from old_budgets
+            budgets.push(old_budgets.remove(eq_idx));
+            continue;
+        }
+        match rule
+            .is_valid()
+            .and_then(|_| RetryBudget::new(Arc::clone(rule)))
+        {
+            Ok(budget) => budgets.push(Arc::new(budget)),
+            Err(err) => logging::warn!(
+                "[Retry build_budgets] Ignoring invalid retry rule {:?} of resource {}, reason: {:?}",
+                rule,
+                res,
+                err
+            ),
+        }
+    }
+    budgets
+}
+
+/// `load_rules` loads given retry rules to the rule manager, while all previous rules will be replaced.
+// This func acquires the locks on global `CURRENT_RULES` and `BUDGET_MAP`,
+// please release the locks before calling this func
+pub fn load_rules(rules: Vec<Arc<Rule>>) {
+    if check_direct_update("`retry::load_rules()`").is_err() {
+        return;
+    }
+    RULE_CHANGE_LISTENERS.watch(get_rules, || do_load_rules(rules))
+}
+
+fn do_load_rules(rules: Vec<Arc<Rule>>) {
+    let mut res_rules_map = RuleMap::new();
+    for rule in rules {
+        res_rules_map
+            .entry(rule.resource.clone())
+            .or_insert(Vec::new())
+            .push(rule);
+    }
+    let mut current_rules = CURRENT_RULES.lock().unwrap();
+    if &*current_rules == &res_rules_map {
+        logging::info!(
+            "[Retry] Load rules is the same with current rules, so ignore load operation."
+        );
+        return;
+    }
+
+    // ignore invalid rules
+    let mut budget_map = HashMap::with_capacity(res_rules_map.len());
+    for (res, rules) in &res_rules_map {
+        let mut old_budgets = BUDGET_MAP.get(res).unwrap_or_default();
+        let budgets = build_budgets(res, rules, &mut old_budgets);
+        if budgets.len() > 0 {
+            budget_map.insert(res.clone(), budgets);
+        }
+    }
+
+    let start = utils::curr_time_nanos();
+    BUDGET_MAP.replace(budget_map);
+    *current_rules = res_rules_map;
+
+    logging::debug!(
+        "[Retry load_rules] Time statistic(ns) for updating retry rule, timeCost {:?}",
+        utils::curr_time_nanos() - start
+    );
+    logging::info!(
+        "[RetryRuleManager] Retry rules loaded, rules {:?}",
+        *BUDGET_MAP
+    );
+}
+
+/// `load_rules_of_resource` loads the given resource's retry rules to the rule manager, while all previous resource's rules will be replaced.
+// This func acquires the locks on global `CURRENT_RULES` and `BUDGET_MAP`,
+// please release the locks before calling this func
+pub fn load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<bool> {
+    check_direct_update("`retry::load_rules_of_resource()`")?;
+    RULE_CHANGE_LISTENERS.watch(
+        || get_rules_of_resource(res),
+        || do_load_rules_of_resource(res, rules),
+    )
+}
+
+fn do_load_rules_of_resource(res: &String, rules: Vec<Arc<Rule>>) -> Result<bool> {
+    if res.len() == 0 {
+        return Err(Error::msg("empty resource"));
+    }
+    let mut current_rules = CURRENT_RULES.lock().unwrap();
+    if rules.len() == 0 {
+        current_rules.remove(res);
+        BUDGET_MAP.remove(res);
+        logging::info!("[Retry] clear resource level rules, resource {}", res);
+        return Ok(true);
+    }
+    if current_rules.get(res) == Some(&rules) {
+        logging::info!(
+            "[Retry] Load resource level rules is the same with current resource level rules, so ignore load operation."
+        );
+        return Ok(false);
+    }
+
+    let mut old_budgets = BUDGET_MAP.get(res).unwrap_or_default();
+    let budgets = build_budgets(res, &rules, &mut old_budgets);
+    if budgets.len() == 0 {
+        BUDGET_MAP.remove(res);
+    } else {
+        BUDGET_MAP.insert(res.clone(), budgets);
+    }
+    logging::info!(
+        "[RetryRuleManager] Retry rules of resource {} loaded, rules {:?}",
+        res,
+        rules
+    );
+    current_rules.insert(res.clone(), rules);
+    Ok(true)
+}
+
+/// `clear_rules` clears all the rules in retry module
+// This func acquires the locks on global `CURRENT_RULES` and `BUDGET_MAP`,
+// please release the locks before calling this func
+pub fn clear_rules() {
+    if check_direct_update("`retry::clear_rules()`").is_err() {
+        return;
+    }
+    RULE_CHANGE_LISTENERS.watch(get_rules, do_clear_rules)
+}
+
+fn do_clear_rules() {
+    let mut current_rules = CURRENT_RULES.lock().unwrap();
+    current_rules.clear();
+    BUDGET_MAP.clear();
+}
+
+/// `clear_rules_of_resource` clears resource level rules in retry module.
+// This func acquires the locks on global `CURRENT_RULES` and `BUDGET_MAP`,
+// please release the locks before calling this func
+pub fn clear_rules_of_resource(res: &String) {
+    if check_direct_update("`retry::clear_rules_of_resource()`").is_err() {
+        return;
+    }
+    RULE_CHANGE_LISTENERS.watch(
+        || get_rules_of_resource(res),
+        || do_clear_rules_of_resource(res),
+    )
+}
+
+fn do_clear_rules_of_resource(res: &String) {
+    let mut current_rules = CURRENT_RULES.lock().unwrap();
+    current_rules.remove(res);
+    BUDGET_MAP.remove(res);
+}
+
+#[cfg(test)]
+mod test {
+    //! Some tests cannot run in parallel, since we cannot promise that
+    //! the global data structs are not modified before assertion.
+    use super::*;
+
+    #[test]
+    fn rules_of_resource() {
+        let res = "retry_rules_of_resource".to_string();
+        let valid = Arc::new(Rule {
+            resource: res.clone(),
+            ..Default::default()
+        });
+        let invalid = Arc::new(Rule {
+            resource: res.clone(),
+            max_retry_ratio: -1.0,
+            ..Default::default()
+        });
+        let rules = vec![Arc::clone(&valid), invalid];
+        assert!(load_rules_of_resource(&res, rules.clone()).unwrap());
+        assert_eq!(get_rules_of_resource(&res), vec![Arc::clone(&valid)]);
+        // the same rules are ignored
+        assert!(!load_rules_of_resource(&res, rules).unwrap());
+        clear_rules_of_resource(&res);
+        assert!(get_rules_of_resource(&res).is_empty());
+        assert!(try_acquire_retry(&res));
+        assert!(load_rules_of_resource(&String::new(), vec![valid]).is_err());
+    }
+
+    #[test]
+    #[ignore]
+    fn load_and_clear() {
+        let r1 = Arc::new(Rule {
+            resource: "abc1".into(),
+            ..Default::default()
+        });
+        let r2 = Arc::new(Rule {
+            resource: "abc2".into(),
+            min_retries_per_sec: 0,
+            max_retry_ratio: 0.0,
+            ..Default::default()
+        });
+        load_rules(vec![Arc::clone(&r1), Arc::clone(&r2)]);
+        assert_eq!(get_rules().len(), 2);
+        assert_eq!(get_rules_of_resource(&"abc2".into()), vec![r2]);
+        assert!(try_acquire_retry(&"abc1".into()));
+        assert!(!try_acquire_retry(&"abc2".into()));
+        clear_rules();
+        assert!(get_rules().is_empty());
+        assert!(try_acquire_retry(&"abc2".into()));
+    }
+
+    #[test]
+    #[ignore]
+    fn reuse_budgets() {
+        let r1 = Arc::new(Rule {
+            resource: "abc1".into(),
+            ..Default::default()
+        });
+        let r2 = Arc::new(Rule {
+            resource: "abc2".into(),
+            ..Default::default()
+        });
+        load_rules(vec![Arc::clone(&r1)]);
+        let budget = Arc::clone(&get_budgets_of_resource(&"abc1".into())[0]);
+        budget.record_retry();
+
+        // the equivalent rule keeps its budget when other rules are changed
+        load_rules(vec![
+            Arc::new(Rule {
+                resource: "abc1".into(),
+                ..Default::default()
+            }),
+            Arc::clone(&r2),
+        ]);
+        let reused = &get_budgets_of_resource(&"abc1".into())[0];
+        assert!(Arc::ptr_eq(&budget, reused));
+        assert_eq!(reused.retries(), 1);
+        load_rules_of_resource(&"abc2".into(), vec![]).unwrap();
+        assert!(Arc::ptr_eq(
+            &budget,
+            &get_budgets_of_resource(&"abc1".into())[0]
+        ));
+
+        // the changed rule gets a new budget
+        load_rules_of_resource(
+            &"abc1".into(),
+            vec![Arc::new(Rule {
+                resource: "abc1".into(),
+                max_retry_ratio: 0.5,
+                ..Default::default()
+            })],
+        )
+        .unwrap();
+        let rebuilt = &get_budgets_of_resource(&"abc1".into())[0];
+        assert!(!Arc::ptr_eq(&budget, rebuilt));
+        assert_eq!(rebuilt.retries(), 0);
+        clear_rules();
+    }
+}
@@ -331,4 +331,37 @@ mod test {
         let head = arr.get_valid_head().unwrap();
         assert_eq!(2, head.value().load(Ordering::SeqCst));
     }
+
+    #[test]
+    fn valid_head_with_mock_clock() {
+        let clock = Arc::new(crate::utils::MockClock::starting_at(1_600_000_000_000));
+        let _guard = clock.install();
+        let sample_count = 10;
+        let interval_ms = 1000;
+        let bucket_len_ms = 100;
+        let mut arr = LeapArrayAtomicU64::new(sample_count, interval_ms).unwrap();
+
+        arr.current_bucket()
+            .unwrap()
+            .value()
+            .store(1, Ordering::SeqCst);
+        clock.advance_millis(bucket_len_ms);
+        arr.current_bucket()
+            .unwrap()
+            .value()
+            .store(2, Ordering::SeqCst);
+        for i in 0..((sample_count as u64) - 2) {
+            clock.advance_millis(bucket_len_ms);
+            arr.current_bucket()
+                .unwrap()
+                .value()
+                .store(i + 3, Ordering::SeqCst);
+        }
+        let head = arr.get_valid_head().unwrap();
+        assert_eq!(1, head.value().load(Ordering::SeqCst));
+        // the window rotates, the eldest bucket is deprecated
+        clock.advance_millis(bucket_len_ms);
+        let head = arr.get_valid_head().unwrap();
+        assert_eq!(2, head.value().load(Ordering::SeqCst));
+    }
 }
@@ -12,6 +12,27 @@ pub trait MetricTrait: fmt::Debug + Default + Send + Sync {
     fn reset(&self);
 }
 
+/// the amount of the buckets of the round trip histogram,
+/// the last one covers `DEFAULT_STATISTIC_MAX_RT` and the longer round trips
+pub const RT_HISTOGRAM_LEN: usize = 17;
+
+/// `rt_histogram_index` returns the histogram bucket of the round trip time,
+/// the bucket `i > 0` covers `[2^(i-1), 2^i)` ms and the bucket `0` covers 0 ms.
+#[inline]
+pub fn rt_histogram_index(round_trip: u64) -> usize {
+    ((u64::BITS - round_trip.leading_zeros()) as usize).min(RT_HISTOGRAM_LEN - 1)
+}
+
+/// `rt_histogram_bounds` returns the range `[lower, upper)` of the round trip time of the histogram bucket.
+#[inline]
+pub fn rt_histogram_bounds(idx: usize) -> (u64, u64) {
+    if idx == 0 {
+        (0, 1)
+    } else {
+        (1 << (idx - 1), 1 << idx)
+    }
+}
+
 /// MetricBucket represents the entity to record metrics per minimum time unit (i.e. the bucket time span).
 /// Note that all operations of the MetricBucket are required to be thread-safe.
 #[derive(Debug)]
@@ -20,6 +41,8 @@ pub struct MetricBucket {
     counter: EnumMap<MetricEvent, AtomicU64>,
     min_rt: AtomicU64,
     max_concurrency: AtomicU32,
+    // the completions by the round trip time, for the percentiles
+    rt_histogram: [AtomicU64; RT_HISTOGRAM_LEN],
 }
 
 impl MetricTrait for MetricBucket {
@@ -30,6 +53,9 @@ impl MetricTrait for MetricBucket {
         self.min_rt
             .store(DEFAULT_STATISTIC_MAX_RT as u64, Ordering::SeqCst);
         self.max_concurrency.store(0, Ordering::SeqCst);
+        for item in &self.rt_histogram {
+            item.store(0, Ordering::SeqCst);
+        }
     }
 }
 
@@ -39,6 +65,7 @@ impl Default for MetricBucket {
             counter: EnumMap::default(),
             min_rt: AtomicU64::new(DEFAULT_STATISTIC_MAX_RT as u64),
             max_concurrency: AtomicU32::new(0),
+            rt_histogram: Default::default(),
         }
     }
 }
@@ -62,6 +89,7 @@ impl MetricBucket {
 
     pub fn add_rt(&self, round_trip: u64) {
         self.add_count(MetricEvent::Rt, round_trip);
+        self.rt_histogram[rt_histogram_index(round_trip)].fetch_add(1, Ordering::SeqCst);
         if round_trip < self.min_rt.load(Ordering::SeqCst) {
             // Might not be accurate here.
             self.min_rt.store(round_trip, Ordering::SeqCst);
@@ -76,6 +104,11 @@ impl MetricBucket {
     pub fn min_rt(&self) -> u64 {
         self.min_rt.load(Ordering::SeqCst)
     }
+
+    /// `rt_histogram` returns the amount of the round trips recorded in the histogram bucket.
+    pub fn rt_histogram(&self, idx: usize) -> u64 {
+        self.rt_histogram[idx].load(Ordering::SeqCst)
+    }
     pub fn update_concurrency(&self, concurrency: u32) {
         if concurrency > self.max_concurrency.load(Ordering::SeqCst) {
             // Might not be accurate here.
@@ -210,5 +243,23 @@ mod test {
         mb.reset();
         assert_eq!(mb.min_rt(), DEFAULT_STATISTIC_MAX_RT as u64);
         assert_eq!(mb.max_concurrency(), 0);
+        assert_eq!(mb.rt_histogram(rt_histogram_index(100)), 0);
+    }
+
+    #[test]
+    fn rt_histogram() {
+        let mb = MetricBucket::new();
+        for rt in &[0, 1, 3, 100, 127, 128, DEFAULT_STATISTIC_MAX_RT, 1 << 20] {
+            mb.add_rt(*rt);
+        }
+        assert_eq!(mb.rt_histogram(0), 1);
+        assert_eq!(mb.rt_histogram(1), 1);
+        assert_eq!(mb.rt_histogram(2), 1);
+        assert_eq!(mb.rt_histogram(7), 2);
+        assert_eq!(mb.rt_histogram(8), 1);
+        assert_eq!(mb.rt_histogram(RT_HISTOGRAM_LEN - 1), 2);
+        assert_eq!(rt_histogram_bounds(7), (64, 128));
+        let (lower, upper) = rt_histogram_bounds(RT_HISTOGRAM_LEN - 1);
+        assert!(lower <= DEFAULT_STATISTIC_MAX_RT && DEFAULT_STATISTIC_MAX_RT < upper);
     }
 }
@@ -1,4 +1,4 @@
-use super::{BucketLeapArray, BucketWrap, MetricBucket};
+use super::{rt_histogram_bounds, BucketLeapArray, BucketWrap, MetricBucket, RT_HISTOGRAM_LEN};
 use crate::base::{
     check_validity_for_reuse_statistic, MetricEvent, MetricItem, ReadStat, TimePredicate,
     WriteStat, DEFAULT_STATISTIC_MAX_RT,
@@ -101,6 +101,37 @@ impl SlidingWindowMetric {
         res
     }
 
+    /// `rt_percentile` returns the round trip time (ms) at the percentile in `(0.0, 1.0]` of the completions,
+    /// it is interpolated linearly in the bucket of the histogram, thus an approximation at the scale of the bucket.
+    pub fn rt_percentile(&self, percentile: f64) -> f64 {
+        let buckets = self.satisfied_buckets(curr_time_millis());
+        let mut histogram = [0u64; RT_HISTOGRAM_LEN];
+        for b in buckets {
+            for (idx, count) in histogram.iter_mut().enumerate() {
+                *count += b.value().rt_histogram(idx);
+            }
+        }
+        let total: u64 = histogram.iter().sum();
+        if total == 0 {
+            return 0f64;
+        }
+        let rank = (percentile.max(0.0).min(1.0) * total as f64)
+            .ceil()
+            .max(1.0);
+        let mut before = 0u64;
+        for (idx, count) in histogram.iter().enumerate() {
+            if *count > 0 && (before + count) as f64 >= rank {
+                let (lower, upper) = rt_histogram_bounds(idx);
+                // the round trips are assumed to spread evenly in the bucket
+                let fraction = (rank - before as f64 - 0.5) / *count as f64;
+                return lower as f64 + (upper - lower) as f64 * fraction;
+            }
+            before += count;
+        }
+        // unreachable, since the rank is at most the total
+        rt_histogram_bounds(RT_HISTOGRAM_LEN - 1).1 as f64
+    }
+
     /// second_metrics_on_condition aggregates metric items by second on condition that
     /// the startTime of the statistic buckets satisfies the time predicate.
     pub fn second_metrics_on_condition(&self, condition: &TimePredicate) -> Vec<MetricItem> {
@@ -192,7 +223,7 @@ impl ReadStat for SlidingWindowMetric {
 
     fn avg_rt(&self) -> f64 {
         let completed = self.sum(MetricEvent::Complete);
-        if completed <= 0 {
+        if completed == 0 {
             0f64
         } else {
             self.sum(MetricEvent::Rt) as f64 / completed as f64
@@ -401,6 +432,24 @@ mod test {
         assert_eq!(swm.avg_rt(), 1.0);
     }
 
+    #[test]
+    fn rt_percentile() {
+        let arr = Arc::new(BucketLeapArray::new(SAMPLE_COUNT, INTERVAL_MS).unwrap());
+        let swm = SlidingWindowMetric::new(4, 2000, arr.clone()).unwrap();
+        assert_eq!(swm.rt_percentile(0.99), 0.0);
+        for _ in 0..98 {
+            arr.add_count(MetricEvent::Rt, 10);
+        }
+        arr.add_count(MetricEvent::Rt, 100);
+        arr.add_count(MetricEvent::Rt, 1000);
+        // in [8, 16)
+        assert!((8.0..16.0).contains(&swm.rt_percentile(0.5)));
+        // in [64, 128)
+        assert!((64.0..128.0).contains(&swm.rt_percentile(0.99)));
+        // in [512, 1024)
+        assert!((512.0..1024.0).contains(&swm.rt_percentile(1.0)));
+    }
+
     #[test]
     fn metric_item_from_buckets() {
         let arr = Arc::new(BucketLeapArray::new(SAMPLE_COUNT, INTERVAL_MS).unwrap());
@@ -8,6 +8,8 @@ use std::collections::HashMap;
 use std::sync::{Arc, Mutex, RwLock};
 
 type ResourceNodeMap = HashMap<String, Arc<ResourceNode>>;
+/// entrance name -> resource name -> node
+type ChainNodeMap = HashMap<String, ResourceNodeMap>;
 
 lazy_static! {
     pub static ref INBOUND_NODE: Arc<ResourceNode> = Arc::new(ResourceNode::new(
@@ -15,6 +17,7 @@ lazy_static! {
         ResourceType::Common
     ));
     static ref RESOURCE_NODE_MAP: RwLock<ResourceNodeMap> = RwLock::new(ResourceNodeMap::new());
+    static ref CHAIN_NODE_MAP: RwLock<ChainNodeMap> = RwLock::new(ChainNodeMap::new());
 }
 
 pub fn inbound_node() -> Arc<ResourceNode> {
@@ -63,3 +66,59 @@ pub fn get_or_create_resource_node(
 pub fn reset_resource_map() {
     RESOURCE_NODE_MAP.write().unwrap().clear();
 }
+
+/// `get_chain_node` returns the statistic node of the resource invoked through the entrance.
+pub fn get_chain_node(entrance: &String, res_name: &String) -> Option<Arc<ResourceNode>> {
+    let chain_map = CHAIN_NODE_MAP.read().unwrap();
+    chain_map
+        .get(entrance)
+        .and_then(|res_map| res_map.get(res_name))
+        .cloned()
+}
+
+pub fn get_or_create_chain_node(
+    entrance: &String,
+    res_name: &String,
+    resource_type: &ResourceType,
+) -> Arc<ResourceNode> {
+    if let Some(node) = get_chain_node(entrance, res_name) {
+        return node;
+    }
+    let mut chain_map = CHAIN_NODE_MAP.write().unwrap();
+    let res_map = chain_map.entry(entrance.clone()).or_default();
+    if res_map.len() >= DEFAULT_MAX_RESOURCE_AMOUNT {
+        logging::warn!(
+            "[get_or_create_chain_node] Resource amount of entrance {} exceeds the threshold {}",
+            entrance,
+            DEFAULT_MAX_RESOURCE_AMOUNT
+        )
+    }
+    res_map
+        .entry(res_name.clone())
+        .or_insert_with(|| Arc::new(ResourceNode::new(res_name.clone(), resource_type.clone())))
+        .clone()
+}
+
+pub fn reset_chain_map() {
+    CHAIN_NODE_MAP.write().unwrap().clear();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn chain_node() {
+        let entrance = String::from("chain_node_entrance");
+        let res_name = String::from("chain_node_res");
+        assert!(get_chain_node(&entrance, &res_name).is_none());
+        let node = get_or_create_chain_node(&entrance, &res_name, &ResourceType::Common);
+        assert!(Arc::ptr_eq(
+            &node,
+            &get_chain_node(&entrance, &res_name).unwrap()
+        ));
+        assert!(get_chain_node(&"another".into(), &res_name).is_none());
+        // independent of the resource node
+        assert!(get_resource_node(&res_name).is_none());
+    }
+}
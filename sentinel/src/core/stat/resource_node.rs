@@ -46,6 +46,14 @@ impl ResourceNode {
         }
     }
 
+    pub fn res_name(&self) -> &String {
+        &self.res_name
+    }
+
+    pub fn resource_type(&self) -> ResourceType {
+        self.resource_type
+    }
+
     pub fn default_metric(&self) -> Arc<dyn ReadStat> {
         self.metric.clone()
     }
@@ -59,6 +67,11 @@ impl ResourceNode {
     pub fn max_concurrency(&self) -> u32 {
         self.metric.max_concurrency()
     }
+
+    /// `rt_percentile` returns the approximate round trip time at the percentile, see `SlidingWindowMetric::rt_percentile()`.
+    pub fn rt_percentile(&self, percentile: f64) -> f64 {
+        self.metric.rt_percentile(percentile)
+    }
 }
 
 impl MetricItemRetriever for ResourceNode {
@@ -1,8 +1,6 @@
-use super::get_or_create_resource_node;
+use super::{get_or_create_chain_node, get_or_create_resource_node};
 use crate::base::{BaseSlot, ContextPtr, EntryContext, StatPrepareSlot};
 use lazy_static::lazy_static;
-use std::cell::RefCell;
-use std::rc::Rc;
 use std::sync::Arc;
 
 const PREPARE_SLOT_ORDER: u32 = 1000;
@@ -25,23 +23,20 @@ impl BaseSlot for ResourceNodePrepareSlot {
 }
 
 impl StatPrepareSlot for ResourceNodePrepareSlot {
-    cfg_async! {
-        fn prepare(&self, ctx: ContextPtr) {
-            let node = get_or_create_resource_node(
+    fn prepare(&self, ctx: ContextPtr) {
+        let node = get_or_create_resource_node(
+            ctx.read().unwrap().resource().name(),
+            ctx.read().unwrap().resource().resource_type(),
+        );
+        ctx.write().unwrap().set_stat_node(node);
+        let entrance = ctx.read().unwrap().entrance().cloned();
+        if let Some(entrance) = entrance {
+            let chain_node = get_or_create_chain_node(
+                &entrance,
                 ctx.read().unwrap().resource().name(),
                 ctx.read().unwrap().resource().resource_type(),
             );
-            ctx.write().unwrap().set_stat_node(node);
-        }
-    }
-
-    cfg_not_async! {
-        fn prepare(&self, ctx: ContextPtr) {
-            let node = get_or_create_resource_node(
-                ctx.borrow().resource().name(),
-                ctx.borrow().resource().resource_type(),
-            );
-            ctx.borrow_mut().set_stat_node(node);
+            ctx.write().unwrap().set_chain_node(chain_node);
         }
     }
 }
@@ -1,14 +1,8 @@
 use super::inbound_node;
-use crate::{
-    base::{
-        BaseSlot, BlockError, ContextPtr, EntryContext, MetricEvent, StatNode, StatSlot,
-        TrafficType,
-    },
-    utils::curr_time_millis,
+use crate::base::{
+    BaseSlot, BlockError, ContextPtr, EntryContext, MetricEvent, StatNode, StatSlot, TrafficType,
 };
 use lazy_static::lazy_static;
-use std::cell::RefCell;
-use std::rc::Rc;
 use std::sync::Arc;
 
 const STAT_SLOT_ORDER: u32 = 1000;
@@ -34,10 +28,19 @@ impl ResourceNodeStatSlot {
         node.add_count(MetricEvent::Block, count as u64)
     }
 
-    fn record_complete_for(&self, node: Arc<dyn StatNode>, count: u32, round_trip: u64) {
-        // todo: cannot capture error now
+    fn record_complete_for(
+        &self,
+        node: Arc<dyn StatNode>,
+        count: u32,
+        round_trip: u64,
+        errored: bool,
+    ) {
         node.add_count(MetricEvent::Rt, round_trip as u64);
         node.add_count(MetricEvent::Complete, count as u64);
+        // the business errors set before exiting, e.g., by `EntryContext::set_err()`
+        if errored {
+            node.add_count(MetricEvent::Error, count as u64);
+        }
         node.decrease_concurrency();
     }
 }
@@ -50,10 +53,7 @@ impl BaseSlot for ResourceNodeStatSlot {
 
 impl StatSlot for ResourceNodeStatSlot {
     fn on_entry_pass(&self, ctx: ContextPtr) {
-        cfg_if_async! {
-            let ctx = ctx.read().unwrap(),
-            let ctx = ctx.borrow()
-        };
+        let ctx = ctx.read().unwrap();
         let res = ctx.resource();
         let input = ctx.input();
         if let Some(stat_node) = ctx.stat_node().clone() {
@@ -62,13 +62,13 @@ impl StatSlot for ResourceNodeStatSlot {
                 self.record_pass_for(inbound_node(), input.batch_count())
             }
         }
+        if let Some(chain_node) = ctx.chain_node() {
+            self.record_pass_for(chain_node, input.batch_count());
+        }
     }
 
     fn on_entry_blocked(&self, ctx: ContextPtr, block_error: Option<BlockError>) {
-        cfg_if_async! {
-            let ctx = ctx.read().unwrap(),
-            let ctx = ctx.borrow()
-        };
+        let ctx = ctx.read().unwrap();
         let res = ctx.resource();
         let input = ctx.input();
         if let Some(stat_node) = ctx.stat_node().clone() {
@@ -77,31 +77,24 @@ impl StatSlot for ResourceNodeStatSlot {
                 self.record_block_for(inbound_node(), input.batch_count())
             }
         }
-    }
-
-    cfg_async! {
-        fn on_completed(&self, ctx: ContextPtr) {
-            let mut round_trip = curr_time_millis() - ctx.read().unwrap().start_time();
-            ctx.write().unwrap().set_round_trip(round_trip);
-            if let Some(stat_node) = ctx.read().unwrap().stat_node().clone() {
-                self.record_complete_for(stat_node, ctx.read().unwrap().input().batch_count(), round_trip);
-                if *ctx.read().unwrap().resource().traffic_type() == TrafficType::Inbound {
-                    self.record_block_for(inbound_node(), ctx.read().unwrap().input().batch_count());
-                }
-            }
+        if let Some(chain_node) = ctx.chain_node() {
+            self.record_block_for(chain_node, input.batch_count());
         }
     }
 
-    cfg_not_async! {
-        fn on_completed(&self, ctx: ContextPtr) {
-            let round_trip = curr_time_millis() - ctx.borrow().start_time();
-            ctx.borrow_mut().set_round_trip(round_trip);
-            if let Some(stat_node) = ctx.borrow().stat_node().clone() {
-                self.record_complete_for(stat_node, ctx.borrow().input().batch_count(), round_trip);
-                if *ctx.borrow().resource().traffic_type() == TrafficType::Inbound {
-                    self.record_block_for(inbound_node(), ctx.borrow().input().batch_count());
-                }
+    fn on_completed(&self, ctx: ContextPtr) {
+        let round_trip = ctx.write().unwrap().complete_round_trip();
+        let ctx = ctx.read().unwrap();
+        let count = ctx.input().batch_count();
+        let errored = ctx.get_err().is_some();
+        if let Some(stat_node) = ctx.stat_node().clone() {
+            self.record_complete_for(stat_node, count, round_trip, errored);
+            if *ctx.resource().traffic_type() == TrafficType::Inbound {
+                self.record_complete_for(inbound_node(), count, round_trip, errored);
             }
         }
+        if let Some(chain_node) = ctx.chain_node() {
+            self.record_complete_for(chain_node, count, round_trip, errored);
+        }
     }
 }
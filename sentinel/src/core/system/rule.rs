@@ -1,4 +1,7 @@
-use crate::{base::SentinelRule, logging, system_metric};
+use crate::{
+    base::{Diagnostic, SentinelRule},
+    logging, system_metric,
+};
 use crate::{Error, Result};
 use serde::{Deserialize, Serialize};
 use serde_json;
@@ -56,15 +59,22 @@ impl SentinelRule for Rule {
         format!("{:?}", self.metric_type)
     }
 
-    fn is_valid(&self) -> Result<()> {
+    fn rule_id(&self) -> Option<String> {
+        self.id.clone()
+    }
+
+    fn diagnose(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
         if self.trigger_count < 0.0 {
-            return Err(Error::msg("negative threshold"));
+            diagnostics.push(Diagnostic::error("trigger_count", "negative threshold"));
         }
-
         if self.metric_type == MetricType::CpuUsage && self.trigger_count > 1.0 {
-            return Err(Error::msg("invalid CPU usage, valid range is [0.0, 1.0]"));
+            diagnostics.push(Diagnostic::error(
+                "trigger_count",
+                "invalid CPU usage, valid range is [0.0, 1.0]",
+            ));
         }
-        Ok(())
+        diagnostics
     }
 }
 
@@ -1,10 +1,12 @@
 use super::*;
+use crate::base::{check_direct_update, RuleChangeListener, RuleChangeListeners};
 use crate::{
     base,
     base::{nop_read_stat, nop_write_stat, ReadStat, ResourceType, SentinelRule, StatNode},
     config, logging, stat,
     stat::{ResourceNode, SlidingWindowMetric},
     system_metric, utils,
+    utils::ShardedMap,
 };
 use lazy_static::lazy_static;
 use std::collections::HashMap;
@@ -14,19 +16,28 @@ use std::sync::{Arc, Mutex, RwLock, Weak};
 pub type RuleMap = HashMap<MetricType, Vec<Arc<Rule>>>;
 
 lazy_static! {
-    static ref RULE_MAP: RwLock<RuleMap> = RwLock::new(RuleMap::new());
+    static ref RULE_MAP: ShardedMap<MetricType, Vec<Arc<Rule>>> = ShardedMap::new();
     static ref CURRENT_RULES: Mutex<Vec<Arc<Rule>>> = Mutex::new(Vec::new());
 }
 
+lazy_static! {
+    static ref RULE_CHANGE_LISTENERS: RuleChangeListeners<Rule> = RuleChangeListeners::new();
+}
+
+/// `register_rule_change_listeners` registers the listeners notified after the rules are changed.
+pub fn register_rule_change_listeners(listeners: Vec<Arc<dyn RuleChangeListener<Rule>>>) {
+    RULE_CHANGE_LISTENERS.register(listeners);
+}
+
+pub fn clear_rule_change_listeners() {
+    RULE_CHANGE_LISTENERS.clear();
+}
+
 /// `get_rules` returns all the rules in the global `RULE_MAP`
-// This func acquires a read lock on global `RULE_MAP`,
-// please release the lock before calling this func
+// This func acquires the read locks on the shards of global `RULE_MAP` one by one
 pub fn get_rules() -> Vec<Arc<Rule>> {
-    let rule_map = RULE_MAP.read().unwrap();
-    let mut rules = Vec::with_capacity(rule_map.len());
-    for r in rule_map.values() {
-        rules.append(&mut r.clone());
-    }
+    let mut rules = Vec::new();
+    RULE_MAP.for_each(|_, r| rules.extend(r.iter().cloned()));
     rules
 }
 
@@ -34,6 +45,13 @@ pub fn get_rules() -> Vec<Arc<Rule>> {
 // This func acquires the lock on global `CURRENT_RULES`,
 // please release the lock before calling this func
 pub fn load_rules(rules: Vec<Arc<Rule>>) {
+    if check_direct_update("`system::load_rules()`").is_err() {
+        return;
+    }
+    RULE_CHANGE_LISTENERS.watch(get_rules, || do_load_rules(rules))
+}
+
+fn do_load_rules(rules: Vec<Arc<Rule>>) {
     let mut current_rules = CURRENT_RULES.lock().unwrap();
     if &*current_rules == &rules {
         logging::info!(
@@ -47,8 +65,7 @@ pub fn load_rules(rules: Vec<Arc<Rule>>) {
     let m = build_rule_map(rules.clone());
 
     let start = utils::curr_time_nanos();
-    let mut rule_map = RULE_MAP.write().unwrap();
-    *rule_map = m;
+    RULE_MAP.replace(m);
 
     logging::debug!(
         "[System load_rules] Time statistic(ns) for updating system rule, timeCost {:?}",
@@ -56,7 +73,7 @@ pub fn load_rules(rules: Vec<Arc<Rule>>) {
     );
     logging::info!(
         "[SystemRuleManager] System rules loaded, rules {:?}",
-        rule_map
+        *RULE_MAP
     );
     *current_rules = rules;
 }
@@ -65,8 +82,16 @@ pub fn load_rules(rules: Vec<Arc<Rule>>) {
 // This func acquires the locks on global `CURRENT_RULES` and `RULE_MAP`,
 // please release the locks before calling this func
 pub fn clear_rules() {
-    CURRENT_RULES.lock().unwrap().clear();
-    RULE_MAP.write().unwrap().clear();
+    if check_direct_update("`system::clear_rules()`").is_err() {
+        return;
+    }
+    RULE_CHANGE_LISTENERS.watch(get_rules, do_clear_rules)
+}
+
+fn do_clear_rules() {
+    let mut current_rules = CURRENT_RULES.lock().unwrap();
+    current_rules.clear();
+    RULE_MAP.clear();
 }
 
 fn build_rule_map(rules: Vec<Arc<Rule>>) -> RuleMap {
@@ -120,9 +145,7 @@ mod test {
             })],
         );
 
-        let mut rule_map = RULE_MAP.write().unwrap();
-        *rule_map = map.clone();
-        drop(rule_map);
+        RULE_MAP.replace(map.clone());
         let rules = get_rules();
         assert_eq!(2, rules.len());
 
@@ -132,9 +155,7 @@ mod test {
             ..Default::default()
         });
         map.get_mut(&MetricType::InboundQPS).unwrap().push(rule);
-        let mut rule_map = RULE_MAP.write().unwrap();
-        *rule_map = map;
-        drop(rule_map);
+        RULE_MAP.replace(map);
         let rules = get_rules();
         assert_eq!(3, rules.len());
 
@@ -157,9 +178,9 @@ mod test {
             }),
         ];
         load_rules(rules);
-        assert_eq!(2, RULE_MAP.read().unwrap().len());
+        assert_eq!(2, RULE_MAP.len());
         clear_rules();
-        assert_eq!(0, RULE_MAP.read().unwrap().len());
+        assert_eq!(0, RULE_MAP.len());
         assert_eq!(0, CURRENT_RULES.lock().unwrap().len());
     }
 
@@ -9,8 +9,6 @@ use crate::{
 };
 use lazy_static::lazy_static;
 use std::any::Any;
-use std::cell::RefCell;
-use std::rc::Rc;
 use std::sync::Arc;
 
 const RULE_CHECK_SLOT_ORDER: u32 = 1000;
@@ -34,10 +32,7 @@ impl BaseSlot for AdaptiveSlot {
 
 impl RuleCheckSlot for AdaptiveSlot {
     fn check(&self, ctx: &ContextPtr) -> TokenResult {
-        cfg_if_async! {
-            let mut ctx = ctx.write().unwrap(),
-            let mut ctx = ctx.borrow_mut()
-        };
+        let mut ctx = ctx.write().unwrap();
         let res = ctx.resource();
         let traffic_type = res.traffic_type();
         if *traffic_type == TrafficType::Outbound {
@@ -133,8 +128,7 @@ mod test {
         base::{ResourceType, ResourceWrapper, SentinelInput},
         flow::StandaloneStat,
     };
-    use std::cell::RefCell;
-    use std::rc::Rc;
+    use std::sync::RwLock;
 
     #[test]
     fn unsuitable_traffic_type() {
@@ -146,9 +140,9 @@ mod test {
         ctx.set_input(SentinelInput::new(1, 0));
         ctx.set_stat_node(res_node);
         ctx.set_resource(rw);
-        let ctx = Rc::new(RefCell::new(ctx));
+        let ctx = Arc::new(RwLock::new(ctx));
         let r = slot.check(&ctx);
-        assert_eq!(r.status(), ctx.borrow().result().status());
+        assert_eq!(r.status(), ctx.read().unwrap().result().status());
     }
 
     #[test]
@@ -161,7 +155,7 @@ mod test {
         ctx.set_input(SentinelInput::new(1, 0));
         ctx.set_stat_node(res_node);
         ctx.set_resource(rw);
-        let ctx = Rc::new(RefCell::new(ctx));
+        let ctx = Arc::new(RwLock::new(ctx));
         let r = slot.check(&ctx);
         assert!(r.is_pass());
     }
@@ -3,7 +3,14 @@ cfg_monitor! {
     use crate::monitor;
 }
 use lazy_static::lazy_static;
-use psutil::{host, memory, process::Process};
+cfg_system_metric! {
+    use psutil::{host, memory, process::Process};
+
+    lazy_static! {
+        static ref CURRENT_PROCESS: Arc<Mutex<Process>> =
+            Arc::new(Mutex::new(Process::new(std::process::id()).unwrap()));
+    }
+}
 use std::sync::{
     atomic::{AtomicU64, Ordering},
     Arc, Mutex, Once,
@@ -17,12 +24,11 @@ lazy_static! {
     static ref LOAD_ONCE: Once = Once::new();
     static ref CPU_ONCE: Once = Once::new();
     static ref MEMORY_ONCE: Once = Once::new();
-    static ref CURRENT_PROCESS: Arc<Mutex<Process>> =
-        Arc::new(Mutex::new(Process::new(std::process::id()).unwrap()));
     static ref TOTAL_MEMORY_SIZE: u64 = get_total_memory_size();
 }
 
 /// getMemoryStat returns the current machine's memory statistic
+#[cfg(all(feature = "system-metric", not(target_arch = "wasm32")))]
 pub fn get_total_memory_size() -> u64 {
     let vm = memory::virtual_memory();
     if let Ok(vm) = vm {
@@ -53,6 +59,7 @@ pub fn init_memory_collector(cpu_interval: u32) {
 
 #[inline]
 // get_process_memory_stat gets current process's memory usage in Bytes
+#[cfg(all(feature = "system-metric", not(target_arch = "wasm32")))]
 fn get_process_memory_stat() -> Result<u64> {
     let process = CURRENT_PROCESS.lock().unwrap();
     process
@@ -80,6 +87,7 @@ pub fn init_cpu_collector(cpu_interval: u32) {
 }
 
 #[inline]
+#[cfg(all(feature = "system-metric", not(target_arch = "wasm32")))]
 fn get_process_cpu_stat() -> Result<f32> {
     let mut process = CURRENT_PROCESS.lock().unwrap();
     process
@@ -106,11 +114,32 @@ pub fn init_load_collector(load_interval: u32) {
 }
 
 #[inline]
+#[cfg(all(feature = "system-metric", not(target_arch = "wasm32")))]
 fn get_system_load() -> Result<f64> {
     let avg = host::loadavg()?;
     Ok(avg.one)
 }
 
+// the system statistics are not collected without the `system-metric` feature or on wasm32, e.g., the proxy-wasm filters,
+// thus the system adaptive rules only work with the statistics set by the host
+cfg_not_system_metric! {
+    pub fn get_total_memory_size() -> u64 {
+        0
+    }
+
+    fn get_process_memory_stat() -> Result<u64> {
+        Err(Error::msg("the process memory is not collected"))
+    }
+
+    fn get_process_cpu_stat() -> Result<f32> {
+        Err(Error::msg("the process cpu is not collected"))
+    }
+
+    fn get_system_load() -> Result<f64> {
+        Err(Error::msg("the system load is not collected"))
+    }
+}
+
 #[inline]
 pub fn current_load() -> f64 {
     *CURRENT_LOAD.lock().unwrap()
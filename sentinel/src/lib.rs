@@ -9,12 +9,18 @@
 #[doc(hidden)]
 pub mod macros;
 
+// the modules are declared without the `cfg_*!` macros, which are not visited by rustfmt
+#[cfg(feature = "async")]
+pub mod adapters;
 pub mod api;
 pub mod core;
 pub mod logging;
 cfg_monitor! {
     pub mod monitor;
 }
+#[cfg(any(test, feature = "test-util"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+pub mod testing;
 pub mod utils;
 
 pub use crate::core::*;
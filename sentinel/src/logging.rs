@@ -1,10 +1,22 @@
 use env_logger;
 use lazy_static::lazy_static;
-pub use log::{debug, error, info, trace, warn};
+pub use log::Level;
+cfg_not_tracing! {
+    pub use log::{debug, error, info, trace, warn};
+}
+// with the `tracing` feature, the logs are the events of `tracing`,
+// which obey the subscriber of the application, and the built-in loggers are not initialized
+cfg_tracing! {
+    pub use tracing::{debug, error, info, trace, warn};
+}
+#[cfg(all(feature = "log4rs", not(target_arch = "wasm32")))]
 use log4rs;
 use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fmt;
+use std::io::Write;
 use std::path::Path;
-use std::sync::Once;
+use std::sync::{Once, RwLock};
 
 // todo: may conflict with the logger used by users
 
@@ -14,10 +26,251 @@ use std::sync::Once;
 pub const DEFAULT_LOG_LEVEL: &str = "trace";
 // or set log level via `RUST_LOG=debug ./main`
 pub const DEFAULT_DIR_NAME: &str = "logs";
+/// the target of the structured events logged by `log_event()`
+pub const EVENT_TARGET: &str = "sentinel_rs::event";
+/// the target of the block events, which can be routed to a dedicated file by the logger (e.g., `log4rs`)
+pub const BLOCK_LOG_TARGET: &str = "sentinel_rs::block";
 
 lazy_static! {
     static ref LOG_FILE_NAME: String = String::from("sentinel-record.log");
     pub static ref FREQUENT_ERROR_ONCE: Once = Once::new();
+    // the env logger in use, it can be replaced to change the logging level at runtime
+    static ref ENV_LOGGER: RwLock<Option<env_logger::Logger>> = RwLock::new(None);
+    static ref LOG_FORMAT: RwLock<LogFormat> = RwLock::new(LogFormat::default());
+}
+
+std::thread_local! {
+    // the event being logged, it is picked up by the JSON formatter in the same thread
+    static CURRENT_EVENT: RefCell<Option<serde_json::Value>> = RefCell::new(None);
+}
+
+static RELOADABLE_ENV_LOGGER: ReloadableEnvLogger = ReloadableEnvLogger;
+
+/// `ReloadableEnvLogger` delegates to the env logger in `ENV_LOGGER`.
+struct ReloadableEnvLogger;
+
+impl log::Log for ReloadableEnvLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        ENV_LOGGER
+            .read()
+            .unwrap()
+            .as_ref()
+            .map_or(false, |logger| logger.enabled(metadata))
+    }
+
+    fn log(&self, record: &log::Record) {
+        if let Some(logger) = ENV_LOGGER.read().unwrap().as_ref() {
+            logger.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(logger) = ENV_LOGGER.read().unwrap().as_ref() {
+            logger.flush();
+        }
+    }
+}
+
+fn build_env_logger(level: &str) -> env_logger::Logger {
+    let mut builder =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(level));
+    if *LOG_FORMAT.read().unwrap() == LogFormat::Json {
+        builder.format(|buf, record| {
+            let line = json_line(record, buf.timestamp_millis().to_string());
+            writeln!(buf, "{}", line)
+        });
+    }
+    builder.build()
+}
+
+/// the output format of the env logger, `log4rs` is formatted by the encoders in its own configuration
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum LogFormat {
+    Text,
+    /// one JSON object per line, the fields of the structured events are the fields of the object
+    Json,
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        LogFormat::Text
+    }
+}
+
+fn json_line(record: &log::Record, timestamp: String) -> serde_json::Value {
+    let mut line = serde_json::Map::new();
+    line.insert("timestamp".into(), timestamp.into());
+    line.insert("level".into(), record.level().to_string().into());
+    line.insert("target".into(), record.target().into());
+    line.insert("message".into(), record.args().to_string().into());
+    if record.target() == EVENT_TARGET || record.target() == BLOCK_LOG_TARGET {
+        if let Some(serde_json::Value::Object(fields)) = CURRENT_EVENT.with(|e| e.borrow().clone())
+        {
+            line.extend(fields);
+        }
+    }
+    serde_json::Value::Object(line)
+}
+
+#[derive(Serialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// an entry is blocked, only the sampled ones are logged
+    Block,
+    /// the number of the blocked entries of a resource in the last interval
+    BlockSummary,
+    /// a rule is added or removed
+    RuleUpdate,
+    /// a circuit breaker changes its state
+    BreakerTransition,
+}
+
+impl EventKind {
+    pub fn target(&self) -> &'static str {
+        match self {
+            EventKind::Block | EventKind::BlockSummary => BLOCK_LOG_TARGET,
+            _ => EVENT_TARGET,
+        }
+    }
+}
+
+/// `Event` is the structured record of sentinel,
+/// it is consumable by the log collectors without parsing when the JSON format is used.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Event {
+    pub kind: EventKind,
+    pub message: String,
+    pub resource: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<u64>,
+}
+
+impl Event {
+    pub fn new(kind: EventKind, resource: String, message: impl Into<String>) -> Self {
+        Event {
+            kind,
+            message: message.into(),
+            resource,
+            rule_id: None,
+            state: None,
+            snapshot: None,
+            count: None,
+        }
+    }
+
+    pub fn with_rule_id(mut self, rule_id: Option<String>) -> Self {
+        self.rule_id = rule_id;
+        self
+    }
+
+    pub fn with_state(mut self, state: impl Into<String>) -> Self {
+        self.state = Some(state.into());
+        self
+    }
+
+    pub fn with_snapshot(mut self, snapshot: Option<String>) -> Self {
+        self.snapshot = snapshot;
+        self
+    }
+
+    pub fn with_count(mut self, count: u64) -> Self {
+        self.count = Some(count);
+        self
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, resource: {}", self.message, self.resource)?;
+        if let Some(rule_id) = &self.rule_id {
+            write!(f, ", rule_id: {}", rule_id)?;
+        }
+        if let Some(state) = &self.state {
+            write!(f, ", state: {}", state)?;
+        }
+        if let Some(snapshot) = &self.snapshot {
+            write!(f, ", snapshot: {}", snapshot)?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ", count: {}", count)?;
+        }
+        Ok(())
+    }
+}
+
+cfg_not_tracing! {
+    /// `event_enabled` indicates whether the structured events of the `kind` and `level` are logged.
+    pub fn event_enabled(kind: EventKind, level: Level) -> bool {
+        log::log_enabled!(target: kind.target(), level)
+    }
+
+    /// `log_event` logs the structured event to the target of its kind.
+    pub fn log_event(level: Level, event: &Event) {
+        if !event_enabled(event.kind, level) {
+            return;
+        }
+        CURRENT_EVENT.with(|e| *e.borrow_mut() = serde_json::to_value(event).ok());
+        log::log!(target: event.kind.target(), level, "{}", event);
+        CURRENT_EVENT.with(|e| e.borrow_mut().take());
+    }
+}
+
+cfg_tracing! {
+    // the targets and levels of `tracing` events are constant
+    macro_rules! dispatch {
+        ($kind:expr, $level:expr, $m:ident) => {
+            match ($kind.target() == BLOCK_LOG_TARGET, $level) {
+                (true, Level::Error) => $m!(BLOCK_LOG_TARGET, tracing::Level::ERROR),
+                (true, Level::Warn) => $m!(BLOCK_LOG_TARGET, tracing::Level::WARN),
+                (true, Level::Info) => $m!(BLOCK_LOG_TARGET, tracing::Level::INFO),
+                (true, Level::Debug) => $m!(BLOCK_LOG_TARGET, tracing::Level::DEBUG),
+                (true, Level::Trace) => $m!(BLOCK_LOG_TARGET, tracing::Level::TRACE),
+                (false, Level::Error) => $m!(EVENT_TARGET, tracing::Level::ERROR),
+                (false, Level::Warn) => $m!(EVENT_TARGET, tracing::Level::WARN),
+                (false, Level::Info) => $m!(EVENT_TARGET, tracing::Level::INFO),
+                (false, Level::Debug) => $m!(EVENT_TARGET, tracing::Level::DEBUG),
+                (false, Level::Trace) => $m!(EVENT_TARGET, tracing::Level::TRACE),
+            }
+        };
+    }
+
+    /// `event_enabled` indicates whether the structured events of the `kind` and `level` are logged.
+    pub fn event_enabled(kind: EventKind, level: Level) -> bool {
+        macro_rules! enabled {
+            ($target:expr, $level:expr) => {
+                tracing::enabled!(target: $target, $level)
+            };
+        }
+        dispatch!(kind, level, enabled)
+    }
+
+    /// `log_event` emits the structured event as a `tracing` event with the target of its kind,
+    /// the fields of the event are recorded as the fields of the `tracing` event.
+    pub fn log_event(level: Level, event: &Event) {
+        macro_rules! emit {
+            ($target:expr, $level:expr) => {
+                tracing::event!(
+                    target: $target,
+                    $level,
+                    kind = ?event.kind,
+                    resource = %event.resource,
+                    rule_id = event.rule_id.as_deref(),
+                    state = event.state.as_deref(),
+                    snapshot = event.snapshot.as_deref(),
+                    count = event.count,
+                    "{}",
+                    event.message
+                )
+            };
+        }
+        dispatch!(event.kind, level, emit)
+    }
 }
 
 /// supported loggers with user-defined settings
@@ -31,14 +284,34 @@ pub enum Logger {
 }
 
 pub fn logger_init(logger: Logger) {
+    logger_init_with_format(logger, LogFormat::Text)
+}
+
+/// `logger_init_with_format` is similar to `logger_init()`, the format applies to the env logger.
+pub fn logger_init_with_format(logger: Logger, format: LogFormat) {
+    *LOG_FORMAT.write().unwrap() = format;
+    if cfg!(feature = "tracing") {
+        // the subscriber is set up by the application
+        return;
+    }
     match logger {
         Logger::None => {
             default_logger_init();
         }
         Logger::EnvLogger(level) => {
-            env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(level))
-                .init()
+            let logger = build_env_logger(&level);
+            let max_level = logger.filter();
+            *ENV_LOGGER.write().unwrap() = Some(logger);
+            log::set_logger(&RELOADABLE_ENV_LOGGER)
+                .expect("logger_init should not be called after logger initialized");
+            log::set_max_level(max_level);
+        }
+        // without the `log4rs` feature, or on wasm32 where the configuration files are not accessible
+        #[cfg(not(all(feature = "log4rs", not(target_arch = "wasm32"))))]
+        Logger::Log4rs(_) => {
+            default_logger_init();
         }
+        #[cfg(all(feature = "log4rs", not(target_arch = "wasm32")))]
         Logger::Log4rs(ref file_path) => {
             let path = Path::new(file_path);
             if path.exists() {
@@ -53,6 +326,143 @@ pub fn logger_init(logger: Logger) {
 
 #[inline]
 fn default_logger_init() {
-    logger_init(Logger::EnvLogger(DEFAULT_LOG_LEVEL.into()));
+    let format = *LOG_FORMAT.read().unwrap();
+    logger_init_with_format(Logger::EnvLogger(DEFAULT_LOG_LEVEL.into()), format);
     info!("Current logger is the default one. If this is unexpected, check your configuration.");
 }
+
+/// `is_env_logger_in_use` indicates whether the env logger is initialized by `logger_init()`.
+pub fn is_env_logger_in_use() -> bool {
+    ENV_LOGGER.read().unwrap().is_some()
+}
+
+/// `reload_env_logger_level` changes the level of the env logger at runtime,
+/// the filters in the env `RUST_LOG` still take the precedence.
+/// It returns `false` if the env logger is not in use, e.g., `log4rs` is configured.
+pub fn reload_env_logger_level(level: &str) -> bool {
+    let mut env_logger = ENV_LOGGER.write().unwrap();
+    if env_logger.is_none() {
+        return false;
+    }
+    let logger = build_env_logger(level);
+    log::set_max_level(logger.filter());
+    *env_logger = Some(logger);
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn json_format() {
+        let event = Event::new(EventKind::Block, "abc".into(), "[Block] entry blocked")
+            .with_rule_id(Some("rule-1".into()))
+            .with_state("Flow");
+        assert_eq!(
+            event.to_string(),
+            "[Block] entry blocked, resource: abc, rule_id: rule-1, state: Flow"
+        );
+        CURRENT_EVENT.with(|e| *e.borrow_mut() = serde_json::to_value(&event).ok());
+        let line = json_line(
+            &log::Record::builder()
+                .args(format_args!("{}", event))
+                .level(Level::Warn)
+                .target(BLOCK_LOG_TARGET)
+                .build(),
+            "2021-01-01T00:00:00.000Z".into(),
+        );
+        CURRENT_EVENT.with(|e| e.borrow_mut().take());
+        assert_eq!(
+            line,
+            serde_json::json!({
+                "timestamp": "2021-01-01T00:00:00.000Z",
+                "level": "WARN",
+                "target": BLOCK_LOG_TARGET,
+                "kind": "block",
+                "message": "[Block] entry blocked",
+                "resource": "abc",
+                "rule_id": "rule-1",
+                "state": "Flow",
+            })
+        );
+
+        // the plain records
+        let line = json_line(
+            &log::Record::builder()
+                .args(format_args!("rules loaded"))
+                .level(Level::Info)
+                .target("sentinel_rs::flow")
+                .build(),
+            "2021-01-01T00:00:00.000Z".into(),
+        );
+        assert_eq!(line["message"], "rules loaded");
+        assert!(line.get("kind").is_none());
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn tracing_event() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+
+        #[derive(Default)]
+        struct Fields(Vec<(String, String)>);
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                self.0.push((field.name().into(), format!("{:?}", value)));
+            }
+        }
+
+        #[derive(Clone, Default)]
+        struct Recorder(Arc<Mutex<Vec<(String, Fields)>>>);
+        impl tracing::Subscriber for Recorder {
+            fn enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+                *metadata.level() <= tracing::Level::INFO
+            }
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, event: &tracing::Event<'_>) {
+                let mut fields = Fields::default();
+                event.record(&mut fields);
+                let target = event.metadata().target().to_string();
+                self.0.lock().unwrap().push((target, fields));
+            }
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            assert!(event_enabled(EventKind::Block, Level::Info));
+            assert!(!event_enabled(EventKind::Block, Level::Debug));
+            let event = Event::new(EventKind::Block, "abc".into(), "[Block] entry blocked")
+                .with_state("Flow");
+            log_event(Level::Info, &event);
+            log_event(Level::Debug, &event);
+            info!("plain message {}", 1);
+        });
+        let events = recorder.0.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].0, BLOCK_LOG_TARGET);
+        let fields: Vec<_> = events[0]
+            .1
+             .0
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        assert!(fields.contains(&("kind", "Block")));
+        assert!(fields.contains(&("resource", "abc")));
+        assert!(fields.contains(&("state", "\"Flow\"")));
+        // the absent fields are not recorded
+        assert!(!fields.iter().any(|(k, _)| *k == "rule_id"));
+        assert_eq!(
+            events[1].1 .0[0],
+            ("message".into(), "plain message 1".into())
+        );
+    }
+}
@@ -1,16 +1,5 @@
 #![allow(unused_macros)]
 
-// crate `cfg_if` cannot be used in stable toolchains (due to `expr`),
-// so currently we use `stmt` instead
-macro_rules! cfg_if_async {
-    ($item1:stmt, $item2:stmt) => {
-        #[cfg(feature = "async")]
-        $item1;
-        #[cfg(not(feature = "async"))]
-        $item2;
-    };
-}
-
 macro_rules! cfg_async {
     ($($item:item)*) => {
         $(
@@ -38,3 +27,73 @@ macro_rules! cfg_monitor {
         )*
     }
 }
+
+macro_rules! cfg_signal {
+    ($($item:item)*) => {
+        $(
+            #[cfg(all(unix, feature = "signal"))]
+            #[cfg_attr(docsrs, doc(cfg(all(unix, feature = "signal"))))]
+            $item
+        )*
+    }
+}
+
+macro_rules! cfg_tracing {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "tracing")]
+            #[cfg_attr(docsrs, doc(cfg(feature = "tracing")))]
+            $item
+        )*
+    }
+}
+
+macro_rules! cfg_not_tracing {
+    ($($item:item)*) => {
+        $(
+            #[cfg(not(feature = "tracing"))]
+            $item
+        )*
+    }
+}
+
+// the utilities for the tests, e.g., `utils::MockClock`, they are always available in the tests of the crate itself
+macro_rules! cfg_test_util {
+    ($($item:item)*) => {
+        $(
+            #[cfg(any(test, feature = "test-util"))]
+            #[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+            $item
+        )*
+    }
+}
+
+// the system metrics are collected by psutil, which is not available on wasm32, e.g., the proxy-wasm filters
+macro_rules! cfg_system_metric {
+    ($($item:item)*) => {
+        $(
+            #[cfg(all(feature = "system-metric", not(target_arch = "wasm32")))]
+            #[cfg_attr(docsrs, doc(cfg(all(feature = "system-metric", not(target_arch = "wasm32")))))]
+            $item
+        )*
+    }
+}
+
+macro_rules! cfg_not_system_metric {
+    ($($item:item)*) => {
+        $(
+            #[cfg(not(all(feature = "system-metric", not(target_arch = "wasm32"))))]
+            $item
+        )*
+    }
+}
+
+macro_rules! cfg_metric_log {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "metric-log")]
+            #[cfg_attr(docsrs, doc(cfg(feature = "metric-log")))]
+            $item
+        )*
+    }
+}
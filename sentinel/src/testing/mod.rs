@@ -0,0 +1,15 @@
+//! The traffic simulation and replay harness, for evaluating the candidate rules offline before deploying them.
+//!
+//! A `TrafficProfile` is the requests of a resource, recorded (e.g., deserialized from the access logs),
+//! or generated from the `Phase`s of the QPS, the response time distribution and the error ratio over time.
+//! The `Replayer` replays it against a `RuleSet` on a `utils::MockClock`, thus a profile of hours completes at once,
+//! and reports the timeline of the passed, blocked and errored requests and the state transitions of the circuit breakers.
+//!
+//! The rules loaded by the replay are global, so do not replay in the process serving the real traffic.
+//! This module is only available with the `test-util` feature.
+
+pub mod profile;
+pub mod replay;
+
+pub use profile::*;
+pub use replay::*;
@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+
+/// `RtDistribution` is the distribution of the response time of the simulated requests, in milliseconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RtDistribution {
+    Constant(u64),
+    Uniform { min_ms: u64, max_ms: u64 },
+    Exponential { mean_ms: f64 },
+}
+
+impl Default for RtDistribution {
+    fn default() -> Self {
+        RtDistribution::Constant(0)
+    }
+}
+
+impl RtDistribution {
+    fn sample(&self, rng: &mut XorShift) -> u64 {
+        match *self {
+            RtDistribution::Constant(rt) => rt,
+            RtDistribution::Uniform { min_ms, max_ms } => {
+                if max_ms <= min_ms {
+                    min_ms
+                } else {
+                    min_ms + rng.next_u64() % (max_ms - min_ms + 1)
+                }
+            }
+            // inverse transform sampling
+            RtDistribution::Exponential { mean_ms } => {
+                (-mean_ms.max(0.0) * (1.0 - rng.next_f64()).ln()).round() as u64
+            }
+        }
+    }
+}
+
+/// `Phase` is a period of the synthetic traffic with a steady QPS,
+/// e.g., the error bursts are the phases with a high `error_ratio`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Phase {
+    pub duration_ms: u64,
+    /// the requests arrive evenly in the phase
+    pub qps: f64,
+    pub rt: RtDistribution,
+    /// the ratio of the requests completed with a business error, in `[0.0, 1.0]`
+    pub error_ratio: f64,
+}
+
+impl Default for Phase {
+    fn default() -> Self {
+        Phase {
+            duration_ms: 1000,
+            qps: 0.0,
+            rt: RtDistribution::default(),
+            error_ratio: 0.0,
+        }
+    }
+}
+
+impl Phase {
+    pub fn new(duration_ms: u64, qps: f64) -> Self {
+        Phase {
+            duration_ms,
+            qps,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_rt(mut self, rt: RtDistribution) -> Self {
+        self.rt = rt;
+        self
+    }
+
+    pub fn with_error_ratio(mut self, error_ratio: f64) -> Self {
+        self.error_ratio = error_ratio;
+        self
+    }
+}
+
+/// `RecordedRequest` is a request of the traffic, the offset is relative to the start of the traffic.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecordedRequest {
+    pub offset_ms: u64,
+    pub rt_ms: u64,
+    pub error: bool,
+}
+
+/// `TrafficProfile` is the traffic of a resource, either recorded or generated from the phases.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrafficProfile {
+    pub resource: String,
+    /// the requests sorted by the offsets
+    pub requests: Vec<RecordedRequest>,
+}
+
+impl TrafficProfile {
+    /// `recorded` creates the profile from the recorded requests, e.g., deserialized from the access logs.
+    pub fn recorded(resource: String, mut requests: Vec<RecordedRequest>) -> Self {
+        requests.sort_by_key(|r| r.offset_ms);
+        TrafficProfile { resource, requests }
+    }
+
+    /// `synthetic` generates the requests of the phases one after another,
+    /// the response time and the errors are sampled by the `seed`, thus the same profile is always generated.
+    pub fn synthetic(resource: String, phases: &[Phase], seed: u64) -> Self {
+        let mut rng = XorShift::new(seed);
+        let mut requests = Vec::new();
+        let mut phase_start = 0;
+        for phase in phases {
+            if phase.qps > 0.0 {
+                let amount = (phase.qps * phase.duration_ms as f64 / 1000.0) as u64;
+                for i in 0..amount {
+                    requests.push(RecordedRequest {
+                        offset_ms: phase_start + (i as f64 * 1000.0 / phase.qps) as u64,
+                        rt_ms: phase.rt.sample(&mut rng),
+                        error: rng.next_f64() < phase.error_ratio,
+                    });
+                }
+            }
+            phase_start += phase.duration_ms;
+        }
+        TrafficProfile { resource, requests }
+    }
+
+    /// `duration_ms` returns the time until the last request completes.
+    pub fn duration_ms(&self) -> u64 {
+        self.requests
+            .iter()
+            .map(|r| r.offset_ms + r.rt_ms)
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+// the deterministic pseudo random numbers, it is not for cryptography
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        // the state must be non-zero
+        XorShift(seed ^ 0x9E37_79B9_7F4A_7C15)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    // uniform in `[0.0, 1.0)`
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn synthetic() {
+        let phases = [
+            Phase::new(1000, 10.0).with_rt(RtDistribution::Constant(20)),
+            Phase::new(500, 4.0)
+                .with_rt(RtDistribution::Uniform {
+                    min_ms: 10,
+                    max_ms: 30,
+                })
+                .with_error_ratio(1.0),
+        ];
+        let profile = TrafficProfile::synthetic("abc".into(), &phases, 1);
+        assert_eq!(profile.requests.len(), 12);
+        assert_eq!(profile.requests[1].offset_ms, 100);
+        assert_eq!(profile.requests[10].offset_ms, 1000);
+        assert_eq!(profile.requests[11].offset_ms, 1250);
+        assert!(profile.requests[..10]
+            .iter()
+            .all(|r| r.rt_ms == 20 && !r.error));
+        assert!(profile.requests[10..]
+            .iter()
+            .all(|r| (10..=30).contains(&r.rt_ms) && r.error));
+        // the same seed, the same profile
+        assert_eq!(profile, TrafficProfile::synthetic("abc".into(), &phases, 1));
+    }
+
+    #[test]
+    fn recorded() {
+        let profile: TrafficProfile = serde_json::from_str(
+            r#"{"resource": "abc", "requests": [{"offset_ms": 20, "rt_ms": 5}, {"offset_ms": 10, "error": true}]}"#,
+        )
+        .unwrap();
+        let profile = TrafficProfile::recorded(profile.resource, profile.requests);
+        assert_eq!(profile.requests[0].offset_ms, 10);
+        assert!(profile.requests[0].error);
+        assert_eq!(profile.duration_ms(), 25);
+    }
+
+    #[test]
+    fn exponential_rt() {
+        let mut rng = XorShift::new(7);
+        let rt = RtDistribution::Exponential { mean_ms: 50.0 };
+        let mean = (0..10000).map(|_| rt.sample(&mut rng)).sum::<u64>() as f64 / 10000.0;
+        assert!((mean - 50.0).abs() < 5.0, "{}", mean);
+    }
+}
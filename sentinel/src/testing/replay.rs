@@ -0,0 +1,267 @@
+use super::TrafficProfile;
+use crate::base::{BlockError, EntryStrongPtr, Snapshot};
+use crate::circuitbreaker::{self, State, StateChangeListener};
+use crate::utils::MockClock;
+use crate::{load_rule_set, EntryBuilder, Error, Result, RuleSet};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Once};
+
+// the replays start at distinct epochs (aligned to minutes), thus the statistics left by the previous replays are deprecated
+const REPLAY_EPOCH_ALIGNMENT_MS: u64 = 60_000;
+static NEXT_REPLAY_EPOCH: AtomicU64 = AtomicU64::new(1_600_000_000_000);
+static LISTENER_ONCE: Once = Once::new();
+
+lazy_static! {
+    // the rules are global, thus the replays are serialized
+    static ref REPLAY_LOCK: Mutex<()> = Mutex::new(());
+}
+
+std::thread_local! {
+    // the start of the replay in current thread and the transitions recorded
+    static TRANSITIONS: RefCell<Option<(u64, Vec<StateTransition>)>> = RefCell::new(None);
+}
+
+/// `StateTransition` is a state change of the circuit breakers during the replay.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateTransition {
+    pub offset_ms: u64,
+    pub resource: String,
+    pub from: State,
+    pub to: State,
+}
+
+/// `TimelineBucket` is the statistic of the requests in a period of the replay,
+/// the completions are counted in the bucket where the requests complete.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct TimelineBucket {
+    pub offset_ms: u64,
+    pub passed: u64,
+    pub blocked: u64,
+    /// the blocked requests grouped by the block types
+    pub blocked_by: BTreeMap<String, u64>,
+    pub completed: u64,
+    pub errors: u64,
+    pub total_rt_ms: u64,
+}
+
+impl TimelineBucket {
+    pub fn avg_rt_ms(&self) -> f64 {
+        if self.completed == 0 {
+            0.0
+        } else {
+            self.total_rt_ms as f64 / self.completed as f64
+        }
+    }
+}
+
+/// `ReplayReport` is the timeline of the replay.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ReplayReport {
+    pub bucket_ms: u64,
+    pub timeline: Vec<TimelineBucket>,
+    pub transitions: Vec<StateTransition>,
+}
+
+impl ReplayReport {
+    fn bucket_mut(&mut self, offset_ms: u64) -> &mut TimelineBucket {
+        let idx = (offset_ms / self.bucket_ms) as usize;
+        while self.timeline.len() <= idx {
+            let offset_ms = self.timeline.len() as u64 * self.bucket_ms;
+            self.timeline.push(TimelineBucket {
+                offset_ms,
+                ..Default::default()
+            });
+        }
+        &mut self.timeline[idx]
+    }
+
+    pub fn passed(&self) -> u64 {
+        self.timeline.iter().map(|b| b.passed).sum()
+    }
+
+    pub fn blocked(&self) -> u64 {
+        self.timeline.iter().map(|b| b.blocked).sum()
+    }
+
+    pub fn block_ratio(&self) -> f64 {
+        let total = self.passed() + self.blocked();
+        if total == 0 {
+            0.0
+        } else {
+            self.blocked() as f64 / total as f64
+        }
+    }
+}
+
+/// `Replayer` replays the traffic profiles against the rule set.
+#[derive(Debug, Clone)]
+pub struct Replayer {
+    rule_set: RuleSet,
+    bucket_ms: u64,
+}
+
+impl Replayer {
+    pub fn new(rule_set: RuleSet) -> Self {
+        Replayer {
+            rule_set,
+            bucket_ms: 1000,
+        }
+    }
+
+    /// `with_bucket_ms` sets the resolution of the timeline, 1000 ms by default.
+    pub fn with_bucket_ms(mut self, bucket_ms: u64) -> Self {
+        self.bucket_ms = bucket_ms.max(1);
+        self
+    }
+
+    /// `replay` loads the rule set on a `MockClock`, replays the requests of the profile in current thread
+    /// and clears the rules at the end. The passed requests exit after their response time,
+    /// and the ones of errors are completed with a business error.
+    pub fn replay(&self, profile: &TrafficProfile) -> Result<ReplayReport> {
+        let _lock = REPLAY_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        LISTENER_ONCE.call_once(|| {
+            circuitbreaker::register_state_change_listeners(vec![Arc::new(TransitionRecorder)])
+        });
+
+        let windows = profile.duration_ms() / REPLAY_EPOCH_ALIGNMENT_MS + 2;
+        let start =
+            NEXT_REPLAY_EPOCH.fetch_add(windows * REPLAY_EPOCH_ALIGNMENT_MS, Ordering::SeqCst);
+        let clock = Arc::new(MockClock::starting_at(start));
+        let _guard = clock.install();
+        // the breakers and the controllers of the previous rules are not reused
+        load_rule_set(RuleSet::default())?;
+        load_rule_set(self.rule_set.clone())?;
+        TRANSITIONS.with(|t| *t.borrow_mut() = Some((start, Vec::new())));
+
+        let mut report = ReplayReport {
+            bucket_ms: self.bucket_ms,
+            ..Default::default()
+        };
+        let mut inflight = Inflight::default();
+        for request in &profile.requests {
+            inflight.complete_until(request.offset_ms, start, &clock, &mut report);
+            clock.set_millis(start + request.offset_ms);
+            match EntryBuilder::new(profile.resource.clone()).build() {
+                Ok(entry) => {
+                    report.bucket_mut(request.offset_ms).passed += 1;
+                    inflight.push(request.offset_ms + request.rt_ms, entry, request.error);
+                }
+                Err(err) => {
+                    let block_type = err
+                        .downcast_ref::<BlockError>()
+                        .map(|err| err.block_type().to_string())
+                        .unwrap_or_default();
+                    let bucket = report.bucket_mut(request.offset_ms);
+                    bucket.blocked += 1;
+                    *bucket.blocked_by.entry(block_type).or_default() += 1;
+                }
+            }
+        }
+        inflight.complete_until(u64::MAX, start, &clock, &mut report);
+
+        report.transitions = TRANSITIONS
+            .with(|t| t.borrow_mut().take())
+            .map(|(_, transitions)| transitions)
+            .unwrap_or_default();
+        load_rule_set(RuleSet::default())?;
+        Ok(report)
+    }
+}
+
+// the passed entries of the replay by the exiting offsets
+#[derive(Default)]
+struct Inflight {
+    exits: BinaryHeap<Reverse<(u64, usize)>>,
+    entries: Vec<Option<(EntryStrongPtr, bool)>>,
+}
+
+impl Inflight {
+    fn push(&mut self, exit_ms: u64, entry: EntryStrongPtr, error: bool) {
+        self.exits.push(Reverse((exit_ms, self.entries.len())));
+        self.entries.push(Some((entry, error)));
+    }
+
+    // exits the entries until the offset, in the order of the exiting offsets
+    fn complete_until(
+        &mut self,
+        until: u64,
+        start: u64,
+        clock: &MockClock,
+        report: &mut ReplayReport,
+    ) {
+        while let Some(Reverse((exit_ms, idx))) = self.exits.peek().copied() {
+            if exit_ms > until {
+                break;
+            }
+            self.exits.pop();
+            clock.set_millis(start + exit_ms);
+            if let Some((entry, error)) = self.entries[idx].take() {
+                let entry = entry.read().unwrap();
+                if error {
+                    entry
+                        .context()
+                        .write()
+                        .unwrap()
+                        .set_err(Error::msg("simulated error"));
+                }
+                entry.exit();
+                // including the errors injected by the fault rules
+                let (rt, errored) = {
+                    let ctx = entry.context().read().unwrap();
+                    (ctx.round_trip(), ctx.get_err().is_some())
+                };
+                let bucket = report.bucket_mut(exit_ms);
+                bucket.completed += 1;
+                bucket.total_rt_ms += rt;
+                if errored {
+                    bucket.errors += 1;
+                }
+            }
+        }
+    }
+}
+
+// records the transitions of the replay in current thread, it is registered once
+struct TransitionRecorder;
+
+impl TransitionRecorder {
+    fn record(&self, from: State, to: State, rule: &circuitbreaker::Rule) {
+        // the transitions out of the replays are ignored
+        let _ = TRANSITIONS.try_with(|t| {
+            if let Some((start, transitions)) = t.borrow_mut().as_mut() {
+                transitions.push(StateTransition {
+                    offset_ms: crate::utils::curr_time_millis().saturating_sub(*start),
+                    resource: rule.resource.clone(),
+                    from,
+                    to,
+                });
+            }
+        });
+    }
+}
+
+impl StateChangeListener for TransitionRecorder {
+    fn on_transform_to_closed(&self, prev: State, rule: Arc<circuitbreaker::Rule>) {
+        self.record(prev, State::Closed, &rule);
+    }
+
+    fn on_transform_to_open(
+        &self,
+        prev: State,
+        rule: Arc<circuitbreaker::Rule>,
+        _snapshot: Option<Arc<Snapshot>>,
+    ) {
+        self.record(prev, State::Open, &rule);
+    }
+
+    fn on_transform_to_half_open(&self, prev: State, rule: Arc<circuitbreaker::Rule>) {
+        self.record(prev, State::HalfOpen, &rule);
+    }
+}
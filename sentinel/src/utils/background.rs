@@ -0,0 +1,137 @@
+//! The background tasks of Sentinel, e.g., the time ticker, the aggregation of the block log and the reload watcher.
+//! The tasks are threads spawned by `spawn_task()`, they sleep by `sleep_unless_stopped()` between the rounds,
+//! thus `api::shutdown()` wakes them up and joins them instead of leaking them.
+//! The tasks blocking on something else (e.g., the signals) are interrupted by the closers registered by `on_stop()`.
+use lazy_static::lazy_static;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+type Closer = Box<dyn FnOnce() + Send>;
+
+lazy_static! {
+    static ref TASKS: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+    static ref CLOSERS: Mutex<Vec<Closer>> = Mutex::new(Vec::new());
+    static ref STOPPED: Mutex<bool> = Mutex::new(false);
+    static ref STOPPED_CHANGED: Condvar = Condvar::new();
+}
+
+const JOIN_POLL_INTERVAL_MS: u64 = 1;
+
+// the tasks never panic while holding the locks, but the panics of the closers are tolerated
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// `spawn_task` spawns the named thread of the background task, which is joined by `api::shutdown()`.
+pub fn spawn_task<F>(name: &str, task: F) -> std::io::Result<()>
+where
+    F: FnOnce() + Send + 'static,
+{
+    let handle = std::thread::Builder::new().name(name.into()).spawn(task)?;
+    let mut tasks = lock(&TASKS);
+    // the exited tasks are forgotten
+    tasks.retain(|task| !task.is_finished());
+    tasks.push(handle);
+    Ok(())
+}
+
+/// `on_stop` registers the closer called once when the tasks are stopped,
+/// it interrupts the task blocking on something other than `sleep_unless_stopped()`.
+pub fn on_stop<F>(closer: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    lock(&CLOSERS).push(Box::new(closer));
+}
+
+/// `is_stopped` returns whether the background tasks are stopped by `api::shutdown()`.
+pub fn is_stopped() -> bool {
+    *lock(&STOPPED)
+}
+
+/// `sleep_unless_stopped` sleeps for the duration and returns `true`,
+/// or returns `false` as soon as the background tasks are stopped.
+pub fn sleep_unless_stopped(ms: u64) -> bool {
+    let stopped = lock(&STOPPED);
+    let (stopped, _) = STOPPED_CHANGED
+        .wait_timeout_while(stopped, Duration::from_millis(ms), |stopped| !*stopped)
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    !*stopped
+}
+
+// stops the tasks and waits for them until the deadline, the names of the unfinished tasks are returned
+pub(crate) fn stop_tasks(deadline: Instant) -> Vec<String> {
+    *lock(&STOPPED) = true;
+    STOPPED_CHANGED.notify_all();
+    let closers = std::mem::take(&mut *lock(&CLOSERS));
+    for closer in closers {
+        closer();
+    }
+
+    let mut tasks = std::mem::take(&mut *lock(&TASKS));
+    // there is no timeout to join a thread, thus the tasks are polled
+    while tasks.iter().any(|task| !task.is_finished()) && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(JOIN_POLL_INTERVAL_MS));
+    }
+    let (finished, unfinished): (Vec<_>, Vec<_>) =
+        tasks.into_iter().partition(|task| task.is_finished());
+    for task in finished {
+        let _ = task.join();
+    }
+    let names = unfinished
+        .iter()
+        .map(|task| task.thread().name().unwrap_or_default().to_string())
+        .collect();
+    // the unfinished tasks are waited by the next shutdown
+    lock(&TASKS).extend(unfinished);
+    names
+}
+
+// allows the background tasks to be spawned again, e.g., Sentinel is initialized again after the shutdown
+pub(crate) fn resume_tasks() {
+    *lock(&STOPPED) = false;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    #[ignore]
+    fn stop() {
+        let rounds = Arc::new(AtomicBool::new(false));
+        let closed = Arc::new(AtomicBool::new(false));
+        spawn_task("sentinel-test-task", {
+            let rounds = Arc::clone(&rounds);
+            move || {
+                while sleep_unless_stopped(10) {
+                    rounds.store(true, Ordering::SeqCst);
+                }
+            }
+        })
+        .unwrap();
+        on_stop({
+            let closed = Arc::clone(&closed);
+            move || closed.store(true, Ordering::SeqCst)
+        });
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(rounds.load(Ordering::SeqCst));
+
+        let start = Instant::now();
+        let unfinished = stop_tasks(start + Duration::from_secs(1));
+        assert!(unfinished.is_empty());
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert!(closed.load(Ordering::SeqCst));
+        assert!(is_stopped());
+        assert!(!sleep_unless_stopped(1000));
+
+        resume_tasks();
+        assert!(!is_stopped());
+        assert!(sleep_unless_stopped(1));
+    }
+}
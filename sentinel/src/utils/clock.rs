@@ -0,0 +1,293 @@
+//! `Clock` is the source of the timestamps of Sentinel, i.e., `utils::curr_time_millis()` and `utils::curr_time_nanos()`,
+//! which drive the sliding windows, the retry timeout of the circuit breakers, the warm-up of the flow controllers, etc.
+//! The monotonic clock anchored at the system time is used by default, thus the steps of the system time
+//! (e.g., by NTP or manually) never expire the open circuit breakers instantly or corrupt the sliding windows.
+//! The exported timestamps, e.g., of the names and the reports, still read the system time, see `utils::wall_time_millis()`.
+//! The clock could be overridden
+//! 1. globally by `set_clock()`, e.g., the clock of the host of the proxy-wasm filters,
+//! 2. in current thread by `set_thread_clock()`, which takes precedence over the global one,
+//!    thus the tests running in parallel threads control their own time.
+//!
+//! The `MockClock` of the `test-util` feature is a manually advanced clock for the deterministic tests without real sleeps.
+use lazy_static::lazy_static;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use time::OffsetDateTime;
+cfg_test_util! {
+    use std::sync::atomic::AtomicU64;
+    use std::time::Duration;
+}
+
+/// `Clock` provides current unix timestamp.
+pub trait Clock: Send + Sync {
+    /// `curr_time_nanos` returns the unix timestamp in nanoseconds.
+    fn curr_time_nanos(&self) -> i128;
+}
+
+/// `SystemClock` reads the system time.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline]
+    fn curr_time_nanos(&self) -> i128 {
+        OffsetDateTime::now_utc().unix_timestamp_nanos()
+    }
+}
+
+/// `MonotonicClock` reads the monotonic time, anchored at the system time when it is read at the first time.
+/// It is the default clock of Sentinel, and it drifts from the system time by the steps of the system time since then.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MonotonicClock;
+
+lazy_static! {
+    // the monotonic time and the system time at the anchor
+    static ref MONOTONIC_ANCHOR: (Instant, i128) = (Instant::now(), SystemClock.curr_time_nanos());
+}
+
+impl Clock for MonotonicClock {
+    #[inline]
+    fn curr_time_nanos(&self) -> i128 {
+        let (instant, nanos) = *MONOTONIC_ANCHOR;
+        nanos + instant.elapsed().as_nanos() as i128
+    }
+}
+
+// the clock of `utils::set_time_source()`
+struct FnClock(fn() -> i128);
+
+impl Clock for FnClock {
+    #[inline]
+    fn curr_time_nanos(&self) -> i128 {
+        (self.0)()
+    }
+}
+
+lazy_static! {
+    static ref GLOBAL_CLOCK: RwLock<Option<Arc<dyn Clock>>> = RwLock::new(None);
+}
+
+// the amount of the overridden clocks (the global one and the thread ones),
+// the system clock is read directly without any lookup if there is none
+static OVERRIDDEN_CLOCKS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Default)]
+struct ThreadClock(RefCell<Option<Arc<dyn Clock>>>);
+
+impl Drop for ThreadClock {
+    fn drop(&mut self) {
+        if self.0.get_mut().is_some() {
+            OVERRIDDEN_CLOCKS.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+std::thread_local! {
+    static THREAD_CLOCK: ThreadClock = ThreadClock::default();
+}
+
+/// `set_clock` overrides the system clock in all threads.
+pub fn set_clock(clock: Arc<dyn Clock>) {
+    if GLOBAL_CLOCK
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .replace(clock)
+        .is_none()
+    {
+        OVERRIDDEN_CLOCKS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// `reset_clock` restores the system clock overridden by `set_clock()`.
+pub fn reset_clock() {
+    if GLOBAL_CLOCK
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take()
+        .is_some()
+    {
+        OVERRIDDEN_CLOCKS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// `set_thread_clock` overrides the clock in current thread,
+/// notice that the background tasks and the asynchronous tasks moved across threads still use the global clock.
+pub fn set_thread_clock(clock: Arc<dyn Clock>) {
+    THREAD_CLOCK.with(|c| {
+        if c.0.borrow_mut().replace(clock).is_none() {
+            OVERRIDDEN_CLOCKS.fetch_add(1, Ordering::SeqCst);
+        }
+    })
+}
+
+/// `reset_thread_clock` restores the clock of current thread overridden by `set_thread_clock()`.
+pub fn reset_thread_clock() {
+    // the thread clock may have been destroyed if the thread is exiting
+    let _ = THREAD_CLOCK.try_with(|c| {
+        if c.0.borrow_mut().take().is_some() {
+            OVERRIDDEN_CLOCKS.fetch_sub(1, Ordering::SeqCst);
+        }
+    });
+}
+
+/// `set_time_source` replaces the system clock by the source returning the unix timestamp in nanoseconds,
+/// e.g., the clock of the host of the proxy-wasm filters, where `std::time` is not fully available.
+pub fn set_time_source(source: fn() -> i128) {
+    set_clock(Arc::new(FnClock(source)));
+}
+
+/// `reset_time_source` restores the system clock.
+pub fn reset_time_source() {
+    reset_clock();
+}
+
+// returns the timestamp of the overridden clock of current thread or the global one, if there is any
+#[inline]
+pub(super) fn overridden_time_nanos() -> Option<i128> {
+    if OVERRIDDEN_CLOCKS.load(Ordering::Relaxed) == 0 {
+        return None;
+    }
+    // the clock is cloned out, in case it reads the time by `utils` itself
+    let clock = THREAD_CLOCK
+        .try_with(|c| c.0.borrow().clone())
+        .ok()
+        .flatten()
+        .or_else(|| {
+            GLOBAL_CLOCK
+                .read()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .clone()
+        });
+    clock.map(|clock| clock.curr_time_nanos())
+}
+
+cfg_test_util! {
+    /// `MockClock` is the clock only advanced manually, for the deterministic tests.
+    /// It is installed in current thread by `MockClock::install()`, or globally by `utils::set_clock()`.
+    #[derive(Debug)]
+    pub struct MockClock {
+        nanos: AtomicU64,
+    }
+
+    impl Default for MockClock {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl MockClock {
+        /// `new` creates the clock starting at current system time.
+        pub fn new() -> Self {
+            Self::starting_at_nanos(SystemClock.curr_time_nanos() as u64)
+        }
+
+        /// `starting_at` creates the clock starting at the unix timestamp in milliseconds.
+        pub fn starting_at(ts_millis: u64) -> Self {
+            Self::starting_at_nanos(ts_millis * 1_000_000)
+        }
+
+        pub fn starting_at_nanos(ts_nanos: u64) -> Self {
+            MockClock {
+                nanos: AtomicU64::new(ts_nanos),
+            }
+        }
+
+        /// `install` overrides the clock of current thread by this one, until the returned guard is dropped.
+        pub fn install(self: &Arc<Self>) -> MockClockGuard {
+            set_thread_clock(Arc::clone(self) as Arc<dyn Clock>);
+            MockClockGuard { _private: () }
+        }
+
+        pub fn curr_time_millis(&self) -> u64 {
+            self.nanos.load(Ordering::SeqCst) / 1_000_000
+        }
+
+        pub fn set_millis(&self, ts_millis: u64) {
+            self.nanos.store(ts_millis * 1_000_000, Ordering::SeqCst);
+        }
+
+        pub fn advance(&self, duration: Duration) {
+            self.nanos
+                .fetch_add(duration.as_nanos() as u64, Ordering::SeqCst);
+        }
+
+        pub fn advance_millis(&self, ms: u64) {
+            self.advance(Duration::from_millis(ms));
+        }
+    }
+
+    impl Clock for MockClock {
+        fn curr_time_nanos(&self) -> i128 {
+            self.nanos.load(Ordering::SeqCst) as i128
+        }
+    }
+
+    /// `MockClockGuard` restores the clock of current thread when dropped, see `MockClock::install()`.
+    #[derive(Debug)]
+    pub struct MockClockGuard {
+        _private: (),
+    }
+
+    impl Drop for MockClockGuard {
+        fn drop(&mut self) {
+            reset_thread_clock();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils;
+
+    #[test]
+    fn mock_clock() {
+        let clock = Arc::new(MockClock::starting_at(1_600_000_000_000));
+        let guard = clock.install();
+        assert_eq!(utils::curr_time_millis(), 1_600_000_000_000);
+        assert_eq!(utils::curr_time_nanos(), 1_600_000_000_000_000_000);
+        clock.advance_millis(1500);
+        assert_eq!(utils::curr_time_millis(), 1_600_000_001_500);
+        clock.set_millis(1000);
+        assert_eq!(utils::curr_time_millis(), 1000);
+        drop(guard);
+        assert!(utils::curr_time_millis() > 1_600_000_001_500);
+    }
+
+    #[test]
+    fn monotonic_clock() {
+        let anchored = MonotonicClock.curr_time_nanos();
+        let system = SystemClock.curr_time_nanos();
+        // the system time is not stepped during the test
+        assert!((system - anchored).abs() < 1_000_000_000);
+        let mut last = anchored;
+        for _ in 0..1000 {
+            let curr = MonotonicClock.curr_time_nanos();
+            assert!(curr >= last);
+            last = curr;
+        }
+    }
+
+    #[test]
+    fn wall_time_of_mock_clock() {
+        let clock = Arc::new(MockClock::starting_at(1_600_000_000_000));
+        let _guard = clock.install();
+        // the overridden clock is regarded as the system time as well
+        assert_eq!(utils::wall_time_millis(), 1_600_000_000_000);
+        assert_eq!(
+            utils::to_wall_time_millis(1_599_999_999_000),
+            1_599_999_999_000
+        );
+    }
+
+    #[test]
+    fn thread_clock_isolated() {
+        let clock = Arc::new(MockClock::starting_at(1000));
+        let _guard = clock.install();
+        let other = std::thread::spawn(utils::curr_time_millis).join().unwrap();
+        assert!(other > 1_600_000_000_000);
+        assert_eq!(utils::curr_time_millis(), 1000);
+    }
+}
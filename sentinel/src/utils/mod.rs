@@ -1,8 +1,13 @@
 use std::any::Any;
 use std::sync::Arc;
 
+pub mod background;
+pub mod clock;
+pub mod sharded_map;
 pub mod time;
 
+pub use self::clock::*;
+pub use self::sharded_map::*;
 pub use self::time::*;
 
 pub fn is_blank(path: &String) -> bool {
@@ -0,0 +1,249 @@
+//! `ShardedMap` is the concurrent map of the global rules, breakers and traffic shaping controllers,
+//! i.e., the maps of the rule managers looked up on the request hot path.
+//! The entries are spread over the shards guarded by their own `RwLock`, the lookups only take the read lock
+//! of a shard, and the reloads build the new entries without any lock,
+//! then swap them into the shards one by one, thus a reload never stalls the traffic of the whole map
+//! and the replaced entries are dropped after the locks are released.
+//! The reloads of a map are expected to be serialized by the rule managers,
+//! which keep the raw rules behind a lock held while loading them.
+//!
+//! Notice that a reload is atomic per key, but not across the keys: the lookups during `replace()`
+//! may see the new entries of some resources and the previous entries of the others,
+//! e.g., a rule set moving a limit from one resource to another takes effect resource by resource.
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// the default number of the shards, a power of two
+pub const DEFAULT_SHARD_AMOUNT: usize = 16;
+
+pub struct ShardedMap<K, V> {
+    shards: Box<[RwLock<HashMap<K, V>>]>,
+    hasher: RandomState,
+}
+
+impl<K: Hash + Eq, V> Default for ShardedMap<K, V> {
+    fn default() -> Self {
+        Self::with_shard_amount(DEFAULT_SHARD_AMOUNT)
+    }
+}
+
+impl<K: Hash + Eq + fmt::Debug, V: fmt::Debug> fmt::Debug for ShardedMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        for idx in 0..self.shards.len() {
+            map.entries(self.read_shard(idx).iter());
+        }
+        map.finish()
+    }
+}
+
+impl<K: Hash + Eq, V> ShardedMap<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `with_shard_amount` creates the map with the amount of shards, it is rounded up to a power of two.
+    pub fn with_shard_amount(amount: usize) -> Self {
+        let amount = amount.max(1).next_power_of_two();
+        ShardedMap {
+            shards: (0..amount).map(|_| RwLock::new(HashMap::new())).collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    pub fn shard_amount(&self) -> usize {
+        self.shards.len()
+    }
+
+    fn shard_index<Q: Hash + ?Sized>(&self, key: &Q) -> usize {
+        let mut hasher = self.hasher.build_hasher();
+        key.hash(&mut hasher);
+        (hasher.finish() as usize) & (self.shards.len() - 1)
+    }
+
+    // the poisoned locks are recovered, since the shards are always left consistent
+    fn read_shard(&self, idx: usize) -> RwLockReadGuard<'_, HashMap<K, V>> {
+        self.shards[idx]
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write_shard(&self, idx: usize) -> RwLockWriteGuard<'_, HashMap<K, V>> {
+        self.shards[idx]
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// `get` returns the cloned value of the key.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.read_shard(self.shard_index(key)).get(key).cloned()
+    }
+
+    /// `with` calls `f` on the value of the key under the read lock of its shard,
+    /// `f` should be short and must not access the same map.
+    pub fn with<Q, R>(&self, key: &Q, f: impl FnOnce(Option<&V>) -> R) -> R
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        f(self.read_shard(self.shard_index(key)).get(key))
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.read_shard(self.shard_index(key)).contains_key(key)
+    }
+
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        let idx = self.shard_index(&key);
+        self.write_shard(idx).insert(key, value)
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.write_shard(self.shard_index(key)).remove(key)
+    }
+
+    /// `replace` replaces all the entries by the map, the previous entries are returned.
+    /// Each shard is swapped under its own write lock, thus it is not atomic across the shards.
+    pub fn replace(&self, map: HashMap<K, V>) -> HashMap<K, V> {
+        let mut shards: Vec<HashMap<K, V>> =
+            (0..self.shards.len()).map(|_| HashMap::new()).collect();
+        for (key, value) in map {
+            shards[self.shard_index(&key)].insert(key, value);
+        }
+        let mut previous = HashMap::new();
+        for (idx, shard) in shards.into_iter().enumerate() {
+            let shard = std::mem::replace(&mut *self.write_shard(idx), shard);
+            previous.extend(shard);
+        }
+        previous
+    }
+
+    /// `clear` removes all the entries, they are dropped after the locks are released.
+    pub fn clear(&self) {
+        self.replace(HashMap::new());
+    }
+
+    pub fn len(&self) -> usize {
+        (0..self.shards.len())
+            .map(|idx| self.read_shard(idx).len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// `for_each` visits the entries shard by shard, under the read lock of each shard.
+    pub fn for_each(&self, mut f: impl FnMut(&K, &V)) {
+        for idx in 0..self.shards.len() {
+            for (key, value) in self.read_shard(idx).iter() {
+                f(key, value);
+            }
+        }
+    }
+
+    /// `snapshot` returns the cloned entries.
+    pub fn snapshot(&self) -> HashMap<K, V>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let mut map = HashMap::new();
+        self.for_each(|key, value| {
+            map.insert(key.clone(), value.clone());
+        });
+        map
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn shard_amount() {
+        assert_eq!(
+            ShardedMap::<String, u32>::new().shard_amount(),
+            DEFAULT_SHARD_AMOUNT
+        );
+        assert_eq!(
+            ShardedMap::<String, u32>::with_shard_amount(0).shard_amount(),
+            1
+        );
+        assert_eq!(
+            ShardedMap::<String, u32>::with_shard_amount(5).shard_amount(),
+            8
+        );
+    }
+
+    #[test]
+    fn insert_get_remove() {
+        let map = ShardedMap::new();
+        assert!(map.is_empty());
+        assert_eq!(map.insert("a".to_string(), 1), None);
+        assert_eq!(map.insert("a".to_string(), 2), Some(1));
+        map.insert("b".to_string(), 3);
+        assert_eq!(map.get("a"), Some(2));
+        assert!(map.contains_key("b"));
+        assert_eq!(map.with("b", |v| v.copied().unwrap_or_default() + 1), 4);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.remove("a"), Some(2));
+        assert_eq!(map.get("a"), None);
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn replace() {
+        let map = ShardedMap::new();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        let previous = map.replace((50..150).map(|i| (i, i * 2)).collect());
+        assert_eq!(previous.len(), 100);
+        assert_eq!(map.len(), 100);
+        assert_eq!(map.get(&10), None);
+        assert_eq!(map.get(&60), Some(120));
+        assert_eq!(map.snapshot().len(), 100);
+        map.clear();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn lookup_during_replace() {
+        let map = Arc::new(ShardedMap::new());
+        map.replace((0..64).map(|i| (i, 0)).collect());
+        let reader = {
+            let map = Arc::clone(&map);
+            std::thread::spawn(move || {
+                for _ in 0..10000 {
+                    // each key is either of the old or the new value
+                    let value = map.get(&7).unwrap();
+                    assert!(value <= 100);
+                }
+            })
+        };
+        for round in 1..=100 {
+            map.replace((0..64).map(|i| (i, round)).collect());
+        }
+        reader.join().unwrap();
+        assert_eq!(map.get(&7), Some(100));
+    }
+}
@@ -1,3 +1,4 @@
+use super::clock::{overridden_time_nanos, Clock, MonotonicClock, SystemClock};
 use lazy_static::lazy_static;
 use time::{Duration, OffsetDateTime};
 
@@ -26,7 +27,7 @@ pub fn sleep_for_ns(ns: u64) {
 
 #[inline]
 fn cal_curr_time_millis() -> u64 {
-    (OffsetDateTime::now_utc().unix_timestamp_nanos() / (*UNIX_TIME_UNIT_OFFSET)) as u64
+    (curr_time_nanos() / (*UNIX_TIME_UNIT_OFFSET)) as u64
 }
 
 #[inline]
@@ -37,11 +38,15 @@ pub fn format_time_millis(ts_millis: u64) -> String {
 
 #[inline]
 pub fn format_time_nanos_curr() -> String {
-    OffsetDateTime::from_unix_timestamp_nanos(curr_time_nanos())
+    OffsetDateTime::from_unix_timestamp_nanos(wall_time_nanos())
         .format(time::Format::Custom(TIME_FORMAT.into()))
 }
 
 pub fn curr_time_millis() -> u64 {
+    // the overridden clock takes precedence over the ticker, see `utils::clock`
+    if let Some(nanos) = overridden_time_nanos() {
+        return (nanos / (*UNIX_TIME_UNIT_OFFSET)) as u64;
+    }
     // todo: conditional compilation, `config::use_cache_time()`
     let ticker_time = curr_time_millis_with_ticker();
     if ticker_time > 0 {
@@ -51,9 +56,30 @@ pub fn curr_time_millis() -> u64 {
     }
 }
 
+/// `curr_time_nanos` returns the timestamp of the monotonic clock (unless overridden) in nanoseconds,
+/// which is used for all the scheduling, e.g., the sliding windows and the retry timeout of the circuit breakers.
 #[inline]
 pub fn curr_time_nanos() -> i128 {
-    OffsetDateTime::now_utc().unix_timestamp_nanos()
+    overridden_time_nanos().unwrap_or_else(|| MonotonicClock.curr_time_nanos())
+}
+
+/// `wall_time_nanos` returns the unix timestamp of the system time (unless overridden) in nanoseconds,
+/// which is only used for the exported timestamps, since it may be stepped.
+#[inline]
+pub fn wall_time_nanos() -> i128 {
+    overridden_time_nanos().unwrap_or_else(|| SystemClock.curr_time_nanos())
+}
+
+#[inline]
+pub fn wall_time_millis() -> u64 {
+    (wall_time_nanos() / (*UNIX_TIME_UNIT_OFFSET)) as u64
+}
+
+/// `to_wall_time_millis` converts the timestamp of `curr_time_millis()` (e.g., the start of a bucket)
+/// to the system time, by the current offset between the clocks.
+pub fn to_wall_time_millis(ts_millis: u64) -> u64 {
+    let offset_ms = (wall_time_nanos() - curr_time_nanos()) / (*UNIX_TIME_UNIT_OFFSET);
+    (ts_millis as i128 + offset_ms).max(0) as u64
 }
 
 #[inline]
@@ -66,22 +92,44 @@ pub use ticker::*;
 // provide cached time by a ticker
 pub mod ticker {
     use super::*;
+    use crate::{logging, utils::background};
     use lazy_static::lazy_static;
-    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
     lazy_static! {
         static ref NOW_IN_MS: AtomicU64 = AtomicU64::new(0);
     }
+    static TICKER_STARTED: AtomicBool = AtomicBool::new(false);
 
     /// `start_time_ticker()` starts a background task that caches current timestamp per millisecond,
     /// which may provide better performance in high-concurrency scenarios.
+    /// The ticker is stopped by `api::shutdown()`, then the timestamp is calculated directly.
     pub fn start_time_ticker() {
+        if background::is_stopped() || TICKER_STARTED.swap(true, Ordering::SeqCst) {
+            return;
+        }
         update_time();
-        std::thread::spawn(move || {
-            update_time();
-            std::thread::sleep(std::time::Duration::from_millis(1));
+        let spawned = background::spawn_task("sentinel-time-ticker", || {
+            while background::sleep_unless_stopped(1) {
+                update_time();
+            }
+            stop_time_ticker();
         });
+        if let Err(err) = spawned {
+            logging::error!(
+                "[TimeTicker] Failed to start the time ticker, error: {:?}",
+                err
+            );
+            stop_time_ticker();
+        }
     }
+
+    // the cached timestamp is outdated since then
+    fn stop_time_ticker() {
+        NOW_IN_MS.store(0, Ordering::SeqCst);
+        TICKER_STARTED.store(false, Ordering::SeqCst);
+    }
+
     #[inline]
     fn update_time() {
         let curr = cal_curr_time_millis();
@@ -0,0 +1,165 @@
+#![cfg(feature = "actix-web")]
+
+mod common;
+
+use actix_web::http::{header, StatusCode};
+use actix_web::{test, web, App, HttpResponse};
+use sentinel_rs::adapters::actix_web::Sentinel;
+use sentinel_rs::base::BlockType;
+use std::time::Duration;
+
+fn middleware() -> Sentinel {
+    Sentinel::new().with_origin_header(header::HeaderName::from_static("x-sentinel-origin"))
+}
+
+fn get_request(uri: &str) -> test::TestRequest {
+    test::TestRequest::get()
+        .uri(uri)
+        .insert_header(("x-sentinel-origin", "caller"))
+}
+
+#[actix_web::test]
+async fn pattern_resource() {
+    let recorder = common::register("GET:/actix/users/{id}", None);
+    let failed = common::register("GET:/actix/failed", None);
+    let app = test::init_service(
+        App::new()
+            .wrap(middleware())
+            .route("/actix/users/{id}", web::get().to(|| async { "user" }))
+            .route(
+                "/actix/failed",
+                web::get().to(|| async { HttpResponse::InternalServerError().finish() }),
+            ),
+    )
+    .await;
+
+    for id in 0..3 {
+        let request = get_request(&format!("/actix/users/{}", id)).to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    assert_eq!(recorder.passed(), 3);
+    assert_eq!(recorder.completed(), 3);
+    assert_eq!(recorder.errors(), 0);
+    assert_eq!(recorder.origins(), vec!["caller"; 3]);
+
+    let response = test::call_service(&app, get_request("/actix/failed").to_request()).await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(failed.completed(), 1);
+    assert_eq!(failed.errors(), 1);
+}
+
+#[actix_web::test]
+async fn blocked() {
+    let flow = common::register("GET:/actix/flow", Some(BlockType::Flow));
+    common::register("GET:/actix/breaker", Some(BlockType::CircuitBreaking));
+    let app = test::init_service(
+        App::new()
+            .wrap(middleware())
+            .route("/actix/flow", web::get().to(|| async { "flow" }))
+            .route("/actix/breaker", web::get().to(|| async { "breaker" })),
+    )
+    .await;
+
+    let response = test::call_service(&app, get_request("/actix/flow").to_request()).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "1");
+    let body: serde_json::Value = test::read_body_json(response).await;
+    assert_eq!(body["resource"], "GET:/actix/flow");
+    assert_eq!(flow.blocked(), 1);
+    assert_eq!(flow.passed(), 0);
+
+    let response = test::call_service(&app, get_request("/actix/breaker").to_request()).await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[actix_web::test]
+async fn custom_resource_and_block_response() {
+    let recorder = common::register("actix_custom", Some(BlockType::Isolation));
+    let app = test::init_service(
+        App::new()
+            .wrap(
+                Sentinel::new()
+                    .with_resource_extractor(|_| "actix_custom".into())
+                    .with_block_response(|_| HttpResponse::ServiceUnavailable().body("busy")),
+            )
+            .route("/actix/custom", web::get().to(|| async { "custom" })),
+    )
+    .await;
+    let response = test::call_service(&app, get_request("/actix/custom").to_request()).await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(test::read_body(response).await, "busy");
+    assert_eq!(recorder.blocked(), 1);
+}
+
+#[actix_web::test]
+async fn panicked_and_cancelled() {
+    let panicked = common::register("GET:/actix/panicked", None);
+    let cancelled = common::register("GET:/actix/cancelled", None);
+    let app = test::init_service(
+        App::new()
+            .wrap(middleware())
+            .route(
+                "/actix/panicked",
+                web::get().to(|| async {
+                    if true {
+                        panic!("handler panicked");
+                    }
+                    ""
+                }),
+            )
+            .route(
+                "/actix/cancelled",
+                web::get().to(|| async {
+                    actix_web::rt::time::sleep(Duration::from_secs(60)).await;
+                    ""
+                }),
+            ),
+    )
+    .await;
+    let app = std::rc::Rc::new(app);
+
+    let cloned = app.clone();
+    let handle = actix_web::rt::spawn(async move {
+        test::call_service(&*cloned, get_request("/actix/panicked").to_request()).await
+    });
+    assert!(handle.await.unwrap_err().is_panic());
+    assert_eq!(panicked.completed(), 1);
+    assert_eq!(panicked.errors(), 1);
+
+    // the client disconnects
+    let request = test::call_service(&*app, get_request("/actix/cancelled").to_request());
+    assert!(
+        actix_web::rt::time::timeout(Duration::from_millis(10), request)
+            .await
+            .is_err()
+    );
+    assert_eq!(cancelled.passed(), 1);
+    assert_eq!(cancelled.completed(), 1);
+}
+
+#[actix_web::test]
+async fn rate_limit_headers() {
+    let quota = common::Quota::load("GET:/actix/quota");
+    let app = test::init_service(
+        App::new()
+            .wrap(middleware().with_rate_limit_headers(true))
+            .route("/actix/quota", web::get().to(|| async { "quota" })),
+    )
+    .await;
+
+    let mut responses = Vec::new();
+    for _ in 0..=common::Quota::LIMIT {
+        let response = test::call_service(&app, get_request("/actix/quota").to_request()).await;
+        let header = |name| {
+            let value = response.headers().get(name)?;
+            value.to_str().ok().map(String::from)
+        };
+        responses.push((
+            response.status() == StatusCode::OK,
+            header("ratelimit-limit"),
+            header("ratelimit-remaining"),
+        ));
+    }
+    quota.check(responses);
+}
@@ -0,0 +1,87 @@
+//! Tests on the `Send + Sync` entries, which are only available with the `async` feature.
+#![cfg(feature = "async")]
+
+use sentinel_rs::base::{
+    AsyncEntry, BaseSlot, BlockError, ContextPtr, EntryStrongPtr, SlotChain, StatSlot,
+};
+use sentinel_rs::EntryBuilder;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+#[derive(Default)]
+struct CountingStatSlot {
+    passed: AtomicU32,
+    completed: AtomicU32,
+}
+
+impl BaseSlot for CountingStatSlot {}
+
+impl StatSlot for CountingStatSlot {
+    fn on_entry_pass(&self, _ctx: ContextPtr) {
+        self.passed.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_entry_blocked(&self, _ctx: ContextPtr, _block_error: Option<BlockError>) {}
+
+    fn on_completed(&self, _ctx: ContextPtr) {
+        self.completed.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn slot_chain_with(slot: Arc<CountingStatSlot>) -> Arc<SlotChain> {
+    let mut sc = SlotChain::new();
+    sc.add_stat_slot(slot);
+    Arc::new(sc)
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn entry_types_are_send_sync() {
+    assert_send_sync::<ContextPtr>();
+    assert_send_sync::<EntryStrongPtr>();
+    assert_send_sync::<AsyncEntry>();
+}
+
+#[test]
+fn exit_on_another_thread() {
+    let slot = Arc::new(CountingStatSlot::default());
+    let entry = EntryBuilder::new("async_entry_exit_on_another_thread".into())
+        .with_slot_chain(slot_chain_with(slot.clone()))
+        .build_async()
+        .unwrap();
+    assert_eq!(slot.passed.load(Ordering::SeqCst), 1);
+
+    let moved = entry.clone();
+    std::thread::spawn(move || moved.exit()).join().unwrap();
+    assert!(entry.is_exited());
+    assert_eq!(slot.completed.load(Ordering::SeqCst), 1);
+
+    // exit is idempotent
+    entry.exit();
+    assert_eq!(slot.completed.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn exit_across_await_points() {
+    let slot = Arc::new(CountingStatSlot::default());
+    let sc = slot_chain_with(slot.clone());
+    let mut handles = Vec::new();
+    for i in 0..10 {
+        let sc = sc.clone();
+        handles.push(tokio::spawn(async move {
+            let entry = EntryBuilder::new(format!("async_entry_across_await_{}", i))
+                .with_slot_chain(sc)
+                .build_async()
+                .unwrap();
+            tokio::task::yield_now().await;
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            entry.exit();
+        }));
+    }
+    for h in handles {
+        h.await.unwrap();
+    }
+    assert_eq!(slot.passed.load(Ordering::SeqCst), 10);
+    assert_eq!(slot.completed.load(Ordering::SeqCst), 10);
+}
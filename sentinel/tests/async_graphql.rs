@@ -0,0 +1,112 @@
+#![cfg(feature = "async-graphql")]
+
+mod common;
+
+use async_graphql::{EmptyMutation, EmptySubscription, Object, Request, Schema, Variables};
+use sentinel_rs::adapters::async_graphql::Sentinel;
+use sentinel_rs::base::{
+    BaseSlot, BlockType, ContextPtr, ParamsMap, RuleCheckSlot, SlotChain, TokenResult,
+};
+use std::sync::{Arc, Mutex};
+
+struct Query;
+
+#[Object]
+impl Query {
+    async fn hello(&self) -> &str {
+        "world"
+    }
+
+    async fn search(&self, keyword: String, limit: i32) -> Vec<String> {
+        vec![keyword; limit as usize]
+    }
+
+    async fn broken(&self) -> async_graphql::Result<i32> {
+        Err("resolver failed".into())
+    }
+}
+
+fn schema(sentinel: Sentinel) -> Schema<Query, EmptyMutation, EmptySubscription> {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .extension(sentinel)
+        .finish()
+}
+
+/// `Attachments` records the attachments of the entries.
+#[derive(Default)]
+struct Attachments(Mutex<Vec<ParamsMap>>);
+
+impl BaseSlot for Attachments {}
+
+impl RuleCheckSlot for Attachments {
+    fn check(&self, ctx: &ContextPtr) -> TokenResult {
+        let ctx = ctx.read().unwrap();
+        if let Some(attachments) = ctx.input().attachments() {
+            self.0.lock().unwrap().push(attachments.clone());
+        }
+        TokenResult::new_pass()
+    }
+}
+
+#[tokio::test]
+async fn operation() {
+    let recorder = common::register("graphql:Hello", None);
+    let schema = schema(Sentinel::new());
+
+    let response = schema.execute("query Hello { hello }").await;
+    assert!(response.errors.is_empty());
+    let response = schema.execute("query Hello { broken }").await;
+    assert_eq!(response.errors[0].message, "resolver failed");
+    assert_eq!(recorder.passed(), 2);
+    assert_eq!(recorder.completed(), 2);
+    assert_eq!(recorder.errors(), 1);
+}
+
+#[tokio::test]
+async fn operation_blocked() {
+    let recorder = common::register("graphql:Blocked", Some(BlockType::Flow));
+    let schema = schema(Sentinel::new());
+
+    let response = schema.execute("query Blocked { hello }").await;
+    let err = &response.errors[0];
+    let extensions = serde_json::to_value(err.extensions.as_ref().unwrap()).unwrap();
+    assert_eq!(extensions["code"], "TOO_MANY_REQUESTS");
+    assert_eq!(extensions["retryAfter"], 1);
+    assert_eq!(recorder.passed(), 0);
+    assert_eq!(recorder.blocked(), 1);
+}
+
+#[tokio::test]
+async fn field() {
+    let attachments = Arc::new(Attachments::default());
+    let mut sc = SlotChain::new();
+    sc.add_rule_check_slot(attachments.clone());
+    sentinel_rs::register_resource_slot_chain(vec!["graphql:Query.search".into()], Arc::new(sc));
+    let schema = schema(Sentinel::new().with_field("Query.search"));
+
+    let request = Request::new(
+        "query Search($keyword: String!) { search(keyword: $keyword, limit: 2) hello }",
+    )
+    .variables(Variables::from_json(
+        serde_json::json!({ "keyword": "rust" }),
+    ));
+    let response = schema.execute(request).await;
+    assert!(response.errors.is_empty());
+    // the arguments are resolved with the variables, the fields not registered are not guarded
+    let attachments = attachments.0.lock().unwrap();
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0]["keyword"], "rust");
+    assert_eq!(attachments[0]["limit"], "2");
+}
+
+#[tokio::test]
+async fn field_blocked() {
+    common::register("graphql:Query.broken", Some(BlockType::CircuitBreaking));
+    let schema = schema(Sentinel::new().with_field("Query.broken"));
+
+    let response = schema.execute("{ hello broken }").await;
+    let data = response.data.into_json().unwrap();
+    assert_eq!(data["hello"], "world");
+    let extensions = serde_json::to_value(response.errors[0].extensions.as_ref().unwrap()).unwrap();
+    assert_eq!(extensions["code"], "SERVICE_UNAVAILABLE");
+}
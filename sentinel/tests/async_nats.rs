@@ -0,0 +1,93 @@
+#![cfg(feature = "async-nats")]
+
+mod common;
+
+use sentinel_rs::adapters::async_nats::{BoxError, NatsMessage, SentinelSubscriber};
+use sentinel_rs::adapters::MessageError;
+use sentinel_rs::base::BlockType;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// `JetStreamMessage` records the redelivery delays like a JetStream message.
+struct JetStreamMessage {
+    subject: &'static str,
+    requeued: Mutex<Vec<Duration>>,
+}
+
+impl JetStreamMessage {
+    fn new(subject: &'static str) -> Self {
+        JetStreamMessage {
+            subject,
+            requeued: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl NatsMessage for JetStreamMessage {
+    fn subject(&self) -> &str {
+        self.subject
+    }
+
+    fn requeue(&self, delay: Duration) -> impl Future<Output = Result<(), BoxError>> + Send {
+        self.requeued.lock().unwrap().push(delay);
+        async { Ok(()) }
+    }
+}
+
+fn message(subject: &'static str) -> async_nats::Message {
+    async_nats::Message {
+        subject: subject.into(),
+        reply: None,
+        payload: Default::default(),
+        headers: None,
+        status: None,
+        description: None,
+        length: 0,
+    }
+}
+
+#[tokio::test]
+async fn process() {
+    let recorder = common::register("nats.orders.created", None);
+    let subscriber = SentinelSubscriber::new();
+
+    let message = message("nats.orders.created");
+    let result: Result<_, MessageError<String>> = subscriber
+        .process(&message, || async { Ok(message.subject.to_string()) })
+        .await;
+    assert_eq!(result.unwrap(), "nats.orders.created");
+    let result: Result<(), _> = subscriber
+        .process(&message, || async { Err("handler failed") })
+        .await;
+    assert!(matches!(
+        result,
+        Err(MessageError::Handler("handler failed"))
+    ));
+    assert_eq!(recorder.passed(), 2);
+    assert_eq!(recorder.completed(), 2);
+    assert_eq!(recorder.errors(), 1);
+}
+
+#[tokio::test]
+async fn requeue_when_blocked() {
+    let recorder = common::register("nats.payments", Some(BlockType::Flow));
+    let subscriber =
+        SentinelSubscriber::new().with_resource_extractor(|subject| subject.replacen(".eu", "", 1));
+
+    let message = JetStreamMessage::new("nats.payments.eu");
+    let result: Result<(), MessageError<String>> = subscriber
+        .process(&message, || async {
+            panic!("the blocked message is handled")
+        })
+        .await;
+    let err = result.unwrap_err();
+    assert_eq!(err.block_error().unwrap().block_type(), BlockType::Flow);
+    // the retry-after hint of the block error is the redelivery delay
+    assert_eq!(
+        *message.requeued.lock().unwrap(),
+        vec![Duration::from_secs(1)]
+    );
+    assert_eq!(recorder.passed(), 0);
+    assert_eq!(recorder.blocked(), 1);
+}
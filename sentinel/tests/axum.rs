@@ -0,0 +1,215 @@
+#![cfg(feature = "axum")]
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::routing::get;
+use axum::Router;
+use sentinel_rs::adapters::axum::{ResourceName, SentinelLayer, SentinelResource};
+use sentinel_rs::base::BlockType;
+use std::time::Duration;
+use tower::ServiceExt;
+
+fn get_request(uri: &str) -> Request<Body> {
+    Request::builder()
+        .uri(uri)
+        .header("x-sentinel-origin", "caller")
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn layer() -> SentinelLayer {
+    SentinelLayer::new().with_origin_header("x-sentinel-origin".parse().unwrap())
+}
+
+#[tokio::test]
+async fn route_resource() {
+    let recorder = common::register("GET:/axum/users/{id}", None);
+    let failed = common::register("GET:/axum/failed", None);
+    let app = Router::new()
+        .route("/axum/users/{id}", get(|| async { "user" }))
+        .route(
+            "/axum/failed",
+            get(|| async { StatusCode::INTERNAL_SERVER_ERROR }),
+        )
+        .layer(layer());
+
+    for id in 0..3 {
+        let uri = format!("/axum/users/{}", id);
+        let response = app.clone().oneshot(get_request(&uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    assert_eq!(recorder.passed(), 3);
+    assert_eq!(recorder.completed(), 3);
+    assert_eq!(recorder.errors(), 0);
+    assert_eq!(recorder.origins(), vec!["caller"; 3]);
+
+    let response = app.oneshot(get_request("/axum/failed")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(failed.completed(), 1);
+    assert_eq!(failed.errors(), 1);
+}
+
+#[tokio::test]
+async fn blocked() {
+    let flow = common::register("GET:/axum/flow", Some(BlockType::Flow));
+    common::register("GET:/axum/breaker", Some(BlockType::CircuitBreaking));
+    let app = Router::new()
+        .route("/axum/flow", get(|| async { "flow" }))
+        .route("/axum/breaker", get(|| async { "breaker" }))
+        .layer(layer());
+
+    let response = app
+        .clone()
+        .oneshot(get_request("/axum/flow"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["resource"], "GET:/axum/flow");
+    assert_eq!(flow.blocked(), 1);
+    assert_eq!(flow.passed(), 0);
+
+    let response = app.oneshot(get_request("/axum/breaker")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    // the custom block response
+    let app = Router::new()
+        .route("/axum/flow", get(|| async { "flow" }))
+        .layer(SentinelLayer::new().with_block_response(|_| {
+            let mut response = axum::response::Response::new(Body::from("busy"));
+            *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            response
+        }));
+    let response = app.oneshot(get_request("/axum/flow")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn panicked_and_cancelled() {
+    let panicked = common::register("GET:/axum/panicked", None);
+    let cancelled = common::register("GET:/axum/cancelled", None);
+    let app = Router::new()
+        .route(
+            "/axum/panicked",
+            get(|| async {
+                if true {
+                    panic!("handler panicked");
+                }
+            }),
+        )
+        .route(
+            "/axum/cancelled",
+            get(|| async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }),
+        )
+        .layer(layer());
+
+    let handle = tokio::spawn(app.clone().oneshot(get_request("/axum/panicked")));
+    assert!(handle.await.unwrap_err().is_panic());
+    assert_eq!(panicked.completed(), 1);
+    assert_eq!(panicked.errors(), 1);
+
+    // the client disconnects
+    let request = app.oneshot(get_request("/axum/cancelled"));
+    assert!(tokio::time::timeout(Duration::from_millis(10), request)
+        .await
+        .is_err());
+    assert_eq!(cancelled.passed(), 1);
+    assert_eq!(cancelled.completed(), 1);
+}
+
+struct Checkout;
+
+impl ResourceName for Checkout {
+    const NAME: &'static str = "axum_checkout";
+}
+
+#[tokio::test]
+async fn resource_extractor() {
+    let checkout = common::register("axum_checkout", None);
+    let app = Router::new()
+        .route(
+            "/axum/checkout",
+            get(|_: SentinelResource<Checkout>| async { "checkout" }),
+        )
+        .layer(layer());
+    let response = app.oneshot(get_request("/axum/checkout")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(checkout.passed(), 1);
+    assert_eq!(checkout.completed(), 1);
+    assert_eq!(checkout.origins(), vec!["caller"]);
+
+    struct Blocked;
+    impl ResourceName for Blocked {
+        const NAME: &'static str = "axum_checkout_blocked";
+    }
+    common::register(Blocked::NAME, Some(BlockType::Isolation));
+    let app = Router::new().route(
+        "/axum/checkout",
+        get(|_: SentinelResource<Blocked>| async { "checkout" }),
+    );
+    let response = app
+        .clone()
+        .oneshot(get_request("/axum/checkout"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // the block response builder of the layer is followed
+    let app = app.layer(SentinelLayer::new().with_block_response(|_| {
+        let mut response = axum::response::Response::new(Body::from("busy"));
+        *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+        response
+    }));
+    let response = app.oneshot(get_request("/axum/checkout")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn rate_limit_headers() {
+    let quota = common::Quota::load("GET:/axum/quota");
+    let app = Router::new()
+        .route("/axum/quota", get(|| async { "quota" }))
+        .layer(layer().with_rate_limit_headers(true));
+
+    let mut responses = Vec::new();
+    for _ in 0..=common::Quota::LIMIT {
+        let response = app
+            .clone()
+            .oneshot(get_request("/axum/quota"))
+            .await
+            .unwrap();
+        let header = |name| {
+            let value = response.headers().get(name)?;
+            value.to_str().ok().map(String::from)
+        };
+        responses.push((
+            response.status() == StatusCode::OK,
+            header("ratelimit-limit"),
+            header("ratelimit-remaining"),
+        ));
+    }
+    quota.check(responses);
+}
+
+#[tokio::test]
+async fn unmatched_resource() {
+    let unmatched = common::register("GET:<unmatched>", None);
+    let app = Router::new()
+        .route("/axum/matched", get(|| async { "matched" }))
+        .fallback(|| async { StatusCode::NOT_FOUND })
+        .layer(layer());
+    // the raw paths do not name the resources
+    for uri in ["/axum/probe/1", "/axum/probe/2"] {
+        let response = app.clone().oneshot(get_request(uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+    assert_eq!(unmatched.completed(), 2);
+}
@@ -0,0 +1,179 @@
+//! The slot chains shared by the tests of the adapters,
+//! each resource is registered with its own chain, thus the global rules are not touched.
+#![allow(dead_code)]
+
+use sentinel_rs::base::{
+    BaseSlot, BlockError, BlockType, ContextPtr, RuleCheckSlot, SlotChain, StatSlot, TokenResult,
+};
+use sentinel_rs::utils::{self, Clock};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+#[derive(Default)]
+pub struct Recorder {
+    passed: AtomicU32,
+    blocked: AtomicU32,
+    completed: AtomicU32,
+    errors: AtomicU32,
+    origins: Mutex<Vec<String>>,
+    round_trips: Mutex<Vec<u64>>,
+}
+
+impl Recorder {
+    pub fn passed(&self) -> u32 {
+        self.passed.load(Ordering::SeqCst)
+    }
+
+    pub fn blocked(&self) -> u32 {
+        self.blocked.load(Ordering::SeqCst)
+    }
+
+    pub fn completed(&self) -> u32 {
+        self.completed.load(Ordering::SeqCst)
+    }
+
+    pub fn errors(&self) -> u32 {
+        self.errors.load(Ordering::SeqCst)
+    }
+
+    pub fn origins(&self) -> Vec<String> {
+        self.origins.lock().unwrap().clone()
+    }
+
+    pub fn round_trips(&self) -> Vec<u64> {
+        self.round_trips.lock().unwrap().clone()
+    }
+}
+
+impl BaseSlot for Recorder {}
+
+impl StatSlot for Recorder {
+    fn on_entry_pass(&self, ctx: ContextPtr) {
+        self.passed.fetch_add(1, Ordering::SeqCst);
+        if let Some(origin) = ctx.read().unwrap().input().origin() {
+            self.origins.lock().unwrap().push(origin.clone());
+        }
+    }
+
+    fn on_entry_blocked(&self, _ctx: ContextPtr, _block_error: Option<BlockError>) {
+        self.blocked.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_completed(&self, ctx: ContextPtr) {
+        self.completed.fetch_add(1, Ordering::SeqCst);
+        let ctx = ctx.read().unwrap();
+        self.round_trips.lock().unwrap().push(ctx.round_trip());
+        if ctx.get_err().is_some() {
+            self.errors.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// `Blocker` blocks all the entries, with the retry-after hint of 1s.
+pub struct Blocker(pub BlockType);
+
+impl BaseSlot for Blocker {}
+
+impl RuleCheckSlot for Blocker {
+    fn check(&self, _ctx: &ContextPtr) -> TokenResult {
+        TokenResult::new_blocked_with_msg(self.0, "blocked in test".into())
+            .with_retry_after(Duration::from_secs(1))
+    }
+}
+
+/// `register` guards the resource by the chain of a `Recorder`,
+/// and a `Blocker` of the `block_type` if there is.
+pub fn register(resource: &str, block_type: Option<BlockType>) -> Arc<Recorder> {
+    let recorder = Arc::new(Recorder::default());
+    let mut sc = SlotChain::new();
+    if let Some(block_type) = block_type {
+        sc.add_rule_check_slot(Arc::new(Blocker(block_type)));
+    }
+    sc.add_stat_slot(recorder.clone());
+    sentinel_rs::register_resource_slot_chain(vec![resource.into()], Arc::new(sc));
+    recorder
+}
+
+/// `FrozenClock` stops the time of current thread, see `Quota`.
+struct FrozenClock(i128);
+
+impl Clock for FrozenClock {
+    fn curr_time_nanos(&self) -> i128 {
+        self.0
+    }
+}
+
+/// `Quota` limits the resource to `Quota::LIMIT` requests per second by the flow rules,
+/// and freezes the clock of current thread, thus the requests never straddle the windows of the rules.
+/// The requests must be handled in current thread, e.g., by the current-thread runtime of the test.
+/// The rules are cleared and the clock is restored when it is dropped.
+pub struct Quota(String);
+
+impl Quota {
+    pub const LIMIT: u32 = 2;
+
+    pub fn load(resource: &str) -> Self {
+        utils::set_thread_clock(Arc::new(FrozenClock(utils::curr_time_nanos())));
+        sentinel_rs::flow::load_rules_of_resource(
+            &resource.to_owned(),
+            vec![Arc::new(sentinel_rs::flow::Rule {
+                resource: resource.into(),
+                threshold: Self::LIMIT as f64,
+                ..Default::default()
+            })],
+        )
+        .unwrap();
+        Quota(resource.into())
+    }
+
+    /// `check` asserts the `(passed, RateLimit-Limit, RateLimit-Remaining)` of the responses
+    /// to `Quota::LIMIT + 1` requests sent in order: the quota is used up by the passed ones,
+    /// and the last one is blocked with no remaining.
+    pub fn check(&self, responses: Vec<(bool, Option<String>, Option<String>)>) {
+        let limit = Self::LIMIT.to_string();
+        let expected: Vec<(bool, Option<String>, Option<String>)> = (1..=Self::LIMIT + 1)
+            .map(|n| {
+                let remaining = Self::LIMIT.saturating_sub(n).to_string();
+                (n <= Self::LIMIT, Some(limit.clone()), Some(remaining))
+            })
+            .collect();
+        assert_eq!(responses, expected, "the responses of {}", self.0);
+    }
+}
+
+impl Drop for Quota {
+    fn drop(&mut self) {
+        sentinel_rs::flow::clear_rules_of_resource(&self.0);
+        utils::reset_thread_clock();
+    }
+}
+
+/// `serve` starts an HTTP server answering `/status/{code}` with the status, and `/slow` after 2s,
+/// it returns the authority of the server.
+pub async fn serve() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let mut buf = vec![0u8; 4096];
+                let n = stream.read(&mut buf).await.unwrap();
+                let head = String::from_utf8_lossy(&buf[..n]).into_owned();
+                let path = head.split_whitespace().nth(1).unwrap_or("/").to_owned();
+                if path == "/slow" {
+                    tokio::time::sleep(Duration::from_secs(2)).await;
+                }
+                let code = path.strip_prefix("/status/").unwrap_or("200");
+                let response = format!(
+                    "HTTP/1.1 {} Test\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok",
+                    code
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+    format!("127.0.0.1:{}", addr.port())
+}
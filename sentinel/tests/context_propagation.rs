@@ -0,0 +1,65 @@
+#![cfg(feature = "async")]
+
+use sentinel_rs::base::{in_current_context, SentinelContext, SlotChain};
+use sentinel_rs::EntryBuilder;
+use std::sync::Arc;
+
+fn builder(resource: &str) -> EntryBuilder {
+    EntryBuilder::new(resource.into()).with_slot_chain(Arc::new(SlotChain::new()))
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn propagate_to_spawned_tasks() {
+    let parent = builder("handler").build().unwrap();
+    let ctx = SentinelContext::new().with_entry(&parent);
+
+    let parent_cloned = Arc::clone(&parent);
+    ctx.scope_future(async move {
+        // the context is kept across await points
+        tokio::task::yield_now().await;
+        let handle = tokio::spawn(in_current_context(async move {
+            tokio::task::yield_now().await;
+            let child = builder("subtask").build().unwrap();
+            let linked = child
+                .read()
+                .unwrap()
+                .context()
+                .read()
+                .unwrap()
+                .parent_entry()
+                .unwrap();
+            assert!(Arc::ptr_eq(&linked, &parent_cloned));
+            child.read().unwrap().exit();
+        }));
+        handle.await.unwrap();
+    })
+    .await;
+
+    // out of the scope
+    assert!(SentinelContext::current().is_none());
+    let orphan = builder("orphan").build().unwrap();
+    assert!(orphan
+        .read()
+        .unwrap()
+        .context()
+        .read()
+        .unwrap()
+        .parent_entry()
+        .is_none());
+    orphan.read().unwrap().exit();
+    parent.read().unwrap().exit();
+}
+
+#[tokio::test]
+async fn spawn_without_propagation() {
+    let parent = builder("handler_no_propagation").build().unwrap();
+    SentinelContext::new()
+        .with_entry(&parent)
+        .scope_future(async {
+            tokio::spawn(async { assert!(SentinelContext::current().is_none()) })
+                .await
+                .unwrap();
+        })
+        .await;
+    parent.read().unwrap().exit();
+}
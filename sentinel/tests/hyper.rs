@@ -0,0 +1,112 @@
+#![cfg(feature = "hyper")]
+
+mod common;
+
+use http::{Request, StatusCode};
+use http_body_util::Empty;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use sentinel_rs::adapters::hyper::{
+    authority, SentinelClientLayer, SentinelConnector, TimeoutError,
+};
+use sentinel_rs::base::{BlockError, BlockType};
+use std::time::Duration;
+use tower::{Layer, ServiceExt};
+
+type Body = Empty<&'static [u8]>;
+
+fn get_request(url: &str) -> Request<Body> {
+    Request::get(url).body(Empty::new()).unwrap()
+}
+
+#[tokio::test]
+async fn authority_resource() {
+    let host = common::serve().await;
+    let recorder = common::register(&host, None);
+    let client = Client::builder(TokioExecutor::new()).build_http::<Body>();
+    let service = SentinelClientLayer::new()
+        .with_timeout(Duration::from_millis(200))
+        .layer(client);
+
+    for _ in 0..3 {
+        let url = format!("http://{}/status/200", host);
+        let response = service.clone().oneshot(get_request(&url)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    assert_eq!(recorder.passed(), 3);
+    assert_eq!(recorder.completed(), 3);
+    assert_eq!(recorder.errors(), 0);
+
+    let url = format!("http://{}/status/500", host);
+    let response = service.clone().oneshot(get_request(&url)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    // the client errors are not recorded by default
+    let url = format!("http://{}/status/404", host);
+    service.clone().oneshot(get_request(&url)).await.unwrap();
+    assert_eq!(recorder.completed(), 5);
+    assert_eq!(recorder.errors(), 1);
+
+    let url = format!("http://{}/slow", host);
+    let err = service.oneshot(get_request(&url)).await.unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<TimeoutError>(),
+        Some(&TimeoutError(Duration::from_millis(200)))
+    );
+    assert_eq!(recorder.completed(), 6);
+    assert_eq!(recorder.errors(), 2);
+}
+
+#[tokio::test]
+async fn blocked() {
+    let host = common::serve().await;
+    let recorder = common::register(&host, Some(BlockType::CircuitBreaking));
+    let client = Client::builder(TokioExecutor::new()).build_http::<Body>();
+    let service = SentinelClientLayer::new().layer(client);
+
+    let url = format!("http://{}/status/200", host);
+    let err = service.oneshot(get_request(&url)).await.unwrap_err();
+    let block_error = err.downcast_ref::<BlockError>().unwrap();
+    assert_eq!(block_error.block_type(), BlockType::CircuitBreaking);
+    assert_eq!(recorder.blocked(), 1);
+    assert_eq!(recorder.passed(), 0);
+}
+
+#[tokio::test]
+async fn connector() {
+    let host = common::serve().await;
+    let connections = common::register(&format!("connect:{}", host), None);
+    let connector = SentinelConnector::new(HttpConnector::new());
+    let client = Client::builder(TokioExecutor::new()).build::<_, Body>(connector);
+
+    let url = format!("http://{}/status/200", host);
+    let response = client.request(get_request(&url)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(connections.passed(), 1);
+    assert_eq!(connections.completed(), 1);
+    assert_eq!(connections.errors(), 0);
+
+    // the refused connection
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let closed = format!("127.0.0.1:{}", listener.local_addr().unwrap().port());
+    drop(listener);
+    let refused = common::register(&format!("connect:{}", closed), None);
+    let url = format!("http://{}/status/200", closed);
+    assert!(client.request(get_request(&url)).await.is_err());
+    assert_eq!(refused.completed(), 1);
+    assert_eq!(refused.errors(), 1);
+}
+
+#[tokio::test]
+async fn connector_blocked() {
+    let host = common::serve().await;
+    let connections = common::register(&format!("dial:{}", host), Some(BlockType::Flow));
+    let connector = SentinelConnector::new(HttpConnector::new())
+        .with_resource_extractor(|uri| format!("dial:{}", authority(uri)));
+    let client = Client::builder(TokioExecutor::new()).build::<_, Body>(connector);
+
+    let url = format!("http://{}/status/200", host);
+    assert!(client.request(get_request(&url)).await.is_err());
+    assert_eq!(connections.blocked(), 1);
+    assert_eq!(connections.passed(), 0);
+}
@@ -0,0 +1,72 @@
+#![cfg(feature = "lapin")]
+
+mod common;
+
+use lapin::message::Delivery;
+use sentinel_rs::adapters::lapin::SentinelConsumer;
+use sentinel_rs::adapters::MessageError;
+use sentinel_rs::base::BlockType;
+
+fn delivery(routing_key: &str) -> Delivery {
+    Delivery::mock(1, "".into(), routing_key.into(), false, b"payload".to_vec())
+}
+
+#[tokio::test]
+async fn process() {
+    let recorder = common::register("amqp.orders", None);
+    let consumer = SentinelConsumer::new("amqp.orders");
+
+    let delivery = delivery("orders.created");
+    let result: Result<_, MessageError<String>> = consumer
+        .process(&delivery, || async { Ok(delivery.data.len()) })
+        .await;
+    assert_eq!(result.unwrap(), 7);
+    let result: Result<(), _> = consumer
+        .process(&delivery, || async { Err("handler failed") })
+        .await;
+    assert!(matches!(
+        result,
+        Err(MessageError::Handler("handler failed"))
+    ));
+    assert_eq!(recorder.passed(), 2);
+    assert_eq!(recorder.completed(), 2);
+    assert_eq!(recorder.errors(), 1);
+    // the successful deliveries are left to the caller
+    assert!(delivery.acker.usable());
+}
+
+#[tokio::test]
+async fn requeue_when_blocked() {
+    let recorder = common::register("amqp.payments:payments.refund", Some(BlockType::Flow));
+    let consumer = SentinelConsumer::new("amqp.payments")
+        .with_resource_extractor(|queue, routing_key| format!("{}:{}", queue, routing_key));
+
+    let delivery = delivery("payments.refund");
+    let result: Result<(), MessageError<String>> = consumer
+        .process(&delivery, || async {
+            panic!("the blocked delivery is handled")
+        })
+        .await;
+    let err = result.unwrap_err();
+    assert_eq!(err.block_error().unwrap().block_type(), BlockType::Flow);
+    // the delivery is negatively acknowledged
+    assert!(!delivery.acker.usable());
+    assert_eq!(recorder.passed(), 0);
+    assert_eq!(recorder.blocked(), 1);
+}
+
+#[tokio::test]
+async fn dead_letter_when_blocked() {
+    let recorder = common::register("amqp.refunds", Some(BlockType::Flow));
+    let consumer = SentinelConsumer::new("amqp.refunds").with_requeue(false);
+
+    let delivery = delivery("refunds.created");
+    let result: Result<(), MessageError<String>> = consumer
+        .process(&delivery, || async {
+            panic!("the blocked delivery is handled")
+        })
+        .await;
+    assert!(result.unwrap_err().block_error().is_some());
+    assert!(!delivery.acker.usable());
+    assert_eq!(recorder.blocked(), 1);
+}
@@ -0,0 +1,35 @@
+//! Tests driving the time of Sentinel manually, which are only available with the `test-util` feature.
+#![cfg(feature = "test-util")]
+
+use sentinel_rs::utils::MockClock;
+use sentinel_rs::{flow, EntryBuilder};
+use std::sync::Arc;
+
+#[test]
+fn flow_window_rotates_with_mock_clock() {
+    let clock = Arc::new(MockClock::starting_at(1_600_000_000_000));
+    let _guard = clock.install();
+    let resource = "mock_clock_flow".to_string();
+    flow::load_rules(vec![Arc::new(flow::Rule {
+        resource: resource.clone(),
+        threshold: 2.0,
+        ..Default::default()
+    })]);
+
+    let try_pass = || match EntryBuilder::new(resource.clone()).build() {
+        Ok(entry) => {
+            entry.read().unwrap().exit();
+            true
+        }
+        Err(_) => false,
+    };
+    assert!(try_pass());
+    assert!(try_pass());
+    assert!(!try_pass());
+    // still in the same window
+    clock.advance_millis(999);
+    assert!(!try_pass());
+    clock.advance_millis(1);
+    assert!(try_pass());
+    flow::clear_rules();
+}
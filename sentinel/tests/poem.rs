@@ -0,0 +1,153 @@
+#![cfg(feature = "poem")]
+
+mod common;
+
+use poem::error::NotFoundError;
+use poem::http::{header, StatusCode};
+use poem::{get, handler, Endpoint, EndpointExt, Request, Response, Route};
+use sentinel_rs::adapters::poem::Sentinel;
+use sentinel_rs::base::BlockType;
+
+fn get_request(uri: &str) -> Request {
+    Request::builder()
+        .uri(uri.parse().unwrap())
+        .header("x-sentinel-origin", "caller")
+        .finish()
+}
+
+fn middleware() -> Sentinel {
+    Sentinel::new().with_origin_header("x-sentinel-origin".parse().unwrap())
+}
+
+#[handler]
+fn user() -> &'static str {
+    "user"
+}
+
+#[handler]
+fn failed() -> StatusCode {
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+#[handler]
+fn not_found() -> poem::Result<&'static str> {
+    Err(NotFoundError.into())
+}
+
+#[tokio::test]
+async fn route_resource() {
+    let recorder = common::register("GET:/poem/users/:id", None);
+    let failed_recorder = common::register("GET:/poem/failed", None);
+    let not_found_recorder = common::register("GET:/poem/not_found", None);
+    let app = Route::new()
+        .at("/poem/users/:id", get(user).with(middleware()))
+        .at("/poem/failed", get(failed).with(middleware()))
+        .at("/poem/not_found", get(not_found).with(middleware()));
+
+    for id in 0..3 {
+        let uri = format!("/poem/users/{}", id);
+        let response = app.get_response(get_request(&uri)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    assert_eq!(recorder.passed(), 3);
+    assert_eq!(recorder.completed(), 3);
+    assert_eq!(recorder.errors(), 0);
+    assert_eq!(recorder.origins(), vec!["caller"; 3]);
+
+    let response = app.get_response(get_request("/poem/failed")).await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(failed_recorder.completed(), 1);
+    assert_eq!(failed_recorder.errors(), 1);
+
+    // the client errors are not recorded
+    let response = app.get_response(get_request("/poem/not_found")).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(not_found_recorder.completed(), 1);
+    assert_eq!(not_found_recorder.errors(), 0);
+}
+
+#[tokio::test]
+async fn unmatched_resource() {
+    // the middleware applied to the whole route sees no path pattern, the raw paths do not name the resources
+    let recorder = common::register("GET:<unmatched>", None);
+    let app = Route::new()
+        .at("/poem/uri/:id", get(user))
+        .with(middleware());
+    for uri in ["/poem/uri/1", "/poem/uri/2"] {
+        let response = app.get_response(get_request(uri)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    assert_eq!(recorder.completed(), 2);
+}
+
+#[tokio::test]
+async fn blocked() {
+    let flow = common::register("GET:/poem/flow", Some(BlockType::Flow));
+    common::register("GET:/poem/breaker", Some(BlockType::CircuitBreaking));
+    let app = Route::new()
+        .at("/poem/flow", get(user).with(middleware()))
+        .at("/poem/breaker", get(user).with(middleware()));
+
+    let response = app.get_response(get_request("/poem/flow")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+    let body = response.into_body().into_string().await.unwrap();
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["resource"], "GET:/poem/flow");
+    assert_eq!(flow.blocked(), 1);
+    assert_eq!(flow.passed(), 0);
+
+    let response = app.get_response(get_request("/poem/breaker")).await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    // the custom block response
+    let app = Route::new().at(
+        "/poem/flow",
+        get(user).with(Sentinel::new().with_block_response(|_| {
+            Response::builder()
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .body("busy")
+        })),
+    );
+    let response = app.get_response(get_request("/poem/flow")).await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[handler]
+fn panicked() -> &'static str {
+    panic!("handler panicked")
+}
+
+#[tokio::test]
+async fn panicked_handler() {
+    let recorder = common::register("GET:/poem/panicked", None);
+    let app = Route::new().at("/poem/panicked", get(panicked).with(middleware()));
+    let handle = tokio::spawn(async move { app.get_response(get_request("/poem/panicked")).await });
+    assert!(handle.await.unwrap_err().is_panic());
+    assert_eq!(recorder.completed(), 1);
+    assert_eq!(recorder.errors(), 1);
+}
+
+#[tokio::test]
+async fn rate_limit_headers() {
+    let quota = common::Quota::load("GET:/poem/quota");
+    let app = Route::new().at(
+        "/poem/quota",
+        get(user).with(middleware().with_rate_limit_headers(true)),
+    );
+
+    let mut responses = Vec::new();
+    for _ in 0..=common::Quota::LIMIT {
+        let response = app.get_response(get_request("/poem/quota")).await;
+        let header = |name| {
+            let value = response.headers().get(name)?;
+            value.to_str().ok().map(String::from)
+        };
+        responses.push((
+            response.status() == StatusCode::OK,
+            header("ratelimit-limit"),
+            header("ratelimit-remaining"),
+        ));
+    }
+    quota.check(responses);
+}
@@ -0,0 +1,73 @@
+#![cfg(feature = "proxy-wasm")]
+
+use sentinel_rs::adapters::proxy_wasm::{FilterConfig, SentinelRootContext};
+use sentinel_rs::{flow, utils};
+
+fn configuration(resource: &str, threshold: f64) -> Vec<u8> {
+    let rule = flow::Rule {
+        resource: resource.into(),
+        threshold,
+        ..Default::default()
+    };
+    serde_json::to_vec(&serde_json::json!({
+        "resource": "{authority}{path}",
+        "origin_header": "x-sentinel-origin",
+        "rules": { "flow": [rule] },
+    }))
+    .unwrap()
+}
+
+#[test]
+fn configure_loads_rules() {
+    let resource = "proxy-wasm:configure".to_string();
+    let mut root = SentinelRootContext::new();
+    root.configure(&configuration(&resource, 2.0)).unwrap();
+    assert_eq!(root.config().resource, "{authority}{path}");
+    assert_eq!(
+        root.config().origin_header.as_deref(),
+        Some("x-sentinel-origin")
+    );
+    let rules = flow::get_rules_of_resource(&resource);
+    assert_eq!(rules.len(), 1);
+    assert_eq!(rules[0].threshold, 2.0);
+
+    // the invalid configuration changes nothing
+    assert!(root.configure(b"{\"resource\": 1}").is_err());
+    assert!(root.configure(&configuration(&resource, -1.0)).is_err());
+    assert_eq!(root.config().resource, "{authority}{path}");
+    assert_eq!(flow::get_rules_of_resource(&resource)[0].threshold, 2.0);
+
+    root.configure(b"{}").unwrap();
+    assert_eq!(root.config().resource, "{method}:{path}");
+    assert!(flow::get_rules_of_resource(&resource).is_empty());
+}
+
+#[test]
+fn render_resource() {
+    let config = FilterConfig::default();
+    assert_eq!(
+        config.render_resource("GET", "example.com", "/users/1?verbose=true"),
+        "GET:/users/1"
+    );
+    let config = FilterConfig {
+        resource: "{authority}{path}".into(),
+        ..Default::default()
+    };
+    assert_eq!(
+        config.render_resource("GET", "example.com", "/users"),
+        "example.com/users"
+    );
+}
+
+fn host_time_nanos() -> i128 {
+    1_600_000_000_123_000_000
+}
+
+#[test]
+fn time_source() {
+    utils::set_time_source(host_time_nanos);
+    assert_eq!(utils::curr_time_nanos(), host_time_nanos());
+    assert_eq!(utils::curr_time_millis(), 1_600_000_000_123);
+    utils::reset_time_source();
+    assert!(utils::curr_time_millis() > 1_600_000_000_123);
+}
@@ -0,0 +1,126 @@
+#![cfg(feature = "rdkafka")]
+
+mod common;
+
+use rdkafka::error::KafkaResult;
+use rdkafka::message::OwnedMessage;
+use rdkafka::{Message, Timestamp};
+use sentinel_rs::adapters::rdkafka::{PartitionControl, SentinelConsumer};
+use sentinel_rs::base::{BaseSlot, BlockType, ContextPtr, RuleCheckSlot, SlotChain, TokenResult};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// `Partitions` records the pauses and the resumptions of the partitions.
+#[derive(Default)]
+struct Partitions(Mutex<Vec<String>>);
+
+impl Partitions {
+    fn events(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl PartitionControl for Partitions {
+    fn pause(&self, topic: &str, partition: i32) -> KafkaResult<()> {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("pause {}-{}", topic, partition));
+        Ok(())
+    }
+
+    fn resume(&self, topic: &str, partition: i32) -> KafkaResult<()> {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("resume {}-{}", topic, partition));
+        Ok(())
+    }
+}
+
+/// `Throttler` blocks the first `n` entries, with the retry-after hint of 10ms.
+struct Throttler(AtomicU32);
+
+impl BaseSlot for Throttler {}
+
+impl RuleCheckSlot for Throttler {
+    fn check(&self, _ctx: &ContextPtr) -> TokenResult {
+        let blocked = self
+            .0
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        if blocked {
+            TokenResult::new_blocked_with_msg(BlockType::Flow, "throttled in test".into())
+                .with_retry_after(Duration::from_millis(10))
+        } else {
+            TokenResult::new_pass()
+        }
+    }
+}
+
+fn message(topic: &str, partition: i32, offset: i64) -> OwnedMessage {
+    OwnedMessage::new(
+        Some(b"payload".to_vec()),
+        None,
+        topic.into(),
+        Timestamp::NotAvailable,
+        partition,
+        offset,
+        None,
+    )
+}
+
+#[tokio::test]
+async fn process() {
+    let recorder = common::register("kafka.orders", None);
+    let consumer = SentinelConsumer::new(Partitions::default());
+
+    let message = message("kafka.orders", 0, 1);
+    let result: Result<_, String> = consumer
+        .process(&message, || async { Ok(message.offset()) })
+        .await;
+    assert_eq!(result, Ok(1));
+    let result: Result<(), _> = consumer
+        .process(&message, || async { Err("handler failed") })
+        .await;
+    assert!(result.is_err());
+    assert_eq!(recorder.passed(), 2);
+    assert_eq!(recorder.completed(), 2);
+    assert_eq!(recorder.errors(), 1);
+    assert!(consumer.consumer().events().is_empty());
+}
+
+#[tokio::test]
+async fn pause_when_throttled() {
+    let mut sc = SlotChain::new();
+    sc.add_rule_check_slot(Arc::new(Throttler(AtomicU32::new(3))));
+    sentinel_rs::register_resource_slot_chain(vec!["kafka.payments-1".into()], Arc::new(sc));
+    let consumer = SentinelConsumer::new(Partitions::default())
+        .with_resource_extractor(|topic, partition| format!("{}-{}", topic, partition));
+
+    let batch = vec![
+        message("kafka.payments", 1, 7),
+        message("kafka.payments", 1, 8),
+        message("kafka.payments", 2, 3),
+    ];
+    let handled = AtomicU32::new(0);
+    let result: Result<(), String> = consumer
+        .process_batch(&batch, || async {
+            handled.fetch_add(batch.len() as u32, Ordering::SeqCst);
+            Ok(())
+        })
+        .await;
+    assert!(result.is_ok());
+    // no message is dropped, the partitions are paused only once
+    assert_eq!(handled.load(Ordering::SeqCst), 3);
+    assert_eq!(
+        consumer.consumer().events(),
+        vec![
+            "pause kafka.payments-1",
+            "pause kafka.payments-2",
+            "resume kafka.payments-1",
+            "resume kafka.payments-2",
+        ]
+    );
+}
@@ -0,0 +1,136 @@
+//! Tests on the traffic replay harness, which is only available with the `test-util` feature.
+#![cfg(feature = "test-util")]
+
+use sentinel_rs::circuitbreaker::{self, BreakerStrategy, State};
+use sentinel_rs::testing::{Phase, RecordedRequest, Replayer, RtDistribution, TrafficProfile};
+use sentinel_rs::{fault, flow, RuleSet};
+use std::sync::Arc;
+
+#[test]
+fn flow_threshold() {
+    let resource = "replay_flow".to_string();
+    let rule_set = RuleSet {
+        flow: vec![Arc::new(flow::Rule {
+            resource: resource.clone(),
+            threshold: 10.0,
+            ..Default::default()
+        })],
+        ..Default::default()
+    };
+    let profile = TrafficProfile::synthetic(
+        resource,
+        &[
+            Phase::new(2000, 5.0).with_rt(RtDistribution::Constant(10)),
+            Phase::new(3000, 20.0).with_rt(RtDistribution::Constant(10)),
+        ],
+        0,
+    );
+    let report = Replayer::new(rule_set).replay(&profile).unwrap();
+    assert_eq!(report.timeline.len(), 5);
+    assert_eq!(report.timeline[0].passed, 5);
+    assert_eq!(report.timeline[0].blocked, 0);
+    assert_eq!(report.timeline[0].avg_rt_ms(), 10.0);
+    for bucket in &report.timeline[2..] {
+        assert_eq!(bucket.passed, 10);
+        assert_eq!(bucket.blocked, 10);
+        assert_eq!(bucket.blocked_by["Flow"], 10);
+    }
+    assert_eq!(report.passed(), 40);
+    assert_eq!(report.block_ratio(), 30.0 / 70.0);
+    // the rules are cleared after the replay
+    assert!(flow::get_rules().is_empty());
+}
+
+#[test]
+fn breaker_transitions() {
+    let resource = "replay_breaker".to_string();
+    let rule_set = RuleSet {
+        circuit_breaker: vec![Arc::new(circuitbreaker::Rule {
+            resource: resource.clone(),
+            strategy: BreakerStrategy::ErrorCount,
+            retry_timeout_ms: 1000,
+            min_request_amount: 1,
+            stat_interval_ms: 1000,
+            threshold: 5.0,
+            ..Default::default()
+        })],
+        ..Default::default()
+    };
+    let phases = [
+        Phase::new(1000, 10.0),
+        // the error burst
+        Phase::new(500, 10.0).with_error_ratio(1.0),
+        Phase::new(3000, 10.0),
+    ];
+    let report = Replayer::new(rule_set)
+        .replay(&TrafficProfile::synthetic(resource.clone(), &phases, 0))
+        .unwrap();
+    let transitions: Vec<_> = report
+        .transitions
+        .iter()
+        .map(|t| (t.offset_ms, t.from, t.to))
+        .collect();
+    assert_eq!(
+        transitions,
+        vec![
+            // the 5th error completes at once
+            (1400, State::Closed, State::Open),
+            // the probe after the retry timeout succeeds
+            (2400, State::Open, State::HalfOpen),
+            (2400, State::HalfOpen, State::Closed),
+        ]
+    );
+    assert!(report.transitions.iter().all(|t| t.resource == resource));
+    assert_eq!(report.timeline[1].errors, 5);
+    assert_eq!(report.timeline[1].blocked, 5);
+    assert_eq!(report.timeline[2].blocked, 4);
+    assert_eq!(report.timeline[3].blocked, 0);
+}
+
+#[test]
+fn recorded_profile() {
+    let resource = "replay_recorded".to_string();
+    let requests = (0..3)
+        .map(|i| RecordedRequest {
+            offset_ms: 100 * i,
+            rt_ms: 50,
+            error: false,
+        })
+        .collect();
+    let report = Replayer::new(RuleSet::default())
+        .with_bucket_ms(100)
+        .replay(&TrafficProfile::recorded(resource, requests))
+        .unwrap();
+    assert_eq!(report.timeline.len(), 3);
+    assert_eq!(report.passed(), 3);
+    assert!(report.timeline.iter().all(|b| b.completed == 1));
+    assert!(report.transitions.is_empty());
+}
+
+#[test]
+fn fault_trips_breaker() {
+    let resource = "replay_fault".to_string();
+    let rule_set = RuleSet {
+        circuit_breaker: vec![Arc::new(circuitbreaker::Rule {
+            resource: resource.clone(),
+            strategy: BreakerStrategy::ErrorRatio,
+            retry_timeout_ms: 1000,
+            min_request_amount: 10,
+            stat_interval_ms: 1000,
+            threshold: 0.4,
+            ..Default::default()
+        })],
+        fault: vec![Arc::new(fault::Rule {
+            resource: resource.clone(),
+            ratio: 0.5,
+            ..Default::default()
+        })],
+        ..Default::default()
+    };
+    let profile = TrafficProfile::synthetic(resource, &[Phase::new(1000, 20.0)], 0);
+    let report = Replayer::new(rule_set).replay(&profile).unwrap();
+    // the injected errors are counted by the breaker
+    assert_eq!(report.transitions[0].to, State::Open);
+    assert!(report.timeline[0].errors >= 5);
+    assert!(report.timeline[0].blocked > 0);
+}
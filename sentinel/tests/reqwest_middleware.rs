@@ -0,0 +1,87 @@
+#![cfg(feature = "reqwest-middleware")]
+
+mod common;
+
+use reqwest_middleware::reqwest::{Client, StatusCode};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use sentinel_rs::adapters::reqwest_middleware::{block_error, SentinelMiddleware};
+use sentinel_rs::base::BlockType;
+use std::time::Duration;
+
+fn client(middleware: SentinelMiddleware) -> ClientWithMiddleware {
+    let client = Client::builder()
+        .timeout(Duration::from_millis(200))
+        .build()
+        .unwrap();
+    ClientBuilder::new(client).with(middleware).build()
+}
+
+#[tokio::test]
+async fn host_resource() {
+    let host = common::serve().await;
+    let recorder = common::register(&host, None);
+    let client = client(SentinelMiddleware::new());
+
+    for _ in 0..3 {
+        let url = format!("http://{}/status/200", host);
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    assert_eq!(recorder.passed(), 3);
+    assert_eq!(recorder.completed(), 3);
+    assert_eq!(recorder.errors(), 0);
+
+    let url = format!("http://{}/status/503", host);
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    // the client errors are not recorded by default
+    let url = format!("http://{}/status/404", host);
+    client.get(&url).send().await.unwrap();
+    assert_eq!(recorder.completed(), 5);
+    assert_eq!(recorder.errors(), 1);
+
+    // the timeout
+    let url = format!("http://{}/slow", host);
+    assert!(client.get(&url).send().await.is_err());
+    assert_eq!(recorder.completed(), 6);
+    assert_eq!(recorder.errors(), 2);
+}
+
+#[tokio::test]
+async fn template_resource() {
+    let host = common::serve().await;
+    let resource = format!("GET:{}/status/404", host);
+    let recorder = common::register(&resource, None);
+    let slow = common::register(&format!("GET:{}/slow", host), None);
+    let client = client(
+        SentinelMiddleware::new()
+            .with_resource_template("{method}:{host}{path}")
+            .with_error_predicate(|status| status.is_client_error())
+            .with_timeout_as_error(false),
+    );
+
+    let url = format!("http://{}/status/404", host);
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(recorder.completed(), 1);
+    assert_eq!(recorder.errors(), 1);
+
+    let url = format!("http://{}/slow", host);
+    assert!(client.get(&url).send().await.is_err());
+    assert_eq!(slow.completed(), 1);
+    assert_eq!(slow.errors(), 0);
+}
+
+#[tokio::test]
+async fn blocked() {
+    let host = common::serve().await;
+    let recorder = common::register(&host, Some(BlockType::CircuitBreaking));
+    let client = client(SentinelMiddleware::new());
+
+    let url = format!("http://{}/status/200", host);
+    let err = client.get(&url).send().await.unwrap_err();
+    let block_error = block_error(&err).unwrap();
+    assert_eq!(block_error.block_type(), BlockType::CircuitBreaking);
+    assert_eq!(recorder.blocked(), 1);
+    assert_eq!(recorder.passed(), 0);
+}
@@ -0,0 +1,129 @@
+#![cfg(feature = "rocket")]
+
+mod common;
+
+use rocket::http::{Header, Status};
+use rocket::local::blocking::Client;
+use rocket::{get, routes, Response};
+use sentinel_rs::adapters::rocket::{Sentinel, SentinelGuard};
+use sentinel_rs::base::BlockType;
+use std::io::Cursor;
+
+#[get("/rocket/users/<id>")]
+fn user(id: u32, guard: SentinelGuard) -> String {
+    format!("{} {}", guard.resource(), id)
+}
+
+#[get("/rocket/failed")]
+fn failed(_guard: SentinelGuard) -> Status {
+    Status::InternalServerError
+}
+
+#[get("/rocket/panicked")]
+fn panicked(_guard: SentinelGuard) -> &'static str {
+    panic!("handler panicked")
+}
+
+#[get("/rocket/flow")]
+fn flow(_guard: SentinelGuard) -> &'static str {
+    "flow"
+}
+
+#[get("/rocket/breaker")]
+fn breaker(_guard: SentinelGuard) -> &'static str {
+    "breaker"
+}
+
+#[get("/rocket/quota")]
+fn quota(_guard: SentinelGuard) -> &'static str {
+    "quota"
+}
+
+fn client(sentinel: Sentinel) -> Client {
+    let rocket = rocket::build()
+        .attach(sentinel)
+        .mount("/", routes![user, failed, panicked, flow, breaker, quota]);
+    Client::tracked(rocket).unwrap()
+}
+
+#[test]
+fn route_resource() {
+    let recorder = common::register("GET:/rocket/users/<id>", None);
+    let failed = common::register("GET:/rocket/failed", None);
+    let panicked = common::register("GET:/rocket/panicked", None);
+    let client = client(Sentinel::new().with_origin_header("X-Sentinel-Origin"));
+
+    for id in 0..3 {
+        let response = client
+            .get(format!("/rocket/users/{}", id))
+            .header(Header::new("X-Sentinel-Origin", "caller"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.into_string().unwrap(),
+            format!("GET:/rocket/users/<id> {}", id)
+        );
+    }
+    assert_eq!(recorder.passed(), 3);
+    assert_eq!(recorder.completed(), 3);
+    assert_eq!(recorder.errors(), 0);
+    assert_eq!(recorder.origins(), vec!["caller"; 3]);
+
+    let response = client.get("/rocket/failed").dispatch();
+    assert_eq!(response.status(), Status::InternalServerError);
+    assert_eq!(failed.completed(), 1);
+    assert_eq!(failed.errors(), 1);
+
+    // the panic is caught by Rocket
+    let response = client.get("/rocket/panicked").dispatch();
+    assert_eq!(response.status(), Status::InternalServerError);
+    assert_eq!(panicked.completed(), 1);
+    assert_eq!(panicked.errors(), 1);
+}
+
+#[test]
+fn blocked() {
+    let flow = common::register("GET:/rocket/flow", Some(BlockType::Flow));
+    common::register("GET:/rocket/breaker", Some(BlockType::CircuitBreaking));
+    let client = client(Sentinel::new());
+
+    let response = client.get("/rocket/flow").dispatch();
+    assert_eq!(response.status(), Status::TooManyRequests);
+    assert_eq!(response.headers().get_one("Retry-After"), Some("1"));
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(body["resource"], "GET:/rocket/flow");
+    assert_eq!(flow.blocked(), 1);
+    assert_eq!(flow.passed(), 0);
+
+    let response = client.get("/rocket/breaker").dispatch();
+    assert_eq!(response.status(), Status::ServiceUnavailable);
+
+    // the custom responder
+    let client = self::client(Sentinel::new().with_block_responder(|_| {
+        Response::build()
+            .status(Status::ServiceUnavailable)
+            .sized_body(4, Cursor::new("busy"))
+            .finalize()
+    }));
+    let response = client.get("/rocket/flow").dispatch();
+    assert_eq!(response.status(), Status::ServiceUnavailable);
+    assert_eq!(response.into_string().unwrap(), "busy");
+}
+
+#[test]
+fn rate_limit_headers() {
+    let quota = common::Quota::load("GET:/rocket/quota");
+    let client = client(Sentinel::new().with_rate_limit_headers(true));
+
+    let mut responses = Vec::new();
+    for _ in 0..=common::Quota::LIMIT {
+        let response = client.get("/rocket/quota").dispatch();
+        let header = |name| response.headers().get_one(name).map(String::from);
+        responses.push((
+            response.status() == Status::Ok,
+            header("RateLimit-Limit"),
+            header("RateLimit-Remaining"),
+        ));
+    }
+    quota.check(responses);
+}
@@ -0,0 +1,124 @@
+#![cfg(feature = "salvo")]
+
+mod common;
+
+use salvo_core::http::{header, StatusCode};
+use salvo_core::test::{ResponseExt, TestClient};
+use salvo_core::{handler, Response, Router, Service};
+use sentinel_rs::adapters::salvo::Sentinel;
+use sentinel_rs::base::BlockType;
+
+fn url(path: &str) -> String {
+    format!("http://127.0.0.1:5800{}", path)
+}
+
+fn middleware() -> Sentinel {
+    Sentinel::new().with_origin_header("x-sentinel-origin".parse().unwrap())
+}
+
+async fn get(service: &Service, path: &str) -> Response {
+    TestClient::get(url(path))
+        .add_header("x-sentinel-origin", "caller", true)
+        .send(service)
+        .await
+}
+
+#[handler]
+async fn user() -> &'static str {
+    "user"
+}
+
+#[handler]
+async fn failed(res: &mut Response) {
+    res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[tokio::test]
+async fn route_resource() {
+    let recorder = common::register("GET:/salvo/users/{id}", None);
+    let failed_recorder = common::register("GET:/salvo/failed", None);
+    let service = Service::new(
+        Router::with_path("salvo")
+            .hoop(middleware())
+            .push(Router::with_path("users/{id}").get(user))
+            .push(Router::with_path("failed").get(failed)),
+    );
+
+    for id in 0..3 {
+        let response = get(&service, &format!("/salvo/users/{}", id)).await;
+        assert_eq!(response.status_code, Some(StatusCode::OK));
+    }
+    assert_eq!(recorder.passed(), 3);
+    assert_eq!(recorder.completed(), 3);
+    assert_eq!(recorder.errors(), 0);
+    assert_eq!(recorder.origins(), vec!["caller"; 3]);
+
+    let response = get(&service, "/salvo/failed").await;
+    assert_eq!(
+        response.status_code,
+        Some(StatusCode::INTERNAL_SERVER_ERROR)
+    );
+    assert_eq!(failed_recorder.completed(), 1);
+    assert_eq!(failed_recorder.errors(), 1);
+}
+
+#[tokio::test]
+async fn blocked() {
+    let flow = common::register("GET:/salvo/flow", Some(BlockType::Flow));
+    common::register("GET:/salvo/breaker", Some(BlockType::CircuitBreaking));
+    let service = Service::new(
+        Router::with_path("salvo")
+            .hoop(middleware())
+            .push(Router::with_path("flow").get(user))
+            .push(Router::with_path("breaker").get(user)),
+    );
+
+    let mut response = get(&service, "/salvo/flow").await;
+    assert_eq!(response.status_code, Some(StatusCode::TOO_MANY_REQUESTS));
+    assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+    let body: serde_json::Value = response.take_json().await.unwrap();
+    assert_eq!(body["resource"], "GET:/salvo/flow");
+    assert_eq!(flow.blocked(), 1);
+    assert_eq!(flow.passed(), 0);
+
+    let response = get(&service, "/salvo/breaker").await;
+    assert_eq!(response.status_code, Some(StatusCode::SERVICE_UNAVAILABLE));
+
+    // the custom block responder
+    let service = Service::new(
+        Router::with_path("salvo/flow")
+            .hoop(Sentinel::new().with_block_responder(|_, res| {
+                res.status_code(StatusCode::SERVICE_UNAVAILABLE);
+                res.body("busy");
+            }))
+            .get(user),
+    );
+    let mut response = get(&service, "/salvo/flow").await;
+    assert_eq!(response.status_code, Some(StatusCode::SERVICE_UNAVAILABLE));
+    assert_eq!(response.take_string().await.unwrap(), "busy");
+}
+
+#[tokio::test]
+async fn rate_limit_headers() {
+    let quota = common::Quota::load("GET:/salvo/quota");
+    let service = Service::new(
+        Router::with_path("salvo/quota")
+            .hoop(middleware().with_rate_limit_headers(true))
+            .get(user),
+    );
+
+    let mut responses = Vec::new();
+    for _ in 0..=common::Quota::LIMIT {
+        let response = get(&service, "/salvo/quota").await;
+        let header = |name| {
+            let value = response.headers().get(name)?;
+            value.to_str().ok().map(String::from)
+        };
+        responses.push((
+            response.status_code == Some(StatusCode::OK),
+            header("ratelimit-limit"),
+            header("ratelimit-remaining"),
+        ));
+    }
+    quota.check(responses);
+}
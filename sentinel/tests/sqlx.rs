@@ -0,0 +1,113 @@
+#![cfg(feature = "sqlx")]
+
+mod common;
+
+use sentinel_rs::adapters::sqlx::{block_error, SentinelExecutor, SentinelPool};
+use sentinel_rs::base::BlockType;
+use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use sqlx::Row;
+use std::time::Duration;
+
+async fn pool() -> SqlitePool {
+    SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn executor() {
+    let select = common::register("sqlx.executor.select", None);
+    let missing = common::register("sqlx.executor.missing", None);
+    let pool = pool().await;
+    let mut conn = pool.acquire().await.unwrap();
+
+    let rows = sqlx::query("SELECT 1 UNION SELECT 2")
+        .fetch_all(SentinelExecutor::new("sqlx.executor.select", &mut *conn))
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 2);
+    let row = sqlx::query("SELECT 3")
+        .fetch_one(SentinelExecutor::new("sqlx.executor.select", &mut *conn))
+        .await
+        .unwrap();
+    assert_eq!(row.get::<i64, _>(0), 3);
+    assert_eq!(select.passed(), 2);
+    assert_eq!(select.completed(), 2);
+    assert_eq!(select.errors(), 0);
+
+    let result = sqlx::query("SELECT * FROM missing")
+        .fetch_all(SentinelExecutor::new("sqlx.executor.missing", &mut *conn))
+        .await;
+    assert!(result.is_err());
+    // the errors not matching the predicate are not recorded
+    let result = sqlx::query("SELECT * FROM missing")
+        .execute(
+            SentinelExecutor::new("sqlx.executor.missing", &mut *conn)
+                .with_error_predicate(|err| !matches!(err, sqlx::Error::Database(_))),
+        )
+        .await;
+    assert!(result.is_err());
+    assert_eq!(missing.completed(), 2);
+    assert_eq!(missing.errors(), 1);
+}
+
+#[tokio::test]
+async fn pool_acquire_time() {
+    let counted = common::register("sqlx.pool.counted", None);
+    let excluded = common::register("sqlx.pool.excluded", None);
+    let pool = pool().await;
+
+    for (guarded, label, recorder) in [
+        (
+            SentinelPool::new(pool.clone()),
+            "sqlx.pool.counted",
+            &counted,
+        ),
+        (
+            SentinelPool::new(pool.clone()).with_acquire_time(false),
+            "sqlx.pool.excluded",
+            &excluded,
+        ),
+    ] {
+        // the only connection is held for 200ms
+        let conn = pool.acquire().await.unwrap();
+        let holder = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            drop(conn);
+        });
+        sqlx::query("SELECT 1")
+            .fetch_one(guarded.guard(label))
+            .await
+            .unwrap();
+        holder.await.unwrap();
+        assert_eq!(recorder.completed(), 1);
+    }
+    assert!(counted.round_trips()[0] >= 150);
+    assert!(excluded.round_trips()[0] < 150);
+}
+
+#[tokio::test]
+async fn blocked() {
+    let recorder = common::register("sqlx.blocked", Some(BlockType::Isolation));
+    let pool = SentinelPool::new(pool().await);
+
+    let err = sqlx::query("SELECT 1")
+        .fetch_all(pool.guard("sqlx.blocked"))
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(
+        block_error(&err).unwrap().block_type(),
+        BlockType::Isolation
+    );
+    let err = sqlx::query("SELECT 1")
+        .fetch_optional(pool.guard("sqlx.blocked"))
+        .await
+        .err()
+        .unwrap();
+    assert!(block_error(&err).is_some());
+    assert_eq!(recorder.blocked(), 2);
+    assert_eq!(recorder.passed(), 0);
+}
@@ -0,0 +1,207 @@
+#![cfg(feature = "tonic")]
+
+mod common;
+
+use http::{HeaderMap, Request, Response};
+use http_body::{Body, Frame};
+use http_body_util::BodyExt;
+use sentinel_rs::adapters::tonic::client::{is_ejected, SentinelClientLayer};
+use sentinel_rs::adapters::tonic::server::SentinelLayer;
+use sentinel_rs::adapters::tonic::{grpc_code, BLOCK_METADATA_KEY};
+use sentinel_rs::base::BlockType;
+use sentinel_rs::circuitbreaker;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::Code;
+use tower::{service_fn, ServiceBuilder, ServiceExt};
+
+/// `Frames` is the response body of a unary RPC, the message followed by the trailers.
+#[derive(Default)]
+struct Frames(VecDeque<Frame<&'static [u8]>>);
+
+impl Frames {
+    fn unary(code: Code) -> Self {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", (code as i32).into());
+        Frames(
+            vec![Frame::data(&b"message"[..]), Frame::trailers(trailers)]
+                .into_iter()
+                .collect(),
+        )
+    }
+}
+
+impl Body for Frames {
+    type Data = &'static [u8];
+    type Error = Infallible;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Poll::Ready(self.get_mut().0.pop_front().map(Ok))
+    }
+}
+
+async fn handle(req: Request<()>) -> Result<Response<Frames>, Infallible> {
+    let response = match req.uri().path() {
+        "/test.Greeter/Failed" => Response::new(Frames::unary(Code::Internal)),
+        "/test.Greeter/NotFound" => {
+            let mut response = Response::new(Frames::default());
+            response
+                .headers_mut()
+                .insert("grpc-status", (Code::NotFound as i32).into());
+            response
+        }
+        _ => Response::new(Frames::unary(Code::Ok)),
+    };
+    Ok(response)
+}
+
+fn request(method: &str) -> Request<()> {
+    Request::builder()
+        .uri(format!("http://localhost/test.Greeter/{}", method))
+        .header("x-sentinel-origin", "caller")
+        .body(())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn statuses() {
+    let ok = common::register("/test.Greeter/SayHello", None);
+    let failed = common::register("/test.Greeter/Failed", None);
+    let not_found = common::register("/test.Greeter/NotFound", None);
+    let service = ServiceBuilder::new()
+        .layer(SentinelLayer::new().with_origin_header("x-sentinel-origin".parse().unwrap()))
+        .service(service_fn(handle));
+
+    let response = service.clone().oneshot(request("SayHello")).await.unwrap();
+    // the entry is exited at the end of the body
+    assert_eq!(ok.passed(), 1);
+    assert_eq!(ok.completed(), 0);
+    let body = response.into_body().collect().await.unwrap();
+    assert_eq!(grpc_code(body.trailers().unwrap()), Some(Code::Ok));
+    assert_eq!(ok.completed(), 1);
+    assert_eq!(ok.errors(), 0);
+    assert_eq!(ok.origins(), vec!["caller"]);
+
+    let response = service.clone().oneshot(request("Failed")).await.unwrap();
+    response.into_body().collect().await.unwrap();
+    assert_eq!(failed.completed(), 1);
+    assert_eq!(failed.errors(), 1);
+
+    let response = service.clone().oneshot(request("NotFound")).await.unwrap();
+    response.into_body().collect().await.unwrap();
+    assert_eq!(not_found.completed(), 1);
+    assert_eq!(not_found.errors(), 1);
+
+    // the body is dropped before the end, e.g., the client cancels the RPC
+    let response = service.oneshot(request("SayHello")).await.unwrap();
+    drop(response);
+    assert_eq!(ok.completed(), 2);
+}
+
+#[tokio::test]
+async fn blocked() {
+    let flow = common::register("/test.Greeter/Flow", Some(BlockType::Flow));
+    common::register("/test.Greeter/Breaker", Some(BlockType::CircuitBreaking));
+    let service = ServiceBuilder::new()
+        .layer(SentinelLayer::new())
+        .service(service_fn(handle));
+
+    let response = service.clone().oneshot(request("Flow")).await.unwrap();
+    assert_eq!(grpc_code(response.headers()), Some(Code::ResourceExhausted));
+    assert_eq!(response.headers()["retry-after"], "1");
+    let details: serde_json::Value =
+        serde_json::from_slice(response.headers()[BLOCK_METADATA_KEY].as_bytes()).unwrap();
+    assert_eq!(details["resource"], "/test.Greeter/Flow");
+    assert!(response.into_body().is_end_stream());
+    assert_eq!(flow.blocked(), 1);
+
+    let response = service.oneshot(request("Breaker")).await.unwrap();
+    assert_eq!(grpc_code(response.headers()), Some(Code::Unavailable));
+}
+
+#[tokio::test]
+async fn client() {
+    let target = common::register("greeter", None);
+    let flow = common::register("greeter/test.Greeter/Flow", Some(BlockType::Flow));
+    let client = ServiceBuilder::new()
+        .layer(SentinelClientLayer::new("greeter"))
+        .service(service_fn(handle));
+
+    for method in ["SayHello", "Failed"].iter() {
+        let response = client.clone().oneshot(request(method)).await.unwrap();
+        response.into_body().collect().await.unwrap();
+    }
+    assert_eq!(target.passed(), 2);
+    assert_eq!(target.completed(), 2);
+    assert_eq!(target.errors(), 1);
+
+    // the resources per method
+    let client = ServiceBuilder::new()
+        .layer(
+            SentinelClientLayer::new("greeter")
+                .with_resource_extractor(|target, path| format!("{}{}", target, path)),
+        )
+        .service(service_fn(handle));
+    let err = client.oneshot(request("Flow")).await.unwrap_err();
+    let status = err.downcast_ref::<tonic::Status>().unwrap();
+    assert_eq!(status.code(), Code::ResourceExhausted);
+    assert_eq!(flow.blocked(), 1);
+}
+
+#[tokio::test]
+#[ignore]
+async fn client_ejected() {
+    let target = "tonic_ejected";
+    circuitbreaker::load_rules(vec![Arc::new(circuitbreaker::Rule {
+        resource: target.into(),
+        strategy: circuitbreaker::BreakerStrategy::ErrorCount,
+        retry_timeout_ms: 60_000,
+        min_request_amount: 1,
+        stat_interval_ms: 1000,
+        threshold: 2.0,
+        ..Default::default()
+    })]);
+    let client = ServiceBuilder::new()
+        .layer(SentinelClientLayer::new(target))
+        .service(service_fn(handle));
+    assert!(!is_ejected(target));
+    for _ in 0..2 {
+        let response = client.clone().oneshot(request("Failed")).await.unwrap();
+        response.into_body().collect().await.unwrap();
+    }
+    assert!(is_ejected(target));
+    let err = client.oneshot(request("SayHello")).await.unwrap_err();
+    let status = err.downcast_ref::<tonic::Status>().unwrap();
+    assert_eq!(status.code(), Code::Unavailable);
+    circuitbreaker::clear_rules();
+}
+
+#[tokio::test]
+async fn rate_limit_headers() {
+    let quota = common::Quota::load("/test.Greeter/Quota");
+    let service = ServiceBuilder::new()
+        .layer(SentinelLayer::new().with_rate_limit_headers(true))
+        .service(service_fn(handle));
+
+    let mut responses = Vec::new();
+    for _ in 0..=common::Quota::LIMIT {
+        let response = service.clone().oneshot(request("Quota")).await.unwrap();
+        let header = |name| {
+            let value = response.headers().get(name)?;
+            value.to_str().ok().map(String::from)
+        };
+        // the blocked RPC is answered by the trailers-only response
+        responses.push((
+            grpc_code(response.headers()).is_none(),
+            header("ratelimit-limit"),
+            header("ratelimit-remaining"),
+        ));
+    }
+    quota.check(responses);
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "tower")]
+
+mod common;
+
+use sentinel_rs::adapters::tower::{BoxError, SentinelLayer};
+use sentinel_rs::base::{BlockError, BlockType, TrafficType};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower::{service_fn, Service, ServiceBuilder, ServiceExt};
+
+struct Request {
+    path: String,
+    caller: Option<String>,
+}
+
+fn request(path: &str) -> Request {
+    Request {
+        path: path.into(),
+        caller: Some("caller".into()),
+    }
+}
+
+fn layer() -> SentinelLayer<Request> {
+    SentinelLayer::new(|req: &Request| format!("tower:{}", req.path))
+        .with_origin_extractor(|req: &Request| req.caller.clone())
+}
+
+async fn handle(req: Request) -> Result<String, BoxError> {
+    match req.path.as_str() {
+        "failed" => Err("failed".into()),
+        _ => Ok(req.path),
+    }
+}
+
+#[tokio::test]
+async fn guard_service() {
+    let ok = common::register("tower:ok", None);
+    let failed = common::register("tower:failed", None);
+    let blocked = common::register("tower:blocked", Some(BlockType::Flow));
+    let mut service = ServiceBuilder::new()
+        .layer(layer())
+        .service(service_fn(handle));
+
+    let response = service.ready().await.unwrap().call(request("ok")).await;
+    assert_eq!(response.unwrap(), "ok");
+    assert_eq!(ok.passed(), 1);
+    assert_eq!(ok.completed(), 1);
+    assert_eq!(ok.origins(), vec!["caller"]);
+
+    let response = service.ready().await.unwrap().call(request("failed")).await;
+    assert_eq!(response.unwrap_err().to_string(), "failed");
+    assert_eq!(failed.errors(), 1);
+
+    let err = service
+        .ready()
+        .await
+        .unwrap()
+        .call(request("blocked"))
+        .await
+        .unwrap_err();
+    let block_error = err.downcast_ref::<BlockError>().unwrap();
+    assert_eq!(block_error.block_type(), BlockType::Flow);
+    assert_eq!(block_error.resource(), "tower:blocked");
+    assert_eq!(blocked.blocked(), 1);
+}
+
+/// `SlowReady` becomes ready after the delay, e.g., waiting for a connection.
+struct SlowReady {
+    delay: Option<Pin<Box<tokio::time::Sleep>>>,
+}
+
+impl Service<Request> for SlowReady {
+    type Response = ();
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<(), BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+        let delay = self
+            .delay
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(Duration::from_millis(50))));
+        delay.as_mut().poll(cx).map(Ok)
+    }
+
+    fn call(&mut self, _req: Request) -> Self::Future {
+        self.delay = None;
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[tokio::test]
+async fn ready_latency() {
+    let recorder = common::register("tower:slow", None);
+    let mut service = ServiceBuilder::new()
+        .layer(layer().with_traffic_type(TrafficType::Outbound))
+        .service(SlowReady { delay: None });
+    service
+        .ready()
+        .await
+        .unwrap()
+        .call(request("slow"))
+        .await
+        .unwrap();
+    assert_eq!(recorder.completed(), 1);
+    assert!(recorder.round_trips()[0] >= 40);
+}
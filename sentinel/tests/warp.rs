@@ -0,0 +1,108 @@
+#![cfg(feature = "warp")]
+
+mod common;
+
+use sentinel_rs::adapters::warp::{
+    protect, recover_blocked, recover_blocked_with_rate_limit_headers, SentinelGuard,
+};
+use sentinel_rs::base::BlockType;
+use warp::http::StatusCode;
+use warp::Filter;
+
+fn routes() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let user = warp::path!("warp" / "users" / u32)
+        .and(protect(|method, _| {
+            format!("{}:/warp/users/{{id}}", method)
+        }))
+        .map(|id, guard: SentinelGuard| guard.complete(format!("user {}", id)));
+    let failed = warp::path!("warp" / "failed")
+        .and(protect(|method, path| format!("{}:{}", method, path)))
+        .map(|guard: SentinelGuard| guard.complete(StatusCode::INTERNAL_SERVER_ERROR));
+    let rejected = warp::path!("warp" / "rejected")
+        .and(protect(|method, path| format!("{}:{}", method, path)))
+        .and_then(|_guard: SentinelGuard| async { Err::<String, _>(warp::reject::not_found()) });
+    let flow = warp::path!("warp" / "flow")
+        .and(protect(|method, path| format!("{}:{}", method, path)))
+        .map(|guard: SentinelGuard| guard.complete("flow"));
+    user.or(failed)
+        .or(rejected)
+        .or(flow)
+        .recover(recover_blocked)
+}
+
+#[tokio::test]
+async fn protect_routes() {
+    let recorder = common::register("GET:/warp/users/{id}", None);
+    let failed = common::register("GET:/warp/failed", None);
+    let rejected = common::register("GET:/warp/rejected", None);
+    let routes = routes();
+
+    for id in 0..3 {
+        let response = warp::test::request()
+            .path(&format!("/warp/users/{}", id))
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    assert_eq!(recorder.passed(), 3);
+    assert_eq!(recorder.completed(), 3);
+    assert_eq!(recorder.errors(), 0);
+
+    let response = warp::test::request()
+        .path("/warp/failed")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(failed.completed(), 1);
+    assert_eq!(failed.errors(), 1);
+
+    // the guard is dropped when the handler rejects
+    let response = warp::test::request()
+        .path("/warp/rejected")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(rejected.completed(), 1);
+}
+
+#[tokio::test]
+async fn blocked() {
+    let flow = common::register("GET:/warp/flow", Some(BlockType::Flow));
+    let response = warp::test::request()
+        .path("/warp/flow")
+        .reply(&routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["retry-after"], "1");
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["resource"], "GET:/warp/flow");
+    assert_eq!(flow.blocked(), 1);
+    assert_eq!(flow.passed(), 0);
+}
+
+#[tokio::test]
+async fn rate_limit_headers() {
+    let quota = common::Quota::load("GET:/warp/quota");
+    let routes = warp::path!("warp" / "quota")
+        .and(protect(|method, path| format!("{}:{}", method, path)))
+        .map(|guard: SentinelGuard| guard.complete_with_rate_limit_headers("quota"))
+        .recover(recover_blocked_with_rate_limit_headers);
+
+    let mut responses = Vec::new();
+    for _ in 0..=common::Quota::LIMIT {
+        let response = warp::test::request()
+            .path("/warp/quota")
+            .reply(&routes)
+            .await;
+        let header = |name| {
+            let value = response.headers().get(name)?;
+            value.to_str().ok().map(String::from)
+        };
+        responses.push((
+            response.status() == StatusCode::OK,
+            header("ratelimit-limit"),
+            header("ratelimit-remaining"),
+        ));
+    }
+    quota.check(responses);
+}